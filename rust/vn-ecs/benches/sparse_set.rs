@@ -0,0 +1,80 @@
+//! Rough timings for the `SparseSet` storage variants.
+//! Run with `cargo bench -p vn-ecs`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use vn_ecs::{DenseOrder, SparseSet};
+
+const COUNT: u32 = 100_000;
+const RUNS: u32 = 10;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    let mut total = Duration::ZERO;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        f();
+        total += start.elapsed();
+    }
+    println!("{:<40} {:>12.3?}", name, total / RUNS);
+}
+
+// deterministic, scrambled ids so sorted insertion is not trivially appending
+fn ids(count: u32, stride: u32) -> Vec<u32> {
+    (0..count)
+        .map(|i| (i.wrapping_mul(2_654_435_761) % count) * stride)
+        .collect()
+}
+
+fn filled(order: DenseOrder<u32>, ids: &[u32]) -> SparseSet<u32> {
+    let mut set = SparseSet::with_order(order);
+    for &id in ids {
+        set.insert(id, id ^ 0x5555);
+    }
+    set
+}
+
+fn bench_order(label: &str, order: DenseOrder<u32>, count: u32) {
+    let ids = ids(count, 1);
+
+    bench(&format!("{label}: insert {count}"), || {
+        black_box(filled(order, &ids));
+    });
+
+    let set = filled(order, &ids);
+    bench(&format!("{label}: get {count}"), || {
+        for &id in &ids {
+            black_box(set.get(id));
+        }
+    });
+
+    bench(&format!("{label}: iterate {count}"), || {
+        let sum: u64 = set.iter().map(|(_, &v)| v as u64).sum();
+        black_box(sum);
+    });
+
+    bench(&format!("{label}: remove {count}"), || {
+        let mut set = filled(order, &ids);
+        for &id in &ids {
+            black_box(set.remove(id));
+        }
+    });
+}
+
+fn main() {
+    bench_order("unordered", DenseOrder::Unordered, COUNT);
+    // sorted orders shift the dense array on insert / remove, so use fewer elements
+    bench_order("by entity id", DenseOrder::EntityId, COUNT / 10);
+    bench_order("by key", DenseOrder::By(|a, b| a.cmp(b)), COUNT / 10);
+
+    // memory of the sparse array for a rare component on high entity ids
+    let mut set = SparseSet::<u32>::new();
+    for id in ids(100, 1_000_000) {
+        set.insert(id, id);
+    }
+    println!(
+        "{:<40} {:>12} bytes (a flat array would use {} bytes)",
+        "sparse memory: 100 ids up to 1e8",
+        set.sparse_size_in_bytes(),
+        100_000_000 * size_of::<Option<u32>>()
+    );
+}
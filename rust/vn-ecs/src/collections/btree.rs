@@ -1,15 +1,20 @@
-use std::ops::RangeInclusive;
+use crate::collections::page_store::{MemoryPageStore, PageId, PageStore};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
 
-struct BTreeNode<K, V, const ORDER: usize> {
-    keys: Vec<K>,
-    values: Vec<V>,
-    children: Vec<BTreeNode<K, V, ORDER>>,
-    is_leaf: bool,
+/// A node of a `BTree`, stored in a page of a `PageStore`. Children are referenced by page.
+pub struct BTreeNode<K, V, const ORDER: usize> {
+    pub(crate) keys: Vec<K>,
+    pub(crate) values: Vec<V>,
+    pub(crate) children: Vec<PageId>,
+    pub(crate) is_leaf: bool,
 }
 
-impl<K: Ord + Clone, V: Clone, const ORDER: usize> BTreeNode<K, V, ORDER> {
-    const MIN_KEYS: usize = ORDER / 2 - 1;
-
+impl<K, V, const ORDER: usize> BTreeNode<K, V, ORDER> {
     fn new(is_leaf: bool) -> Self {
         Self {
             keys: Vec::with_capacity(ORDER),
@@ -19,172 +24,432 @@ impl<K: Ord + Clone, V: Clone, const ORDER: usize> BTreeNode<K, V, ORDER> {
         }
     }
 
-    fn split_child(&mut self, i: usize) {
-        let mut y = self.children.remove(i);
-        let mut z = BTreeNode::new(y.is_leaf);
+    /// Creates a node from its parts, for page stores that deserialize nodes.
+    pub fn from_parts(keys: Vec<K>, values: Vec<V>, children: Vec<PageId>, is_leaf: bool) -> Self {
+        Self {
+            keys,
+            values,
+            children,
+            is_leaf,
+        }
+    }
 
-        z.keys = y.keys.split_off(Self::MIN_KEYS + 1);
-        z.values = y.values.split_off(Self::MIN_KEYS + 1);
-        let mid_key = y.keys.pop().unwrap();
-        let mid_val = y.values.pop().unwrap();
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
 
-        if !y.is_leaf {
-            z.children = y.children.split_off(Self::MIN_KEYS + 1);
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    pub fn children(&self) -> &[PageId] {
+        &self.children
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        self.is_leaf
+    }
+}
+
+/// A B-Tree map where `ORDER` is the maximum number of children of a node.
+/// `ORDER` has to be at least 4.
+///
+/// Nodes are kept in a `PageStore`, in memory by default. With a persistent store such as
+/// `FilePageStore`, changes become durable once `flush` succeeds.
+pub struct BTree<K, V, const ORDER: usize = 8, S = MemoryPageStore<K, V, ORDER>> {
+    store: S,
+    _phantom: PhantomData<(K, V)>,
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize> BTree<K, V, ORDER> {
+    pub fn new() -> Self {
+        Self::with_store(MemoryPageStore::new())
+    }
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> BTree<K, V, ORDER, S> {
+    const VALID_ORDER: () = assert!(ORDER >= 4, "BTree ORDER has to be at least 4");
+    const MIN_KEYS: usize = ORDER / 2 - 1;
+
+    /// Creates a tree on top of `store`, which may already contain a tree (e.g. when reopening
+    /// a file).
+    pub fn with_store(store: S) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_ORDER;
+        Self {
+            store,
+            _phantom: PhantomData,
         }
+    }
 
-        self.keys.insert(i, mid_key);
-        self.values.insert(i, mid_val);
-        self.children.insert(i, y);
-        self.children.insert(i + 1, z);
+    pub fn store(&self) -> &S {
+        &self.store
     }
 
-    fn insert_non_full(&mut self, key: K, value: V) {
-        if self.is_leaf {
-            match self.keys.binary_search(&key) {
-                Ok(idx) => self.values[idx] = value,
-                Err(idx) => {
-                    self.keys.insert(idx, key);
-                    self.values.insert(idx, value);
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.store.flush()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.store.clear();
+    }
+
+    /// Inserts a key-value pair, returning the previous value if the key was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = match self.store.root() {
+            Some(root) => {
+                if self.store.get(root).keys.len() == ORDER - 1 {
+                    let mut new_root = BTreeNode::new(false);
+                    new_root.children.push(root);
+                    let new_root = self.store.allocate(new_root);
+                    self.store.set_root(Some(new_root));
+                    self.split_child(new_root, 0);
                 }
+                self.insert_non_full(self.store.root().unwrap(), key, value)
             }
-        } else {
-            match self.keys.binary_search(&key) {
-                Ok(idx) => self.values[idx] = value,
-                Err(idx) => {
-                    if self.children[idx].keys.len() == ORDER - 1 {
-                        self.split_child(idx);
-                        match self.keys.binary_search(&key) {
-                            Ok(new_idx) => self.values[new_idx] = value,
-                            Err(new_idx) => self.children[new_idx].insert_non_full(key, value),
-                        }
-                    } else {
-                        self.children[idx].insert_non_full(key, value);
-                    }
-                }
+            None => {
+                let mut root = BTreeNode::new(true);
+                root.keys.push(key);
+                root.values.push(value);
+                let root = self.store.allocate(root);
+                self.store.set_root(Some(root));
+                None
             }
+        };
+
+        if old.is_none() {
+            self.store.set_len(self.store.len() + 1);
         }
+        old
     }
 
-    fn get(&self, key: &K) -> Option<&V> {
-        match self.keys.binary_search(key) {
-            Ok(idx) => Some(&self.values[idx]),
-            Err(idx) => {
-                if self.is_leaf {
-                    None
-                } else {
-                    self.children[idx].get(key)
-                }
-            }
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let (id, idx) = self.find(key)?;
+        Some(&self.store.get(id).values[idx])
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let (id, idx) = self.find(key)?;
+        Some(&mut self.store.get_mut(id).values[idx])
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, ORDER, S> {
+        if self.contains_key(&key) {
+            Entry::Occupied(OccupiedEntry { tree: self, key })
+        } else {
+            Entry::Vacant(VacantEntry { tree: self, key })
         }
     }
 
-    fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        match self.keys.binary_search(key) {
-            Ok(idx) => Some(&mut self.values[idx]),
-            Err(idx) => {
-                if self.is_leaf {
-                    None
-                } else {
-                    self.children[idx].get_mut(key)
-                }
-            }
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.store.get(self.store.root()?);
+        while !node.is_leaf {
+            node = self.store.get(node.children[0]);
+        }
+        Some((node.keys.first()?, node.values.first()?))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        let mut node = self.store.get(self.store.root()?);
+        while !node.is_leaf {
+            node = self.store.get(node.children[node.children.len() - 1]);
+        }
+        Some((node.keys.last()?, node.values.last()?))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let entry = self.pop_first_from(self.store.root()?);
+        self.store.set_len(self.store.len() - 1);
+        self.collapse_root();
+        Some(entry)
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let entry = self.pop_last_from(self.store.root()?);
+        self.store.set_len(self.store.len() - 1);
+        self.collapse_root();
+        Some(entry)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let res = self.remove_from(self.store.root()?, key);
+        if res.is_some() {
+            self.store.set_len(self.store.len() - 1);
         }
+        self.collapse_root();
+        res
+    }
+
+    /// Removes all entries with keys greater than or equal to `key` and returns them as a new tree.
+    ///
+    /// The nodes along the path to `key` are split in two, the subtrees right of it are moved
+    /// page by page, and the nodes left underfull at the cut are rebalanced afterwards.
+    pub fn split_off(&mut self, key: &K) -> Self
+    where
+        S: Default,
+    {
+        let mut other = Self::with_store(S::default());
+        let Some(root) = self.store.root() else {
+            return other;
+        };
+
+        let (other_root, moved) = Self::split_node(&mut self.store, &mut other.store, root, key);
+        other.store.set_root(Some(other_root));
+        other.store.set_len(moved);
+        self.store.set_len(self.store.len() - moved);
+
+        self.fix_border(Border::Right);
+        other.fix_border(Border::Left);
+        other
     }
 
-    fn range(&self, range: &RangeInclusive<K>, results: &mut Vec<(K, V)>) {
-        let start_idx = self
-            .keys
-            .binary_search(range.start())
-            .unwrap_or_else(|idx| idx);
+    /// Moves all entries of `other` into this tree, replacing the values of existing keys.
+    ///
+    /// Like std, both trees are drained in order and the merged entries are bulk loaded into new
+    /// nodes, which is linear in the size of both.
+    pub fn append<S2: PageStore<K, V, ORDER>>(&mut self, other: &mut BTree<K, V, ORDER, S2>) {
+        if other.is_empty() {
+            return;
+        }
+
+        let mut theirs = Vec::with_capacity(other.len());
+        if let Some(root) = other.store.root() {
+            BTree::<K, V, ORDER, S2>::drain_node(&mut other.store, root, &mut theirs);
+        }
+        other.store.set_root(None);
+        other.store.set_len(0);
 
-        for i in start_idx..self.keys.len() {
-            if !self.is_leaf {
-                self.children[i].range(range, results);
+        let mut ours = Vec::with_capacity(self.len());
+        if let Some(root) = self.store.root() {
+            Self::drain_node(&mut self.store, root, &mut ours);
+        }
+        self.store.set_root(None);
+        self.store.set_len(0);
+
+        let mut ours = ours.into_iter().peekable();
+        let mut theirs = theirs.into_iter().peekable();
+        let merged = std::iter::from_fn(|| match (ours.peek(), theirs.peek()) {
+            (Some((a, _)), Some((b, _))) if a < b => ours.next(),
+            (Some((a, _)), Some((b, _))) if a == b => {
+                ours.next();
+                theirs.next()
             }
-            if range.contains(&self.keys[i]) {
-                results.push((self.keys[i].clone(), self.values[i].clone()));
-            } else if &self.keys[i] > range.end() {
-                return;
+            (_, Some(_)) => theirs.next(),
+            (Some(_), None) => ours.next(),
+            (None, None) => None,
+        });
+        self.bulk_load(merged);
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, ORDER, S> {
+        Range::new(
+            &self.store,
+            range.start_bound().cloned(),
+            range.end_bound().cloned(),
+        )
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, ORDER, S> {
+        Iter {
+            range: Range::new(&self.store, Bound::Unbounded, Bound::Unbounded),
+            remaining: self.len(),
+        }
+    }
+
+    // Without parent pointers a lazy mutable traversal would need unsafe code, so the references
+    // are collected upfront instead.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let mut entries = Vec::with_capacity(self.len());
+        if let Some(root) = self.store.root() {
+            let mut pages: HashMap<PageId, &mut BTreeNode<K, V, ORDER>> =
+                self.store.pages_mut().into_iter().collect();
+            Self::collect_mut(root, &mut pages, &mut entries);
+        }
+        IterMut {
+            inner: entries.into_iter(),
+        }
+    }
+
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.iter().map(|(_, v)| v)
+    }
+
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut V> + ExactSizeIterator {
+        self.iter_mut().map(|(_, v)| v)
+    }
+
+    fn find(&self, key: &K) -> Option<(PageId, usize)> {
+        let mut id = self.store.root()?;
+        loop {
+            let node = self.store.get(id);
+            match node.keys.binary_search(key) {
+                Ok(idx) => return Some((id, idx)),
+                Err(_) if node.is_leaf => return None,
+                Err(idx) => id = node.children[idx],
             }
         }
+    }
+
+    fn keys_in(&self, id: PageId) -> usize {
+        self.store.get(id).keys.len()
+    }
+
+    fn split_child(&mut self, parent_id: PageId, i: usize) {
+        let y_id = self.store.get(parent_id).children[i];
+        let y = self.store.get_mut(y_id);
+        let mut z = BTreeNode::new(y.is_leaf);
+
+        z.keys = y.keys.split_off(Self::MIN_KEYS + 1);
+        z.values = y.values.split_off(Self::MIN_KEYS + 1);
+        let mid_key = y.keys.pop().unwrap();
+        let mid_val = y.values.pop().unwrap();
 
-        if !self.is_leaf {
-            self.children[self.keys.len()].range(range, results);
+        if !y.is_leaf {
+            z.children = y.children.split_off(Self::MIN_KEYS + 1);
         }
+
+        let z_id = self.store.allocate(z);
+        let parent = self.store.get_mut(parent_id);
+        parent.keys.insert(i, mid_key);
+        parent.values.insert(i, mid_val);
+        parent.children.insert(i + 1, z_id);
     }
 
-    fn remove(&mut self, key: &K) -> Option<V> {
-        let idx = match self.keys.binary_search(key) {
+    fn insert_non_full(&mut self, mut id: PageId, key: K, value: V) -> Option<V> {
+        loop {
+            let node = self.store.get(id);
+            match node.keys.binary_search(&key) {
+                Ok(idx) => {
+                    let node = self.store.get_mut(id);
+                    return Some(std::mem::replace(&mut node.values[idx], value));
+                }
+                Err(idx) if node.is_leaf => {
+                    let node = self.store.get_mut(id);
+                    node.keys.insert(idx, key);
+                    node.values.insert(idx, value);
+                    return None;
+                }
+                Err(idx) => {
+                    let child = node.children[idx];
+                    if self.keys_in(child) < ORDER - 1 {
+                        id = child;
+                        continue;
+                    }
+
+                    self.split_child(id, idx);
+                    let node = self.store.get(id);
+                    match node.keys.binary_search(&key) {
+                        Ok(new_idx) => {
+                            let node = self.store.get_mut(id);
+                            return Some(std::mem::replace(&mut node.values[new_idx], value));
+                        }
+                        Err(new_idx) => id = node.children[new_idx],
+                    }
+                }
+            }
+        }
+    }
+
+    fn remove_from(&mut self, id: PageId, key: &K) -> Option<V> {
+        let node = self.store.get(id);
+        let idx = match node.keys.binary_search(key) {
             Ok(idx) => {
-                if self.is_leaf {
-                    self.keys.remove(idx);
-                    return Some(self.values.remove(idx));
+                if node.is_leaf {
+                    let node = self.store.get_mut(id);
+                    node.keys.remove(idx);
+                    return Some(node.values.remove(idx));
                 }
 
                 // Internal node
-                if self.children[idx].keys.len() > Self::MIN_KEYS {
+                let (left, right) = (node.children[idx], node.children[idx + 1]);
+                if self.keys_in(left) > Self::MIN_KEYS {
                     // Predecessor
-                    let (k, v) = self.children[idx].pop_last();
-                    let old_val = std::mem::replace(&mut self.values[idx], v);
-                    self.keys[idx] = k;
-                    return Some(old_val);
-                } else if self.children[idx + 1].keys.len() > Self::MIN_KEYS {
+                    let (k, v) = self.pop_last_from(left);
+                    let node = self.store.get_mut(id);
+                    node.keys[idx] = k;
+                    return Some(std::mem::replace(&mut node.values[idx], v));
+                } else if self.keys_in(right) > Self::MIN_KEYS {
                     // Successor
-                    let (k, v) = self.children[idx + 1].pop_first();
-                    let old_val = std::mem::replace(&mut self.values[idx], v);
-                    self.keys[idx] = k;
-                    return Some(old_val);
+                    let (k, v) = self.pop_first_from(right);
+                    let node = self.store.get_mut(id);
+                    node.keys[idx] = k;
+                    return Some(std::mem::replace(&mut node.values[idx], v));
                 } else {
                     // Merge
-                    self.merge(idx);
-                    return self.children[idx].remove(key);
+                    self.merge(id, idx);
+                    return self.remove_from(left, key);
                 }
             }
             Err(idx) => idx,
         };
 
-        if self.is_leaf {
+        if node.is_leaf {
             return None;
         }
 
         // Key not in this node, it might be in children[idx]
-        if self.children[idx].keys.len() <= Self::MIN_KEYS {
-            self.fill(idx);
+        if self.keys_in(node.children[idx]) <= Self::MIN_KEYS {
+            self.fill(id, idx);
         }
 
-        match self.keys.binary_search(key) {
-            Ok(_) => self.remove(key),
-            Err(new_idx) => self.children[new_idx].remove(key),
+        let node = self.store.get(id);
+        match node.keys.binary_search(key) {
+            Ok(_) => self.remove_from(id, key),
+            Err(new_idx) => self.remove_from(node.children[new_idx], key),
         }
     }
 
-    fn fill(&mut self, i: usize) {
-        if i != 0 && self.children[i - 1].keys.len() > Self::MIN_KEYS {
-            self.borrow_from_prev(i);
-        } else if i != self.keys.len() && self.children[i + 1].keys.len() > Self::MIN_KEYS {
-            self.borrow_from_next(i);
+    fn fill(&mut self, id: PageId, i: usize) {
+        let node = self.store.get(id);
+        let keys = node.keys.len();
+        if i != 0 && self.keys_in(node.children[i - 1]) > Self::MIN_KEYS {
+            self.borrow_from_prev(id, i);
+        } else if i != keys && self.keys_in(node.children[i + 1]) > Self::MIN_KEYS {
+            self.borrow_from_next(id, i);
+        } else if i != keys {
+            self.merge(id, i);
         } else {
-            if i != self.keys.len() {
-                self.merge(i);
-            } else {
-                self.merge(i - 1);
-            }
+            self.merge(id, i - 1);
         }
     }
 
-    fn borrow_from_prev(&mut self, i: usize) {
-        let (left, right) = self.children.split_at_mut(i);
-        let sibling = &mut left[i - 1];
-        let child = &mut right[0];
+    fn borrow_from_prev(&mut self, id: PageId, i: usize) {
+        let mut node = self.store.take(id);
+        let mut sibling = self.store.take(node.children[i - 1]);
+        let mut child = self.store.take(node.children[i]);
 
-        let k = self.keys.remove(i - 1);
-        let v = self.values.remove(i - 1);
+        let k = node.keys.remove(i - 1);
+        let v = node.values.remove(i - 1);
 
         let sibling_k = sibling.keys.pop().unwrap();
         let sibling_v = sibling.values.pop().unwrap();
 
-        self.keys.insert(i - 1, sibling_k);
-        self.values.insert(i - 1, sibling_v);
+        node.keys.insert(i - 1, sibling_k);
+        node.values.insert(i - 1, sibling_v);
 
         child.keys.insert(0, k);
         child.values.insert(0, v);
@@ -192,21 +457,25 @@ impl<K: Ord + Clone, V: Clone, const ORDER: usize> BTreeNode<K, V, ORDER> {
             let sibling_child = sibling.children.pop().unwrap();
             child.children.insert(0, sibling_child);
         }
+
+        self.store.put(node.children[i - 1], sibling);
+        self.store.put(node.children[i], child);
+        self.store.put(id, node);
     }
 
-    fn borrow_from_next(&mut self, i: usize) {
-        let (left, right) = self.children.split_at_mut(i + 1);
-        let child = &mut left[i];
-        let sibling = &mut right[0];
+    fn borrow_from_next(&mut self, id: PageId, i: usize) {
+        let mut node = self.store.take(id);
+        let mut child = self.store.take(node.children[i]);
+        let mut sibling = self.store.take(node.children[i + 1]);
 
-        let k = self.keys.remove(i);
-        let v = self.values.remove(i);
+        let k = node.keys.remove(i);
+        let v = node.values.remove(i);
 
         let sibling_k = sibling.keys.remove(0);
         let sibling_v = sibling.values.remove(0);
 
-        self.keys.insert(i, sibling_k);
-        self.values.insert(i, sibling_v);
+        node.keys.insert(i, sibling_k);
+        node.values.insert(i, sibling_v);
 
         child.keys.push(k);
         child.values.push(v);
@@ -214,14 +483,23 @@ impl<K: Ord + Clone, V: Clone, const ORDER: usize> BTreeNode<K, V, ORDER> {
             let sibling_child = sibling.children.remove(0);
             child.children.push(sibling_child);
         }
+
+        self.store.put(node.children[i], child);
+        self.store.put(node.children[i + 1], sibling);
+        self.store.put(id, node);
     }
 
-    fn merge(&mut self, i: usize) {
-        let mut next = self.children.remove(i + 1);
-        let k = self.keys.remove(i);
-        let v = self.values.remove(i);
+    fn merge(&mut self, id: PageId, i: usize) {
+        let node = self.store.get_mut(id);
+        let next_id = node.children.remove(i + 1);
+        let child_id = node.children[i];
+        let k = node.keys.remove(i);
+        let v = node.values.remove(i);
+
+        let mut next = self.store.take(next_id);
+        self.store.free(next_id);
 
-        let child = &mut self.children[i];
+        let child = self.store.get_mut(child_id);
         child.keys.push(k);
         child.values.push(v);
         child.keys.append(&mut next.keys);
@@ -231,87 +509,655 @@ impl<K: Ord + Clone, V: Clone, const ORDER: usize> BTreeNode<K, V, ORDER> {
         }
     }
 
-    fn pop_first(&mut self) -> (K, V) {
-        if self.is_leaf {
-            (self.keys.remove(0), self.values.remove(0))
-        } else {
-            if self.children[0].keys.len() <= Self::MIN_KEYS {
-                self.fill(0);
+    fn pop_first_from(&mut self, mut id: PageId) -> (K, V) {
+        loop {
+            let node = self.store.get(id);
+            if node.is_leaf {
+                let node = self.store.get_mut(id);
+                return (node.keys.remove(0), node.values.remove(0));
+            }
+            if self.keys_in(node.children[0]) <= Self::MIN_KEYS {
+                self.fill(id, 0);
             }
-            self.children[0].pop_first()
+            id = self.store.get(id).children[0];
         }
     }
 
-    fn pop_last(&mut self) -> (K, V) {
-        if self.is_leaf {
-            (self.keys.pop().unwrap(), self.values.pop().unwrap())
-        } else {
-            let last_idx = self.children.len() - 1;
-            if self.children[last_idx].keys.len() <= Self::MIN_KEYS {
-                self.fill(last_idx);
+    fn pop_last_from(&mut self, mut id: PageId) -> (K, V) {
+        loop {
+            let node = self.store.get(id);
+            if node.is_leaf {
+                let node = self.store.get_mut(id);
+                return (node.keys.pop().unwrap(), node.values.pop().unwrap());
+            }
+            let last_idx = node.children.len() - 1;
+            if self.keys_in(node.children[last_idx]) <= Self::MIN_KEYS {
+                self.fill(id, last_idx);
+            }
+            let node = self.store.get(id);
+            id = node.children[node.children.len() - 1];
+        }
+    }
+
+    // Moves the entries of subtree `id` with keys >= `key` into a new subtree in `to` of the same
+    // height, returning it and the number of moved entries. The nodes on the path to `key` keep
+    // their left part, so both subtrees can have underfull nodes along the cut.
+    fn split_node<S2: PageStore<K, V, ORDER>>(
+        from: &mut S,
+        to: &mut S2,
+        id: PageId,
+        key: &K,
+    ) -> (PageId, usize) {
+        let node = from.get_mut(id);
+        let idx = node.keys.partition_point(|k| k < key);
+        let mut right = BTreeNode::new(node.is_leaf);
+        right.keys = node.keys.split_off(idx);
+        right.values = node.values.split_off(idx);
+        let mut moved = right.keys.len();
+
+        if !node.is_leaf {
+            let children = node.children.split_off(idx + 1);
+            let cut = node.children[idx];
+            let (split, count) = Self::split_node(from, to, cut, key);
+            right.children.push(split);
+            moved += count;
+            for child in children {
+                let (child, count) = Self::transfer(from, to, child);
+                right.children.push(child);
+                moved += count;
             }
-            let last_idx = self.children.len() - 1;
-            self.children[last_idx].pop_last()
         }
+
+        (to.allocate(right), moved)
+    }
+
+    // Moves the subtree `id` into `to`, returning its new page and the number of entries.
+    fn transfer<S2: PageStore<K, V, ORDER>>(
+        from: &mut S,
+        to: &mut S2,
+        id: PageId,
+    ) -> (PageId, usize) {
+        let mut node = from.take(id);
+        from.free(id);
+        let mut count = node.keys.len();
+        for child in node.children.iter_mut() {
+            let (moved, moved_count) = Self::transfer(from, to, *child);
+            *child = moved;
+            count += moved_count;
+        }
+        (to.allocate(node), count)
+    }
+
+    // Frees all pages of subtree `id`, pushing its entries to `out` in order.
+    fn drain_node(store: &mut S, id: PageId, out: &mut Vec<(K, V)>) {
+        let node = store.take(id);
+        store.free(id);
+        if node.is_leaf {
+            out.extend(node.keys.into_iter().zip(node.values));
+            return;
+        }
+
+        let mut children = node.children.into_iter();
+        for entry in node.keys.into_iter().zip(node.values) {
+            Self::drain_node(store, children.next().unwrap(), out);
+            out.push(entry);
+        }
+        Self::drain_node(store, children.next().unwrap(), out);
+    }
+
+    // Builds the tree from sorted, unique entries by appending to the rightmost leaf. Once it is
+    // full, the next entry goes up into the lowest ancestor with room and a new empty right
+    // spine is started below it, so only the right border can end up underfull.
+    fn bulk_load(&mut self, entries: impl Iterator<Item = (K, V)>) {
+        debug_assert!(self.store.root().is_none());
+        let mut path = vec![self.store.allocate(BTreeNode::new(true))];
+        let mut len = 0;
+
+        for (key, value) in entries {
+            len += 1;
+            let leaf = self.store.get_mut(path[path.len() - 1]);
+            if leaf.keys.len() < ORDER - 1 {
+                leaf.keys.push(key);
+                leaf.values.push(value);
+                continue;
+            }
+
+            let open = match path.iter().rposition(|&id| self.keys_in(id) < ORDER - 1) {
+                Some(open) => open,
+                None => {
+                    let mut root = BTreeNode::new(false);
+                    root.children.push(path[0]);
+                    path.insert(0, self.store.allocate(root));
+                    0
+                }
+            };
+            let node = self.store.get_mut(path[open]);
+            node.keys.push(key);
+            node.values.push(value);
+
+            let height = path.len();
+            path.truncate(open + 1);
+            for level in open + 1..height {
+                let child = self.store.allocate(BTreeNode::new(level == height - 1));
+                self.store.get_mut(path[level - 1]).children.push(child);
+                path.push(child);
+            }
+        }
+
+        self.store.set_root(Some(path[0]));
+        self.store.set_len(len);
+        self.fix_border(Border::Right);
+    }
+
+    // Rebalances the nodes along the left or right border from the top down, the same way
+    // removals do on their path, so every border child has more than the minimum number of
+    // keys before descending into it.
+    fn fix_border(&mut self, border: Border) {
+        self.collapse_root();
+        let Some(mut id) = self.store.root() else {
+            return;
+        };
+
+        let child_index = |node: &BTreeNode<K, V, ORDER>| match border {
+            Border::Left => 0,
+            Border::Right => node.children.len() - 1,
+        };
+        while !self.store.get(id).is_leaf {
+            loop {
+                let node = self.store.get(id);
+                let i = child_index(node);
+                if self.keys_in(node.children[i]) > Self::MIN_KEYS {
+                    break;
+                }
+                self.fill(id, i);
+            }
+            let node = self.store.get(id);
+            id = node.children[child_index(node)];
+        }
+
+        self.collapse_root();
+    }
+
+    fn collapse_root(&mut self) {
+        while let Some(root) = self.store.root()
+            && self.store.get(root).keys.is_empty()
+        {
+            let node = self.store.take(root);
+            self.store.free(root);
+            self.store.set_root(node.children.first().copied());
+        }
+    }
+
+    fn collect_mut<'a>(
+        id: PageId,
+        pages: &mut HashMap<PageId, &'a mut BTreeNode<K, V, ORDER>>,
+        results: &mut Vec<(&'a K, &'a mut V)>,
+    ) {
+        let BTreeNode {
+            keys,
+            values,
+            children,
+            is_leaf,
+        } = pages.remove(&id).expect("page is reachable only once");
+
+        if *is_leaf {
+            results.extend(keys.iter().zip(values.iter_mut()));
+            return;
+        }
+
+        for (i, entry) in keys.iter().zip(values.iter_mut()).enumerate() {
+            Self::collect_mut(children[i], pages, results);
+            results.push(entry);
+        }
+        Self::collect_mut(children[keys.len()], pages, results);
     }
 }
 
-pub struct BTree<K, V, const ORDER: usize = 8> {
-    root: Option<BTreeNode<K, V, ORDER>>,
+#[derive(Clone, Copy)]
+enum Border {
+    Left,
+    Right,
 }
 
-impl<K: Ord + Clone, V: Clone, const ORDER: usize> BTree<K, V, ORDER> {
-    pub fn new() -> Self {
-        Self { root: None }
-    }
-
-    pub fn insert(&mut self, key: K, value: V) {
-        if let Some(ref mut root) = self.root {
-            if root.keys.len() == ORDER - 1 {
-                let mut new_root = BTreeNode::new(false);
-                let old_root = std::mem::replace(root, BTreeNode::new(true));
-                new_root.children.push(old_root);
-                new_root.split_child(0);
-                new_root.insert_non_full(key, value);
-                *root = new_root;
-            } else {
-                root.insert_non_full(key, value);
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER> + Default> Default
+    for BTree<K, V, ORDER, S>
+{
+    fn default() -> Self {
+        Self::with_store(S::default())
+    }
+}
+
+impl<K: Ord + Clone + fmt::Debug, V: fmt::Debug, const ORDER: usize, S: PageStore<K, V, ORDER>>
+    fmt::Debug for BTree<K, V, ORDER, S>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER> + Default>
+    FromIterator<(K, V)> for BTree<K, V, ORDER, S>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tree = Self::default();
+        tree.extend(iter);
+        tree
+    }
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> Extend<(K, V)>
+    for BTree<K, V, ORDER, S>
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> IntoIterator
+    for &'a BTree<K, V, ORDER, S>
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, ORDER, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> IntoIterator
+    for &'a mut BTree<K, V, ORDER, S>
+{
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> IntoIterator
+    for BTree<K, V, ORDER, S>
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, ORDER, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { tree: self }
+    }
+}
+
+pub enum Entry<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> {
+    Occupied(OccupiedEntry<'a, K, V, ORDER, S>),
+    Vacant(VacantEntry<'a, K, V, ORDER, S>),
+}
+
+pub struct OccupiedEntry<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> {
+    tree: &'a mut BTree<K, V, ORDER, S>,
+    key: K,
+}
+
+pub struct VacantEntry<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> {
+    tree: &'a mut BTree<K, V, ORDER, S>,
+    key: K,
+}
+
+impl<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>>
+    Entry<'a, K, V, ORDER, S>
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
             }
-        } else {
-            let mut root = BTreeNode::new(true);
-            root.keys.push(key);
-            root.values.push(value);
-            self.root = Some(root);
+            Entry::Vacant(entry) => Entry::Vacant(entry),
         }
     }
+}
 
-    pub fn get(&self, key: &K) -> Option<&V> {
-        self.root.as_ref().and_then(|r| r.get(key))
+// The entries look up their key again instead of keeping a position in the tree,
+// since insertions and removals restructure the nodes on the way down.
+impl<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>>
+    OccupiedEntry<'a, K, V, ORDER, S>
+{
+    pub fn key(&self) -> &K {
+        &self.key
     }
 
-    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
-        self.root.as_mut().and_then(|r| r.get_mut(key))
+    pub fn get(&self) -> &V {
+        self.tree.get(&self.key).unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.tree.get_mut(&self.key).unwrap()
     }
 
-    pub fn range(&self, range: RangeInclusive<K>) -> Vec<(K, V)> {
-        let mut results = Vec::new();
-        if let Some(ref root) = self.root {
-            root.range(&range, &mut results);
+    pub fn into_mut(self) -> &'a mut V {
+        self.tree.get_mut(&self.key).unwrap()
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.tree.remove(&self.key).unwrap()
+    }
+}
+
+impl<'a, K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>>
+    VacantEntry<'a, K, V, ORDER, S>
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.tree.insert(self.key.clone(), value);
+        self.tree.get_mut(&self.key).unwrap()
+    }
+}
+
+type Cursor<'a, K, V, const ORDER: usize> = Vec<(&'a BTreeNode<K, V, ORDER>, usize)>;
+
+/// A double-ended iterator over a range of entries in a `BTree`.
+///
+/// Both ends keep a stack of `(node, index)` pairs. For the front, `index` is the next key of
+/// the node to yield once the child left of it has been exhausted; for the back it is the
+/// number of keys of the node that have not been yielded yet.
+pub struct Range<'a, K, V, const ORDER: usize, S> {
+    store: &'a S,
+    front: Cursor<'a, K, V, ORDER>,
+    back: Cursor<'a, K, V, ORDER>,
+    start: Bound<K>,
+    end: Bound<K>,
+    front_last: Option<&'a K>,
+    back_last: Option<&'a K>,
+    finished: bool,
+}
+
+impl<'a, K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> Range<'a, K, V, ORDER, S> {
+    fn new(store: &'a S, start: Bound<K>, end: Bound<K>) -> Self {
+        let mut front = Vec::new();
+        let mut back = Vec::new();
+
+        let root = store.root().map(|root| store.get(root));
+
+        let mut node = root;
+        while let Some(n) = node {
+            let idx = match &start {
+                Bound::Unbounded => 0,
+                Bound::Included(k) => n.keys.partition_point(|x| x < k),
+                Bound::Excluded(k) => n.keys.partition_point(|x| x <= k),
+            };
+            front.push((n, idx));
+            node = (!n.is_leaf).then(|| store.get(n.children[idx]));
+        }
+
+        let mut node = root;
+        while let Some(n) = node {
+            let idx = match &end {
+                Bound::Unbounded => n.keys.len(),
+                Bound::Included(k) => n.keys.partition_point(|x| x <= k),
+                Bound::Excluded(k) => n.keys.partition_point(|x| x < k),
+            };
+            back.push((n, idx));
+            node = (!n.is_leaf).then(|| store.get(n.children[idx]));
+        }
+
+        Self {
+            store,
+            front,
+            back,
+            start,
+            end,
+            front_last: None,
+            back_last: None,
+            finished: false,
         }
-        results
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let res = self.root.as_mut().and_then(|r| r.remove(key));
-        if let Some(ref root) = self.root {
-            if root.keys.is_empty() {
-                if root.is_leaf {
-                    self.root = None;
-                } else {
-                    self.root = Some(self.root.take().unwrap().children.remove(0));
+    fn next_front(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            let (node, idx) = self.front.last_mut()?;
+            let node: &'a BTreeNode<K, V, ORDER> = node;
+            if *idx < node.keys.len() {
+                let i = *idx;
+                *idx += 1;
+                if !node.is_leaf {
+                    let mut child = self.store.get(node.children[i + 1]);
+                    loop {
+                        self.front.push((child, 0));
+                        if child.is_leaf {
+                            break;
+                        }
+                        child = self.store.get(child.children[0]);
+                    }
                 }
+                return Some((&node.keys[i], &node.values[i]));
             }
+            self.front.pop();
         }
-        res
     }
+
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            let (node, idx) = self.back.last_mut()?;
+            let node: &'a BTreeNode<K, V, ORDER> = node;
+            if *idx > 0 {
+                *idx -= 1;
+                let i = *idx;
+                if !node.is_leaf {
+                    let mut child = self.store.get(node.children[i]);
+                    loop {
+                        self.back.push((child, child.keys.len()));
+                        if child.is_leaf {
+                            break;
+                        }
+                        child = self.store.get(child.children[child.keys.len()]);
+                    }
+                }
+                return Some((&node.keys[i], &node.values[i]));
+            }
+            self.back.pop();
+        }
+    }
+}
+
+impl<'a, K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> Iterator
+    for Range<'a, K, V, ORDER, S>
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let Some((k, v)) = self.next_front() else {
+            self.finished = true;
+            return None;
+        };
+        // once the back has yielded, it marks the end; until then the bound does
+        let past_end = match self.back_last {
+            Some(back) => k >= back,
+            None => match &self.end {
+                Bound::Unbounded => false,
+                Bound::Included(end) => k > end,
+                Bound::Excluded(end) => k >= end,
+            },
+        };
+        if past_end {
+            self.finished = true;
+            return None;
+        }
+        self.front_last = Some(k);
+        Some((k, v))
+    }
+}
+
+impl<K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> DoubleEndedIterator
+    for Range<'_, K, V, ORDER, S>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let Some((k, v)) = Range::next_back(self) else {
+            self.finished = true;
+            return None;
+        };
+        let past_start = match self.front_last {
+            Some(front) => k <= front,
+            None => match &self.start {
+                Bound::Unbounded => false,
+                Bound::Included(start) => k < start,
+                Bound::Excluded(start) => k <= start,
+            },
+        };
+        if past_start {
+            self.finished = true;
+            return None;
+        }
+        self.back_last = Some(k);
+        Some((k, v))
+    }
+}
+
+impl<K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> FusedIterator
+    for Range<'_, K, V, ORDER, S>
+{
+}
+
+pub struct Iter<'a, K, V, const ORDER: usize, S> {
+    range: Range<'a, K, V, ORDER, S>,
+    remaining: usize,
+}
+
+impl<'a, K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> Iterator
+    for Iter<'a, K, V, ORDER, S>
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.range.next()?;
+        self.remaining -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> DoubleEndedIterator
+    for Iter<'_, K, V, ORDER, S>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let item = self.range.next_back()?;
+        self.remaining -= 1;
+        Some(item)
+    }
+}
+
+impl<K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> ExactSizeIterator
+    for Iter<'_, K, V, ORDER, S>
+{
+}
+
+impl<K: Ord, V, const ORDER: usize, S: PageStore<K, V, ORDER>> FusedIterator
+    for Iter<'_, K, V, ORDER, S>
+{
+}
+
+pub struct IterMut<'a, K, V> {
+    inner: std::vec::IntoIter<(&'a K, &'a mut V)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+pub struct IntoIter<K, V, const ORDER: usize, S> {
+    tree: BTree<K, V, ORDER, S>,
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> Iterator
+    for IntoIter<K, V, ORDER, S>
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tree.pop_first()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.tree.len(), Some(self.tree.len()))
+    }
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> DoubleEndedIterator
+    for IntoIter<K, V, ORDER, S>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.tree.pop_last()
+    }
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> ExactSizeIterator
+    for IntoIter<K, V, ORDER, S>
+{
+}
+
+impl<K: Ord + Clone, V, const ORDER: usize, S: PageStore<K, V, ORDER>> FusedIterator
+    for IntoIter<K, V, ORDER, S>
+{
 }
@@ -0,0 +1,604 @@
+use crate::collections::btree::BTreeNode;
+use crate::collections::page_store::{PageCodec, PageId, PageStore, take_bytes};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+const DEFAULT_POOL_CAPACITY: usize = 256;
+
+const MAGIC: &[u8; 4] = b"VNBT";
+const VERSION: u32 = 1;
+const HEADER_PAGE: PageId = 0;
+const NO_PAGE: u32 = u32::MAX;
+const WAL_COMMIT: u32 = u32::MAX;
+
+const PAGE_NODE: u8 = 1;
+const PAGE_FREE: u8 = 2;
+
+struct Header {
+    page_size: u32,
+    order: u32,
+    root: Option<PageId>,
+    len: u64,
+    page_count: u32,
+    free_head: Option<PageId>,
+}
+
+impl Header {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        VERSION.encode(out);
+        self.page_size.encode(out);
+        self.order.encode(out);
+        self.root.unwrap_or(NO_PAGE).encode(out);
+        self.len.encode(out);
+        self.page_count.encode(out);
+        self.free_head.unwrap_or(NO_PAGE).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        if take_bytes(input, MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a vn-ecs btree file"));
+        }
+        let version = u32::decode(input)?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported file version {}",
+                version
+            )));
+        }
+        let page = |id: u32| (id != NO_PAGE).then_some(id);
+        Ok(Self {
+            page_size: u32::decode(input)?,
+            order: u32::decode(input)?,
+            root: page(u32::decode(input)?),
+            len: u64::decode(input)?,
+            page_count: u32::decode(input)?,
+            free_head: page(u32::decode(input)?),
+        })
+    }
+}
+
+struct Frame<K, V, const ORDER: usize> {
+    // boxed, so references handed out by `get` stay valid while the pool grows
+    node: Box<BTreeNode<K, V, ORDER>>,
+    dirty: bool,
+    // checksum of the encoded node while it is exposed by `pages_mut`, which can modify it
+    // without the store noticing. Compared with the node's encoding to find out if it is dirty.
+    exposed: Option<u64>,
+    last_used: Cell<u64>,
+}
+
+/// A file backed `PageStore` with fixed size pages.
+///
+/// Pages are cached in a buffer pool of limited capacity. Modified pages stay in the pool until
+/// `flush`, which first writes them to a write-ahead log (`<path>.wal`) and only then into the
+/// file, so a crash leaves the file in the state of the last successful flush. Changes that are
+/// not flushed are lost when the store is dropped.
+///
+/// Read-only operations load pages through a shared reference and can temporarily grow the pool
+/// past its capacity, it is shrunk again on the next modification.
+///
+/// I/O errors while loading pages during tree operations cause a panic, since the `BTree` API
+/// does not report errors; `flush` returns them. The same goes for nodes that do not fit into a
+/// page, which are detected as soon as they are written to the pool.
+pub struct FilePageStore<K, V, const ORDER: usize> {
+    file: File,
+    wal: File,
+    header: Header,
+    header_dirty: bool,
+    // in the same order as the chain of free pages in the file
+    free: VecDeque<PageId>,
+    // pages freed since the last flush, whose link to the next free page is not written yet
+    freed: HashSet<PageId>,
+    // frames must only be removed, replaced or mutated through `&mut self`, see `get`
+    pool: RefCell<HashMap<PageId, Frame<K, V, ORDER>>>,
+    pool_capacity: usize,
+    clock: Cell<u64>,
+    // the page handed out by `get_mut`, whose size is checked on the next access to the store
+    modified: Cell<Option<PageId>>,
+}
+
+impl<K: PageCodec, V: PageCodec, const ORDER: usize> FilePageStore<K, V, ORDER> {
+    /// Opens or creates the file at `path`, recovering committed changes from the write-ahead
+    /// log if the last flush was interrupted.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open_with_page_size(path, DEFAULT_PAGE_SIZE)
+    }
+
+    /// Like `open`, but uses `page_size` if a new file is created.
+    /// Existing files keep the page size they were created with.
+    pub fn open_with_page_size(path: impl AsRef<Path>, page_size: usize) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut wal = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(Self::wal_path(path))?;
+
+        Self::recover(&mut file, &mut wal)?;
+
+        let header = if file.metadata()?.len() == 0 {
+            let header = Header {
+                page_size: page_size as u32,
+                order: ORDER as u32,
+                root: None,
+                len: 0,
+                page_count: 1,
+                free_head: None,
+            };
+            let mut page = Vec::with_capacity(page_size);
+            header.encode(&mut page);
+            page.resize(page_size, 0);
+            file.write_all(&page)?;
+            file.sync_all()?;
+            header
+        } else {
+            let mut bytes = Vec::new();
+            file.seek(SeekFrom::Start(0))?;
+            (&file).take(64).read_to_end(&mut bytes)?;
+            let header = Header::decode(&mut bytes.as_slice())?;
+            if header.order != ORDER as u32 {
+                return Err(invalid_data(format!(
+                    "file was created with ORDER {} but opened with {}",
+                    header.order, ORDER
+                )));
+            }
+            header
+        };
+
+        let mut store = Self {
+            file,
+            wal,
+            header,
+            header_dirty: false,
+            free: VecDeque::new(),
+            freed: HashSet::new(),
+            pool: RefCell::new(HashMap::new()),
+            pool_capacity: DEFAULT_POOL_CAPACITY,
+            clock: Cell::new(0),
+            modified: Cell::new(None),
+        };
+
+        let mut next = store.header.free_head;
+        while let Some(id) = next {
+            store.free.push_back(id);
+            let page = store.read_page(id)?;
+            let mut input = page.as_slice();
+            if u8::decode(&mut input)? != PAGE_FREE {
+                return Err(invalid_data(format!("page {} is not a free page", id)));
+            }
+            next = Some(u32::decode(&mut input)?).filter(|&id| id != NO_PAGE);
+        }
+
+        Ok(store)
+    }
+
+    /// Sets the number of pages kept in memory. Pages modified since the last flush are always
+    /// kept, so the pool can exceed this for large transactions.
+    pub fn with_pool_capacity(mut self, pool_capacity: usize) -> Self {
+        self.pool_capacity = pool_capacity.max(1);
+        self
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.header.page_size as usize
+    }
+
+    pub fn cached_pages(&self) -> usize {
+        self.pool.borrow().len()
+    }
+
+    fn wal_path(path: &Path) -> PathBuf {
+        let mut wal = path.as_os_str().to_owned();
+        wal.push(".wal");
+        PathBuf::from(wal)
+    }
+
+    /// Re-applies the pages of a fully committed log, then empties it.
+    /// A log without a valid commit record is from an interrupted flush and is discarded.
+    fn recover(file: &mut File, wal: &mut File) -> io::Result<()> {
+        let mut data = Vec::new();
+        wal.seek(SeekFrom::Start(0))?;
+        wal.read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(records) = Self::parse_wal(&data) {
+            for (id, page) in records {
+                file.seek(SeekFrom::Start(id as u64 * page.len() as u64))?;
+                file.write_all(page)?;
+            }
+            file.sync_all()?;
+        } else {
+            log::warn!("Discarding incomplete write-ahead log");
+        }
+
+        wal.set_len(0)?;
+        wal.sync_all()
+    }
+
+    fn parse_wal(data: &[u8]) -> Option<Vec<(PageId, &[u8])>> {
+        let mut input = data;
+        let mut records = Vec::new();
+        loop {
+            let id = u32::decode(&mut input).ok()?;
+            if id == WAL_COMMIT {
+                let content = &data[..data.len() - input.len() - size_of::<u32>()];
+                let checksum = u64::decode(&mut input).ok()?;
+                return (checksum == fnv1a(content)).then_some(records);
+            }
+            let len = u32::decode(&mut input).ok()? as usize;
+            records.push((id, take_bytes(&mut input, len).ok()?));
+        }
+    }
+
+    fn read_page(&self, id: PageId) -> io::Result<Vec<u8>> {
+        let mut page = vec![0; self.page_size()];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(id as u64 * self.page_size() as u64))?;
+        file.read_exact(&mut page)?;
+        Ok(page)
+    }
+
+    fn load(&self, id: PageId) -> BTreeNode<K, V, ORDER> {
+        self.read_page(id)
+            .and_then(|page| Self::decode_node(&page))
+            .unwrap_or_else(|e| panic!("failed to load page {}: {}", id, e))
+    }
+
+    fn decode_node(page: &[u8]) -> io::Result<BTreeNode<K, V, ORDER>> {
+        let mut input = page;
+        if u8::decode(&mut input)? != PAGE_NODE {
+            return Err(invalid_data("page does not contain a node"));
+        }
+        let is_leaf = bool::decode(&mut input)?;
+        let keys = Vec::<K>::decode(&mut input)?;
+        let values = Vec::<V>::decode(&mut input)?;
+        let children = Vec::<u32>::decode(&mut input)?;
+        Ok(BTreeNode::from_parts(keys, values, children, is_leaf))
+    }
+
+    fn encode_node(&self, node: &BTreeNode<K, V, ORDER>) -> io::Result<Vec<u8>> {
+        let mut page = Vec::with_capacity(self.page_size());
+        PAGE_NODE.encode(&mut page);
+        node.is_leaf.encode(&mut page);
+        (node.keys.len() as u32).encode(&mut page);
+        node.keys.iter().for_each(|k| k.encode(&mut page));
+        (node.values.len() as u32).encode(&mut page);
+        node.values.iter().for_each(|v| v.encode(&mut page));
+        node.children.encode(&mut page);
+        self.pad(page)
+    }
+
+    fn pad(&self, mut page: Vec<u8>) -> io::Result<Vec<u8>> {
+        if page.len() > self.page_size() {
+            return Err(invalid_data(format!(
+                "node needs {} bytes but pages are {} bytes, use a smaller ORDER or larger pages",
+                page.len(),
+                self.page_size()
+            )));
+        }
+        page.resize(self.page_size(), 0);
+        Ok(page)
+    }
+
+    fn check_size(&self, id: PageId, node: &BTreeNode<K, V, ORDER>) {
+        if let Err(e) = self.encode_node(node) {
+            panic!("failed to write page {}: {}", id, e);
+        }
+    }
+
+    fn check_modified(&self) {
+        if let Some(id) = self.modified.take()
+            && let Some(frame) = self.pool.borrow().get(&id)
+        {
+            self.check_size(id, &frame.node);
+        }
+    }
+
+    /// Marks the pages exposed by `pages_mut` as dirty if their content changed.
+    fn check_exposed(&self) {
+        for frame in self.pool.borrow_mut().values_mut() {
+            if let Some(checksum) = frame.exposed.take() {
+                match self.encode_node(&frame.node) {
+                    Ok(page) => frame.dirty |= fnv1a(&page) != checksum,
+                    // kept, so `flush` reports that the node no longer fits
+                    Err(_) => frame.dirty = true,
+                }
+            }
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        let tick = self.clock.get() + 1;
+        self.clock.set(tick);
+        tick
+    }
+
+    fn frame_mut(&mut self, id: PageId) -> &mut Frame<K, V, ORDER> {
+        self.check_modified();
+        self.evict();
+        let tick = self.tick();
+        if !self.pool.get_mut().contains_key(&id) {
+            let node = self.load(id);
+            self.pool.get_mut().insert(id, Frame::new(node, false));
+        }
+        let frame = self.pool.get_mut().get_mut(&id).unwrap();
+        frame.last_used.set(tick);
+        frame
+    }
+
+    /// Drops the least recently used clean pages once the pool exceeds its capacity.
+    /// Shrinks to 3/4 of the capacity, so this does not run on every access.
+    fn evict(&mut self) {
+        let capacity = self.pool_capacity;
+        if self.pool.get_mut().len() <= capacity {
+            return;
+        }
+
+        self.check_exposed();
+        let pool = self.pool.get_mut();
+        let mut clean: Vec<(u64, PageId)> = pool
+            .iter()
+            .filter(|(_, frame)| !frame.dirty)
+            .map(|(&id, frame)| (frame.last_used.get(), id))
+            .collect();
+        clean.sort_unstable();
+
+        let excess = pool.len() - capacity * 3 / 4;
+        for (_, id) in clean.into_iter().take(excess) {
+            pool.remove(&id);
+        }
+    }
+
+    /// Writes all changes since the last flush to the write-ahead log and returns the pages that
+    /// still need to be written to the file.
+    fn write_wal(&mut self) -> io::Result<Vec<(PageId, Vec<u8>)>> {
+        self.modified.set(None);
+        self.check_exposed();
+        let mut pages = Vec::new();
+        for (&id, frame) in self.pool.borrow().iter() {
+            if frame.dirty {
+                pages.push((id, self.encode_node(&frame.node)?));
+            }
+        }
+
+        for (i, &id) in self.free.iter().enumerate() {
+            if self.freed.contains(&id) {
+                let mut page = Vec::new();
+                PAGE_FREE.encode(&mut page);
+                self.free
+                    .get(i + 1)
+                    .copied()
+                    .unwrap_or(NO_PAGE)
+                    .encode(&mut page);
+                pages.push((id, self.pad(page)?));
+            }
+        }
+
+        if pages.is_empty() && !self.header_dirty {
+            return Ok(pages);
+        }
+
+        self.header.free_head = self.free.front().copied();
+        let mut header = Vec::new();
+        self.header.encode(&mut header);
+        pages.push((HEADER_PAGE, self.pad(header)?));
+        pages.sort_unstable_by_key(|(id, _)| *id);
+
+        let mut log = Vec::new();
+        for (id, page) in &pages {
+            id.encode(&mut log);
+            (page.len() as u32).encode(&mut log);
+            log.extend_from_slice(page);
+        }
+        let checksum = fnv1a(&log);
+        WAL_COMMIT.encode(&mut log);
+        checksum.encode(&mut log);
+
+        self.wal.set_len(0)?;
+        self.wal.seek(SeekFrom::Start(0))?;
+        self.wal.write_all(&log)?;
+        self.wal.sync_all()?;
+        Ok(pages)
+    }
+
+    fn apply(&mut self, pages: Vec<(PageId, Vec<u8>)>) -> io::Result<()> {
+        for (id, page) in &pages {
+            self.file
+                .seek(SeekFrom::Start(*id as u64 * self.page_size() as u64))?;
+            self.file.write_all(page)?;
+        }
+        let size = self.header.page_count as u64 * self.page_size() as u64;
+        if self.file.metadata()?.len() > size {
+            self.file.set_len(size)?;
+        }
+        self.file.sync_all()?;
+
+        self.wal.set_len(0)?;
+        self.wal.sync_all()?;
+
+        for frame in self.pool.get_mut().values_mut() {
+            frame.dirty = false;
+        }
+        self.freed.clear();
+        self.header_dirty = false;
+        self.evict();
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(crate) fn flush_wal_only(&mut self) -> io::Result<()> {
+        self.write_wal().map(|_| ())
+    }
+}
+
+impl<K, V, const ORDER: usize> Frame<K, V, ORDER> {
+    fn new(node: BTreeNode<K, V, ORDER>, dirty: bool) -> Self {
+        Self {
+            node: Box::new(node),
+            dirty,
+            exposed: None,
+            last_used: Cell::new(0),
+        }
+    }
+}
+
+impl<K: PageCodec, V: PageCodec, const ORDER: usize> PageStore<K, V, ORDER>
+    for FilePageStore<K, V, ORDER>
+{
+    fn root(&self) -> Option<PageId> {
+        self.header.root
+    }
+
+    fn set_root(&mut self, root: Option<PageId>) {
+        self.check_modified();
+        self.header.root = root;
+        self.header_dirty = true;
+    }
+
+    fn len(&self) -> usize {
+        self.header.len as usize
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.check_modified();
+        self.header.len = len as u64;
+        self.header_dirty = true;
+    }
+
+    fn allocate(&mut self, node: BTreeNode<K, V, ORDER>) -> PageId {
+        let id = match self.free.pop_front() {
+            Some(id) => {
+                self.freed.remove(&id);
+                id
+            }
+            None => {
+                self.header.page_count += 1;
+                self.header.page_count - 1
+            }
+        };
+        self.header_dirty = true;
+        self.put(id, node);
+        id
+    }
+
+    fn free(&mut self, id: PageId) {
+        self.check_modified();
+        self.pool.get_mut().remove(&id);
+        self.free.push_front(id);
+        self.freed.insert(id);
+        self.header_dirty = true;
+    }
+
+    fn get(&self, id: PageId) -> &BTreeNode<K, V, ORDER> {
+        self.check_modified();
+        let tick = self.tick();
+        // only frames that are not in the pool yet are inserted, existing ones are never
+        // borrowed mutably here, as nodes of earlier calls may still be borrowed
+        if !self.pool.borrow().contains_key(&id) {
+            let frame = Frame::new(self.load(id), false);
+            self.pool.borrow_mut().insert(id, frame);
+        }
+        let pool = self.pool.borrow();
+        let frame = &pool[&id];
+        frame.last_used.set(tick);
+        let node: *const BTreeNode<K, V, ORDER> = &*frame.node;
+        // SAFETY: the returned reference outlives the `RefCell` borrow, which is sound as long as
+        // frames are never evicted, replaced or mutated while it is alive:
+        // - nodes are boxed, so they do not move when inserting into the pool moves the frames
+        // - frames are only removed or replaced (`evict`, `take`, `put`, `free`, `clear`) and
+        //   only borrowed mutably (`get_mut`, `pages_mut`, `check_exposed` via `evict` and
+        //   `flush`) by methods taking `&mut self`, which the borrow checker rejects while the
+        //   reference bound to `&self` is alive
+        // - methods taking `&self` only insert new frames and update `Cell`s of existing ones
+        unsafe { &*node }
+    }
+
+    fn get_mut(&mut self, id: PageId) -> &mut BTreeNode<K, V, ORDER> {
+        self.modified.set(Some(id));
+        let frame = self.frame_mut(id);
+        frame.dirty = true;
+        &mut frame.node
+    }
+
+    fn take(&mut self, id: PageId) -> BTreeNode<K, V, ORDER> {
+        self.check_modified();
+        match self.pool.get_mut().remove(&id) {
+            Some(frame) => *frame.node,
+            None => self.load(id),
+        }
+    }
+
+    fn put(&mut self, id: PageId, node: BTreeNode<K, V, ORDER>) {
+        self.check_modified();
+        self.check_size(id, &node);
+        self.evict();
+        let frame = Frame::new(node, true);
+        frame.last_used.set(self.tick());
+        self.pool.get_mut().insert(id, frame);
+    }
+
+    /// Loads the whole tree into the pool, as the references have to stay valid at once.
+    /// Instead of marking every page as modified, the pages remember their checksum and are
+    /// compared on the next eviction or flush, so only pages that actually changed are written.
+    fn pages_mut(&mut self) -> Vec<(PageId, &mut BTreeNode<K, V, ORDER>)> {
+        let mut pending: Vec<PageId> = self.header.root.into_iter().collect();
+        while let Some(id) = pending.pop() {
+            pending.extend_from_slice(&self.get(id).children);
+        }
+
+        for frame in self.pool.borrow_mut().values_mut() {
+            if !frame.dirty && frame.exposed.is_none() {
+                frame.exposed = self.encode_node(&frame.node).ok().map(|page| fnv1a(&page));
+            }
+        }
+
+        self.pool
+            .get_mut()
+            .iter_mut()
+            .map(|(&id, frame)| (id, &mut *frame.node))
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.modified.set(None);
+        self.pool.get_mut().clear();
+        self.free.clear();
+        self.freed.clear();
+        self.header.root = None;
+        self.header.len = 0;
+        self.header.page_count = 1;
+        self.header_dirty = true;
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let pages = self.write_wal()?;
+        if pages.is_empty() {
+            return Ok(());
+        }
+        self.apply(pages)
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -1,5 +1,9 @@
 pub mod btree;
+pub mod file_page_store;
+pub mod page_store;
 pub mod rtree;
 
 pub use btree::BTree;
+pub use file_page_store::FilePageStore;
+pub use page_store::{MemoryPageStore, PageCodec, PageId, PageStore};
 pub use rtree::{RTreeNode, Rect};
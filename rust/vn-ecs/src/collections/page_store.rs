@@ -0,0 +1,259 @@
+use crate::collections::btree::BTreeNode;
+use crate::entity::Entity;
+use std::io;
+
+pub type PageId = u32;
+
+/// Storage for the nodes of a `BTree`. Every node lives in its own page, addressed by a `PageId`.
+///
+/// Nodes are read through `get` and modified either in place via `get_mut` or by moving them
+/// out with `take` and back in with `put`, which allows the tree to work on several nodes at
+/// once (e.g. when merging siblings).
+pub trait PageStore<K, V, const ORDER: usize> {
+    fn root(&self) -> Option<PageId>;
+    fn set_root(&mut self, root: Option<PageId>);
+    fn len(&self) -> usize;
+    fn set_len(&mut self, len: usize);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn allocate(&mut self, node: BTreeNode<K, V, ORDER>) -> PageId;
+    fn free(&mut self, id: PageId);
+    fn get(&self, id: PageId) -> &BTreeNode<K, V, ORDER>;
+    fn get_mut(&mut self, id: PageId) -> &mut BTreeNode<K, V, ORDER>;
+    fn take(&mut self, id: PageId) -> BTreeNode<K, V, ORDER>;
+    fn put(&mut self, id: PageId, node: BTreeNode<K, V, ORDER>);
+
+    /// Mutable access to every page of the tree at once, used for mutable iteration.
+    fn pages_mut(&mut self) -> Vec<(PageId, &mut BTreeNode<K, V, ORDER>)>;
+
+    /// Releases all pages.
+    fn clear(&mut self);
+
+    /// Persists all changes, if the store is persistent.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The default store, keeping all nodes in memory.
+pub struct MemoryPageStore<K, V, const ORDER: usize> {
+    pages: Vec<Option<BTreeNode<K, V, ORDER>>>,
+    free: Vec<PageId>,
+    root: Option<PageId>,
+    len: usize,
+}
+
+impl<K, V, const ORDER: usize> MemoryPageStore<K, V, ORDER> {
+    pub fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            len: 0,
+        }
+    }
+}
+
+impl<K, V, const ORDER: usize> Default for MemoryPageStore<K, V, ORDER> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const ORDER: usize> PageStore<K, V, ORDER> for MemoryPageStore<K, V, ORDER> {
+    fn root(&self) -> Option<PageId> {
+        self.root
+    }
+
+    fn set_root(&mut self, root: Option<PageId>) {
+        self.root = root;
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn set_len(&mut self, len: usize) {
+        self.len = len;
+    }
+
+    fn allocate(&mut self, node: BTreeNode<K, V, ORDER>) -> PageId {
+        if let Some(id) = self.free.pop() {
+            self.pages[id as usize] = Some(node);
+            id
+        } else {
+            self.pages.push(Some(node));
+            (self.pages.len() - 1) as PageId
+        }
+    }
+
+    fn free(&mut self, id: PageId) {
+        self.pages[id as usize] = None;
+        self.free.push(id);
+    }
+
+    fn get(&self, id: PageId) -> &BTreeNode<K, V, ORDER> {
+        self.pages[id as usize]
+            .as_ref()
+            .expect("page is not in use")
+    }
+
+    fn get_mut(&mut self, id: PageId) -> &mut BTreeNode<K, V, ORDER> {
+        self.pages[id as usize]
+            .as_mut()
+            .expect("page is not in use")
+    }
+
+    fn take(&mut self, id: PageId) -> BTreeNode<K, V, ORDER> {
+        self.pages[id as usize].take().expect("page is not in use")
+    }
+
+    fn put(&mut self, id: PageId, node: BTreeNode<K, V, ORDER>) {
+        self.pages[id as usize] = Some(node);
+    }
+
+    fn pages_mut(&mut self) -> Vec<(PageId, &mut BTreeNode<K, V, ORDER>)> {
+        self.pages
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(id, page)| page.as_mut().map(|node| (id as PageId, node)))
+            .collect()
+    }
+
+    fn clear(&mut self) {
+        self.pages.clear();
+        self.free.clear();
+        self.root = None;
+        self.len = 0;
+    }
+}
+
+/// Binary encoding of keys and values for persistent page stores.
+pub trait PageCodec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(input: &mut &[u8]) -> io::Result<Self>;
+}
+
+pub(crate) fn take_bytes<'a>(input: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "page ended unexpectedly",
+        ));
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+macro_rules! impl_page_codec_for_num {
+    ($($t:ty),*) => {
+        $(
+            impl PageCodec for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn decode(input: &mut &[u8]) -> io::Result<Self> {
+                    let bytes = take_bytes(input, size_of::<$t>())?;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_page_codec_for_num!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl PageCodec for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        usize::try_from(u64::decode(input)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl PageCodec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(u8::decode(input)? != 0)
+    }
+}
+
+impl PageCodec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let len = u32::decode(input)? as usize;
+        let bytes = take_bytes(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<T: PageCodec> PageCodec for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        let len = u32::decode(input)? as usize;
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+}
+
+impl<T: PageCodec> PageCodec for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_some().encode(out);
+        if let Some(value) = self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        if bool::decode(input)? {
+            Ok(Some(T::decode(input)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<A: PageCodec, B: PageCodec> PageCodec for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl PageCodec for Entity {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.id.encode(out);
+        self.generation.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        Ok(Entity {
+            id: u32::decode(input)?,
+            generation: u32::decode(input)?,
+        })
+    }
+}
@@ -0,0 +1,40 @@
+use crate::entity::Entity;
+use std::any::{Any, TypeId};
+
+pub type EqFn = fn(&dyn Any, &dyn Any) -> bool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentDiff {
+    pub entity: Entity,
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    pub change: ComponentChange,
+}
+
+/// The result of `World::diff`, describing how to get from the first world to the second.
+/// All lists are sorted by entity so diffs are stable and can be used in golden tests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorldDiff {
+    pub added_entities: Vec<Entity>,
+    pub removed_entities: Vec<Entity>,
+    pub components: Vec<ComponentDiff>,
+}
+
+impl WorldDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_entities.is_empty()
+            && self.removed_entities.is_empty()
+            && self.components.is_empty()
+    }
+
+    pub fn changes_for(&self, entity: Entity) -> impl Iterator<Item = &ComponentDiff> {
+        self.components.iter().filter(move |c| c.entity == entity)
+    }
+}
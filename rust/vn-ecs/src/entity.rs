@@ -14,6 +14,9 @@ impl Entity {
 
 pub struct EntityManager {
     pub(crate) generations: Vec<u32>,
+    // whether the current generation of each slot is spawned, so free slots don't have to be
+    // looked up in `free_indices`
+    alive: Vec<bool>,
     free_indices: VecDeque<u32>,
 }
 
@@ -21,12 +24,14 @@ impl EntityManager {
     pub fn new() -> Self {
         Self {
             generations: Vec::new(),
+            alive: Vec::new(),
             free_indices: VecDeque::new(),
         }
     }
 
     pub fn spawn(&mut self) -> Entity {
         if let Some(id) = self.free_indices.pop_front() {
+            self.alive[id as usize] = true;
             Entity {
                 id,
                 generation: self.generations[id as usize],
@@ -34,6 +39,7 @@ impl EntityManager {
         } else {
             let id = self.generations.len() as u32;
             self.generations.push(0);
+            self.alive.push(true);
             Entity { id, generation: 0 }
         }
     }
@@ -42,6 +48,7 @@ impl EntityManager {
         let index = entity.id as usize;
         if self.is_alive(entity) {
             self.generations[index] += 1;
+            self.alive[index] = false;
             self.free_indices.push_back(entity.id);
             true
         } else {
@@ -49,8 +56,22 @@ impl EntityManager {
         }
     }
 
+    pub fn alive(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.generations
+            .iter()
+            .zip(&self.alive)
+            .enumerate()
+            .filter(|(_, (_, alive))| **alive)
+            .map(|(id, (&generation, _))| Entity {
+                id: id as u32,
+                generation,
+            })
+    }
+
     pub fn is_alive(&self, entity: Entity) -> bool {
         let index = entity.id as usize;
-        index < self.generations.len() && self.generations[index] == entity.generation
+        index < self.generations.len()
+            && self.alive[index]
+            && self.generations[index] == entity.generation
     }
 }
@@ -54,8 +54,7 @@ impl<T: Any, V: Ord + Clone + Any, const ORDER: usize> BTreeIndex<T, V, ORDER> {
     pub fn query_range(&self, range: std::ops::RangeInclusive<V>) -> Vec<Entity> {
         self.map
             .range(range)
-            .into_iter()
-            .flat_map(|(_, entities)| entities.into_iter())
+            .flat_map(|(_, entities)| entities.iter().copied())
             .collect()
     }
 }
@@ -1,15 +1,21 @@
 pub mod collections;
+pub mod diff;
 pub mod entity;
 pub mod index;
+pub mod query;
+pub mod replay;
 pub mod storage;
 pub mod system;
 pub mod world;
 
+pub use diff::{ComponentChange, ComponentDiff, WorldDiff};
 pub use entity::{Entity, EntityManager};
 pub use index::{BTreeIndex, BTreeIndexBuilder, Index, RTreeIndex, RTreeIndexBuilder};
-pub use storage::{ComponentStorage, SparseSet};
+pub use query::{CachedQuery, QueryId};
+pub use replay::{RecordedStorage, RecordedValue, Recording, WorldCommand};
+pub use storage::{ComponentStorage, DenseOrder, SparseSet};
 pub use system::{System, SystemManager};
-pub use world::World;
+pub use world::{EntityMap, World};
 
 #[cfg(test)]
 mod tests {
@@ -504,4 +510,816 @@ mod tests {
         sm.run(&mut world);
         assert_eq!(world.get_component::<Counter>(e).unwrap().0, 2);
     }
+
+    #[test]
+    fn test_world_diff() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Pos(i32);
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Health(i32);
+
+        let mut a = World::new();
+        a.register_comparable::<Pos>();
+        let e1 = a.spawn();
+        a.add_component(e1, Pos(0));
+        a.add_component(e1, Health(10));
+        let e2 = a.spawn();
+        a.add_component(e2, Pos(5));
+
+        let mut b = World::new();
+        let b1 = b.spawn();
+        b.add_component(b1, Pos(1));
+        b.add_component(b1, Health(5));
+        b.add_component(b1, true);
+        let b2 = b.spawn();
+        b.despawn(b2);
+        let b3 = b.spawn();
+        assert_eq!((e1, e2.id), (b1, b3.id));
+
+        assert!(a.diff(&a).is_empty());
+
+        let diff = a.diff(&b);
+        assert_eq!(diff.added_entities, vec![b3]);
+        assert_eq!(diff.removed_entities, vec![e2]);
+
+        // Health is not comparable, so only the changed Pos and the added bool are reported
+        let changes: Vec<_> = diff
+            .changes_for(e1)
+            .map(|c| (c.type_id, c.change))
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.contains(&(TypeId::of::<Pos>(), ComponentChange::Changed)));
+        assert!(changes.contains(&(TypeId::of::<bool>(), ComponentChange::Added)));
+
+        let diff = b.diff(&a);
+        assert!(
+            diff.changes_for(e1)
+                .any(|c| c.type_id == TypeId::of::<bool>() && c.change == ComponentChange::Removed)
+        );
+    }
+
+    #[test]
+    fn test_recording_replay() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Position(i32);
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Velocity(i32);
+
+        struct MovementSystem;
+        impl System for MovementSystem {
+            fn run(&mut self, world: &mut World) {
+                let entities = world
+                    .query_entities_with_all(&[TypeId::of::<Position>(), TypeId::of::<Velocity>()]);
+                for entity in entities {
+                    let vel = *world.get_component::<Velocity>(entity).unwrap();
+                    let pos = *world.get_component::<Position>(entity).unwrap();
+                    world.add_component(entity, Position(pos.0 + vel.0));
+                }
+            }
+        }
+
+        let setup = |world: &mut World| {
+            world.register_cloneable::<Position>();
+            world.register_cloneable::<Velocity>();
+            world.register_comparable::<Position>();
+            world.register_comparable::<Velocity>();
+        };
+
+        let mut world = World::new();
+        setup(&mut world);
+        world.start_recording();
+
+        let e = world.spawn();
+        world.add_component(e, Position(0));
+        world.add_component(e, Velocity(2));
+        let temp = world.spawn();
+        world.add_component(temp, Position(100));
+
+        let mut sm = SystemManager::new();
+        sm.add_system(MovementSystem);
+        sm.run(&mut world);
+        world.despawn(temp);
+        sm.run(&mut world);
+
+        let recording = world.stop_recording().unwrap();
+        assert!(!world.is_recording());
+        // two closed ticks plus the (empty) current one
+        assert_eq!(recording.ticks().len(), 3);
+        assert!(matches!(recording.tick(0).unwrap()[0], WorldCommand::Spawn(s) if s == e));
+        assert!(matches!(recording.tick(1).unwrap()[0], WorldCommand::Despawn(d) if d == temp));
+
+        let mut replayed = World::new();
+        setup(&mut replayed);
+        recording.replay(&mut replayed).unwrap();
+        assert!(world.diff(&replayed).is_empty());
+        assert_eq!(replayed.get_component::<Position>(e), Some(&Position(4)));
+
+        // replaying on a world in a different state desyncs
+        let mut desynced = World::new();
+        desynced.spawn();
+        assert!(recording.replay(&mut desynced).is_err());
+    }
+
+    #[test]
+    fn test_recording_without_cloneable() {
+        let mut world = World::new();
+        world.start_recording();
+        let e = world.spawn();
+        world.add_component(e, 1u8);
+
+        let recording = world.stop_recording().unwrap();
+        match &recording.tick(0).unwrap()[1] {
+            WorldCommand::AddComponent { component, .. } => assert!(!component.is_replayable()),
+            _ => panic!("expected an AddComponent command"),
+        }
+        assert!(recording.replay(&mut World::new()).is_err());
+    }
+
+    #[test]
+    fn test_recording_storage_and_noop_removals() {
+        struct Tag;
+
+        let mut world = World::new();
+        world.register_cloneable::<i32>();
+        world.start_recording();
+        world
+            .register_storage::<i32>(Box::new(SparseSet::<i32>::new()))
+            .unwrap();
+        let e = world.spawn();
+        world.add_component(e, 1i32);
+
+        // nothing to remove, so nothing is recorded
+        world.remove_component::<u8>(e);
+        world.remove_resource::<u8>();
+        world.remove_named_resource::<u8>("missing");
+        world.untag_component::<i32, Tag>(e);
+
+        let recording = world.stop_recording().unwrap();
+        let tick = recording.tick(0).unwrap();
+        assert_eq!(tick.len(), 3);
+        assert!(
+            matches!(&tick[0], WorldCommand::RegisterStorage(s) if s.type_id == TypeId::of::<i32>())
+        );
+
+        let mut replayed = World::new();
+        replayed.register_cloneable::<i32>();
+        recording.replay(&mut replayed).unwrap();
+        assert_eq!(replayed.get_component::<i32>(e), Some(&1));
+        // the storage was registered by the replay
+        assert!(
+            replayed
+                .register_storage::<i32>(Box::new(SparseSet::<i32>::new()))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_alive_after_respawn() {
+        let mut entities = EntityManager::new();
+        let a = entities.spawn();
+        let b = entities.spawn();
+        entities.despawn(a);
+        assert_eq!(entities.alive().collect::<Vec<_>>(), vec![b]);
+        assert!(!entities.is_alive(a));
+
+        let c = entities.spawn();
+        assert_eq!(c.id, a.id);
+        assert!(!entities.is_alive(a));
+        assert!(entities.is_alive(c));
+        assert_eq!(entities.alive().count(), 2);
+    }
+
+    #[test]
+    fn test_recording_moved_and_copied_entities() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Pos(i32);
+        struct Selected;
+
+        let mut source = World::new();
+        source.register_cloneable::<Pos>();
+        source.register_comparable::<Pos>();
+        let moved = source.spawn();
+        source.add_component(moved, Pos(1));
+        source.tag_component::<Pos, Selected>(moved);
+        let copied = source.spawn();
+        source.add_component(copied, Pos(2));
+
+        let mut target = World::new();
+        target.start_recording();
+        let mut mapping = source.move_entities(&[moved], &mut target);
+        source.copy_entities(&[copied], &mut target, &mut mapping);
+        let recording = target.stop_recording().unwrap();
+
+        let tick = recording.tick(0).unwrap();
+        assert!(
+            matches!(&tick[1], WorldCommand::AddComponent { component, .. } if component.is_replayable())
+        );
+        assert!(matches!(&tick[2], WorldCommand::TagComponent { .. }));
+
+        let mut replayed = World::new();
+        recording.replay(&mut replayed).unwrap();
+        assert!(target.diff(&replayed).is_empty());
+        assert_eq!(
+            replayed.get_component::<Pos>(mapping[&copied]),
+            Some(&Pos(2))
+        );
+        assert!(replayed.has_tag::<Pos, Selected>(mapping[&moved]));
+    }
+
+    #[test]
+    fn test_move_entities() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Pos {
+            x: i32,
+        }
+        struct Selected;
+
+        let mut source = World::new();
+        source.add_index::<Pos, BTreeIndex<Pos, i32>, _>(BTreeIndexBuilder::new(|p: &Pos| p.x));
+        let e1 = source.spawn();
+        source.add_component(e1, Pos { x: 1 });
+        source.add_component(e1, 10i32);
+        source.tag_component::<Pos, Selected>(e1);
+        let e2 = source.spawn();
+        source.add_component(e2, Pos { x: 2 });
+
+        let mut target = World::new();
+        target.add_index::<Pos, BTreeIndex<Pos, i32>, _>(BTreeIndexBuilder::new(|p: &Pos| p.x));
+        let existing = target.spawn();
+
+        let mapping = source.move_entities(&[e1], &mut target);
+        let moved = mapping[&e1];
+        assert_ne!(moved, existing);
+
+        // gone from the source, including its index
+        assert!(source.get_component::<Pos>(e1).is_none());
+        assert_eq!(source.query_entities_with::<i32>(), vec![]);
+        assert_eq!(
+            source
+                .get_index::<Pos, BTreeIndex<Pos, i32>>()
+                .unwrap()
+                .query_range(0..=10),
+            vec![e2]
+        );
+
+        // present in the target, including tags and its index
+        assert_eq!(target.get_component::<Pos>(moved), Some(&Pos { x: 1 }));
+        assert_eq!(target.get_component::<i32>(moved), Some(&10));
+        assert!(target.has_tag::<Pos, Selected>(moved));
+        assert_eq!(
+            target
+                .get_index::<Pos, BTreeIndex<Pos, i32>>()
+                .unwrap()
+                .query_range(0..=10),
+            vec![moved]
+        );
+    }
+
+    #[test]
+    fn test_copy_entities() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Pos(i32);
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Sprite(u32);
+        struct SimOnly;
+
+        let mut sim = World::new();
+        sim.register_cloneable::<Pos>();
+        sim.register_cloneable::<Sprite>();
+        let e = sim.spawn();
+        sim.add_component(e, Pos(1));
+        sim.add_component(e, Sprite(7));
+        sim.add_component(e, SimOnly);
+
+        let mut render = World::new();
+        let mut mapping = EntityMap::new();
+        sim.copy_entities(&[e], &mut render, &mut mapping);
+
+        let r = mapping[&e];
+        assert_eq!(render.get_component::<Pos>(r), Some(&Pos(1)));
+        assert_eq!(render.get_component::<Sprite>(r), Some(&Sprite(7)));
+        assert!(render.get_component::<SimOnly>(r).is_none());
+        // the source is left untouched
+        assert_eq!(sim.get_component::<Pos>(e), Some(&Pos(1)));
+
+        // a second extraction updates the previously copied entity
+        sim.add_component(e, Pos(2));
+        sim.remove_component::<Sprite>(e);
+        sim.copy_entities(&[e], &mut render, &mut mapping);
+
+        assert_eq!(mapping[&e], r);
+        assert_eq!(render.query_entities_with::<Pos>(), vec![r]);
+        assert_eq!(render.get_component::<Pos>(r), Some(&Pos(2)));
+        assert!(render.get_component::<Sprite>(r).is_none());
+    }
+
+    #[test]
+    fn test_moved_and_copied_storages_keep_their_order() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Z(i32);
+
+        let mut source = World::new();
+        source.register_cloneable::<Z>();
+        source
+            .register_storage::<Z>(Box::new(SparseSet::<Z>::with_order(DenseOrder::By(
+                |a: &Z, b: &Z| a.0.cmp(&b.0),
+            ))))
+            .unwrap();
+        let entities: Vec<Entity> = [3, 1, 2]
+            .into_iter()
+            .map(|z| {
+                let entity = source.spawn();
+                source.add_component(entity, Z(z));
+                entity
+            })
+            .collect();
+
+        let z_order = |world: &World| -> Vec<i32> {
+            world
+                .query_entities_with::<Z>()
+                .into_iter()
+                .map(|entity| world.get_component::<Z>(entity).unwrap().0)
+                .collect()
+        };
+
+        let mut copied = World::new();
+        source.copy_entities(&entities, &mut copied, &mut EntityMap::new());
+        assert_eq!(z_order(&copied), vec![1, 2, 3]);
+
+        let mut moved = World::new();
+        source.move_entities(&entities, &mut moved);
+        assert_eq!(z_order(&moved), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sparse_set_paging() {
+        let mut set = SparseSet::<u8>::new();
+        let empty_size = set.sparse_size_in_bytes();
+
+        set.insert(5_000_000, 1);
+        set.insert(5_000_001, 2);
+        set.insert(3, 3);
+        assert_eq!(set.get(5_000_000), Some(&1));
+        assert_eq!(set.get(5_000_001), Some(&2));
+        assert_eq!(set.get(3), Some(&3));
+        assert_eq!(set.get(4_999_999), None);
+        assert_eq!(set.get(u32::MAX), None);
+
+        // two pages instead of 5 million entries
+        let pages_size = set.sparse_size_in_bytes() - empty_size;
+        assert!(pages_size < 5_000_000 / 10, "{}", pages_size);
+
+        assert_eq!(set.remove(5_000_000), Some(1));
+        assert_eq!(set.remove(5_000_001), Some(2));
+        assert_eq!(set.remove(5_000_001), None);
+        assert_eq!(set.get(3), Some(&3));
+        assert!(set.sparse_size_in_bytes() < pages_size);
+        assert_eq!(set.len(), 1);
+    }
+
+    #[test]
+    fn test_sparse_set_ordering() {
+        let mut set = SparseSet::<i32>::with_order(DenseOrder::EntityId);
+        for id in [5, 1, 9, 3, 7] {
+            set.insert(id, id as i32 * 10);
+        }
+        set.remove(3);
+        set.insert(0, 0);
+        let ids: Vec<u32> = set.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![0, 1, 5, 7, 9]);
+        assert_eq!(set.get(7), Some(&70));
+
+        // draw order by z-index, ties keep insertion order
+        let mut set = SparseSet::<i32>::with_order(DenseOrder::By(|a, b| a.cmp(b)));
+        set.insert(1, 3);
+        set.insert(2, 1);
+        set.insert(3, 2);
+        set.insert(4, 1);
+        let ids: Vec<u32> = set.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![2, 4, 3, 1]);
+
+        // replacing a component repositions it
+        set.insert(2, 5);
+        let ids: Vec<u32> = set.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![4, 3, 1, 2]);
+
+        // mutating the key requires an explicit sort
+        *set.get_mut(1).unwrap() = 0;
+        set.sort();
+        let values: Vec<i32> = set.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![0, 1, 2, 5]);
+        for (id, value) in [(1, 0), (2, 5), (3, 2), (4, 1)] {
+            assert_eq!(set.get(id), Some(&value));
+        }
+
+        set.set_order(DenseOrder::EntityId);
+        let ids: Vec<u32> = set.iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4]);
+    }
+
+    // xorshift64, so the randomized tests are reproducible without pulling in a dependency
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+
+        fn bound(&mut self, key: i32) -> std::ops::Bound<i32> {
+            match self.below(3) {
+                0 => std::ops::Bound::Unbounded,
+                1 => std::ops::Bound::Included(key),
+                _ => std::ops::Bound::Excluded(key),
+            }
+        }
+    }
+
+    // every node but the root is at least half full and all leaves are at the same depth
+    fn check_btree_nodes<const ORDER: usize>(
+        tree: &crate::collections::btree::BTree<i32, i32, ORDER>,
+    ) {
+        use crate::collections::page_store::{PageId, PageStore};
+
+        fn check<const ORDER: usize>(
+            store: &impl PageStore<i32, i32, ORDER>,
+            id: PageId,
+            is_root: bool,
+        ) -> usize {
+            let node = store.get(id);
+            assert!(node.keys().len() < ORDER);
+            assert!(is_root || node.keys().len() >= ORDER / 2 - 1);
+            if node.is_leaf() {
+                return 1;
+            }
+            assert_eq!(node.children().len(), node.keys().len() + 1);
+            let depths: Vec<usize> = node
+                .children()
+                .iter()
+                .map(|&child| check(store, child, false))
+                .collect();
+            assert!(depths.iter().all(|&depth| depth == depths[0]));
+            depths[0] + 1
+        }
+
+        if let Some(root) = tree.store().root() {
+            assert!(!tree.store().get(root).keys().is_empty());
+            check(tree.store(), root, true);
+        }
+    }
+
+    fn check_btree_against_std<const ORDER: usize>(seed: u64) {
+        use crate::collections::btree::BTree;
+        use std::collections::BTreeMap;
+        use std::ops::Bound;
+
+        let mut rng = TestRng(seed);
+        let mut tree: BTree<i32, i32, ORDER> = BTree::new();
+        let mut reference = BTreeMap::new();
+
+        for step in 0..3000 {
+            let key = rng.below(300) as i32;
+            match rng.below(12) {
+                0..=3 => assert_eq!(tree.insert(key, step), reference.insert(key, step)),
+                4..=5 => assert_eq!(tree.remove(&key), reference.remove(&key)),
+                6 => {
+                    *tree.entry(key).and_modify(|v| *v *= 2).or_insert(step) += 1;
+                    *reference.entry(key).and_modify(|v| *v *= 2).or_insert(step) += 1;
+                }
+                7 => {
+                    assert_eq!(tree.first_key_value(), reference.first_key_value());
+                    assert_eq!(tree.last_key_value(), reference.last_key_value());
+                    if key % 2 == 0 {
+                        assert_eq!(tree.pop_first(), reference.pop_first());
+                    } else {
+                        assert_eq!(tree.pop_last(), reference.pop_last());
+                    }
+                }
+                8..=9 => {
+                    let other = rng.below(300) as i32;
+                    let (lo, hi) = (key.min(other), key.max(other));
+                    let mut start = rng.bound(lo);
+                    let end = rng.bound(hi);
+                    // std panics on an empty range with both ends excluded
+                    if lo == hi && start == Bound::Excluded(lo) && end == Bound::Excluded(hi) {
+                        start = Bound::Included(lo);
+                    }
+
+                    let mut range = tree.range((start, end));
+                    let mut expected = reference.range((start, end));
+                    loop {
+                        let (actual, wanted) = if rng.below(2) == 0 {
+                            (range.next(), expected.next())
+                        } else {
+                            (range.next_back(), expected.next_back())
+                        };
+                        assert_eq!(actual, wanted);
+                        if actual.is_none() {
+                            break;
+                        }
+                    }
+                }
+                10 => {
+                    let mut split = tree.split_off(&key);
+                    let mut expected = reference.split_off(&key);
+                    assert!(split.iter().eq(expected.iter()));
+                    assert!(tree.iter().eq(reference.iter()));
+                    check_btree_nodes(&tree);
+                    check_btree_nodes(&split);
+                    // an overlapping key, whose value is taken from the appended tree
+                    split.insert(key - 1, step);
+                    expected.insert(key - 1, step);
+                    if rng.below(2) == 0 {
+                        tree.append(&mut split);
+                        reference.append(&mut expected);
+                    } else {
+                        split.append(&mut tree);
+                        expected.append(&mut reference);
+                        std::mem::swap(&mut tree, &mut split);
+                        std::mem::swap(&mut reference, &mut expected);
+                    }
+                    assert!(split.is_empty());
+                    check_btree_nodes(&tree);
+                }
+                _ => {
+                    for (k, v) in tree.iter_mut().rev().take(5) {
+                        *v += *k;
+                    }
+                    for (k, v) in reference.iter_mut().rev().take(5) {
+                        *v += *k;
+                    }
+                }
+            }
+            assert_eq!(tree.len(), reference.len());
+        }
+
+        assert!(tree.iter().eq(reference.iter()));
+        assert!(tree.iter().rev().eq(reference.iter().rev()));
+        assert!(tree.keys().eq(reference.keys()));
+        assert!(tree.values().eq(reference.values()));
+        assert_eq!(tree.iter().len(), reference.len());
+        assert!(tree.into_iter().rev().eq(reference.into_iter().rev()));
+    }
+
+    #[test]
+    fn test_btree_against_std() {
+        for seed in [1, 0x2545_f491_4f6c_dd1d, 0xdead_beef] {
+            check_btree_against_std::<4>(seed);
+            check_btree_against_std::<5>(seed);
+            check_btree_against_std::<6>(seed);
+            check_btree_against_std::<7>(seed);
+            check_btree_against_std::<8>(seed);
+            check_btree_against_std::<9>(seed);
+            check_btree_against_std::<12>(seed);
+            check_btree_against_std::<16>(seed);
+            check_btree_against_std::<33>(seed);
+        }
+    }
+
+    #[test]
+    fn test_btree_entry() {
+        use crate::collections::btree::{BTree, Entry};
+        let mut btree: BTree<&str, i32> = BTree::new();
+
+        *btree.entry("a").or_default() += 1;
+        *btree.entry("a").or_default() += 1;
+        assert_eq!(btree.get(&"a"), Some(&2));
+
+        match btree.entry("b") {
+            Entry::Occupied(_) => panic!("b should be vacant"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &"b");
+                *entry.insert(5) += 1;
+            }
+        }
+        assert_eq!(btree.get(&"b"), Some(&6));
+
+        match btree.entry("a") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.insert(10), 2);
+                assert_eq!(entry.remove(), 10);
+            }
+            Entry::Vacant(_) => panic!("a should be occupied"),
+        }
+        assert!(!btree.contains_key(&"a"));
+        assert_eq!(btree.len(), 1);
+    }
+
+    // a directory for the files of one test, removed with everything in it when dropped
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("vn-ecs-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn path(&self, name: &str) -> std::path::PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn test_btree_file_store() {
+        use crate::collections::btree::BTree;
+        use crate::collections::file_page_store::FilePageStore;
+        use std::collections::BTreeMap;
+
+        let dir = TempDir::new("file_store");
+        let path = dir.path("file_store.db");
+        let mut rng = TestRng(0x2545_f491_4f6c_dd1d);
+        let mut reference = BTreeMap::new();
+
+        {
+            let store = FilePageStore::open_with_page_size(&path, 512)
+                .unwrap()
+                .with_pool_capacity(8);
+            let mut tree: BTree<u64, String, 8, _> = BTree::with_store(store);
+
+            for round in 0..4 {
+                for step in 0..1000 {
+                    let key = rng.below(2000);
+                    if rng.below(3) == 0 {
+                        assert_eq!(tree.remove(&key), reference.remove(&key));
+                    } else {
+                        let value = format!("{}-{}", round, step);
+                        assert_eq!(
+                            tree.insert(key, value.clone()),
+                            reference.insert(key, value)
+                        );
+                    }
+                }
+                tree.flush().unwrap();
+                assert!(tree.store().cached_pages() <= 8);
+            }
+
+            // never flushed, lost on drop
+            tree.insert(5000, "lost".to_string());
+        }
+
+        let store = FilePageStore::open(&path).unwrap().with_pool_capacity(8);
+        assert_eq!(store.page_size(), 512);
+        let mut tree: BTree<u64, String, 8, _> = BTree::with_store(store);
+        assert_eq!(tree.len(), reference.len());
+        assert!(tree.iter().eq(reference.iter()));
+        assert!(tree.range(100..200).eq(reference.range(100..200)));
+
+        // mutable iteration only writes the pages that changed
+        for (_, value) in tree.iter_mut() {
+            assert!(!value.is_empty());
+        }
+        tree.insert(5000, "kept".to_string());
+        assert!(tree.store().cached_pages() <= 16);
+        for (key, value) in tree.iter_mut().take(10) {
+            *value = key.to_string();
+        }
+        tree.flush().unwrap();
+        drop(tree);
+
+        let store = FilePageStore::open(&path).unwrap().with_pool_capacity(8);
+        let mut tree: BTree<u64, String, 8, _> = BTree::with_store(store);
+        for (key, value) in reference.iter_mut().take(10) {
+            *value = key.to_string();
+        }
+        reference.insert(5000, "kept".to_string());
+        assert!(tree.iter().eq(reference.iter()));
+
+        tree.clear();
+        tree.flush().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 512);
+
+        // a node that does not fit into a page is reported by the insert that grew it
+        let oversize = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            tree.insert(1, "x".repeat(1000));
+        }));
+        assert!(oversize.is_err());
+    }
+
+    #[test]
+    fn test_btree_file_store_shared_reads() {
+        use crate::collections::btree::BTree;
+        use crate::collections::file_page_store::FilePageStore;
+
+        let dir = TempDir::new("shared_reads");
+        let path = dir.path("shared_reads.db");
+        let mut tree: BTree<u32, String, 4, _> =
+            BTree::with_store(FilePageStore::open(&path).unwrap().with_pool_capacity(2));
+        tree.extend((0..500).map(|i| (i, i.to_string())));
+        tree.flush().unwrap();
+        let mut tree: BTree<u32, String, 4, _> =
+            BTree::with_store(FilePageStore::open(&path).unwrap().with_pool_capacity(2));
+
+        // references into the pool stay valid while reads load more pages than it can hold,
+        // nothing is evicted while they are borrowed
+        let values: Vec<&String> = (0..500).map(|i| tree.get(&i).unwrap()).collect();
+        let cached = tree.store().cached_pages();
+        assert!(cached > 2);
+        assert!(values.iter().enumerate().all(|(i, v)| **v == i.to_string()));
+
+        // and the pool shrinks on the next modification
+        tree.insert(500, "500".to_string());
+        assert!(tree.store().cached_pages() < cached);
+    }
+
+    #[test]
+    fn test_btree_file_store_recovery() {
+        use crate::collections::btree::BTree;
+        use crate::collections::file_page_store::FilePageStore;
+
+        let dir = TempDir::new("recovery");
+        let path = dir.path("recovery.db");
+        let wal = path.with_extension("db.wal");
+
+        {
+            let mut tree: BTree<u32, u32, 4, _> =
+                BTree::with_store(FilePageStore::open(&path).unwrap());
+            tree.extend((0..100).map(|i| (i, i)));
+            tree.flush().unwrap();
+
+            // crash after the log was committed, but before the file was updated
+            tree.extend((100..200).map(|i| (i, i)));
+            tree.store_mut().flush_wal_only().unwrap();
+        }
+
+        {
+            let mut tree: BTree<u32, u32, 4, _> =
+                BTree::with_store(FilePageStore::open(&path).unwrap());
+            assert!(
+                tree.iter()
+                    .map(|(k, v)| (*k, *v))
+                    .eq((0..200).map(|i| (i, i)))
+            );
+            assert_eq!(std::fs::metadata(&wal).unwrap().len(), 0);
+
+            // crash while writing the log, the incomplete log is discarded
+            tree.extend((200..300).map(|i| (i, i)));
+            tree.store_mut().flush_wal_only().unwrap();
+            let log = std::fs::read(&wal).unwrap();
+            std::fs::write(&wal, &log[..log.len() - 4]).unwrap();
+        }
+
+        let tree: BTree<u32, u32, 4, _> = BTree::with_store(FilePageStore::open(&path).unwrap());
+        assert_eq!(tree.len(), 200);
+        assert!(tree.keys().copied().eq(0..200));
+    }
+
+    #[test]
+    fn test_cached_query() {
+        let mut world = World::new();
+        let types = [TypeId::of::<i32>(), TypeId::of::<f32>()];
+
+        let e1 = world.spawn();
+        world.add_component(e1, 1i32);
+        world.add_component(e1, 1.0f32);
+        let e2 = world.spawn();
+        world.add_component(e2, 2i32);
+
+        let query = world.register_query(&types);
+        assert_eq!(world.register_query(&[types[1], types[0], types[1]]), query);
+        assert_eq!(world.query(query).unwrap().entities(), &[e1]);
+        let version = world.query(query).unwrap().version();
+
+        // replacing a component does not change the result
+        world.add_component(e1, 5i32);
+        assert_eq!(world.query(query).unwrap().version(), version);
+
+        world.add_component(e2, 2.0f32);
+        let e3 = world.spawn();
+        world.add_component(e3, 3.0f32);
+        world.add_component(e3, 3i32);
+        let mut entities = world.query(query).unwrap().entities().to_vec();
+        entities.sort();
+        assert_eq!(entities, vec![e1, e2, e3]);
+
+        world.remove_component::<f32>(e1);
+        world.despawn(e2);
+        let cached = world.query(query).unwrap();
+        assert_eq!(cached.entities(), &[e3]);
+        assert!(!cached.contains(e1) && !cached.contains(e2));
+        assert!(cached.version() > version);
+
+        let mut other = World::new();
+        let other_query = other.register_query(&types);
+        let mapping = world.move_entities(&[e3], &mut other);
+        assert!(world.query(query).unwrap().is_empty());
+        assert_eq!(
+            other.query(other_query).unwrap().entities(),
+            &[mapping[&e3]]
+        );
+
+        world.unregister_query(query);
+        assert!(world.query(query).is_some());
+        world.unregister_query(query);
+        assert!(world.query(query).is_none());
+    }
 }
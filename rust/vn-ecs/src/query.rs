@@ -0,0 +1,110 @@
+use crate::entity::Entity;
+use std::any::TypeId;
+use std::collections::HashMap;
+
+/// Handle to a query registered via `World::register_query`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QueryId(pub(crate) usize);
+
+/// The entities having all components of a set of types, kept up to date by the `World` as
+/// components are added and removed and entities are despawned.
+pub struct CachedQuery {
+    types: Vec<TypeId>,
+    entities: Vec<Entity>,
+    positions: HashMap<u32, usize>,
+    version: u64,
+    // number of registrations sharing this query
+    users: usize,
+}
+
+impl CachedQuery {
+    pub(crate) fn new(types: Vec<TypeId>, entities: Vec<Entity>) -> Self {
+        let positions = entities
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.id, i))
+            .collect();
+        Self {
+            types,
+            entities,
+            positions,
+            version: 0,
+            users: 1,
+        }
+    }
+
+    pub fn types(&self) -> &[TypeId] {
+        &self.types
+    }
+
+    /// The matching entities, in no particular order.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.positions
+            .get(&entity.id)
+            .is_some_and(|&i| self.entities[i] == entity)
+    }
+
+    /// Incremented whenever an entity enters or leaves the result, so systems can skip work
+    /// if the set did not change since they last looked at it.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub(crate) fn depends_on(&self, type_id: TypeId) -> bool {
+        self.types.contains(&type_id)
+    }
+
+    pub(crate) fn matches(&self, types: &[TypeId]) -> bool {
+        self.types == types
+    }
+
+    pub(crate) fn acquire(&mut self) {
+        self.users += 1;
+    }
+
+    /// Returns true if this was the last registration.
+    pub(crate) fn release(&mut self) -> bool {
+        self.users -= 1;
+        self.users == 0
+    }
+
+    pub(crate) fn set(&mut self, entity: Entity, matching: bool) {
+        if matching {
+            self.insert(entity);
+        } else {
+            self.remove(entity);
+        }
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity) {
+        if self.positions.contains_key(&entity.id) {
+            return;
+        }
+        self.positions.insert(entity.id, self.entities.len());
+        self.entities.push(entity);
+        self.version += 1;
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) {
+        let Some(i) = self.positions.remove(&entity.id) else {
+            return;
+        };
+        self.entities.swap_remove(i);
+        if let Some(moved) = self.entities.get(i) {
+            self.positions.insert(moved.id, i);
+        }
+        self.version += 1;
+    }
+}
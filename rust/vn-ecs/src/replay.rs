@@ -0,0 +1,302 @@
+use crate::entity::Entity;
+use crate::storage::ComponentStorage;
+use crate::world::World;
+use std::any::{Any, TypeId};
+
+pub type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+type InsertResourceFn = fn(&mut World, Box<dyn Any>);
+type InsertNamedResourceFn = fn(&mut World, &str, Box<dyn Any>);
+
+/// A recorded component or resource value.
+/// Only types registered via `World::register_cloneable` carry a value and can be replayed.
+pub struct RecordedValue {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    value: Option<Box<dyn Any>>,
+    clone: Option<CloneFn>,
+    insert_component: InsertComponent,
+    // monomorphized for the recorded type, as the world needs the concrete type to insert it.
+    // None for components recorded without their type
+    insert_resource: Option<InsertResourceFn>,
+    insert_named_resource: Option<InsertNamedResourceFn>,
+}
+
+enum InsertComponent {
+    Typed(fn(&mut World, Entity, Box<dyn Any>)),
+    // components moved or copied between worlds are only known by their storage, which creates
+    // the storage in the replaying world if it is missing
+    Erased(Box<dyn ComponentStorage>),
+}
+
+fn insert_component_boxed<T: Any>(world: &mut World, entity: Entity, value: Box<dyn Any>) {
+    if let Ok(value) = value.downcast::<T>() {
+        world.add_component(entity, *value);
+    }
+}
+
+fn insert_resource_boxed<T: Any>(world: &mut World, value: Box<dyn Any>) {
+    if let Ok(value) = value.downcast::<T>() {
+        world.insert_resource(*value);
+    }
+}
+
+fn insert_named_resource_boxed<T: Any>(world: &mut World, name: &str, value: Box<dyn Any>) {
+    if let Ok(value) = value.downcast::<T>() {
+        world.insert_named_resource(name, *value);
+    }
+}
+
+impl RecordedValue {
+    pub(crate) fn new<T: Any>(value: &T, clone: Option<CloneFn>) -> Self {
+        Self {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            value: clone.map(|clone| clone(value)),
+            clone,
+            insert_component: InsertComponent::Typed(insert_component_boxed::<T>),
+            insert_resource: Some(insert_resource_boxed::<T>),
+            insert_named_resource: Some(insert_named_resource_boxed::<T>),
+        }
+    }
+
+    /// A component of which only the storage it is kept in is known.
+    pub(crate) fn erased_component(
+        type_id: TypeId,
+        type_name: &'static str,
+        value: &dyn Any,
+        clone: Option<CloneFn>,
+        storage: &dyn ComponentStorage,
+    ) -> Self {
+        Self {
+            type_id,
+            type_name,
+            value: clone.map(|clone| clone(value)),
+            clone,
+            insert_component: InsertComponent::Erased(storage.new_empty()),
+            insert_resource: None,
+            insert_named_resource: None,
+        }
+    }
+
+    pub fn value(&self) -> Option<&dyn Any> {
+        self.value.as_deref()
+    }
+
+    pub fn is_replayable(&self) -> bool {
+        self.value.is_some()
+    }
+
+    fn insert_component(&self, world: &mut World, entity: Entity) -> Result<(), String> {
+        let value = self.cloned_value()?;
+        match &self.insert_component {
+            InsertComponent::Typed(insert) => insert(world, entity, value),
+            InsertComponent::Erased(storage) => world.add_component_erased(
+                entity,
+                self.type_id,
+                self.type_name,
+                value,
+                storage.as_ref(),
+            ),
+        }
+        Ok(())
+    }
+
+    fn insert_resource(&self, world: &mut World, name: Option<&str>) -> Result<(), String> {
+        let value = self.cloned_value()?;
+        match (name, self.insert_resource, self.insert_named_resource) {
+            (None, Some(insert), _) => insert(world, value),
+            (Some(name), _, Some(insert)) => insert(world, name, value),
+            _ => {
+                return Err(format!(
+                    "Value of type {} was not recorded as a resource",
+                    self.type_name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn cloned_value(&self) -> Result<Box<dyn Any>, String> {
+        match (&self.value, self.clone) {
+            (Some(value), Some(clone)) => Ok(clone(value.as_ref())),
+            _ => Err(format!(
+                "Value of type {} was recorded without being registered as cloneable",
+                self.type_name
+            )),
+        }
+    }
+}
+
+impl std::fmt::Debug for RecordedValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordedValue")
+            .field("type_name", &self.type_name)
+            .field("replayable", &self.is_replayable())
+            .finish()
+    }
+}
+
+/// A storage registered via `World::register_storage`. Replaying registers an empty storage of
+/// the same kind.
+pub struct RecordedStorage {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    storage: Box<dyn ComponentStorage>,
+}
+
+impl RecordedStorage {
+    pub(crate) fn new(
+        type_id: TypeId,
+        type_name: &'static str,
+        storage: &dyn ComponentStorage,
+    ) -> Self {
+        Self {
+            type_id,
+            type_name,
+            storage: storage.new_empty(),
+        }
+    }
+}
+
+impl std::fmt::Debug for RecordedStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordedStorage")
+            .field("type_name", &self.type_name)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub enum WorldCommand {
+    RegisterStorage(RecordedStorage),
+    Spawn(Entity),
+    Despawn(Entity),
+    AddComponent {
+        entity: Entity,
+        component: RecordedValue,
+    },
+    RemoveComponent {
+        entity: Entity,
+        type_id: TypeId,
+    },
+    TagComponent {
+        entity: Entity,
+        type_id: TypeId,
+        tag_id: TypeId,
+    },
+    UntagComponent {
+        entity: Entity,
+        type_id: TypeId,
+        tag_id: TypeId,
+    },
+    InsertResource(RecordedValue),
+    RemoveResource(TypeId),
+    InsertNamedResource {
+        name: String,
+        resource: RecordedValue,
+    },
+    RemoveNamedResource {
+        name: String,
+        type_id: TypeId,
+    },
+}
+
+/// All commands applied to a `World` while recording, grouped by tick.
+/// A tick is closed by `World::advance_tick`, which `SystemManager::run` calls after running
+/// all systems.
+#[derive(Debug)]
+pub struct Recording {
+    ticks: Vec<Vec<WorldCommand>>,
+}
+
+impl Recording {
+    pub(crate) fn new() -> Self {
+        Self {
+            ticks: vec![Vec::new()],
+        }
+    }
+
+    pub(crate) fn push(&mut self, command: WorldCommand) {
+        // there is always at least one (the current) tick
+        self.ticks.last_mut().unwrap().push(command);
+    }
+
+    pub(crate) fn advance_tick(&mut self) {
+        self.ticks.push(Vec::new());
+    }
+
+    pub fn ticks(&self) -> &[Vec<WorldCommand>] {
+        &self.ticks
+    }
+
+    pub fn tick(&self, tick: usize) -> Option<&[WorldCommand]> {
+        self.ticks.get(tick).map(|t| t.as_slice())
+    }
+
+    /// Re-applies every recorded tick to `world`.
+    /// `world` is expected to be in the same state the recorded world was in when recording
+    /// started, otherwise spawned entities will not line up and an error is returned.
+    pub fn replay(&self, world: &mut World) -> Result<(), String> {
+        for tick in 0..self.ticks.len() {
+            self.replay_tick(tick, world)?;
+        }
+        Ok(())
+    }
+
+    pub fn replay_tick(&self, tick: usize, world: &mut World) -> Result<(), String> {
+        let commands = self
+            .ticks
+            .get(tick)
+            .ok_or_else(|| format!("Tick {} was not recorded", tick))?;
+
+        for command in commands {
+            match command {
+                WorldCommand::RegisterStorage(storage) => world
+                    .register_storage_by_type(
+                        storage.type_id,
+                        storage.type_name,
+                        storage.storage.new_empty(),
+                    )
+                    .map_err(|e| format!("Replay desync in tick {}: {}", tick, e))?,
+                WorldCommand::Spawn(expected) => {
+                    let entity = world.spawn();
+                    if entity != *expected {
+                        return Err(format!(
+                            "Replay desync in tick {}: expected to spawn {:?} but got {:?}",
+                            tick, expected, entity
+                        ));
+                    }
+                }
+                WorldCommand::Despawn(entity) => world.despawn(*entity),
+                WorldCommand::AddComponent { entity, component } => {
+                    component.insert_component(world, *entity)?
+                }
+                WorldCommand::RemoveComponent { entity, type_id } => {
+                    world.remove_component_by_type(*entity, *type_id);
+                }
+                WorldCommand::TagComponent {
+                    entity,
+                    type_id,
+                    tag_id,
+                } => world.tag_component_by_type(*entity, *type_id, *tag_id),
+                WorldCommand::UntagComponent {
+                    entity,
+                    type_id,
+                    tag_id,
+                } => world.untag_component_by_type(*entity, *type_id, *tag_id),
+                WorldCommand::InsertResource(resource) => resource.insert_resource(world, None)?,
+                WorldCommand::RemoveResource(type_id) => {
+                    world.remove_resource_by_type(*type_id);
+                }
+                WorldCommand::InsertNamedResource { name, resource } => {
+                    resource.insert_resource(world, Some(name))?
+                }
+                WorldCommand::RemoveNamedResource { name, type_id } => {
+                    world.remove_named_resource_by_type(name, *type_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
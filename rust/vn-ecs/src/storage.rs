@@ -1,4 +1,6 @@
 use std::any::Any;
+use std::cmp::Ordering;
+use std::num::NonZeroU32;
 
 pub trait ComponentStorage: Any {
     fn as_any(&self) -> &dyn Any;
@@ -10,81 +12,250 @@ pub trait ComponentStorage: Any {
     fn get_any(&self, entity_id: u32) -> Option<&dyn Any>;
     fn get_any_mut(&mut self, entity_id: u32) -> Option<&mut dyn Any>;
     fn remove_any(&mut self, entity_id: u32) -> Option<Box<dyn Any>>;
+    /// Creates an empty storage of the same kind, used when components are moved into a world
+    /// that has no storage for them yet.
+    fn new_empty(&self) -> Box<dyn ComponentStorage>;
 }
 
+const PAGE_SIZE: usize = 1024;
+
+/// Index into the dense array, stored inverted so that `Option<DenseIndex>` has the same size
+/// as a `u32`. `u32::MAX` can therefore not be used as a dense index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DenseIndex(NonZeroU32);
+
+impl DenseIndex {
+    fn new(index: u32) -> Self {
+        Self(NonZeroU32::new(!index).expect("dense index u32::MAX is reserved"))
+    }
+
+    fn get(self) -> u32 {
+        !self.0.get()
+    }
+}
+
+struct SparsePage {
+    entries: [Option<DenseIndex>; PAGE_SIZE],
+    len: usize,
+}
+
+/// Maps entity ids to dense indices. Memory is allocated in fixed size pages which are only
+/// created for ranges of ids that are actually used, and freed again once they become empty.
+pub(crate) struct SparsePages {
+    // boxed, so unused pages only cost a pointer
+    pages: Vec<Option<Box<SparsePage>>>,
+}
+
+impl SparsePages {
+    fn new() -> Self {
+        Self { pages: Vec::new() }
+    }
+
+    fn get(&self, entity_id: u32) -> Option<u32> {
+        let (page, offset) = Self::split(entity_id);
+        self.pages.get(page)?.as_ref()?.entries[offset].map(DenseIndex::get)
+    }
+
+    fn set(&mut self, entity_id: u32, dense_idx: u32) {
+        let (page, offset) = Self::split(entity_id);
+        if page >= self.pages.len() {
+            self.pages.resize_with(page + 1, || None);
+        }
+        let page = self.pages[page].get_or_insert_with(|| {
+            Box::new(SparsePage {
+                entries: [None; PAGE_SIZE],
+                len: 0,
+            })
+        });
+        if page.entries[offset]
+            .replace(DenseIndex::new(dense_idx))
+            .is_none()
+        {
+            page.len += 1;
+        }
+    }
+
+    fn clear(&mut self, entity_id: u32) {
+        let (page_idx, offset) = Self::split(entity_id);
+        let Some(Some(page)) = self.pages.get_mut(page_idx) else {
+            return;
+        };
+        if page.entries[offset].take().is_some() {
+            page.len -= 1;
+            if page.len == 0 {
+                self.pages[page_idx] = None;
+                while matches!(self.pages.last(), Some(None)) {
+                    self.pages.pop();
+                }
+            }
+        }
+    }
+
+    fn size_in_bytes(&self) -> usize {
+        self.pages.capacity() * size_of::<Option<Box<SparsePage>>>()
+            + self.pages.iter().flatten().count() * size_of::<SparsePage>()
+    }
+
+    fn split(entity_id: u32) -> (usize, usize) {
+        let id = entity_id as usize;
+        (id / PAGE_SIZE, id % PAGE_SIZE)
+    }
+}
+
+/// How a `SparseSet` orders its dense array, which is the order components are iterated in.
+pub enum DenseOrder<T> {
+    /// Insertion order, disturbed by removals which move the last element into the gap.
+    /// This is the fastest option.
+    Unordered,
+    /// Sorted by entity id.
+    EntityId,
+    /// Sorted by a user defined comparison, e.g. a z-index for draw order.
+    /// Components with an equal key keep their insertion order.
+    By(fn(&T, &T) -> Ordering),
+}
+
+impl<T> Clone for DenseOrder<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for DenseOrder<T> {}
+
 pub struct SparseSet<T> {
-    pub(crate) sparse: Vec<Option<u32>>,
+    pub(crate) sparse: SparsePages,
     pub(crate) dense: Vec<u32>,
     pub(crate) data: Vec<T>,
+    order: DenseOrder<T>,
 }
 
 impl<T> SparseSet<T> {
     pub fn new() -> Self {
+        Self::with_order(DenseOrder::Unordered)
+    }
+
+    pub fn with_order(order: DenseOrder<T>) -> Self {
         Self {
-            sparse: Vec::new(),
+            sparse: SparsePages::new(),
             dense: Vec::new(),
             data: Vec::new(),
+            order,
         }
     }
 
-    pub fn insert(&mut self, entity_id: u32, component: T) {
-        let index = entity_id as usize;
-        if index >= self.sparse.len() {
-            self.sparse.resize(index + 1, None);
+    pub fn order(&self) -> DenseOrder<T> {
+        self.order
+    }
+
+    pub fn set_order(&mut self, order: DenseOrder<T>) {
+        self.order = order;
+        self.sort();
+    }
+
+    /// Re-establishes the ordering of the dense array.
+    /// Only needed for `DenseOrder::By` after the key of a component was changed via `get_mut`.
+    pub fn sort(&mut self) {
+        let entries = self.dense.drain(..).zip(self.data.drain(..));
+        let mut entries: Vec<(u32, T)> = entries.collect();
+        match self.order {
+            DenseOrder::Unordered => {}
+            DenseOrder::EntityId => entries.sort_by_key(|(id, _)| *id),
+            DenseOrder::By(cmp) => entries.sort_by(|(_, a), (_, b)| cmp(a, b)),
         }
+        (self.dense, self.data) = entries.into_iter().unzip();
+        self.reindex_from(0);
+    }
 
-        if let Some(dense_idx) = self.sparse[index] {
-            self.data[dense_idx as usize] = component;
-        } else {
-            let dense_idx = self.dense.len() as u32;
-            self.sparse[index] = Some(dense_idx);
-            self.dense.push(entity_id);
-            self.data.push(component);
+    pub fn insert(&mut self, entity_id: u32, component: T) {
+        if let Some(dense_idx) = self.sparse.get(entity_id) {
+            match self.order {
+                // the key might have changed, so the component needs to be repositioned
+                DenseOrder::By(_) => {
+                    self.remove(entity_id);
+                }
+                _ => {
+                    self.data[dense_idx as usize] = component;
+                    return;
+                }
+            }
         }
+
+        let dense_idx = match self.order {
+            DenseOrder::Unordered => self.dense.len(),
+            DenseOrder::EntityId => self.dense.partition_point(|&id| id < entity_id),
+            DenseOrder::By(cmp) => self
+                .data
+                .partition_point(|c| cmp(c, &component) != Ordering::Greater),
+        };
+        self.dense.insert(dense_idx, entity_id);
+        self.data.insert(dense_idx, component);
+        self.reindex_from(dense_idx);
     }
 
     pub fn get(&self, entity_id: u32) -> Option<&T> {
-        let index = entity_id as usize;
-        if index < self.sparse.len() {
-            self.sparse[index].map(|dense_idx| &self.data[dense_idx as usize])
-        } else {
-            None
-        }
+        self.sparse
+            .get(entity_id)
+            .map(|dense_idx| &self.data[dense_idx as usize])
     }
 
     pub fn get_mut(&mut self, entity_id: u32) -> Option<&mut T> {
-        let index = entity_id as usize;
-        if index < self.sparse.len() {
-            let dense_idx = self.sparse[index]?;
-            Some(&mut self.data[dense_idx as usize])
-        } else {
-            None
-        }
+        let dense_idx = self.sparse.get(entity_id)?;
+        Some(&mut self.data[dense_idx as usize])
     }
 
     pub fn remove(&mut self, entity_id: u32) -> Option<T> {
-        let index = entity_id as usize;
-        if index < self.sparse.len() {
-            if let Some(dense_idx) = self.sparse[index] {
-                let last_entity_id = *self.dense.last().unwrap();
-                let last_idx = self.dense.len() - 1;
-
-                self.dense.swap(dense_idx as usize, last_idx);
-                self.data.swap(dense_idx as usize, last_idx);
+        let dense_idx = self.sparse.get(entity_id)? as usize;
+        self.sparse.clear(entity_id);
 
-                self.sparse[last_entity_id as usize] = Some(dense_idx);
-                self.sparse[index] = None;
-
-                self.dense.pop();
-                return self.data.pop();
+        match self.order {
+            DenseOrder::Unordered => {
+                self.dense.swap_remove(dense_idx);
+                let component = self.data.swap_remove(dense_idx);
+                if dense_idx < self.dense.len() {
+                    self.sparse.set(self.dense[dense_idx], dense_idx as u32);
+                }
+                Some(component)
+            }
+            _ => {
+                self.dense.remove(dense_idx);
+                let component = self.data.remove(dense_idx);
+                self.reindex_from(dense_idx);
+                Some(component)
             }
         }
-        None
     }
 
     pub fn contains(&self, entity_id: u32) -> bool {
-        let index = entity_id as usize;
-        index < self.sparse.len() && self.sparse[index].is_some()
+        self.sparse.get(entity_id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    /// Iterates over all components in the order of the dense array.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.dense.iter().copied().zip(self.data.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u32, &mut T)> {
+        self.dense.iter().copied().zip(self.data.iter_mut())
+    }
+
+    /// Memory used by the sparse array, which only grows with the number of used pages rather
+    /// than with the highest entity id.
+    pub fn sparse_size_in_bytes(&self) -> usize {
+        self.sparse.size_in_bytes()
+    }
+
+    fn reindex_from(&mut self, start: usize) {
+        for (dense_idx, &entity_id) in self.dense.iter().enumerate().skip(start) {
+            self.sparse.set(entity_id, dense_idx as u32);
+        }
     }
 }
 
@@ -118,4 +289,7 @@ impl<T: Any> ComponentStorage for SparseSet<T> {
     fn remove_any(&mut self, entity_id: u32) -> Option<Box<dyn Any>> {
         self.remove(entity_id).map(|c| Box::new(c) as Box<dyn Any>)
     }
+    fn new_empty(&self) -> Box<dyn ComponentStorage> {
+        Box::new(SparseSet::<T>::with_order(self.order))
+    }
 }
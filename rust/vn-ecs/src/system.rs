@@ -45,11 +45,14 @@ impl SystemManager {
         }
     }
 
+    /// Runs all enabled systems in registration order and closes the current tick of the
+    /// world's recording, if any.
     pub fn run(&mut self, world: &mut World) {
         for sys in &mut self.systems {
             if sys.enabled {
                 sys.system.run(world);
             }
         }
+        world.advance_tick();
     }
 }
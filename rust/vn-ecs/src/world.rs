@@ -1,8 +1,13 @@
+use crate::diff::{ComponentChange, ComponentDiff, EqFn, WorldDiff};
 use crate::entity::{Entity, EntityManager};
 use crate::index::{Index, IndexBuilder};
+use crate::query::{CachedQuery, QueryId};
+use crate::replay::{CloneFn, RecordedStorage, RecordedValue, Recording, WorldCommand};
 use crate::storage::{ComponentStorage, SparseSet};
 use std::any::{Any, TypeId};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+pub type EntityMap = HashMap<Entity, Entity>;
 
 pub struct World {
     entities: EntityManager,
@@ -11,6 +16,22 @@ pub struct World {
     named_resources: HashMap<(String, TypeId), Box<dyn Any>>,
     indices: HashMap<(TypeId, TypeId), Box<dyn Index>>, // (ComponentType, IndexType)
     component_tags: HashMap<(u32, TypeId), Vec<TypeId>>,
+    type_names: HashMap<TypeId, &'static str>,
+    cloneables: HashMap<TypeId, CloneFn>,
+    comparables: HashMap<TypeId, EqFn>,
+    recording: Option<Recording>,
+    queries: Vec<Option<CachedQuery>>,
+}
+
+fn clone_any<T: Clone + Any>(value: &dyn Any) -> Box<dyn Any> {
+    Box::new(value.downcast_ref::<T>().unwrap().clone())
+}
+
+fn eq_any<T: PartialEq + Any>(a: &dyn Any, b: &dyn Any) -> bool {
+    match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
 }
 
 impl World {
@@ -22,39 +43,327 @@ impl World {
             named_resources: HashMap::new(),
             indices: HashMap::new(),
             component_tags: HashMap::new(),
+            type_names: HashMap::new(),
+            cloneables: HashMap::new(),
+            comparables: HashMap::new(),
+            recording: None,
+            queries: Vec::new(),
+        }
+    }
+
+    /// Allows values of type `T` (components and resources) to be captured by a recording and
+    /// replayed later.
+    pub fn register_cloneable<T: Clone + Any>(&mut self) {
+        self.type_names
+            .insert(TypeId::of::<T>(), std::any::type_name::<T>());
+        self.cloneables.insert(TypeId::of::<T>(), clone_any::<T>);
+    }
+
+    /// Allows `World::diff` to detect changed components of type `T`.
+    /// Components of types that are not registered are only reported when added or removed.
+    pub fn register_comparable<T: PartialEq + Any>(&mut self) {
+        self.type_names
+            .insert(TypeId::of::<T>(), std::any::type_name::<T>());
+        self.comparables.insert(TypeId::of::<T>(), eq_any::<T>);
+    }
+
+    pub fn type_name(&self, type_id: TypeId) -> Option<&'static str> {
+        self.type_names.get(&type_id).copied()
+    }
+
+    /// Starts recording all commands applied to this world, discarding any previous recording.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.recording.take()
+    }
+
+    pub fn recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Closes the current tick of the recording, if any.
+    pub fn advance_tick(&mut self) {
+        if let Some(recording) = self.recording.as_mut() {
+            recording.advance_tick();
+        }
+    }
+
+    fn record(&mut self, command: impl FnOnce(&Self) -> WorldCommand) {
+        if self.recording.is_none() {
+            return;
+        }
+        let command = command(self);
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(command);
         }
     }
 
+    fn record_value<T: Any>(&self, value: &T) -> RecordedValue {
+        RecordedValue::new(value, self.cloneables.get(&TypeId::of::<T>()).copied())
+    }
+
     // consider allowing overwriting of storage
     pub fn register_storage<T: Any>(
         &mut self,
         storage: Box<dyn ComponentStorage>,
     ) -> Result<(), String> {
-        let type_id = TypeId::of::<T>();
+        self.register_storage_by_type(TypeId::of::<T>(), std::any::type_name::<T>(), storage)
+    }
+
+    pub(crate) fn register_storage_by_type(
+        &mut self,
+        type_id: TypeId,
+        type_name: &'static str,
+        storage: Box<dyn ComponentStorage>,
+    ) -> Result<(), String> {
         if self.components.contains_key(&type_id) {
-            return Err(format!("Storage for type {:?} already registered", type_id));
+            return Err(format!("Storage for type {} already registered", type_name));
         }
+        self.record(|_| {
+            WorldCommand::RegisterStorage(RecordedStorage::new(
+                type_id,
+                type_name,
+                storage.as_ref(),
+            ))
+        });
         self.components.insert(type_id, storage);
+        self.type_names.insert(type_id, type_name);
         Ok(())
     }
 
     pub fn spawn(&mut self) -> Entity {
-        self.entities.spawn()
+        let entity = self.entities.spawn();
+        self.record(|_| WorldCommand::Spawn(entity));
+        entity
     }
 
     pub fn despawn(&mut self, entity: Entity) {
         if self.entities.despawn(entity) {
+            self.record(|_| WorldCommand::Despawn(entity));
             for storage in self.components.values_mut() {
                 storage.remove(entity.id);
             }
             for index in self.indices.values_mut() {
                 index.remove(entity);
             }
+            for query in self.queries.iter_mut().flatten() {
+                query.remove(entity);
+            }
             self.component_tags
                 .retain(|(e_id, _), _| *e_id != entity.id);
         }
     }
 
+    /// Moves `entities` with all their components and tags into `target`, despawning them in
+    /// this world. Returns the mapping from the entities in this world to the newly spawned ones
+    /// in `target`. Entity references stored inside components are not remapped.
+    pub fn move_entities(&mut self, entities: &[Entity], target: &mut World) -> EntityMap {
+        let mut mapping = EntityMap::new();
+        let mut moved_types = HashSet::new();
+
+        for &entity in entities {
+            if !self.entities.is_alive(entity) || mapping.contains_key(&entity) {
+                continue;
+            }
+
+            let target_entity = target.spawn();
+            for (&type_id, storage) in self.components.iter_mut() {
+                let Some(component) = storage.remove_any(entity.id) else {
+                    continue;
+                };
+                target.record(|_| WorldCommand::AddComponent {
+                    entity: target_entity,
+                    component: RecordedValue::erased_component(
+                        type_id,
+                        self.type_names.get(&type_id).copied().unwrap_or("unknown"),
+                        component.as_ref(),
+                        self.cloneables.get(&type_id).copied(),
+                        storage.as_ref(),
+                    ),
+                });
+                target
+                    .components
+                    .entry(type_id)
+                    .or_insert_with(|| storage.new_empty())
+                    .insert_any(target_entity.id, component);
+                moved_types.insert(type_id);
+
+                if let Some(tags) = self.component_tags.remove(&(entity.id, type_id)) {
+                    target.record_retag(target_entity, type_id, &[], &tags);
+                    target
+                        .component_tags
+                        .insert((target_entity.id, type_id), tags);
+                }
+            }
+
+            self.despawn(entity);
+            mapping.insert(entity, target_entity);
+        }
+
+        target.inherit_registrations(self, &moved_types);
+        target.reindex(&moved_types, mapping.values());
+        mapping
+    }
+
+    /// Copies `entities` with all components of types registered via `register_cloneable` into
+    /// `target`, e.g. to extract a render world from a simulation world.
+    /// `mapping` is used to find entities copied by a previous call, so repeated extraction
+    /// updates them instead of spawning new ones. Cloneable components missing in this world
+    /// are removed from the target entity.
+    pub fn copy_entities(&self, entities: &[Entity], target: &mut World, mapping: &mut EntityMap) {
+        let mut copied_types = HashSet::new();
+        let mut copied = Vec::with_capacity(entities.len());
+
+        for &entity in entities {
+            if !self.entities.is_alive(entity) {
+                continue;
+            }
+
+            let target_entity = match mapping.get(&entity) {
+                Some(&target_entity) if target.entities.is_alive(target_entity) => target_entity,
+                _ => {
+                    let target_entity = target.spawn();
+                    mapping.insert(entity, target_entity);
+                    target_entity
+                }
+            };
+
+            for (&type_id, storage) in self.components.iter() {
+                let Some(clone) = self.cloneables.get(&type_id) else {
+                    continue;
+                };
+
+                match storage.get_any(entity.id) {
+                    Some(component) => {
+                        target.record(|_| WorldCommand::AddComponent {
+                            entity: target_entity,
+                            component: RecordedValue::erased_component(
+                                type_id,
+                                self.type_names.get(&type_id).copied().unwrap_or("unknown"),
+                                component,
+                                Some(*clone),
+                                storage.as_ref(),
+                            ),
+                        });
+                        target
+                            .components
+                            .entry(type_id)
+                            .or_insert_with(|| storage.new_empty())
+                            .insert_any(target_entity.id, clone(component));
+                        copied_types.insert(type_id);
+
+                        let tags = self.component_tags.get(&(entity.id, type_id));
+                        let previous = target.component_tags.get(&(target_entity.id, type_id));
+                        target.record_retag(
+                            target_entity,
+                            type_id,
+                            &previous.cloned().unwrap_or_default(),
+                            tags.map_or(&[], |tags| tags.as_slice()),
+                        );
+                        match tags {
+                            Some(tags) => {
+                                target
+                                    .component_tags
+                                    .insert((target_entity.id, type_id), tags.clone());
+                            }
+                            None => {
+                                target.component_tags.remove(&(target_entity.id, type_id));
+                            }
+                        }
+                    }
+                    None => {
+                        if target
+                            .components
+                            .get(&type_id)
+                            .is_some_and(|s| s.contains(target_entity.id))
+                        {
+                            target.remove_component_by_type(target_entity, type_id);
+                        }
+                    }
+                }
+            }
+
+            copied.push(target_entity);
+        }
+
+        target.inherit_registrations(self, &copied_types);
+        target.reindex(&copied_types, copied.iter());
+    }
+
+    // tags replaced directly in `component_tags` are recorded as untagging the previous tags
+    // and tagging the new ones in order
+    fn record_retag(
+        &mut self,
+        entity: Entity,
+        type_id: TypeId,
+        previous: &[TypeId],
+        tags: &[TypeId],
+    ) {
+        if previous == tags {
+            return;
+        }
+        for &tag_id in previous {
+            self.record(|_| WorldCommand::UntagComponent {
+                entity,
+                type_id,
+                tag_id,
+            });
+        }
+        for &tag_id in tags {
+            self.record(|_| WorldCommand::TagComponent {
+                entity,
+                type_id,
+                tag_id,
+            });
+        }
+    }
+
+    fn inherit_registrations(&mut self, source: &World, types: &HashSet<TypeId>) {
+        for type_id in types {
+            if let Some(&name) = source.type_names.get(type_id) {
+                self.type_names.entry(*type_id).or_insert(name);
+            }
+            if let Some(&clone) = source.cloneables.get(type_id) {
+                self.cloneables.entry(*type_id).or_insert(clone);
+            }
+            if let Some(&eq) = source.comparables.get(type_id) {
+                self.comparables.entry(*type_id).or_insert(eq);
+            }
+        }
+    }
+
+    // components inserted directly into storages bypass the indices, so they need to be
+    // updated afterwards
+    fn reindex<'a>(&mut self, types: &HashSet<TypeId>, entities: impl Iterator<Item = &'a Entity>) {
+        let entities: Vec<Entity> = entities.copied().collect();
+        for ((c_type, _), index) in self.indices.iter_mut() {
+            if !types.contains(c_type) {
+                continue;
+            }
+            let Some(storage) = self.components.get(c_type) else {
+                continue;
+            };
+            let data: Vec<(Entity, &dyn Any)> = entities
+                .iter()
+                .filter_map(|&e| storage.get_any(e.id).map(|c| (e, c)))
+                .collect();
+            index.update_many(&data);
+        }
+
+        for entity in entities {
+            for type_id in types {
+                self.update_queries(entity, *type_id);
+            }
+        }
+    }
+
     pub fn add_index<C: Any, I: Index, B: IndexBuilder<I>>(&mut self, builder: B) {
         let type_id = TypeId::of::<C>();
         let index = if let Some(storage) = self.components.get(&type_id) {
@@ -89,6 +398,11 @@ impl World {
             return;
         }
 
+        self.record(|world| WorldCommand::AddComponent {
+            entity,
+            component: world.record_value(&component),
+        });
+
         // Update indices
         for ((c_type, _), index) in self.indices.iter_mut() {
             if *c_type == TypeId::of::<T>() {
@@ -97,11 +411,55 @@ impl World {
         }
 
         let type_id = TypeId::of::<T>();
+        self.type_names
+            .entry(type_id)
+            .or_insert_with(std::any::type_name::<T>);
         let storage = self
             .components
             .entry(type_id)
             .or_insert_with(|| Box::new(SparseSet::<T>::new()));
         storage.insert_any(entity.id, Box::new(component));
+
+        self.update_queries(entity, type_id);
+    }
+
+    // `add_component` for a component whose type is only known by its storage
+    pub(crate) fn add_component_erased(
+        &mut self,
+        entity: Entity,
+        type_id: TypeId,
+        type_name: &'static str,
+        component: Box<dyn Any>,
+        storage: &dyn ComponentStorage,
+    ) {
+        if !self.entities.is_alive(entity) {
+            return;
+        }
+
+        self.record(|world| WorldCommand::AddComponent {
+            entity,
+            component: RecordedValue::erased_component(
+                type_id,
+                type_name,
+                component.as_ref(),
+                world.cloneables.get(&type_id).copied(),
+                storage,
+            ),
+        });
+
+        for ((c_type, _), index) in self.indices.iter_mut() {
+            if *c_type == type_id {
+                index.update(entity, component.as_ref());
+            }
+        }
+
+        self.type_names.entry(type_id).or_insert(type_name);
+        self.components
+            .entry(type_id)
+            .or_insert_with(|| storage.new_empty())
+            .insert_any(entity.id, component);
+
+        self.update_queries(entity, type_id);
     }
 
     pub fn get_component<T: Any>(&self, entity: Entity) -> Option<&T> {
@@ -115,11 +473,30 @@ impl World {
     }
 
     pub fn remove_component<T: Any>(&mut self, entity: Entity) -> Option<T> {
+        self.remove_component_by_type(entity, TypeId::of::<T>())?
+            .downcast::<T>()
+            .ok()
+            .map(|b| *b)
+    }
+
+    pub fn remove_component_by_type(
+        &mut self,
+        entity: Entity,
+        type_id: TypeId,
+    ) -> Option<Box<dyn Any>> {
         if !self.entities.is_alive(entity) {
             return None;
         }
 
-        let type_id = TypeId::of::<T>();
+        let has_component = self
+            .components
+            .get(&type_id)
+            .is_some_and(|storage| storage.contains(entity.id));
+        if !has_component && !self.component_tags.contains_key(&(entity.id, type_id)) {
+            return None;
+        }
+
+        self.record(|_| WorldCommand::RemoveComponent { entity, type_id });
 
         // Remove from indices
         for ((c_type, _), index) in self.indices.iter_mut() {
@@ -132,15 +509,14 @@ impl World {
         self.component_tags.remove(&(entity.id, type_id));
 
         // Remove from storage
-        let storage = self.components.get_mut(&type_id)?;
-        storage
-            .remove_any(entity.id)?
-            .downcast::<T>()
-            .ok()
-            .map(|b| *b)
+        let component = self.components.get_mut(&type_id)?.remove_any(entity.id);
+
+        self.update_queries(entity, type_id);
+        component
     }
 
     pub fn insert_resource<T: Any>(&mut self, resource: T) {
+        self.record(|world| WorldCommand::InsertResource(world.record_value(&resource)));
         self.resources.insert(TypeId::of::<T>(), Box::new(resource));
     }
 
@@ -149,14 +525,23 @@ impl World {
     }
 
     pub fn remove_resource<T: Any>(&mut self) -> Option<T> {
-        self.resources
-            .remove(&TypeId::of::<T>())?
+        self.remove_resource_by_type(TypeId::of::<T>())?
             .downcast::<T>()
             .ok()
             .map(|b| *b)
     }
 
+    pub fn remove_resource_by_type(&mut self, type_id: TypeId) -> Option<Box<dyn Any>> {
+        let resource = self.resources.remove(&type_id)?;
+        self.record(|_| WorldCommand::RemoveResource(type_id));
+        Some(resource)
+    }
+
     pub fn insert_named_resource<T: Any>(&mut self, name: &str, resource: T) {
+        self.record(|world| WorldCommand::InsertNamedResource {
+            name: name.to_string(),
+            resource: world.record_value(&resource),
+        });
         self.named_resources
             .insert((name.to_string(), TypeId::of::<T>()), Box::new(resource));
     }
@@ -168,13 +553,25 @@ impl World {
     }
 
     pub fn remove_named_resource<T: Any>(&mut self, name: &str) -> Option<T> {
-        self.named_resources
-            .remove(&(name.to_string(), TypeId::of::<T>()))?
+        self.remove_named_resource_by_type(name, TypeId::of::<T>())?
             .downcast::<T>()
             .ok()
             .map(|b| *b)
     }
 
+    pub fn remove_named_resource_by_type(
+        &mut self,
+        name: &str,
+        type_id: TypeId,
+    ) -> Option<Box<dyn Any>> {
+        let resource = self.named_resources.remove(&(name.to_string(), type_id))?;
+        self.record(|_| WorldCommand::RemoveNamedResource {
+            name: name.to_string(),
+            type_id,
+        });
+        Some(resource)
+    }
+
     pub fn query_entities_with<T: Any>(&self) -> Vec<Entity> {
         let type_id = TypeId::of::<T>();
         if let Some(storage) = self.components.get(&type_id) {
@@ -220,6 +617,57 @@ impl World {
             .collect()
     }
 
+    /// Registers a query for entities having all components of `types`, whose result is kept
+    /// up to date as components are added and removed, instead of being rebuilt on every call
+    /// like `query_entities_with_all`. Registering the same set of types again returns the
+    /// same query.
+    pub fn register_query(&mut self, types: &[TypeId]) -> QueryId {
+        let mut types = types.to_vec();
+        types.sort();
+        types.dedup();
+
+        if let Some(id) = self
+            .queries
+            .iter()
+            .position(|q| q.as_ref().is_some_and(|q| q.matches(&types)))
+        {
+            self.queries[id].as_mut().unwrap().acquire();
+            return QueryId(id);
+        }
+
+        let entities = self.query_entities_with_all(&types);
+        self.queries.push(Some(CachedQuery::new(types, entities)));
+        QueryId(self.queries.len() - 1)
+    }
+
+    /// Releases a registration made by `register_query`. The query is dropped once every
+    /// registration of it was released.
+    pub fn unregister_query(&mut self, id: QueryId) {
+        if let Some(slot) = self.queries.get_mut(id.0)
+            && slot.as_mut().is_some_and(|q| q.release())
+        {
+            *slot = None;
+        }
+    }
+
+    pub fn query(&self, id: QueryId) -> Option<&CachedQuery> {
+        self.queries.get(id.0)?.as_ref()
+    }
+
+    fn update_queries(&mut self, entity: Entity, type_id: TypeId) {
+        for query in self.queries.iter_mut().flatten() {
+            if !query.depends_on(type_id) {
+                continue;
+            }
+            let matching = query.types().iter().all(|t| {
+                self.components
+                    .get(t)
+                    .is_some_and(|storage| storage.contains(entity.id))
+            });
+            query.set(entity, matching);
+        }
+    }
+
     pub fn get_entity_components(&self, entity: Entity) -> Vec<TypeId> {
         if !self.entities.is_alive(entity) {
             return Vec::new();
@@ -233,11 +681,18 @@ impl World {
     }
 
     pub fn tag_component<T: Any, TAG: Any>(&mut self, entity: Entity) {
+        self.tag_component_by_type(entity, TypeId::of::<T>(), TypeId::of::<TAG>());
+    }
+
+    pub fn tag_component_by_type(&mut self, entity: Entity, type_id: TypeId, tag_id: TypeId) {
         if !self.entities.is_alive(entity) {
             return;
         }
-        let type_id = TypeId::of::<T>();
-        let tag_id = TypeId::of::<TAG>();
+        self.record(|_| WorldCommand::TagComponent {
+            entity,
+            type_id,
+            tag_id,
+        });
         let tags = self.component_tags.entry((entity.id, type_id)).or_default();
         if !tags.contains(&tag_id) {
             tags.push(tag_id);
@@ -245,17 +700,28 @@ impl World {
     }
 
     pub fn untag_component<T: Any, TAG: Any>(&mut self, entity: Entity) {
+        self.untag_component_by_type(entity, TypeId::of::<T>(), TypeId::of::<TAG>());
+    }
+
+    pub fn untag_component_by_type(&mut self, entity: Entity, type_id: TypeId, tag_id: TypeId) {
         if !self.entities.is_alive(entity) {
             return;
         }
-        let type_id = TypeId::of::<T>();
-        let tag_id = TypeId::of::<TAG>();
-        if let Some(tags) = self.component_tags.get_mut(&(entity.id, type_id)) {
-            tags.retain(|&t| t != tag_id);
-            if tags.is_empty() {
-                self.component_tags.remove(&(entity.id, type_id));
-            }
+        let Some(tags) = self.component_tags.get_mut(&(entity.id, type_id)) else {
+            return;
+        };
+        let Some(position) = tags.iter().position(|&t| t == tag_id) else {
+            return;
+        };
+        tags.remove(position);
+        if tags.is_empty() {
+            self.component_tags.remove(&(entity.id, type_id));
         }
+        self.record(|_| WorldCommand::UntagComponent {
+            entity,
+            type_id,
+            tag_id,
+        });
     }
 
     pub fn get_component_tags<T: Any>(&self, entity: Entity) -> Vec<TypeId> {
@@ -272,4 +738,77 @@ impl World {
             false
         }
     }
+
+    /// Compares this world against `other`, reporting entities that only exist in one of them
+    /// and components that were added, removed or changed on entities alive in both.
+    /// Changes are only detected for types registered with `World::register_comparable`
+    /// in either world.
+    pub fn diff(&self, other: &World) -> WorldDiff {
+        let entities: HashSet<Entity> = self.entities.alive().collect();
+        let other_entities: HashSet<Entity> = other.entities.alive().collect();
+
+        let mut added_entities: Vec<Entity> =
+            other_entities.difference(&entities).copied().collect();
+        let mut removed_entities: Vec<Entity> =
+            entities.difference(&other_entities).copied().collect();
+        added_entities.sort();
+        removed_entities.sort();
+
+        let type_ids: HashSet<TypeId> = self
+            .components
+            .keys()
+            .chain(other.components.keys())
+            .copied()
+            .collect();
+
+        let mut components = Vec::new();
+        for &entity in entities.intersection(&other_entities) {
+            for &type_id in &type_ids {
+                let value = self
+                    .components
+                    .get(&type_id)
+                    .and_then(|s| s.get_any(entity.id));
+                let other_value = other
+                    .components
+                    .get(&type_id)
+                    .and_then(|s| s.get_any(entity.id));
+
+                let change = match (value, other_value) {
+                    (None, None) => continue,
+                    (None, Some(_)) => ComponentChange::Added,
+                    (Some(_), None) => ComponentChange::Removed,
+                    (Some(a), Some(b)) => {
+                        let Some(eq) = self
+                            .comparables
+                            .get(&type_id)
+                            .or_else(|| other.comparables.get(&type_id))
+                        else {
+                            continue;
+                        };
+                        if eq(a, b) {
+                            continue;
+                        }
+                        ComponentChange::Changed
+                    }
+                };
+
+                components.push(ComponentDiff {
+                    entity,
+                    type_id,
+                    type_name: self
+                        .type_name(type_id)
+                        .or_else(|| other.type_name(type_id))
+                        .unwrap_or("<unknown>"),
+                    change,
+                });
+            }
+        }
+        components.sort_by(|a, b| (a.entity, a.type_name).cmp(&(b.entity, b.type_name)));
+
+        WorldDiff {
+            added_entities,
+            removed_entities,
+            components,
+        }
+    }
 }
@@ -9,8 +9,8 @@ use vn_ui::{
     AnchorExt, AnchorLocation, AnchorParams, ButtonExt, ButtonParams, DynamicDimension,
     DynamicSize, Element, ElementId, ElementSize, ElementWorld, EventHandler, EventManager, Flex,
     FlexExt, InteractionEventKind, InteractionState, InteractiveExt, InteractiveParams, PaddingExt,
-    PaddingParams, SimpleLayoutCache, SizeConstraints, StackExt, TextField, TextFieldParams,
-    TextVisuals, UiContext,
+    InputFilter, PaddingParams, SimpleLayoutCache, SizeConstraints, StackExt, TabIndex, TextAlign,
+    TextField, TextFieldParams, TextVisuals, Theme, UiContext, VerticalAlign,
 };
 use vn_wgpu_window::resource_manager::{ResourceManager, Sampling};
 use vn_wgpu_window::{GraphicsContext, WgpuScene};
@@ -116,9 +116,20 @@ impl StartMenu {
                         color: Color::WHITE,
                         caret_width: None,
                         caret_blink_duration: None,
+                        selection_anchor: None,
+                        selection_color: None,
+                        mask: None,
+                        placeholder: None,
+                        error: false,
+                        error_color: None,
+                        align: TextAlign::Left,
+                        vertical_align: VerticalAlign::Top,
+                        max_lines: None,
                     },
                     metrics: metrics.clone(),
                     interaction: Default::default(),
+                    tab_index: TabIndex::None,
+                    input_filter: InputFilter::default(),
                     text_field_action_handler: EventHandler::none(),
                 }),
                 &mut world,
@@ -151,6 +162,8 @@ impl StartMenu {
                             is_hovered: args.ctx.event_manager.borrow().is_hovered(args.id),
                             is_focused,
                         },
+                        // the menu moves the focus between its buttons itself
+                        tab_index: TabIndex::None,
                         on_click: if args.ctx.event_manager.borrow().is_hovered(args.id) {
                             Some(btn_type.to_menu_event())
                         } else {
@@ -255,7 +268,7 @@ impl GameStateEx for StartMenu {
     type Event = StartMenuEvent;
 
     fn process_events(&mut self) -> Option<Self::Event> {
-        let events = self.event_manager.borrow_mut().process_events();
+        let events = self.event_manager.borrow_mut().process_events(Instant::now());
         for event in events {
             let menu_event = match event.target {
                 Some(target) => self.handle_event(target, event.kind),
@@ -283,6 +296,7 @@ impl GameStateEx for StartMenu {
             interactive: true,
             clip_rect: Rect::NO_CLIP,
             now: Instant::now(),
+            theme: Rc::new(Theme::default()),
         };
 
         self.ui.borrow_mut().layout(
@@ -0,0 +1,193 @@
+use std::fmt;
+
+/// The thickness of a font, from 100 (thin) to 900 (black) like in CSS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FontWeight(pub u16);
+
+impl FontWeight {
+    pub const THIN: Self = Self(100);
+    pub const EXTRA_LIGHT: Self = Self(200);
+    pub const LIGHT: Self = Self(300);
+    pub const REGULAR: Self = Self(400);
+    pub const MEDIUM: Self = Self(500);
+    pub const SEMI_BOLD: Self = Self(600);
+    pub const BOLD: Self = Self(700);
+    pub const EXTRA_BOLD: Self = Self(800);
+    pub const BLACK: Self = Self(900);
+
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        let weight = match keyword {
+            "thin" => Self::THIN,
+            "extra-light" => Self::EXTRA_LIGHT,
+            "light" => Self::LIGHT,
+            "regular" => Self::REGULAR,
+            "medium" => Self::MEDIUM,
+            "semi-bold" => Self::SEMI_BOLD,
+            "bold" => Self::BOLD,
+            "extra-bold" => Self::EXTRA_BOLD,
+            "black" => Self::BLACK,
+            _ => match keyword.parse::<u16>() {
+                Ok(weight @ 1..=1000) => Self(weight),
+                _ => return None,
+            },
+        };
+        Some(weight)
+    }
+}
+
+impl Default for FontWeight {
+    fn default() -> Self {
+        Self::REGULAR
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+}
+
+/// Which font to draw text with: a list of font families or registered font names, searched in
+/// order for every character, and the weight and style to pick from each family.
+///
+/// Text takes fonts as strings, which are parsed into a query. Like the CSS `font` shorthand,
+/// the families are separated by commas and may be preceded by a style and a weight, e.g.
+/// `"italic bold Inter, Noto Sans JP, Noto Color Emoji"`. A plain font name is a query for that
+/// font alone. Names starting with a keyword need quotes, e.g. `"\"light sans\""`, since
+/// `"light sans"` is the family `sans` in the light weight.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FontQuery {
+    pub families: Vec<String>,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+}
+
+/// True if `word` sets the style or weight when it comes before the first family.
+fn is_keyword(word: &str) -> bool {
+    matches!(word, "italic" | "oblique" | "normal") || FontWeight::from_keyword(word).is_some()
+}
+
+impl FontQuery {
+    pub fn new(family: impl ToString) -> Self {
+        Self {
+            families: vec![family.to_string()],
+            ..Self::default()
+        }
+    }
+
+    /// Searched for characters the families before it have no glyph for.
+    pub fn fallback(mut self, family: impl ToString) -> Self {
+        self.families.push(family.to_string());
+        self
+    }
+
+    pub fn weight(mut self, weight: FontWeight) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn bold(self) -> Self {
+        self.weight(FontWeight::BOLD)
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.style = FontStyle::Italic;
+        self
+    }
+
+    pub fn parse(font: &str) -> Self {
+        let mut query = Self::default();
+        for (idx, family) in font.split(',').enumerate() {
+            let mut family = family.trim();
+            // the style and weight come before the first family, which needs a name left
+            while idx == 0
+                && let Some((keyword, rest)) = family.split_once(char::is_whitespace)
+            {
+                match keyword {
+                    "italic" | "oblique" => query.style = FontStyle::Italic,
+                    "normal" => {}
+                    _ => match FontWeight::from_keyword(keyword) {
+                        Some(weight) => query.weight = weight,
+                        None => break,
+                    },
+                }
+                family = rest.trim_start();
+            }
+
+            let family = family.trim_matches('"');
+            if !family.is_empty() {
+                query.families.push(family.to_string());
+            }
+        }
+        query
+    }
+}
+
+impl fmt::Display for FontQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.style == FontStyle::Italic {
+            write!(f, "italic ")?;
+        }
+        if self.weight != FontWeight::REGULAR {
+            write!(f, "{} ", self.weight.0)?;
+        }
+        for (idx, family) in self.families.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ", ")?;
+            }
+            // quoted, so the first word isn't read as a keyword
+            match family.split_once(char::is_whitespace) {
+                Some((word, _)) if idx == 0 && is_keyword(word) => write!(f, "\"{family}\"")?,
+                _ => write!(f, "{family}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_style_weight_and_fallbacks() {
+        let query = FontQuery::parse("italic bold Noto Sans, \"Noto Sans JP\" ,emoji");
+        assert_eq!(
+            query,
+            FontQuery::new("Noto Sans")
+                .fallback("Noto Sans JP")
+                .fallback("emoji")
+                .bold()
+                .italic()
+        );
+        assert_eq!(FontQuery::parse(&query.to_string()), query);
+
+        // a name that happens to be a keyword is still a name
+        assert_eq!(FontQuery::parse("bold"), FontQuery::new("bold"));
+        assert_eq!(
+            FontQuery::parse("jetbrains-bold"),
+            FontQuery::new("jetbrains-bold")
+        );
+    }
+
+    #[test]
+    fn quoted_names_starting_with_a_keyword() {
+        assert_eq!(
+            FontQuery::parse("light sans"),
+            FontQuery::new("sans").weight(FontWeight::LIGHT)
+        );
+        assert_eq!(
+            FontQuery::parse("\"light sans\""),
+            FontQuery::new("light sans")
+        );
+        assert_eq!(
+            FontQuery::parse("bold \"Black Ops One\", black"),
+            FontQuery::new("Black Ops One").fallback("black").bold()
+        );
+
+        let query = FontQuery::new("light sans").fallback("bold serif");
+        assert_eq!(query.to_string(), "\"light sans\", bold serif");
+        assert_eq!(FontQuery::parse(&query.to_string()), query);
+    }
+}
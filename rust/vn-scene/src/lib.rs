@@ -1,6 +1,10 @@
 use std::rc::Rc;
 use vn_ui_animation_macros::Interpolatable;
 
+mod font;
+
+pub use font::*;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Interpolatable)]
 pub struct TextureId(#[interpolate_snappy = "snap_middle"] pub Rc<u32>);
 
@@ -254,7 +258,7 @@ pub struct Globals {
     pub resolution: [f32; 2],
 }
 
-pub use winit::event::{ElementState, KeyEvent};
+pub use winit::event::{ElementState, Ime, KeyEvent};
 pub use winit::keyboard::{KeyCode, PhysicalKey};
 
 pub trait Scene {
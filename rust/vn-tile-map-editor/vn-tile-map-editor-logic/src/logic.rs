@@ -3,6 +3,7 @@ use crate::logic::game_state::{
     LoadTileSetMenuStateWithEditorMemory, LoadedTexture, NewLayerMenu,
     NewLayerMenuStateWithEditorMemory, TryLoadTileSetResult,
 };
+use crate::{UI_FONT, UI_FONT_SIZE};
 use std::cell::RefCell;
 use std::future::Future;
 use std::pin::Pin;
@@ -13,13 +14,13 @@ use vn_wgpu_window::StateLogic;
 use vn_wgpu_window::graphics::GraphicsContext;
 use vn_wgpu_window::resource_manager::{ResourceManager, Sampling};
 use vn_wgpu_window::scene_renderer::SceneRenderer;
-use web_time::Instant;
-use winit::event::KeyEvent;
+use web_time::{Duration, Instant};
+use winit::event::{ElementState, Ime, KeyEvent, Modifiers, MouseScrollDelta};
 use winit::event_loop::ActiveEventLoop;
+use winit::keyboard::{Key, NamedKey};
 
 pub mod game_state;
 pub mod grid;
-pub use grid::*;
 
 pub struct TextMetric {
     pub rm: Rc<ResourceManager>,
@@ -47,22 +48,38 @@ impl TextMetrics for TextMetric {
         self.rm.line_height(font, font_size)
     }
 
+    fn ascent(&self, font: &str, font_size: f32) -> f32 {
+        self.rm.ascent(font, font_size)
+    }
+
     fn get_glyphs(&self, text: &str, font: &str, font_size: f32) -> Vec<vn_scene::GlyphData> {
         let glyphs = self.rm.get_glyphs(&self.gc, text, font, font_size);
+        glyphs.into_iter().map(glyph_data).collect()
+    }
+
+    fn shape(&self, text: &str, font: &str, font_size: f32, rtl: bool) -> Vec<ShapedGlyph> {
+        let glyphs = self.rm.shape(&self.gc, text, font, font_size, rtl);
         glyphs
             .into_iter()
-            .map(|g| vn_scene::GlyphData {
-                texture_id: g.texture.clone(),
-                advance: g.advance,
-                x_bearing: g.x_bearing,
-                y_offset: g.y_offset,
-                size: [g.size.0, g.size.1],
-                uv_rect: g.uv_rect,
+            .map(|g| ShapedGlyph {
+                glyph: glyph_data(g.glyph),
+                cluster: g.cluster,
             })
             .collect()
     }
 }
 
+fn glyph_data(g: vn_wgpu_window::Glyph) -> vn_scene::GlyphData {
+    vn_scene::GlyphData {
+        texture_id: g.texture,
+        advance: g.advance,
+        x_bearing: g.x_bearing,
+        y_offset: g.y_offset,
+        size: [g.size.0, g.size.1],
+        uv_rect: g.uv_rect,
+    }
+}
+
 pub struct FpsStats {
     key_frame_time: RefCell<Option<Instant>>,
     frame_count: RefCell<u32>,
@@ -122,6 +139,12 @@ pub trait PlatformHooks {
     fn exit(&self);
 
     fn pick_file(&self, extensions: &[&str]) -> Option<File>;
+
+    /// The clipboard used by text fields. Without a platform clipboard, text can only be copied
+    /// within the editor.
+    fn clipboard(&self) -> Rc<RefCell<dyn Clipboard>> {
+        Rc::new(RefCell::new(MemoryClipboard::new()))
+    }
 }
 
 pub struct EditorCallback<Msg> {
@@ -144,9 +167,20 @@ pub struct MainLogic {
     mouse_position: (f32, f32),
     #[allow(unused)]
     platform: Rc<Box<dyn PlatformHooks>>,
+    clipboard: Rc<RefCell<dyn Clipboard>>,
+    theme: ThemeSwitcher,
+    // sampled once per frame, so events and rendering see the same theme while switching
+    frame_theme: Rc<Theme>,
+    dark_theme: bool,
     app_state: Option<ApplicationState>,
 }
 
+/// The theme of the editor, switched between dark and light with F2.
+fn ui_theme(dark: bool) -> Theme {
+    let theme = if dark { Theme::dark() } else { Theme::light() };
+    theme.with_fonts(FontRoles::new(UI_FONT, UI_FONT_SIZE))
+}
+
 pub struct ApplicationContext {
     #[allow(unused)]
     platform: Rc<Box<dyn PlatformHooks>>,
@@ -158,6 +192,16 @@ pub struct ApplicationContext {
     text_metrics: Rc<TextMetric>,
     #[allow(unused)]
     stats: Rc<RefCell<FpsStats>>,
+    clipboard: Rc<RefCell<dyn Clipboard>>,
+}
+
+impl ApplicationContext {
+    /// An event manager using the clipboard of the platform.
+    pub fn event_manager(&self) -> Rc<RefCell<EventManager>> {
+        let mut event_manager = EventManager::new();
+        event_manager.set_clipboard(self.clipboard.clone());
+        Rc::new(RefCell::new(event_manager))
+    }
 }
 
 impl MainLogic {
@@ -170,10 +214,11 @@ impl MainLogic {
             .load_asset("fonts/JetBrainsMono-Bold.ttf".to_string())
             .await?;
 
-        resource_manager.load_font_from_bytes("jetbrains-bold", &font_bytes)?;
+        resource_manager.add_font_to_family("JetBrains Mono", &font_bytes)?;
         resource_manager.set_glyph_size_increment(4.0);
 
         let fps_stats = Rc::new(RefCell::new(FpsStats::new()));
+        let clipboard = platform.clipboard();
 
         let game_state = ApplicationState::Editor(
             Editor::new(ApplicationContext {
@@ -185,6 +230,7 @@ impl MainLogic {
                     gc: graphics_context.clone(),
                 }),
                 stats: fps_stats.clone(),
+                clipboard: clipboard.clone(),
             })
             .await?,
         );
@@ -196,6 +242,10 @@ impl MainLogic {
             graphics_context,
             fps_stats,
             platform,
+            clipboard,
+            theme: ThemeSwitcher::new(ui_theme(true), Duration::from_millis(300)),
+            frame_theme: Rc::new(ui_theme(true)),
+            dark_theme: true,
             app_state: Some(game_state),
         })
     }
@@ -203,9 +253,11 @@ impl MainLogic {
 
 impl StateLogic<SceneRenderer> for MainLogic {
     fn process_events(&mut self) {
+        self.frame_theme = Rc::new(self.theme.theme(Instant::now()));
+        let theme = self.frame_theme.clone();
         self.app_state = Some(match self.app_state.take().unwrap() {
             ApplicationState::Editor(mut editor) => {
-                if let Some(event) = editor.process_events() {
+                if let Some(event) = editor.process_events(&theme) {
                     match event {
                         ApplicationEvent::NewLayer(already_loaded, editor_callback) => {
                             ApplicationState::NewLayerMenu(NewLayerMenuStateWithEditorMemory {
@@ -220,6 +272,7 @@ impl StateLogic<SceneRenderer> for MainLogic {
                                             gc: self.graphics_context.clone(),
                                         }),
                                         stats: self.fps_stats.clone(),
+                                        clipboard: self.clipboard.clone(),
                                     },
                                 ),
                                 editor_callback,
@@ -233,7 +286,7 @@ impl StateLogic<SceneRenderer> for MainLogic {
                 }
             }
             ApplicationState::LoadTileSetMenu(mut menu) => {
-                if let Some(event) = menu.process_events() {
+                if let Some(event) = menu.process_events(&theme) {
                     match event {
                         ApplicationEvent::TilesetLoaded(tiles) => {
                             log::info!("Loaded tiles {:?}", tiles);
@@ -252,7 +305,7 @@ impl StateLogic<SceneRenderer> for MainLogic {
                 }
             }
             ApplicationState::NewLayerMenu(mut new_menu) => {
-                if let Some(event) = new_menu.process_events() {
+                if let Some(event) = new_menu.process_events(&theme) {
                     match event {
                         ApplicationEvent::LoadTileset(loaded_tilesets) => {
                             log::info!("Start loading tileset");
@@ -289,6 +342,7 @@ impl StateLogic<SceneRenderer> for MainLogic {
                                                         gc: self.graphics_context.clone(),
                                                     }),
                                                     stats: self.fps_stats.clone(),
+                                                    clipboard: self.clipboard.clone(),
                                                 },
                                                 LoadedTexture {
                                                     suggested_name: file.name,
@@ -326,9 +380,27 @@ impl StateLogic<SceneRenderer> for MainLogic {
     }
 
     fn handle_key(&mut self, _event_loop: &ActiveEventLoop, event: &KeyEvent) {
+        if event.state == ElementState::Pressed
+            && !event.repeat
+            && event.logical_key == Key::Named(NamedKey::F2)
+        {
+            self.dark_theme = !self.dark_theme;
+            self.theme
+                .switch_to(ui_theme(self.dark_theme), Instant::now());
+            return;
+        }
+
         self.app_state.as_mut().unwrap().handle_key(event);
     }
 
+    fn handle_ime(&mut self, event: &Ime) {
+        self.app_state.as_mut().unwrap().handle_ime(event);
+    }
+
+    fn handle_modifiers(&mut self, modifiers: &Modifiers) {
+        self.app_state.as_mut().unwrap().handle_modifiers(modifiers);
+    }
+
     fn handle_mouse_position(&mut self, x: f32, y: f32) {
         self.mouse_position = (x, y);
         self.app_state.as_mut().unwrap().handle_mouse_position(x, y);
@@ -345,11 +417,11 @@ impl StateLogic<SceneRenderer> for MainLogic {
             .handle_mouse_button(self.mouse_position, button, state);
     }
 
-    fn handle_mouse_wheel(&mut self, delta_x: f32, delta_y: f32) {
+    fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {
         self.app_state
             .as_mut()
             .unwrap()
-            .handle_mouse_wheel(delta_x, delta_y);
+            .handle_mouse_wheel(self.mouse_position, delta);
     }
 
     fn resized(&mut self, width: u32, height: u32) {
@@ -364,7 +436,10 @@ impl StateLogic<SceneRenderer> for MainLogic {
             .app_state
             .as_ref()
             .unwrap()
-            .render_target((self.size.0 as f32, self.size.1 as f32));
+            .render_target(
+                (self.size.0 as f32, self.size.1 as f32),
+                &self.frame_theme,
+            );
 
         self.resource_manager.cleanup(60, 10000);
 
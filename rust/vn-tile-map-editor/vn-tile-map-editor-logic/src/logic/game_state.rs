@@ -2,8 +2,11 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::time::Instant;
 use winit::event::ElementState;
+use winit::event::Ime;
 use winit::event::KeyEvent;
+use winit::event::Modifiers;
 use winit::event::MouseButton;
+use winit::event::MouseScrollDelta;
 
 pub mod editor;
 pub use editor::*;
@@ -17,7 +20,7 @@ pub use new_layer_menu::*;
 pub mod ui_helper;
 pub use ui_helper::*;
 use vn_scene::TextureId;
-use vn_ui::{DynamicDimension, DynamicSize, Element, ElementSize, EventManager, InteractionEventKind, SimpleLayoutCache, SizeConstraints, UiContext};
+use vn_ui::{DynamicDimension, DynamicSize, Element, ElementSize, EventManager, InteractionEventKind, SimpleLayoutCache, SizeConstraints, Theme, UiContext};
 use vn_ui::InteractionEventKind::MouseScroll;
 use vn_wgpu_window::WgpuScene;
 use crate::logic::{ApplicationEvent, EditorCallback};
@@ -30,11 +33,11 @@ pub trait ApplicationStateEx {
     fn ui(&self) -> &RefCell<Box<dyn Element<State=Self::State, Message=Self::StateEvent>>>;
     fn state(&self) -> &Self::State;
     fn event_manager(&self) -> Rc<RefCell<EventManager>>;
+    /// Kept across frames, so only elements whose layout changed are laid out again.
+    fn layout_cache(&self) -> Rc<RefCell<SimpleLayoutCache>>;
     fn handle_event(&mut self, event: Self::StateEvent) -> Option<Self::ApplicationEvent>;
 
-    fn process_events(&mut self) -> Option<Self::ApplicationEvent> {
-        let events = self.event_manager().borrow_mut().process_events();
-
+    fn process_events(&mut self, theme: &Rc<Theme>) -> Option<Self::ApplicationEvent> {
         let mut ctx = UiContext {
             event_manager: self.event_manager().clone(),
             parent_id: None,
@@ -42,8 +45,16 @@ pub trait ApplicationStateEx {
             interactive: true,
             clip_rect: vn_scene::Rect::NO_CLIP,
             now: Instant::now(),
+            theme: theme.clone(),
         };
 
+        let events = self.event_manager().borrow_mut().process_events(ctx.now);
+
+        // every delivery goes through the whole tree and each element checks `current_target`
+        // itself, so an event targeting an element `depth` levels down walks the tree
+        // 2 * depth + 1 times. That is cheap for the few events of a frame and trees of this
+        // size; routing deliveries along their path only would need each container to know
+        // which of its children leads to the target.
         for event in &events {
             let messages = self.ui().borrow_mut().handle_event(&mut ctx, self.state(), event);
             for msg in messages {
@@ -56,7 +67,7 @@ pub trait ApplicationStateEx {
         None
     }
 
-    fn render_target(&self, size: (f32, f32)) -> WgpuScene {
+    fn render_target(&self, size: (f32, f32), theme: &Rc<Theme>) -> WgpuScene {
         let mut scene = WgpuScene::new((size.0, size.1));
 
         let event_manager = self.event_manager().clone();
@@ -65,12 +76,16 @@ pub trait ApplicationStateEx {
         let mut ctx = UiContext {
             event_manager,
             parent_id: None,
-            layout_cache: Box::new(SimpleLayoutCache::new()),
+            layout_cache: Box::new(self.layout_cache()),
             interactive: true,
             clip_rect: vn_scene::Rect::NO_CLIP,
             now: Instant::now(),
+            theme: theme.clone(),
         };
 
+        ctx.layout_cache.next_frame();
+        self.ui().borrow_mut().update(&mut ctx, self.state());
+
         self.ui().borrow_mut().layout(
             &mut ctx,
             self.state(),
@@ -107,6 +122,18 @@ pub trait ApplicationStateEx {
             .queue_event(InteractionEventKind::Keyboard(event.clone()));
     }
 
+    fn handle_ime(&mut self, event: &Ime) {
+        self.event_manager()
+            .borrow_mut()
+            .queue_event(InteractionEventKind::Ime(event.clone()));
+    }
+
+    fn handle_modifiers(&mut self, modifiers: &Modifiers) {
+        self.event_manager()
+            .borrow_mut()
+            .queue_event(InteractionEventKind::ModifiersChanged(modifiers.state().into()));
+    }
+
     fn handle_mouse_position(&mut self, x: f32, y: f32) {
         self.event_manager()
             .borrow_mut()
@@ -151,10 +178,16 @@ pub trait ApplicationStateEx {
         self.event_manager().borrow_mut().queue_event(kind);
     }
 
-    fn handle_mouse_wheel(&mut self, _delta_x: f32, delta_y: f32) {
+    fn handle_mouse_wheel(&mut self, mouse_position: (f32, f32), delta: MouseScrollDelta) {
         self.event_manager()
             .borrow_mut()
-            .queue_event(MouseScroll { y: delta_y })
+            .queue_event(MouseScroll {
+                delta: delta.into(),
+                x: mouse_position.0,
+                y: mouse_position.1,
+                local_x: mouse_position.0,
+                local_y: mouse_position.1,
+            })
     }
 }
 
@@ -207,18 +240,26 @@ macro_rules! dispatch {
 }
 
 impl ApplicationState {
-    pub fn process_events(&mut self) -> Option<ApplicationEvent> {
-        dispatch!(self, inner, inner.process_events())
+    pub fn process_events(&mut self, theme: &Rc<Theme>) -> Option<ApplicationEvent> {
+        dispatch!(self, inner, inner.process_events(theme))
     }
 
-    pub fn render_target(&self, size: (f32, f32)) -> WgpuScene {
-        dispatch!(self, inner, inner.render_target(size))
+    pub fn render_target(&self, size: (f32, f32), theme: &Rc<Theme>) -> WgpuScene {
+        dispatch!(self, inner, inner.render_target(size, theme))
     }
 
     pub fn handle_key(&mut self, event: &KeyEvent) {
         dispatch!(self, inner, inner.handle_key(event))
     }
 
+    pub fn handle_ime(&mut self, event: &Ime) {
+        dispatch!(self, inner, inner.handle_ime(event))
+    }
+
+    pub fn handle_modifiers(&mut self, modifiers: &Modifiers) {
+        dispatch!(self, inner, inner.handle_modifiers(modifiers))
+    }
+
     pub fn handle_mouse_position(&mut self, x: f32, y: f32) {
         dispatch!(self, inner, inner.handle_mouse_position(x, y))
     }
@@ -232,8 +273,8 @@ impl ApplicationState {
         dispatch!(self, inner, inner.handle_mouse_button(mouse_position, button, state))
     }
 
-    pub fn handle_mouse_wheel(&mut self, delta_x: f32, delta_y: f32) {
-        dispatch!(self, inner, inner.handle_mouse_wheel(delta_x, delta_y))
+    pub fn handle_mouse_wheel(&mut self, mouse_position: (f32, f32), delta: MouseScrollDelta) {
+        dispatch!(self, inner, inner.handle_mouse_wheel(mouse_position, delta))
     }
 }
 
@@ -254,6 +295,10 @@ impl ApplicationStateEx for LoadTileSetMenuStateWithEditorMemory {
         self.menu.event_manager()
     }
 
+    fn layout_cache(&self) -> Rc<RefCell<SimpleLayoutCache>> {
+        self.menu.layout_cache()
+    }
+
     fn handle_event(&mut self, event: Self::StateEvent) -> Option<Self::ApplicationEvent> {
         self.menu.handle_event(event)
     }
@@ -276,6 +321,10 @@ impl ApplicationStateEx for NewLayerMenuStateWithEditorMemory {
         self.menu.event_manager()
     }
 
+    fn layout_cache(&self) -> Rc<RefCell<SimpleLayoutCache>> {
+        self.menu.layout_cache()
+    }
+
     fn handle_event(&mut self, event: Self::StateEvent) -> Option<Self::ApplicationEvent> {
         self.menu.handle_event(event)
     }
@@ -1,15 +1,15 @@
 use crate::logic::game_state::editor_ui::{editor, layers, tileset};
 use crate::logic::game_state::{ApplicationStateEx, TryLoadTileSetResult, label, with_fps};
 use crate::logic::{ApplicationContext, ApplicationEvent, EditorCallback};
-use crate::{UI_FONT, UI_FONT_SIZE};
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use vn_scene::{Color, TextureId};
+use vn_scene::TextureId;
 use vn_tilemap::{TileMapLayerMapSpecification, TileMapLayerSpecification, TileMapSpecification};
 use vn_ui::{
-    AnchorExt, AnchorLocation, AnchorParams, Element, ElementWorld, EventManager, Flex, FlexChild,
-    FlexDirection, FlexParams, PaddingExt, PaddingParams, ScrollBarParams, params,
+    AnchorExt, AnchorLocation, AnchorParams, Element, ElementWorld, EventManager, Flex,
+    FlexChild, FlexDirection, FlexParams, PaddingExt, PaddingParams, SimpleLayoutCache,
+    TextAlign, params,
 };
 
 pub mod editor_ui;
@@ -19,10 +19,10 @@ pub struct EditorState {
     loaded_tilesets: HashMap<String, TextureId>,
     current_layer: Option<usize>,
     tile_map: TileMapSpecification,
-    tileset_view_scroll_x: ScrollBarParams,
-    tileset_view_scroll_y: ScrollBarParams,
-    tilemap_view_scroll_x: ScrollBarParams,
-    tilemap_view_scroll_y: ScrollBarParams,
+    tileset_view_scroll_x: Option<f32>,
+    tileset_view_scroll_y: Option<f32>,
+    tilemap_view_scroll_x: Option<f32>,
+    tilemap_view_scroll_y: Option<f32>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,14 +36,21 @@ pub enum EditorEvent {
     SaveSpec,
     AddLayer(TryLoadTileSetResult),
     SwitchToLayer(usize),
+    /// Sets the tile at `x`, `y` of the current layer.
+    PlaceTile { x: u32, y: u32, tile: usize },
 }
 
+/// The payload of a tile dragged from the tileset panel onto the map.
+#[derive(Debug, Clone, Copy)]
+pub struct DraggedTile(pub usize);
+
 pub struct Editor {
     #[allow(unused)]
     ctx: ApplicationContext,
     ui: RefCell<Box<dyn Element<State = EditorState, Message = EditorEvent>>>,
     state: EditorState,
     event_manager: Rc<RefCell<EventManager>>,
+    layout_cache: Rc<RefCell<SimpleLayoutCache>>,
 }
 
 impl Editor {
@@ -52,9 +59,10 @@ impl Editor {
 
         let title = label(
             |_| "Tile Map Editor".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
-            Color::WHITE,
+            |theme| &theme.fonts.title,
+            |theme| theme.colors.text,
+            TextAlign::Center,
+            None,
             ctx.text_metrics.clone(),
             world.clone(),
         )
@@ -83,9 +91,8 @@ impl Editor {
                                     FlexChild::new(tileset).into_rc_refcell(),
                                 ];
                                 params!(FlexParams {
-                                    direction: FlexDirection::Row,
-                                    force_orthogonal_same_size: true,
                                     children: children.clone(),
+                                    ..FlexParams::new(FlexDirection::Row)
                                 })
                             },
                             world.clone(),
@@ -95,20 +102,14 @@ impl Editor {
                     .into_rc_refcell(),
                 ];
                 params!(FlexParams {
-                    direction: FlexDirection::Column,
                     children: children.clone(),
-                    force_orthogonal_same_size: true,
+                    ..FlexParams::new(FlexDirection::Column)
                 })
             },
             world.clone(),
         );
 
-        let scroll_bar = ScrollBarParams {
-            width: 16.0,
-            color: Color::WHITE,
-            position: Some(0.0),
-            margin: 8.0,
-        };
+        let event_manager = ctx.event_manager();
 
         Ok(Self {
             ui: RefCell::new(with_fps(&ctx, Box::new(ui), world.clone())),
@@ -120,12 +121,13 @@ impl Editor {
                     layers: vec![],
                     map_dimensions: (10, 5),
                 },
-                tileset_view_scroll_x: scroll_bar,
-                tileset_view_scroll_y: scroll_bar,
-                tilemap_view_scroll_y: scroll_bar,
-                tilemap_view_scroll_x: scroll_bar,
+                tileset_view_scroll_x: Some(0.0),
+                tileset_view_scroll_y: Some(0.0),
+                tilemap_view_scroll_y: Some(0.0),
+                tilemap_view_scroll_x: Some(0.0),
             },
-            event_manager: Rc::new(RefCell::new(EventManager::new())),
+            event_manager,
+            layout_cache: Rc::new(RefCell::new(SimpleLayoutCache::new())),
         })
     }
 }
@@ -147,6 +149,10 @@ impl ApplicationStateEx for Editor {
         self.event_manager.clone()
     }
 
+    fn layout_cache(&self) -> Rc<RefCell<SimpleLayoutCache>> {
+        self.layout_cache.clone()
+    }
+
     fn handle_event(&mut self, event: Self::StateEvent) -> Option<Self::ApplicationEvent> {
         log::info!("handling state event: {:?}", event);
 
@@ -155,16 +161,16 @@ impl ApplicationStateEx for Editor {
                 self.state.current_layer = Some(layer.clamp(0, self.state.tile_map.layers.len()));
             }
             EditorEvent::TilesetViewScrollX(v) => {
-                self.state.tileset_view_scroll_x.position = Some(v)
+                self.state.tileset_view_scroll_x = Some(v)
             }
             EditorEvent::TilesetViewScrollY(v) => {
-                self.state.tileset_view_scroll_y.position = Some(v)
+                self.state.tileset_view_scroll_y = Some(v)
             }
             EditorEvent::TilemapViewScrollX(v) => {
-                self.state.tilemap_view_scroll_x.position = Some(v)
+                self.state.tilemap_view_scroll_x = Some(v)
             }
             EditorEvent::TilemapViewScrollY(v) => {
-                self.state.tilemap_view_scroll_y.position = Some(v)
+                self.state.tilemap_view_scroll_y = Some(v)
             }
             EditorEvent::TryAddingLayer => {
                 return Some(ApplicationEvent::NewLayer(
@@ -233,6 +239,17 @@ impl ApplicationStateEx for Editor {
                 }
                 self.state.current_layer = Some(self.state.tile_map.layers.len() - 1);
             }
+            EditorEvent::PlaceTile { x, y, tile } => {
+                if let Some(layer) = self.state.current_layer
+                    && let Some(cell) = self.state.tile_map.layers[layer]
+                        .map
+                        .tiles
+                        .get_mut(y as usize)
+                        .and_then(|row| row.get_mut(x as usize))
+                {
+                    *cell = Some(tile);
+                }
+            }
             EditorEvent::LoadSpec => {}
             EditorEvent::SaveSpec => {}
         }
@@ -1,6 +1,6 @@
-use crate::logic::game_state::{EditorEvent, EditorState, btn, empty_texture, label};
-use crate::logic::{ApplicationContext, Grid, GridParams};
-use crate::{UI_FONT, UI_FONT_SIZE};
+use crate::logic::game_state::{DraggedTile, EditorEvent, EditorState, btn, empty_texture, label};
+use crate::logic::ApplicationContext;
+use crate::logic::grid::{Grid, GridParams};
 use std::cell::RefCell;
 use std::rc::Rc;
 use vn_scene::{Color, Rect};
@@ -14,9 +14,10 @@ pub fn layers(
 ) -> Box<dyn Element<State = EditorState, Message = EditorEvent>> {
     let title = label(
         |_| "Layer Settings".to_string(),
-        UI_FONT,
-        UI_FONT_SIZE,
-        Color::WHITE,
+        |theme| &theme.fonts.title,
+        |theme| theme.colors.text,
+        TextAlign::Center,
+        None,
         ctx.text_metrics.clone(),
         world.clone(),
     )
@@ -31,12 +32,8 @@ pub fn layers(
 
     let new_layer = btn(
         |_| "Add Layer".to_string(),
-        UI_FONT,
-        UI_FONT_SIZE,
         |_| false,
-        |_| Color::WHITE,
-        |_| Color::WHITE,
-        |_| Color::WHITE,
+        |_| false,
         ctx.text_metrics.clone(),
         EventHandler::new(|_, e| match e {
             ButtonAction::Clicked => {
@@ -48,12 +45,8 @@ pub fn layers(
 
     let save = btn(
         |_| "Save".to_string(),
-        UI_FONT,
-        UI_FONT_SIZE,
         |_| false,
-        |_| Color::WHITE,
-        |_| Color::WHITE,
-        |_| Color::WHITE,
+        |_| false,
         ctx.text_metrics.clone(),
         EventHandler::new(|_, e| match e {
             ButtonAction::Clicked => {
@@ -65,12 +58,8 @@ pub fn layers(
 
     let load = btn(
         |_| "Load".to_string(),
-        UI_FONT,
-        UI_FONT_SIZE,
         |_| false,
-        |_| Color::WHITE,
-        |_| Color::WHITE,
-        |_| Color::WHITE,
+        |_| false,
         ctx.text_metrics.clone(),
         EventHandler::new(|_, e| match e {
             ButtonAction::Clicked => {
@@ -92,9 +81,9 @@ pub fn layers(
                 FlexChild::new(load).into_rc_refcell(),
             ];
             params!(FlexParams {
-                direction: FlexDirection::Row,
-                force_orthogonal_same_size: false,
-                children: c.clone()
+                children: c.clone(),
+                align_items: FlexAlign::Start,
+                ..FlexParams::new(FlexDirection::Row)
             })
         },
         world.clone(),
@@ -114,16 +103,8 @@ pub fn layers(
                 for idx in cache_len..args.state.tile_map.layers.len() {
                      let layer = btn(
                         move |state: &EditorState| state.tile_map.layers[idx].name.clone(),
-                        UI_FONT,
-                        UI_FONT_SIZE,
                         |_| false,
-                        move |state: &EditorState| if state.current_layer.map(|l| l == idx).unwrap_or(false) {
-                            Color::GREEN
-                        } else {
-                            Color::WHITE.with_alpha(0.8)
-                        },
-                        |_| Color::TRANSPARENT,
-                        |_| Color::WHITE,
+                        move |state: &EditorState| state.current_layer.map(|l| l == idx).unwrap_or(false),
                         metrics.clone(),
                         EventHandler::new(move |_, e| match e {
                             ButtonAction::Clicked => vec![EditorEvent::SwitchToLayer(idx)],
@@ -137,9 +118,8 @@ pub fn layers(
                 }
 
                 FlexParams {
-                    direction: FlexDirection::Column,
                     children: cache.borrow()[0..args.state.tile_map.layers.len()].iter().cloned().collect(),
-                    force_orthogonal_same_size: true
+                    ..FlexParams::new(FlexDirection::Column)
             }})
         },
         world.clone(),
@@ -148,12 +128,7 @@ pub fn layers(
     let layer_list = layer_flex
         .padding(params!(PaddingParams::uniform(5.0)), world.clone())
         .card(
-            params!(CardParams {
-                border_color: Color::WHITE,
-                corner_radius: 5.0,
-                border_size: 2.0,
-                background_color: Color::BLACK,
-            }),
+            params!(args => args.ctx.theme.panel),
             world.clone(),
         );
 
@@ -166,9 +141,8 @@ pub fn layers(
                             FlexChild::weighted(Empty::new(world.clone()), 1.0).into_rc_refcell(),
                         ];
                         params!(FlexParams {
-                            force_orthogonal_same_size: true,
-                            direction: FlexDirection::Row,
-                            children: c.clone()
+                            children: c.clone(),
+                            ..FlexParams::new(FlexDirection::Row)
                         })
                     },
                     world.clone(),
@@ -184,9 +158,8 @@ pub fn layers(
                 FlexChild::new(save_load).into_rc_refcell(),
             ];
             params!(FlexParams {
-                direction: FlexDirection::Column,
                 children: c.clone(),
-                force_orthogonal_same_size: true,
+                ..FlexParams::new(FlexDirection::Column)
             })
         },
         world.clone(),
@@ -195,12 +168,7 @@ pub fn layers(
     .anchor(top!(), world.clone())
     .fill(world.clone())
     .card(
-        params!(CardParams {
-            border_color: Color::WHITE,
-            border_size: 2.0,
-            background_color: Color::BLACK,
-            corner_radius: 5.0,
-        }),
+        params!(args => args.ctx.theme.panel),
         world.clone(),
     )
     .prefer_size(
@@ -219,9 +187,10 @@ pub fn editor(
 ) -> Box<dyn Element<State = EditorState, Message = EditorEvent>> {
     let title = label(
         |_| "Map".to_string(),
-        UI_FONT,
-        UI_FONT_SIZE,
-        Color::WHITE,
+        |theme| &theme.fonts.title,
+        |theme| theme.colors.text,
+        TextAlign::Center,
+        None,
         ctx.text_metrics.clone(),
         world.clone(),
     )
@@ -239,7 +208,7 @@ pub fn editor(
         GridParams {
             cols: args.state.tile_map.map_dimensions.0,
             rows: args.state.tile_map.map_dimensions.1,
-            grid_color: Color::WHITE.with_alpha(0.5),
+            grid_color: args.ctx.theme.colors.border.with_alpha(0.5),
             grid_width: 3.0,
             grid_size: (32.0, 32.0),
         }),
@@ -260,12 +229,28 @@ pub fn editor(
         world.clone(),
     );
 
+    // tiles dragged from the tileset panel are placed on the current layer
     let map = Stack::new(vec![map.into(), grid.into()], world.clone())
+        .drop_target(
+            params!(DropTargetParams {
+                on_drop: EventHandler::new(|_, e| match e {
+                    DropAction::Dropped { value: DraggedTile(tile), local_x, local_y } => {
+                        vec![EditorEvent::PlaceTile {
+                            x: (local_x / 32.0) as u32,
+                            y: (local_y / 32.0) as u32,
+                            tile,
+                        }]
+                    }
+                    DropAction::Entered(_) | DropAction::Left => vec![],
+                }),
+            }),
+            world.clone(),
+        )
         .anchor(center!(), world.clone())
         .scroll_area(
             params!(args<EditorState> => ScrollAreaParams {
-                scroll_x: args.state.tilemap_view_scroll_x,
-                scroll_y: args.state.tilemap_view_scroll_y,
+                scroll_x: ScrollBarParams::themed(&args.ctx.theme, args.state.tilemap_view_scroll_x),
+                scroll_y: ScrollBarParams::themed(&args.ctx.theme, args.state.tilemap_view_scroll_y),
                 scroll_action_handler: EventHandler::new(|_, e| match e {
                     ScrollAreaAction::ScrollX(v) => vec![EditorEvent::TilemapViewScrollX(v)],
                     ScrollAreaAction::ScrollY(v) => vec![EditorEvent::TilemapViewScrollY(v)],
@@ -283,9 +268,8 @@ pub fn editor(
                         let c =
                             FlexChild::weighted(Empty::new(world.clone()), 1.0).into_rc_refcell();
                         params!(FlexParams {
-                            direction: FlexDirection::Row,
                             children: vec![c.clone()],
-                            force_orthogonal_same_size: true,
+                            ..FlexParams::new(FlexDirection::Row)
                         })
                     },
                     world.clone(),
@@ -296,8 +280,7 @@ pub fn editor(
             ];
             params!(FlexParams {
                 children: c.clone(),
-                direction: FlexDirection::Column,
-                force_orthogonal_same_size: true,
+                ..FlexParams::new(FlexDirection::Column)
             })
         },
         world.clone(),
@@ -310,9 +293,10 @@ pub fn tileset(
 ) -> Box<dyn Element<State = EditorState, Message = EditorEvent>> {
     let title = label(
         |_| "Tileset".to_string(),
-        UI_FONT,
-        UI_FONT_SIZE,
-        Color::WHITE,
+        |theme| &theme.fonts.title,
+        |theme| theme.colors.text,
+        TextAlign::Center,
+        None,
         ctx.text_metrics.clone(),
         world.clone(),
     )
@@ -330,64 +314,82 @@ pub fn tileset(
         .load_texture_from_bytes(empty_texture(), Sampling::Nearest)
         .expect("empty texture");
 
-    let tileset_tex = Texture::new(
+    // one element per tile, so only the visible tiles of large tilesets are built and drawn
+    let tiles = VirtualGrid::new(
+        move |idx, world: Rc<RefCell<ElementWorld>>| {
+            let empty_id = empty_text.id.clone();
+            Texture::new(
+                params!(args<EditorState> =>
+                    let Some(layer) = args.state.current_layer.map(|layer| &args.state.tile_map.layers[layer]) else {
+                        return TextureParams {
+                            texture_id: empty_id.clone(),
+                            preferred_size: ElementSize::ZERO,
+                            uv_rect: Rect::UNIT,
+                            tint: Color::WHITE,
+                            fit_strategy: FitStrategy::Stretch,
+                        };
+                    };
+                    let (cols, rows) = layer.tileset_dimensions;
+                    let uv_width = 1.0 / cols as f32;
+                    let uv_height = 1.0 / rows as f32;
+
+                    TextureParams {
+                        texture_id: args.state.loaded_tilesets.get(&layer.tileset).unwrap().clone(),
+                        preferred_size: ElementSize {
+                            width: layer.tile_dimensions.0 as f32,
+                            height: layer.tile_dimensions.1 as f32,
+                        },
+                        uv_rect: Rect {
+                            position: [
+                                (idx as u32 % cols) as f32 * uv_width,
+                                (idx as u32 / cols) as f32 * uv_height,
+                            ],
+                            size: [uv_width, uv_height],
+                        },
+                        tint: Color::WHITE,
+                        fit_strategy: FitStrategy::Stretch,
+                    }
+                ),
+                world.clone(),
+            )
+            .draggable(
+                params!(args<EditorState> => DraggableParams {
+                    payload: args.state.current_layer.map(|_| DragPayload::new(DraggedTile(idx))),
+                    on_drag: EventHandler::none(),
+                }),
+                world,
+            )
+            .into()
+        },
         params!(args<EditorState> =>
-            let id = args.state.current_layer.map(|layer |
-                    args.state.loaded_tilesets
-                        .get(&args.state.tile_map.layers[layer].tileset)
-                        .unwrap().clone())
-                    .unwrap_or(empty_text.id.clone());
-            let size = args.state.current_layer.map(|layer | {
-                (
-                    args.state.tile_map.layers[layer].tileset_dimensions.0 * args.state.tile_map.layers[layer].tile_dimensions.0,
-                    args.state.tile_map.layers[layer].tileset_dimensions.1 * args.state.tile_map.layers[layer].tile_dimensions.1,
-                )
-            }).unwrap_or((0, 0));
+            let (cols, rows, tile_w, tile_h) = args.state.current_layer.map(|layer | {
+                let tileset_dim = args.state.tile_map.layers[layer].tileset_dimensions;
+                let tile_dim = args.state.tile_map.layers[layer].tile_dimensions;
 
-            let size = ElementSize {
-                width: size.0 as f32,
-                height: size.1 as f32,
-            };
+                (tileset_dim.0, tileset_dim.1, tile_dim.0, tile_dim.1)
+            }).unwrap_or((0, 0, 0, 0));
 
-            TextureParams {
-                texture_id: id,
-                preferred_size: size,
-                uv_rect: Rect::UNIT,
-                tint: Color::WHITE,
-                fit_strategy: FitStrategy::Clip { rotation: 0.0 },
+            VirtualGridParams {
+                count: (cols * rows) as usize,
+                item_size: ElementSize {
+                    width: tile_w as f32,
+                    height: tile_h as f32,
+                },
+                columns: Some(cols as usize),
+                gap: 3.0,
+                revision: 0,
             }
         ),
         world.clone(),
     );
 
-    let grid = Grid::new(
-        params!(args<EditorState> =>
-        let (cols, rows, grid_w, grid_h) = args.state.current_layer.map(|layer | {
-            let tileset_dim = args.state.tile_map.layers[layer].tileset_dimensions;
-            let tile_dim = args.state.tile_map.layers[layer].tile_dimensions;
-
-            (tileset_dim.0, tileset_dim.1, tile_dim.0, tile_dim.1)
-        }).unwrap_or((0, 0, 0, 0));
-
-        GridParams {
-            cols,
-            rows,
-            grid_color: Color::WHITE.with_alpha(0.25),
-            grid_width: 3.0,
-            grid_size: (grid_w as f32, grid_h as f32),
-        }),
-        world.clone(),
-    );
-
-    // todo: add click behaviour for brushes
-
-    let layout = Stack::new(vec![tileset_tex.into(), grid.into()], world.clone());
+    // todo: add click behaviour for brushes, tiles can only be dragged onto the map so far
 
     let tileset = ScrollArea::new(
-        layout,
+        tiles,
         params!(args<EditorState> => ScrollAreaParams {
-            scroll_x: args.state.tileset_view_scroll_x.clone(),
-            scroll_y: args.state.tileset_view_scroll_y.clone(),
+            scroll_x: ScrollBarParams::themed(&args.ctx.theme, args.state.tileset_view_scroll_x),
+            scroll_y: ScrollBarParams::themed(&args.ctx.theme, args.state.tileset_view_scroll_y),
             scroll_action_handler: EventHandler::new(|_, e| {
                 match e {
                     ScrollAreaAction::ScrollX(v) => vec![EditorEvent::TilesetViewScrollX(v)],
@@ -406,9 +408,8 @@ pub fn tileset(
                     FlexChild::weighted(tileset, 1.0).into_rc_refcell(),
                 ];
                 params!(FlexParams {
-                    force_orthogonal_same_size: true,
-                    direction: FlexDirection::Column,
                     children: c.clone(),
+                    ..FlexParams::new(FlexDirection::Column)
                 })
             },
             world.clone(),
@@ -417,12 +418,7 @@ pub fn tileset(
         .anchor(top!(), world.clone())
         .fill(world.clone())
         .card(
-            params!(CardParams {
-                border_color: Color::WHITE,
-                corner_radius: 5.0,
-                border_size: 2.0,
-                background_color: Color::BLACK,
-            }),
+            params!(args => args.ctx.theme.panel),
             world.clone(),
         )
         .prefer_size(
@@ -6,8 +6,7 @@ use crate::logic::game_state::{
     ApplicationStateEx, Input, LoadedTileSet, TextFieldState, TryLoadTileSetResult, btn, input,
     label, labelled_input, suppress_enter_key, with_fps,
 };
-use crate::logic::{ApplicationContext, ApplicationEvent, Grid, GridParams};
-use crate::{UI_FONT, UI_FONT_SIZE};
+use crate::logic::{ApplicationContext, ApplicationEvent};
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::rc::Rc;
@@ -27,8 +26,8 @@ pub struct LoadTileSetMenuState {
     already_loaded_tilesets: Vec<String>,
     tileset_name_input_state: TextFieldState,
     loaded_texture: LoadedTexture,
-    loaded_texture_scroll_x: ScrollBarParams,
-    loaded_texture_scroll_y: ScrollBarParams,
+    loaded_texture_scroll_x: Option<f32>,
+    loaded_texture_scroll_y: Option<f32>,
     tiles_wide_input: TextFieldState,
     tiles_wide: u32,
     tiles_heigh_input: TextFieldState,
@@ -54,21 +53,15 @@ pub enum LoadTileMenuStateErrors {
     TilesetNameAlreadyInUse,
 }
 
-#[derive(Clone, Debug)]
-pub enum LoadTileSetMenuInputEvent {
-    CaretMoved(usize),
-    TextChanged(String),
-}
-
 #[derive(Clone, Debug)]
 pub enum LoadTileSetMenuEvent {
     Reuse(String),
     Save,
     Cancel,
-    TileSetNameInputChanged(LoadTileSetMenuInputEvent),
-    TileWideInputChanged(LoadTileSetMenuInputEvent),
+    TileSetNameInputChanged(TextFieldAction),
+    TileWideInputChanged(TextFieldAction),
     TilesWideChanged(u32),
-    TileHeighInputChanged(LoadTileSetMenuInputEvent),
+    TileHeighInputChanged(TextFieldAction),
     TilesHighChanged(u32),
     TexturePreviewScrollX(f32),
     TexturePreviewScrollY(f32),
@@ -80,6 +73,7 @@ pub struct LoadTileSetMenu {
     ui: RefCell<Box<dyn Element<State = LoadTileSetMenuState, Message = LoadTileSetMenuEvent>>>,
     state: LoadTileSetMenuState,
     event_manager: Rc<RefCell<EventManager>>,
+    layout_cache: Rc<RefCell<SimpleLayoutCache>>,
 }
 
 impl LoadTileSetMenu {
@@ -91,24 +85,16 @@ impl LoadTileSetMenu {
         let world = Rc::new(RefCell::new(ElementWorld::new()));
         let save = btn(
             |_| "Save".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
             |state: &LoadTileSetMenuState| !state.errors.is_empty(),
-            |_| Color::WHITE,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
+            |_| false,
             ctx.text_metrics.clone(),
             EventHandler::new(|_, _| vec![LoadTileSetMenuEvent::Save]),
             world.clone(),
         );
         let cancel = btn(
             |_| "Cancel".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
             |_| false,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
+            |_| false,
             ctx.text_metrics.clone(),
             EventHandler::new(|_, _| vec![LoadTileSetMenuEvent::Cancel]),
             world.clone(),
@@ -126,9 +112,8 @@ impl LoadTileSetMenu {
                     FlexChild::new(cancel).into_rc_refcell(),
                 ];
                 params!(FlexParams {
-                    direction: FlexDirection::Row,
-                    force_orthogonal_same_size: true,
                     children: children.clone(),
+                    ..FlexParams::new(FlexDirection::Row)
                 })
             },
             world.clone(),
@@ -144,22 +129,16 @@ impl LoadTileSetMenu {
             id: tileset_name_input_id,
             element: tileset_name_input,
         } = input(
-            |state: &LoadTileSetMenuState| state.tileset_name_input_state.clone(),
+            |state: &LoadTileSetMenuState| &state.tileset_name_input_state,
             Some("Tileset Name"),
-            UI_FONT,
-            UI_FONT_SIZE,
+            InputFilter::default(),
+            |state: &LoadTileSetMenuState| {
+                state.errors.contains(&TilesetNameIsEmpty)
+                    || state.errors.contains(&TilesetNameAlreadyInUse)
+            },
             ctx.text_metrics.clone(),
-            EventHandler::new(|_, event| match event {
-                TextFieldAction::TextChange(new_text) => {
-                    vec![LoadTileSetMenuEvent::TileSetNameInputChanged(
-                        LoadTileSetMenuInputEvent::TextChanged(new_text),
-                    )]
-                }
-                TextFieldAction::CaretMove(position) => {
-                    vec![LoadTileSetMenuEvent::TileSetNameInputChanged(
-                        LoadTileSetMenuInputEvent::CaretMoved(position),
-                    )]
-                }
+            EventHandler::new(|_, action| {
+                vec![LoadTileSetMenuEvent::TileSetNameInputChanged(action)]
             })
             .with_overwrite(suppress_enter_key()),
             world.clone(),
@@ -170,22 +149,15 @@ impl LoadTileSetMenu {
             id: tiles_wide_id,
             element: tiles_wide,
         } = labelled_input(
-            |state: &LoadTileSetMenuState| state.tiles_wide_input.clone(),
+            |state: &LoadTileSetMenuState| &state.tiles_wide_input,
             "Tiles Wide: ",
-            UI_FONT,
-            UI_FONT_SIZE,
+            InputFilter::integer(Some(0), Some(u32::MAX as i64)),
+            |state: &LoadTileSetMenuState| {
+                state.errors.contains(&TilesWideIsZero) || state.errors.contains(&TilesWideMustDivideTexture)
+            },
             ctx.text_metrics.clone(),
-            EventHandler::new(|_, event| match event {
-                TextFieldAction::TextChange(new_text) => {
-                    vec![LoadTileSetMenuEvent::TileWideInputChanged(
-                        LoadTileSetMenuInputEvent::TextChanged(new_text),
-                    )]
-                }
-                TextFieldAction::CaretMove(position) => {
-                    vec![LoadTileSetMenuEvent::TileWideInputChanged(
-                        LoadTileSetMenuInputEvent::CaretMoved(position),
-                    )]
-                }
+            EventHandler::new(|_, action| {
+                vec![LoadTileSetMenuEvent::TileWideInputChanged(action)]
             })
             .with_overwrite(suppress_enter_key()),
             world.clone(),
@@ -195,22 +167,15 @@ impl LoadTileSetMenu {
             id: tiles_heigh_id,
             element: tiles_high,
         } = labelled_input(
-            |state: &LoadTileSetMenuState| state.tiles_heigh_input.clone(),
+            |state: &LoadTileSetMenuState| &state.tiles_heigh_input,
             "Tiles High: ",
-            UI_FONT,
-            UI_FONT_SIZE,
+            InputFilter::integer(Some(0), Some(u32::MAX as i64)),
+            |state: &LoadTileSetMenuState| {
+                state.errors.contains(&TilesHeighIsZero) || state.errors.contains(&TilesHighMustDivideTexture)
+            },
             ctx.text_metrics.clone(),
-            EventHandler::new(|_, event| match event {
-                TextFieldAction::TextChange(new_text) => {
-                    vec![LoadTileSetMenuEvent::TileHeighInputChanged(
-                        LoadTileSetMenuInputEvent::TextChanged(new_text),
-                    )]
-                }
-                TextFieldAction::CaretMove(position) => {
-                    vec![LoadTileSetMenuEvent::TileHeighInputChanged(
-                        LoadTileSetMenuInputEvent::CaretMoved(position),
-                    )]
-                }
+            EventHandler::new(|_, action| {
+                vec![LoadTileSetMenuEvent::TileHeighInputChanged(action)]
             })
             .with_overwrite(suppress_enter_key()),
             world.clone(),
@@ -222,9 +187,10 @@ impl LoadTileSetMenu {
                 messages.sort();
                 messages.join("\n")
             },
-            UI_FONT,
-            UI_FONT_SIZE,
-            Color::RED,
+            |theme| &theme.fonts.body,
+            |theme| theme.colors.error,
+            TextAlign::Left,
+            None,
             ctx.text_metrics.clone(),
             world.clone(),
         );
@@ -233,21 +199,59 @@ impl LoadTileSetMenu {
             |state: &LoadTileSetMenuState| {
                 format!("Dimension:\n {:?}", state.loaded_texture.dimensions)
             },
-            UI_FONT,
-            UI_FONT_SIZE,
-            Color::WHITE.with_alpha(0.5),
+            |theme| &theme.fonts.body,
+            |theme| theme.colors.text_muted,
+            TextAlign::Left,
+            None,
             ctx.text_metrics.clone(),
             world.clone(),
         );
 
-        let grid = Grid::new(
-            params!(args<LoadTileSetMenuState> => GridParams {
-                cols: args.state.tiles_wide,
-                rows: args.state.tiles_high,
-                grid_size: (args.state.loaded_texture.dimensions.0 as f32 / args.state.tiles_wide as f32, args.state.loaded_texture.dimensions.1 as f32 / args.state.tiles_high as f32),
-                grid_color: Color::WHITE,
-                grid_width: 3.0,
-            }),
+        // one element per tile, so only the visible tiles of large textures are built and drawn
+        let tiles = VirtualGrid::new(
+            |idx, world: Rc<RefCell<ElementWorld>>| {
+                Texture::new(
+                    params!(args<LoadTileSetMenuState> =>
+                        let cols = args.state.tiles_wide.max(1);
+                        let rows = args.state.tiles_high.max(1);
+                        let uv_width = 1.0 / cols as f32;
+                        let uv_height = 1.0 / rows as f32;
+
+                        TextureParams {
+                            texture_id: args.state.loaded_texture.id.clone(),
+                            tint: Color::WHITE,
+                            fit_strategy: FitStrategy::Stretch,
+                            uv_rect: Rect {
+                                position: [
+                                    (idx as u32 % cols) as f32 * uv_width,
+                                    (idx as u32 / cols) as f32 * uv_height,
+                                ],
+                                size: [uv_width, uv_height],
+                            },
+                            preferred_size: ElementSize {
+                                width: args.state.loaded_texture.dimensions.0 as f32 / cols as f32,
+                                height: args.state.loaded_texture.dimensions.1 as f32 / rows as f32,
+                            }
+                    }),
+                    world,
+                )
+                .into()
+            },
+            params!(args<LoadTileSetMenuState> =>
+                let cols = args.state.tiles_wide.max(1);
+                let rows = args.state.tiles_high.max(1);
+
+                VirtualGridParams {
+                    count: (args.state.tiles_wide * args.state.tiles_high) as usize,
+                    item_size: ElementSize {
+                        width: args.state.loaded_texture.dimensions.0 as f32 / cols as f32,
+                        height: args.state.loaded_texture.dimensions.1 as f32 / rows as f32,
+                    },
+                    columns: Some(cols as usize),
+                    gap: 3.0,
+                    revision: 0,
+                }
+            ),
             world.clone(),
         );
 
@@ -255,33 +259,11 @@ impl LoadTileSetMenu {
         // put text with meta information below (specifically the dimensions)
         let texture = PreferSize::new(
             Box::new(ScrollArea::new(
-                Box::new(Stack::new(
-                    vec![
-                        Box::new(Texture::new(
-                            params!(args<LoadTileSetMenuState> =>
-                                TextureParams {
-                                    texture_id: args.state.loaded_texture.id.clone(),
-                                    tint: Color::WHITE,
-                                    fit_strategy: FitStrategy::Clip {rotation: 0.0},
-                                    uv_rect: Rect {
-                                        position: [0.0, 0.0],
-                                        size: [1.0, 1.0],
-                                    },
-                                    preferred_size: ElementSize {
-                                        width: args.state.loaded_texture.dimensions.0 as f32,
-                                        height: args.state.loaded_texture.dimensions.1 as f32,
-                                    }
-                            }),
-                            world.clone(),
-                        )),
-                        Box::new(grid),
-                    ],
-                    world.clone(),
-                )),
+                Box::new(tiles),
                 params!(args<LoadTileSetMenuState> =>
                     ScrollAreaParams {
-                        scroll_x: args.state.loaded_texture_scroll_x.clone(),
-                        scroll_y: args.state.loaded_texture_scroll_y.clone(),
+                        scroll_x: ScrollBarParams::themed(&args.ctx.theme, args.state.loaded_texture_scroll_x),
+                        scroll_y: ScrollBarParams::themed(&args.ctx.theme, args.state.loaded_texture_scroll_y),
                         scroll_action_handler: EventHandler::new(|_, e| {
                                 match e {
                                     ScrollAreaAction::ScrollX(v) => vec![LoadTileSetMenuEvent::TexturePreviewScrollX(v)],
@@ -302,9 +284,10 @@ impl LoadTileSetMenu {
         let title = Padding::new(
             label(
                 |_| "Configure Tileset".to_string(),
-                UI_FONT,
-                UI_FONT_SIZE,
-                Color::WHITE,
+                |theme| &theme.fonts.title,
+                |theme| theme.colors.text,
+                TextAlign::Center,
+                None,
                 ctx.text_metrics.clone(),
                 world.clone(),
             ),
@@ -337,9 +320,8 @@ impl LoadTileSetMenu {
                 Flex::new(
                     {
                         params!(FlexParams {
-                            direction: FlexDirection::Column,
-                            force_orthogonal_same_size: true,
                             children: settings_children.clone(),
+                            ..FlexParams::new(FlexDirection::Column)
                         })
                     },
                     world.clone(),
@@ -351,9 +333,8 @@ impl LoadTileSetMenu {
                 Flex::new(
                     {
                         params!(FlexParams {
-                            direction: FlexDirection::Column,
-                            force_orthogonal_same_size: true,
-                            children: preview_children.clone()
+                            children: preview_children.clone(),
+                            ..FlexParams::new(FlexDirection::Column)
                         })
                     },
                     world.clone(),
@@ -369,9 +350,8 @@ impl LoadTileSetMenu {
                 Flex::new(
                     {
                         params!(FlexParams {
-                            direction: FlexDirection::Row,
-                            force_orthogonal_same_size: true,
                             children: main_panel_children.clone(),
+                            ..FlexParams::new(FlexDirection::Row)
                         })
                     },
                     world.clone(),
@@ -388,9 +368,8 @@ impl LoadTileSetMenu {
             Flex::new(
                 {
                     params!(FlexParams {
-                        direction: FlexDirection::Column,
-                        force_orthogonal_same_size: true,
                         children: main_layout_children.clone(),
+                        ..FlexParams::new(FlexDirection::Column)
                     })
                 },
                 world.clone(),
@@ -403,12 +382,7 @@ impl LoadTileSetMenu {
         )
         .padding(params!(PaddingParams::uniform(25.0)), world.clone())
         .card(
-            params!(CardParams {
-                background_color: Color::BLACK,
-                border_size: 2.0,
-                corner_radius: 5.0,
-                border_color: Color::WHITE,
-            }),
+            params!(args => args.ctx.theme.panel),
             world.clone(),
         )
         .anchor(
@@ -444,46 +418,42 @@ impl LoadTileSetMenu {
         errors.insert(TilesHeighIsZero);
         errors.insert(TilesWideIsZero);
 
+        let event_manager = ctx.event_manager();
+
         Ok(Self {
             ui: RefCell::new(with_fps(&ctx, Box::new(ui), world.clone())),
             ctx,
             state: LoadTileSetMenuState {
                 already_loaded_tilesets,
-                tileset_name_input_state: TextFieldState {
-                    id: tileset_name_input_id,
-                    text: suggested_name,
-                    caret: None,
-                },
+                tileset_name_input_state: TextFieldState::new(tileset_name_input_id, suggested_name),
                 loaded_texture,
                 tiles_high: 1,
-                tiles_heigh_input: TextFieldState {
-                    id: tiles_heigh_id,
-                    text: "".to_string(),
-                    caret: None,
-                },
+                tiles_heigh_input: TextFieldState::new(tiles_heigh_id, ""),
                 tiles_wide: 1,
-                tiles_wide_input: TextFieldState {
-                    id: tiles_wide_id,
-                    text: "".to_string(),
-                    caret: None,
-                },
-                loaded_texture_scroll_x: ScrollBarParams {
-                    position: Some(0.0),
-                    width: 16.0,
-                    margin: 8.0,
-                    color: Color::WHITE,
-                },
-                loaded_texture_scroll_y: ScrollBarParams {
-                    position: Some(0.0),
-                    width: 16.0,
-                    margin: 8.0,
-                    color: Color::WHITE,
-                },
+                tiles_wide_input: TextFieldState::new(tiles_wide_id, ""),
+                loaded_texture_scroll_x: Some(0.0),
+                loaded_texture_scroll_y: Some(0.0),
                 errors,
             },
-            event_manager: Rc::new(RefCell::new(EventManager::new())),
+            event_manager,
+            layout_cache: Rc::new(RefCell::new(SimpleLayoutCache::new())),
         })
     }
+
+    fn validate_tileset_name(&mut self) {
+        let name = &self.state.tileset_name_input_state.text;
+        if name.is_empty() {
+            self.state.errors.insert(TilesetNameIsEmpty);
+        } else {
+            self.state.errors.remove(&TilesetNameIsEmpty);
+        }
+
+        if self.state.already_loaded_tilesets.contains(name) {
+            self.state.errors.insert(TilesetNameAlreadyInUse);
+        } else {
+            self.state.errors.remove(&TilesetNameAlreadyInUse);
+        }
+    }
 }
 
 impl ApplicationStateEx for LoadTileSetMenu {
@@ -503,86 +473,34 @@ impl ApplicationStateEx for LoadTileSetMenu {
         self.event_manager.clone()
     }
 
+    fn layout_cache(&self) -> Rc<RefCell<SimpleLayoutCache>> {
+        self.layout_cache.clone()
+    }
+
     fn handle_event(&mut self, event: Self::StateEvent) -> Option<Self::ApplicationEvent> {
         log::info!("handling event: {:?}", event);
         match event {
-            LoadTileSetMenuEvent::TileSetNameInputChanged(event) => match event {
-                LoadTileSetMenuInputEvent::CaretMoved(mut position) => {
-                    if self.state.tileset_name_input_state.text.is_empty() {
-                        position = 0
-                    }
-                    self.state.tileset_name_input_state.caret = Some(position);
-                }
-                LoadTileSetMenuInputEvent::TextChanged(new_text) => {
-                    if new_text.is_empty() {
-                        self.state.errors.insert(TilesetNameIsEmpty);
-                    } else {
-                        self.state.errors.remove(&TilesetNameIsEmpty);
-                    }
-
-                    if self.state.already_loaded_tilesets.contains(&new_text) {
-                        self.state.errors.insert(TilesetNameAlreadyInUse);
-                    } else {
-                        self.state.errors.remove(&TilesetNameAlreadyInUse);
-                    }
-
-                    self.state.tileset_name_input_state.text = new_text;
+            LoadTileSetMenuEvent::TileSetNameInputChanged(action) => {
+                if self.state.tileset_name_input_state.apply(action) {
+                    self.validate_tileset_name();
                 }
-            },
-            LoadTileSetMenuEvent::TileWideInputChanged(event) => match event {
-                LoadTileSetMenuInputEvent::CaretMoved(mut position) => {
-                    if self.state.tiles_wide_input.text.is_empty() {
-                        position = 0
-                    }
-                    self.state.tiles_wide_input.caret =
-                        Some(position.min(self.state.tiles_wide_input.text.chars().count()));
-                }
-                LoadTileSetMenuInputEvent::TextChanged(new_text) => {
-                    let new_text = new_text.trim().to_string();
-                    if new_text.is_empty() {
-                        self.state.tiles_wide = 0;
-                        self.state.tiles_wide_input.text = new_text;
-                        self.handle_event(LoadTileSetMenuEvent::TilesWideChanged(0));
-                    } else {
-                        let wide = new_text.parse::<u32>();
-                        match wide {
-                            Ok(wide) => {
-                                self.state.tiles_wide = wide;
-                                self.state.tiles_wide_input.text = new_text;
-                                self.handle_event(LoadTileSetMenuEvent::TilesWideChanged(wide));
-                            }
-                            Err(_) => {}
-                        }
-                    }
-                }
-            },
-            LoadTileSetMenuEvent::TileHeighInputChanged(event) => match event {
-                LoadTileSetMenuInputEvent::CaretMoved(mut position) => {
-                    if self.state.tiles_heigh_input.text.is_empty() {
-                        position = 0
-                    }
-                    self.state.tiles_heigh_input.caret =
-                        Some(position.min(self.state.tiles_heigh_input.text.chars().count()));
+            }
+            LoadTileSetMenuEvent::TileWideInputChanged(action) => {
+                if self.state.tiles_wide_input.apply(action) {
+                    // the input filter only lets numbers through, the empty text counts as 0
+                    let wide = self.state.tiles_wide_input.text.parse::<u32>().unwrap_or(0);
+                    self.state.tiles_wide = wide;
+                    self.handle_event(LoadTileSetMenuEvent::TilesWideChanged(wide));
                 }
-                LoadTileSetMenuInputEvent::TextChanged(new_text) => {
-                    let new_text = new_text.trim().to_string();
-                    if new_text.is_empty() {
-                        self.state.tiles_high = 0;
-                        self.state.tiles_heigh_input.text = new_text;
-                        self.handle_event(LoadTileSetMenuEvent::TilesHighChanged(0));
-                    } else {
-                        let heigh = new_text.parse::<u32>();
-                        match heigh {
-                            Ok(heigh) => {
-                                self.state.tiles_high = heigh;
-                                self.state.tiles_heigh_input.text = new_text;
-                                self.handle_event(LoadTileSetMenuEvent::TilesHighChanged(heigh));
-                            }
-                            Err(_) => {}
-                        }
-                    }
+            }
+            LoadTileSetMenuEvent::TileHeighInputChanged(action) => {
+                if self.state.tiles_heigh_input.apply(action) {
+                    // the input filter only lets numbers through, the empty text counts as 0
+                    let heigh = self.state.tiles_heigh_input.text.parse::<u32>().unwrap_or(0);
+                    self.state.tiles_high = heigh;
+                    self.handle_event(LoadTileSetMenuEvent::TilesHighChanged(heigh));
                 }
-            },
+            }
             LoadTileSetMenuEvent::TilesWideChanged(wide) => {
                 if wide == 0 {
                     self.state.errors.insert(TilesWideIsZero);
@@ -608,10 +526,10 @@ impl ApplicationStateEx for LoadTileSetMenu {
                 }
             }
             LoadTileSetMenuEvent::TexturePreviewScrollX(v) => {
-                self.state.loaded_texture_scroll_x.position = Some(v);
+                self.state.loaded_texture_scroll_x = Some(v);
             }
             LoadTileSetMenuEvent::TexturePreviewScrollY(v) => {
-                self.state.loaded_texture_scroll_y.position = Some(v);
+                self.state.loaded_texture_scroll_y = Some(v);
             }
 
             LoadTileSetMenuEvent::Save => {
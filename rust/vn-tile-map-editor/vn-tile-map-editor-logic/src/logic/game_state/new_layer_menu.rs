@@ -1,16 +1,14 @@
 use crate::logic::game_state::{ApplicationStateEx, ListParams, btn, label, list, with_fps};
 use crate::logic::{ApplicationContext, ApplicationEvent};
-use crate::{UI_FONT, UI_FONT_SIZE};
 use std::cell::RefCell;
 use std::rc::Rc;
-use vn_scene::Color;
-use vn_ui::{AnchorExt, ButtonAction, CardExt, CardParams, Element, ElementWorld, Empty, EventHandler, EventManager, Flex, FlexChild, FlexDirection, FlexParams, PaddingExt, PaddingParams, PreferSizeExt, PreferSizeParams, ScrollAreaAction, ScrollAreaExt, ScrollAreaParams, ScrollBarParams, center, params, Stack};
+use vn_ui::{AnchorExt, ButtonAction, CardExt, Element, ElementWorld, Empty, EventHandler, EventManager, Flex, FlexChild, FlexDirection, FlexParams, PaddingExt, PaddingParams, PreferSizeExt, PreferSizeParams, ScrollAreaAction, SimpleLayoutCache, ScrollAreaExt, ScrollAreaParams, ScrollBarParams, center, params, Stack, TextAlign};
 
 pub struct NewLayerState {
     existing_tileset_names: Vec<String>,
     selected_tileset: Option<usize>,
-    scroll_x: ScrollBarParams,
-    scroll_y: ScrollBarParams,
+    scroll_x: Option<f32>,
+    scroll_y: Option<f32>,
     error: Option<String>,
 }
 
@@ -32,6 +30,7 @@ pub struct NewLayerMenu {
     #[allow(unused)]
     ctx: ApplicationContext,
     event_manager: Rc<RefCell<EventManager>>,
+    layout_cache: Rc<RefCell<SimpleLayoutCache>>,
 }
 
 impl NewLayerMenu {
@@ -46,9 +45,10 @@ impl NewLayerMenu {
 
         let title = label(
             |_| "Selecting Tileset for Layer".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
-            Color::WHITE,
+            |theme| &theme.fonts.title,
+            |theme| theme.colors.text,
+            TextAlign::Center,
+            None,
             ctx.text_metrics.clone(),
             world.clone(),
         )
@@ -57,12 +57,8 @@ impl NewLayerMenu {
 
         let new = btn(
             |_| "Load New".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
             |_: &NewLayerState| false,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
+            |_| false,
             ctx.text_metrics.clone(),
             EventHandler::new(|_, e| match e {
                 ButtonAction::Clicked => vec![NewLayerEvent::New],
@@ -72,12 +68,8 @@ impl NewLayerMenu {
 
         let use_selected = btn(
             |_| "Use Selected".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
             |state: &NewLayerState| state.selected_tileset.is_none(),
-            |_| Color::WHITE,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
+            |_| false,
             ctx.text_metrics.clone(),
             EventHandler::new(|_, e| match e {
                 ButtonAction::Clicked => vec![NewLayerEvent::UseSelected],
@@ -87,12 +79,8 @@ impl NewLayerMenu {
 
         let cancel = btn(
             |_| "Cancel".to_string(),
-            UI_FONT,
-            UI_FONT_SIZE,
             |_: &NewLayerState| false,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
-            |_| Color::WHITE,
+            |_| false,
             ctx.text_metrics.clone(),
             EventHandler::new(|_, e| match e {
                 ButtonAction::Clicked => vec![NewLayerEvent::Cancel],
@@ -108,22 +96,13 @@ impl NewLayerMenu {
                 for idx in 0..existing_tileset_names.len() {
                     children.push(Rc::new(RefCell::new(FlexChild::new(btn(
                         move |state: &NewLayerState| state.existing_tileset_names[idx].clone(),
-                        UI_FONT,
-                        UI_FONT_SIZE,
                         |_| false,
                         move |state: &NewLayerState| {
-                            if state
+                            state
                                 .selected_tileset
                                 .map(|s_idx| s_idx == idx)
                                 .unwrap_or(false)
-                            {
-                                Color::GREEN
-                            } else {
-                                Color::WHITE.with_alpha(0.8)
-                            }
                         },
-                        |_| Color::TRANSPARENT,
-                        |_| Color::WHITE,
                         ctx.text_metrics.clone(),
                         EventHandler::new(move |_, e| match e {
                             ButtonAction::Clicked => vec![NewLayerEvent::SelectLayer(idx)],
@@ -147,8 +126,8 @@ impl NewLayerMenu {
         .scroll_area(
             params!( args<NewLayerState> =>
                 ScrollAreaParams {
-                    scroll_x: args.state.scroll_x,
-                    scroll_y: args.state.scroll_y,
+                    scroll_x: ScrollBarParams::themed(&args.ctx.theme, args.state.scroll_x),
+                    scroll_y: ScrollBarParams::themed(&args.ctx.theme, args.state.scroll_y),
                     scroll_action_handler: EventHandler::new(|_, e| {
                         match e {
                             ScrollAreaAction::ScrollX(v) => vec![NewLayerEvent::ScrollX(v)],
@@ -168,20 +147,16 @@ impl NewLayerMenu {
         )
         .padding(params!(PaddingParams::uniform(10.0)), world.clone())
         .card(
-            params!(CardParams {
-                border_color: Color::WHITE,
-                corner_radius: 5.0,
-                border_size: 2.0,
-                background_color: Color::BLACK,
-            }),
+            params!(args => args.ctx.theme.panel),
             world.clone(),
         );
 
         let error = label(
             |state: &NewLayerState| state.error.as_ref().unwrap_or(&"".to_string()).clone(),
-            UI_FONT,
-            UI_FONT_SIZE,
-            Color::RED,
+            |theme| &theme.fonts.body,
+            |theme| theme.colors.error,
+            TextAlign::Left,
+            Some(2),
             ctx.text_metrics.clone(),
             world.clone(),
         );
@@ -220,8 +195,7 @@ impl NewLayerMenu {
                                 ];
                                 params!(FlexParams {
                                     children: children.clone(),
-                                    force_orthogonal_same_size: true,
-                                    direction: FlexDirection::Row
+                                    ..FlexParams::new(FlexDirection::Row)
                                 })
                             },
                             world.clone(),
@@ -232,42 +206,32 @@ impl NewLayerMenu {
                 ];
                 params!(FlexParams {
                     children: children.clone(),
-                    direction: FlexDirection::Column,
-                    force_orthogonal_same_size: true,
+                    ..FlexParams::new(FlexDirection::Column)
                 })
             },
             world.clone(),
         )
         .padding(params!(PaddingParams::uniform(25.0)), world.clone())
         .card(
-            params!(CardParams {
-                border_color: Color::WHITE,
-                border_size: 2.0,
-                background_color: Color::BLACK,
-                corner_radius: 5.0,
-            }),
+            params!(args => args.ctx.theme.panel),
             world.clone(),
         )
         .anchor(center!(), world.clone());
 
-        let scroll_bar = ScrollBarParams {
-            width: 16.0,
-            margin: 8.0,
-            color: Color::WHITE,
-            position: Some(0.0),
-        };
+        let event_manager = ctx.event_manager();
 
         Self {
             ui: RefCell::new(with_fps(&ctx, Box::new(layout), world.clone())),
             state: NewLayerState {
                 existing_tileset_names,
                 selected_tileset: None,
-                scroll_x: scroll_bar,
-                scroll_y: scroll_bar,
+                scroll_x: Some(0.0),
+                scroll_y: Some(0.0),
                 error: None,
             },
             ctx,
-            event_manager: Rc::new(RefCell::new(EventManager::new())),
+            event_manager,
+            layout_cache: Rc::new(RefCell::new(SimpleLayoutCache::new())),
         }
     }
 }
@@ -289,6 +253,10 @@ impl ApplicationStateEx for NewLayerMenu {
         self.event_manager.clone()
     }
 
+    fn layout_cache(&self) -> Rc<RefCell<SimpleLayoutCache>> {
+        self.layout_cache.clone()
+    }
+
     fn handle_event(&mut self, event: Self::StateEvent) -> Option<Self::ApplicationEvent> {
         log::info!("handling state event: {:?}", event);
 
@@ -305,11 +273,11 @@ impl ApplicationStateEx for NewLayerMenu {
                 None
             }
             NewLayerEvent::ScrollX(v) => {
-                self.state.scroll_x.position = Some(v);
+                self.state.scroll_x = Some(v);
                 None
             }
             NewLayerEvent::ScrollY(v) => {
-                self.state.scroll_y.position = Some(v);
+                self.state.scroll_y = Some(v);
                 None
             }
         }
@@ -1,7 +1,6 @@
 use crate::logic::game_state::LoadTileSetMenuEvent;
 use crate::logic::{ApplicationContext, FpsStats, TextMetric};
-use crate::{UI_FONT, UI_FONT_SIZE};
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use vn_scene::Color;
 use vn_ui::*;
@@ -16,42 +15,125 @@ pub struct TextFieldState {
     pub id: ElementId,
     pub text: String,
     pub caret: Option<usize>,
+    pub selection: Option<usize>,
+    pub history: EditHistory,
+}
+
+impl TextFieldState {
+    pub fn new(id: ElementId, text: impl ToString) -> Self {
+        Self {
+            id,
+            text: text.to_string(),
+            caret: None,
+            selection: None,
+            history: EditHistory::new(),
+        }
+    }
+
+    fn snapshot(&self) -> TextSnapshot {
+        TextSnapshot {
+            text: self.text.clone(),
+            caret: self.caret,
+            selection_anchor: self.selection,
+        }
+    }
+
+    fn restore(&mut self, snapshot: TextSnapshot) {
+        self.text = snapshot.text;
+        self.caret = snapshot.caret;
+        self.selection = snapshot.selection_anchor;
+    }
+
+    /// Applies an action of the text field. True if the text changed, including by undo or redo.
+    pub fn apply(&mut self, action: TextFieldAction) -> bool {
+        match action {
+            TextFieldAction::TextChange(text) => {
+                self.edit(text);
+                true
+            }
+            TextFieldAction::CaretMove(position) => {
+                let position = position.min(self.text.chars().count());
+                self.history.caret_moved(position);
+                self.caret = Some(position);
+                self.selection = None;
+                false
+            }
+            TextFieldAction::Select { anchor, caret } => {
+                self.history.break_coalescing();
+                self.caret = Some(caret);
+                self.selection = Some(anchor);
+                false
+            }
+            TextFieldAction::Undo => self.undo(),
+            TextFieldAction::Redo => self.redo(),
+        }
+    }
+
+    /// Replaces the text after an edit of the user, which can be undone.
+    pub fn edit(&mut self, text: String) {
+        self.history.record(self.snapshot(), &text);
+        self.text = text;
+    }
+
+    /// Restores the text before the last edit. False if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo(self.snapshot()) {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restores the text before the last undo. False if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo(self.snapshot()) {
+            Some(snapshot) => {
+                self.restore(snapshot);
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 pub fn input<State: 'static, Event: Clone + 'static, F>(
     text: F,
     place_holder: Option<impl ToString>,
-    font: impl ToString,
-    font_size: f32,
+    filter: InputFilter,
+    error: impl Fn(&State) -> bool + 'static,
     metrics: Rc<TextMetric>,
     handler: EventHandler<TextFieldAction, Event>,
     world: Rc<RefCell<ElementWorld>>,
 ) -> Input<State, Event>
 where
-    F: Fn(&State) -> TextFieldState + 'static,
+    F: Fn(&State) -> &TextFieldState + 'static,
 {
     let input = TextField::new(
         {
-            let font = font.to_string();
             let place_holder = place_holder.map(|x| x.to_string());
             params! { args =>
                 let text = text(args.state);
+                let theme = &args.ctx.theme;
+                let state = WidgetState::of(args.ctx, args.id, false);
                 let is_focused = args.ctx.event_manager.borrow().is_focused(args.id);
                 TextFieldParams {
                     visuals: TextVisuals {
-                        color: if text.text.is_empty() && !is_focused { Color::WHITE.with_alpha(0.3) } else { Color::WHITE },
-                        text: if text.text.is_empty() && let Some(text) = place_holder.as_ref() && !is_focused { text.clone() } else { text.text },
                         caret_position: text.caret,
-                        font: font.clone(),
-                        font_size,
-                        caret_width: Some(2.0),
+                        selection_anchor: text.selection,
                         caret_blink_duration: Some(1.0),
+                        placeholder: place_holder.clone(),
+                        error: error(args.state),
+                        ..TextVisuals::themed(theme, state, &theme.fonts.body, &text.text)
                     },
                     metrics: metrics.clone(),
                     interaction: InteractionState {
                         is_focused,
                         is_hovered: is_focused,
                     },
+                    tab_index: TabIndex::Auto,
+                    input_filter: filter.clone(),
                     text_field_action_handler: handler.clone(),
                 }
             }
@@ -62,17 +144,13 @@ where
     let input_id = input.id().clone();
 
     let input = input
-        .padding(params!(PaddingParams::uniform(5.0)), world.clone())
+        .padding(params!(args => PaddingParams::uniform(args.ctx.theme.spacing.small)), world.clone())
         .card({
                   let input_id = input_id.clone();
                   params!(args =>
-                    let is_hovered = args.ctx.event_manager.borrow().is_hovered(input_id);
-                    CardParams {
-                        background_color: if is_hovered { Color::WHITE.with_alpha(0.15) } else { Color::WHITE.with_alpha(0.1) },
-                        border_color: if is_hovered { Color::WHITE } else { Color::WHITE.with_alpha(0.5) },
-                        corner_radius: 5.0,
-                        border_size: 2.0,
-                    })
+                    let state = WidgetState::of(args.ctx, input_id, false);
+                    *args.ctx.theme.input.get(state)
+                  )
               },
               world.clone(),
         );
@@ -83,11 +161,14 @@ where
     }
 }
 
+/// A read-only text in one of the font roles and colors of the theme, e.g.
+/// `|theme| &theme.fonts.title` and `|theme| theme.colors.text`.
 pub fn label<State: 'static, Event: Clone + 'static, F>(
     text: F,
-    font: impl ToString,
-    font_size: f32,
-    color: Color,
+    font: fn(&Theme) -> &FontRole,
+    color: fn(&Theme) -> Color,
+    align: TextAlign,
+    max_lines: Option<usize>,
     metrics: Rc<TextMetric>,
     world: Rc<RefCell<ElementWorld>>,
 ) -> Box<dyn Element<State = State, Message = Event>>
@@ -95,23 +176,21 @@ where
     F: Fn(&State) -> String + 'static,
 {
     Box::new(TextField::new(
-        {
-            let font = font.to_string();
-            params! { args =>
-                TextFieldParams {
-                    visuals: TextVisuals {
-                        color,
-                        text: text(args.state),
-                        caret_position: None,
-                        font: font.clone(),
-                        font_size,
-                        caret_width: Some(2.0),
-                        caret_blink_duration: Some(1.0),
-                    },
-                    metrics: metrics.clone(),
-                    interaction: InteractionState::default(),
-                    text_field_action_handler: EventHandler::none(),
-                }
+        params! { args =>
+            let theme = &args.ctx.theme;
+            TextFieldParams {
+                visuals: TextVisuals {
+                    color: color(theme),
+                    align,
+                    vertical_align: VerticalAlign::Center,
+                    max_lines,
+                    ..TextVisuals::themed(theme, WidgetState::Normal, font(theme), text(args.state))
+                },
+                metrics: metrics.clone(),
+                interaction: InteractionState::default(),
+                tab_index: TabIndex::None,
+                input_filter: InputFilter::default(),
+                text_field_action_handler: EventHandler::none(),
             }
         },
         world.clone(),
@@ -121,22 +200,21 @@ where
 pub fn labelled_input<State: 'static, Event: Clone + 'static, F>(
     text: F,
     label: impl ToString,
-    font: impl ToString,
-    font_size: f32,
+    filter: InputFilter,
+    error: impl Fn(&State) -> bool + 'static,
     metrics: Rc<TextMetric>,
     handler: EventHandler<TextFieldAction, Event>,
     world: Rc<RefCell<ElementWorld>>,
 ) -> Input<State, Event>
 where
-    F: Fn(&State) -> TextFieldState + 'static,
+    F: Fn(&State) -> &TextFieldState + 'static,
 {
-    let font = font.to_string();
     let label = label.to_string();
     let mut input = input(
         text,
         Some(" "),
-        font.clone(),
-        font_size,
+        filter,
+        error,
         metrics.clone(),
         handler,
         world.clone(),
@@ -145,18 +223,16 @@ where
     let label = TextField::new(
         {
             params! { args =>
+                let theme = &args.ctx.theme;
                 TextFieldParams {
                     visuals: TextVisuals {
-                        color: Color::WHITE.with_alpha(0.5),
-                        text: label.clone(),
-                        caret_position: None,
-                        font: font.clone(),
-                        font_size,
-                        caret_width: Some(2.0),
-                        caret_blink_duration: Some(1.0),
+                        color: theme.colors.text_muted,
+                        ..TextVisuals::themed(theme, WidgetState::Normal, &theme.fonts.label, &label)
                     },
                     metrics: metrics.clone(),
                     interaction: InteractionState::default(),
+                    tab_index: TabIndex::None,
+                    input_filter: InputFilter::default(),
                     text_field_action_handler: EventHandler::none(),
                 }
             }
@@ -178,9 +254,8 @@ where
             ];
 
             params!(FlexParams {
-                direction: FlexDirection::Row,
-                force_orthogonal_same_size: true,
-                children: flex_children.clone()
+                children: flex_children.clone(),
+                ..FlexParams::new(FlexDirection::Row)
             })
         },
         world.clone(),
@@ -202,14 +277,11 @@ pub fn suppress_enter_key() -> fn(ElementId, &InteractionEvent) -> (Vec<LoadTile
     }
 }
 
+/// A button in the style of the theme. Selected buttons show their text in the accent color.
 pub fn btn<State: 'static, Event: Clone + 'static, F>(
     text: impl Fn(&State) -> String + 'static,
-    font: impl ToString,
-    font_size: f32,
     disabled: F,
-    color: impl Fn(&State) -> Color + 'static,
-    border_color: impl Fn(&State) -> Color + 'static,
-    accent_color: impl Fn(&State) -> Color + 'static,
+    selected: impl Fn(&State) -> bool + 'static,
     metrics: Rc<TextMetric>,
     handler: EventHandler<ButtonAction, Event>,
     world: Rc<RefCell<ElementWorld>>,
@@ -217,46 +289,51 @@ pub fn btn<State: 'static, Event: Clone + 'static, F>(
 where
     F: Fn(&State) -> bool + 'static + Clone,
 {
+    let button_id = Rc::new(Cell::new(None));
+
     let btn = TextField::new(
         {
-            let font = font.to_string();
+            let button_id = button_id.clone();
             let disabled = disabled.clone();
             params! {args =>
-                let text = text(args.state);
+                let theme = &args.ctx.theme;
+                let state = button_id.get().map_or(WidgetState::Normal, |id| {
+                    WidgetState::of(args.ctx, id, disabled(args.state))
+                });
+                let color = if selected(args.state) {
+                    theme.colors.accent
+                } else {
+                    theme.button.get(state).text_color
+                };
                 TextFieldParams {
                     visuals: TextVisuals {
-                        text: text.clone(),
-                        caret_position: None,
-                        font: font.clone(),
-                        font_size,
-                        color: if disabled(args.state) { color(args.state).with_alpha(0.5) } else { color(args.state) },
+                        color,
                         caret_width: None,
-                        caret_blink_duration: None,
+                        ..TextVisuals::themed(theme, state, &theme.fonts.body, text(args.state))
                     },
                     metrics: metrics.clone(),
                     interaction: Default::default(),
+                    tab_index: TabIndex::None,
+                    input_filter: InputFilter::default(),
                     text_field_action_handler: EventHandler::none(),
                 }
             }
         },
         world.clone(),
     )
-        .padding(params!(PaddingParams::uniform(5.0)), world.clone())
+        .padding(params!(args => PaddingParams::uniform(args.ctx.theme.spacing.small)), world.clone())
         .interactive_set(false, world.clone())
         .button({
                     let disabled = disabled.clone();
                     params! { args =>
-                        let is_hovered = args.ctx.event_manager.borrow().is_hovered(args.id);
-                        ButtonParams {
-                            background: if is_hovered && !disabled(args.state) { accent_color(args.state).with_alpha(0.15) } else { accent_color(args.state).with_alpha(0.1) },
-                            border_color: if is_hovered && !disabled(args.state) { border_color(args.state) } else { border_color(args.state).with_alpha(0.5) },
-                            border_width: 2.0,
-                            corner_radius: 5.0,
-                            interaction: Default::default(),
-                            on_click: handler.clone(),
-            }}
+                        let state = WidgetState::of(args.ctx, args.id, disabled(args.state));
+                        ButtonParams::themed(&args.ctx.theme, state, handler.clone())
+                    }
                 }, world.clone(),
-        ).interactive({
+        );
+    button_id.set(Some(btn.id()));
+
+    let btn = btn.interactive({
                           let disabled = disabled.clone();
                           params!(args => InteractiveParams {is_interactive: !disabled(args.state)})
                       }, world);
@@ -287,9 +364,13 @@ where
             params!(args<State> =>
             let params = list_params(args.state);
                 FlexParams {
-                    direction,
-                    force_orthogonal_same_size,
                     children: (0..params.len).map(|idx| (params.child)(args.state, idx, world.clone())).collect(),
+                    align_items: if force_orthogonal_same_size {
+                        FlexAlign::Stretch
+                    } else {
+                        FlexAlign::Start
+                    },
+                    ..FlexParams::new(direction)
             })
         },
         world.clone(),
@@ -312,16 +393,28 @@ pub fn with_fps<State: 'static, Message: Clone + 'static>(
                 .map(|fps| format!("FPS: {:>7.2}", fps))
                 .unwrap_or("N/A".to_string())
         },
-        UI_FONT,
-        UI_FONT_SIZE,
-        Color::WHITE.with_alpha(0.5),
+        |theme| &theme.fonts.body,
+        |theme| theme.colors.text_muted,
+        TextAlign::Left,
+        Some(1),
         metrics,
         world.clone(),
     )
     .padding(params!(PaddingParams::uniform(5.0)), world.clone())
     .anchor(top_right!(), world.clone());
 
-    Stack::new(vec![layout, fps.into()], world.clone()).into()
+    // covers the window in the background color of the theme
+    let layout = layout.card(
+        params!(args => CardParams {
+            background_color: args.ctx.theme.colors.background,
+            border_size: 0.0,
+            border_color: Color::TRANSPARENT,
+            corner_radius: 0.0,
+        }),
+        world.clone(),
+    );
+
+    Stack::new(vec![layout.into(), fps.into()], world.clone()).into()
 }
 
 pub fn empty_texture() -> &'static [u8] {
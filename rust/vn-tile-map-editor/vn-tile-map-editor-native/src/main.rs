@@ -1,9 +1,12 @@
 use env_logger::Env;
 use rfd::{AsyncFileDialog};
+use std::cell::RefCell;
 use std::future::Future;
 use std::io::Read;
 use std::pin::Pin;
+use std::rc::Rc;
 use vn_tile_map_editor_logic::logic::{File, FileLoadingError, PlatformHooks};
+use vn_ui::{Clipboard, MemoryClipboard};
 
 pub async fn load_file(path: String) -> anyhow::Result<Vec<u8>, FileLoadingError> {
     let mut file = std::fs::File::open(path)
@@ -14,6 +17,21 @@ pub async fn load_file(path: String) -> anyhow::Result<Vec<u8>, FileLoadingError
     Ok(buffer)
 }
 
+/// The clipboard of the operating system.
+struct SystemClipboard(arboard::Clipboard);
+
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.0.get_text().ok()
+    }
+
+    fn set_text(&mut self, text: String) {
+        if let Err(e) = self.0.set_text(text) {
+            log::warn!("Failed to copy to the clipboard: {}", e);
+        }
+    }
+}
+
 struct NativePlatformHooks;
 impl PlatformHooks for NativePlatformHooks {
     fn load_asset(
@@ -53,6 +71,16 @@ impl PlatformHooks for NativePlatformHooks {
             }
         })
     }
+
+    fn clipboard(&self) -> Rc<RefCell<dyn Clipboard>> {
+        match arboard::Clipboard::new() {
+            Ok(clipboard) => Rc::new(RefCell::new(SystemClipboard(clipboard))),
+            Err(e) => {
+                log::warn!("No system clipboard, copying only within the editor: {}", e);
+                Rc::new(RefCell::new(MemoryClipboard::new()))
+            }
+        }
+    }
 }
 
 fn main() {
@@ -1,11 +1,13 @@
 use crate::utils::ToArray;
 use crate::{
-    Element, ElementId, ElementImpl, ElementSize, ElementWorld, EventHandler, InteractionEventKind,
-    InteractionState, SizeConstraints, StateToParams, UiContext, into_box_impl,
+    Element, ElementId, ElementImpl, ElementSize, ElementWorld, EventHandler, EventPhase, HitShape,
+    InteractionEventKind, InteractionState, LayoutInputs, SizeConstraints, StateToParams, TabIndex,
+    Theme, UiContext, WidgetState, into_box_impl,
 };
 use std::cell::RefCell;
 use std::rc::Rc;
 use vn_scene::{BoxPrimitiveData, Color, Rect, Scene, Transform};
+use winit::keyboard::{Key, NamedKey};
 
 #[derive(Debug, Copy, Clone)]
 pub enum ButtonAction {
@@ -18,13 +20,35 @@ pub struct ButtonParams<Message> {
     pub border_width: f32,
     pub corner_radius: f32,
     pub interaction: InteractionState,
+    pub tab_index: TabIndex,
     pub on_click: EventHandler<ButtonAction, Message>,
 }
 
+impl<Message> ButtonParams<Message> {
+    /// The button style of `theme` for `state`, reachable via Tab.
+    pub fn themed(
+        theme: &Theme,
+        state: WidgetState,
+        on_click: EventHandler<ButtonAction, Message>,
+    ) -> Self {
+        let style = theme.button.get(state);
+        Self {
+            background: style.background,
+            border_color: style.border_color,
+            border_width: style.border_width,
+            corner_radius: style.corner_radius,
+            interaction: InteractionState::default(),
+            tab_index: TabIndex::Auto,
+            on_click,
+        }
+    }
+}
+
 pub struct Button<State: 'static, Message: 'static> {
     id: ElementId,
     child: Box<dyn Element<State = State, Message = Message>>,
     params: StateToParams<State, ButtonParams<Message>>,
+    layout_inputs: LayoutInputs<f32>,
 }
 
 impl<State, Message> Button<State, Message> {
@@ -37,6 +61,7 @@ impl<State, Message> Button<State, Message> {
             id: world.borrow_mut().next_id(),
             child: child.into(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
         }
     }
 }
@@ -75,6 +100,16 @@ impl<State, Message: Clone> ElementImpl for Button<State, Message> {
             .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self.layout_inputs.changed(params.border_width);
+        self.child.update(ctx, state) || changed
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -92,14 +127,19 @@ impl<State, Message: Clone> ElementImpl for Button<State, Message> {
         let background = params.background;
         let border_color = params.border_color;
 
-        ctx.with_hitbox_hierarchy(
+        ctx.with_shaped_hitbox_hierarchy(
             self.id,
             canvas.current_layer_id(),
             Rect {
                 position: origin.to_array(),
                 size: size.to_array(),
             },
+            HitShape::RoundedRect {
+                corner_radius: params.corner_radius,
+            },
             |ctx| {
+                ctx.register_focusable(self.id, params.tab_index, false);
+
                 canvas.add_box(BoxPrimitiveData {
                     transform: Transform {
                         translation: [origin.0, origin.1],
@@ -139,18 +179,37 @@ impl<State, Message: Clone> ElementImpl for Button<State, Message> {
     ) -> Vec<Self::Message> {
         let mut messages = self.child.handle_event(ctx, state, event);
 
-        if event.target == Some(self.id) {
+        if event.reaches(self.id) {
             let params = self.params.call(crate::StateToParamsArgs {
                 state,
                 id: self.id,
                 ctx,
             });
-            messages.extend(params.on_click.handle(self.id, event, || match event.kind {
-                InteractionEventKind::Click { .. } => {
-                    vec![ButtonAction::Clicked]
-                }
-                _ => vec![],
-            }));
+            messages.extend(
+                params
+                    .on_click
+                    .handle(self.id, event, || match &event.kind {
+                        // clicks on the content bubble up to the button, but not any further, so
+                        // enclosing buttons don't see them
+                        InteractionEventKind::Click { .. } => {
+                            event.stop_propagation();
+                            vec![ButtonAction::Clicked]
+                        }
+                        // activation via keyboard while focused, not while a descendant is
+                        InteractionEventKind::Keyboard(key_event)
+                            if event.phase == EventPhase::Target
+                                && key_event.state.is_pressed()
+                                && !key_event.repeat
+                                && matches!(
+                                    key_event.logical_key,
+                                    Key::Named(NamedKey::Enter | NamedKey::Space)
+                                ) =>
+                        {
+                            vec![ButtonAction::Clicked]
+                        }
+                        _ => vec![],
+                    }),
+            );
         }
 
         messages
@@ -178,3 +237,104 @@ impl<State, Message, E: Into<Box<dyn Element<State = State, Message = Message>>>
 }
 
 into_box_impl!(Button);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, context};
+    use crate::{
+        DynamicDimension, DynamicSize, Empty, MouseButton, PreferSize, PreferSizeParams,
+        StateToParamsArgs,
+    };
+    use web_time::Instant;
+
+    fn button(
+        child: impl Into<Box<dyn Element<State = (), Message = &'static str>>>,
+        name: &'static str,
+        world: &Rc<RefCell<ElementWorld>>,
+    ) -> Button<(), &'static str> {
+        Button::new(
+            child,
+            move |_: StateToParamsArgs<()>| ButtonParams {
+                background: Color::WHITE,
+                border_color: Color::BLACK,
+                border_width: 10.0,
+                corner_radius: 0.0,
+                interaction: InteractionState::default(),
+                tab_index: TabIndex::Auto,
+                on_click: EventHandler::new(move |_, _| vec![name]),
+            },
+            world.clone(),
+        )
+    }
+
+    fn click(
+        ctx: &mut UiContext,
+        button: &mut Button<(), &'static str>,
+        x: f32,
+        y: f32,
+    ) -> Vec<&'static str> {
+        let mut event_manager = ctx.event_manager.borrow_mut();
+        event_manager.queue_event(InteractionEventKind::MouseDown {
+            button: MouseButton::Left,
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        });
+        event_manager.queue_event(InteractionEventKind::MouseUp {
+            button: MouseButton::Left,
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        });
+        drop(event_manager);
+        let events = ctx
+            .event_manager
+            .borrow_mut()
+            .process_events(Instant::now());
+        events
+            .iter()
+            .flat_map(|event| button.handle_event(ctx, &(), event))
+            .collect()
+    }
+
+    #[test]
+    fn only_the_innermost_button_is_clicked() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        // a 20x20 button within two buttons with 10 wide borders
+        let inner = button(
+            PreferSize::new(
+                Empty::new(world.clone()),
+                |_: StateToParamsArgs<()>| PreferSizeParams {
+                    width: Some(20.0),
+                    height: Some(20.0),
+                },
+                world.clone(),
+            ),
+            "inner",
+            &world,
+        );
+        let mut outer = button(button(inner, "middle", &world), "outer", &world);
+        let mut ctx = context();
+        let size = outer.layout(
+            &mut ctx,
+            &(),
+            SizeConstraints {
+                min_size: ElementSize::ZERO,
+                max_size: DynamicSize {
+                    width: DynamicDimension::Limit(100.0),
+                    height: DynamicDimension::Limit(100.0),
+                },
+                scene_size: (100.0, 100.0),
+            },
+        );
+        assert_eq!(size.width, 80.0);
+        outer.draw(&mut ctx, &(), (0.0, 0.0), size, &mut TestScene::new());
+
+        assert_eq!(click(&mut ctx, &mut outer, 40.0, 40.0), vec!["inner"]);
+        assert_eq!(click(&mut ctx, &mut outer, 15.0, 15.0), vec!["middle"]);
+        assert_eq!(click(&mut ctx, &mut outer, 5.0, 5.0), vec!["outer"]);
+    }
+}
@@ -1,13 +1,13 @@
 use crate::{
-    Element, ElementId, ElementImpl, ElementSize, ElementWorld, SizeConstraints, StateToParams,
-    UiContext, into_box_impl,
+    Element, ElementId, ElementImpl, ElementSize, ElementWorld, LayoutInputs, SizeConstraints,
+    StateToParams, UiContext, into_box_impl,
 };
 use std::cell::RefCell;
 use std::rc::Rc;
 use vn_scene::{BoxPrimitiveData, Color, Scene, Transform};
 use vn_ui_animation_macros::Interpolatable;
 
-#[derive(Clone, Copy, Interpolatable)]
+#[derive(Clone, Copy, Debug, PartialEq, Interpolatable)]
 pub struct CardParams {
     pub background_color: Color,
     pub border_size: f32,
@@ -19,6 +19,7 @@ pub struct Card<State: 'static, Message: 'static> {
     id: ElementId,
     child: Box<dyn Element<State = State, Message = Message>>,
     params: StateToParams<State, CardParams>,
+    layout_inputs: LayoutInputs<f32>,
 }
 
 impl<State, Message> Card<State, Message> {
@@ -31,6 +32,7 @@ impl<State, Message> Card<State, Message> {
             id: world.borrow_mut().next_id(),
             child: child.into(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
         }
     }
 }
@@ -86,6 +88,16 @@ impl<State, Message> ElementImpl for Card<State, Message> {
         .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self.layout_inputs.changed(params.border_size);
+        self.child.update(ctx, state) || changed
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -0,0 +1,317 @@
+use crate::utils::ToArray;
+use crate::{
+    DragPayload, Element, ElementId, ElementImpl, ElementSize, ElementWorld, EventHandler,
+    InteractionEventKind, SizeConstraints, StateToParams, UiContext, into_box_impl,
+};
+use std::any::Any;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+use vn_scene::{Rect, Scene};
+
+#[derive(Debug, Clone, Copy)]
+pub enum DragAction {
+    Started,
+    /// The cursor moved by `offset_x` / `offset_y` since the mouse was pressed.
+    Moved {
+        offset_x: f32,
+        offset_y: f32,
+    },
+    Ended {
+        drop_target: Option<ElementId>,
+    },
+}
+
+pub struct DraggableParams<Message> {
+    pub payload: Option<DragPayload>,
+    pub on_drag: EventHandler<DragAction, Message>,
+}
+
+/// Makes its child the source of a drag carrying `DraggableParams::payload`. The child is not
+/// moved, use the `DragAction`s to render it at the cursor if needed.
+pub struct Draggable<State: 'static, Message: 'static> {
+    id: ElementId,
+    child: Box<dyn Element<State = State, Message = Message>>,
+    params: StateToParams<State, DraggableParams<Message>>,
+}
+
+impl<State, Message> Draggable<State, Message> {
+    pub fn new<P: Into<StateToParams<State, DraggableParams<Message>>>>(
+        child: impl Into<Box<dyn Element<State = State, Message = Message>>>,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        Self {
+            id: world.borrow_mut().next_id(),
+            child: child.into(),
+            params: params.into(),
+        }
+    }
+}
+
+impl<State, Message: Clone> ElementImpl for Draggable<State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        self.child
+            .layout(ctx, state, constraints)
+            .clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        self.child.update(ctx, state)
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        origin: (f32, f32),
+        size: ElementSize,
+        canvas: &mut dyn Scene,
+    ) {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+
+        ctx.with_hitbox_hierarchy(
+            self.id,
+            canvas.current_layer_id(),
+            Rect {
+                position: origin.to_array(),
+                size: size.to_array(),
+            },
+            |ctx| {
+                ctx.register_draggable(self.id, params.payload);
+                self.child.draw(ctx, state, origin, size, canvas);
+            },
+        );
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        event: &crate::InteractionEvent,
+    ) -> Vec<Self::Message> {
+        let mut messages = self.child.handle_event(ctx, state, event);
+
+        if event.reaches(self.id) {
+            let params = self.params.call(crate::StateToParamsArgs {
+                state,
+                id: self.id,
+                ctx,
+            });
+            messages.extend(params.on_drag.handle(self.id, event, || match &event.kind {
+                InteractionEventKind::DragStart { .. } => vec![DragAction::Started],
+                InteractionEventKind::DragMove {
+                    x,
+                    y,
+                    start_x,
+                    start_y,
+                } => vec![DragAction::Moved {
+                    offset_x: x - start_x,
+                    offset_y: y - start_y,
+                }],
+                InteractionEventKind::DragEnd { drop_target, .. } => vec![DragAction::Ended {
+                    drop_target: *drop_target,
+                }],
+                _ => vec![],
+            }));
+        }
+
+        messages
+    }
+}
+
+pub trait DraggableExt<State, Message> {
+    fn draggable<P: Into<StateToParams<State, DraggableParams<Message>>>>(
+        self,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Draggable<State, Message>;
+}
+
+impl<State, Message, E: Into<Box<dyn Element<State = State, Message = Message>>> + 'static>
+    DraggableExt<State, Message> for E
+{
+    fn draggable<P: Into<StateToParams<State, DraggableParams<Message>>>>(
+        self,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Draggable<State, Message> {
+        Draggable::new(self, params, world)
+    }
+}
+
+into_box_impl!(Draggable);
+
+#[derive(Debug, Clone)]
+pub enum DropAction<T> {
+    /// A drag carrying a `T` entered the drop target.
+    Entered(T),
+    Left,
+    Dropped {
+        value: T,
+        local_x: f32,
+        local_y: f32,
+    },
+}
+
+pub struct DropTargetParams<T, Message> {
+    pub on_drop: EventHandler<DropAction<T>, Message>,
+}
+
+/// Accepts drags whose payload is a `T`. Use `EventManager::is_drop_target_hovered` for hover
+/// feedback while drawing.
+pub struct DropTarget<T: 'static, State: 'static, Message: 'static> {
+    id: ElementId,
+    child: Box<dyn Element<State = State, Message = Message>>,
+    params: StateToParams<State, DropTargetParams<T, Message>>,
+    _payload: PhantomData<T>,
+}
+
+impl<T, State, Message> DropTarget<T, State, Message> {
+    pub fn new<P: Into<StateToParams<State, DropTargetParams<T, Message>>>>(
+        child: impl Into<Box<dyn Element<State = State, Message = Message>>>,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        Self {
+            id: world.borrow_mut().next_id(),
+            child: child.into(),
+            params: params.into(),
+            _payload: PhantomData,
+        }
+    }
+}
+
+impl<T: Any + Clone, State, Message: Clone> ElementImpl for DropTarget<T, State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        self.child
+            .layout(ctx, state, constraints)
+            .clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        self.child.update(ctx, state)
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        origin: (f32, f32),
+        size: ElementSize,
+        canvas: &mut dyn Scene,
+    ) {
+        ctx.with_hitbox_hierarchy(
+            self.id,
+            canvas.current_layer_id(),
+            Rect {
+                position: origin.to_array(),
+                size: size.to_array(),
+            },
+            |ctx| {
+                ctx.register_drop_target::<T>(self.id);
+                self.child.draw(ctx, state, origin, size, canvas);
+            },
+        );
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        event: &crate::InteractionEvent,
+    ) -> Vec<Self::Message> {
+        let mut messages = self.child.handle_event(ctx, state, event);
+
+        if event.reaches(self.id) {
+            let params = self.params.call(crate::StateToParamsArgs {
+                state,
+                id: self.id,
+                ctx,
+            });
+            messages.extend(params.on_drop.handle(self.id, event, || {
+                match &event.kind {
+                    InteractionEventKind::DragEnter { payload } => payload
+                        .get::<T>()
+                        .map(|value| DropAction::Entered(value.clone()))
+                        .into_iter()
+                        .collect(),
+                    InteractionEventKind::DragLeave => vec![DropAction::Left],
+                    InteractionEventKind::Drop {
+                        payload,
+                        local_x,
+                        local_y,
+                        ..
+                    } => payload
+                        .get::<T>()
+                        .map(|value| DropAction::Dropped {
+                            value: value.clone(),
+                            local_x: *local_x,
+                            local_y: *local_y,
+                        })
+                        .into_iter()
+                        .collect(),
+                    _ => vec![],
+                }
+            }));
+        }
+
+        messages
+    }
+}
+
+pub trait DropTargetExt<State, Message> {
+    fn drop_target<T, P: Into<StateToParams<State, DropTargetParams<T, Message>>>>(
+        self,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> DropTarget<T, State, Message>;
+}
+
+impl<State, Message, E: Into<Box<dyn Element<State = State, Message = Message>>> + 'static>
+    DropTargetExt<State, Message> for E
+{
+    fn drop_target<T, P: Into<StateToParams<State, DropTargetParams<T, Message>>>>(
+        self,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> DropTarget<T, State, Message> {
+        DropTarget::new(self, params, world)
+    }
+}
+
+impl<T: Any + Clone, S: 'static, M: Clone + 'static> From<DropTarget<T, S, M>>
+    for Box<dyn Element<State = S, Message = M>>
+{
+    fn from(drop_target: DropTarget<T, S, M>) -> Self {
+        Box::new(drop_target)
+    }
+}
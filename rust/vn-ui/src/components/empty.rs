@@ -36,6 +36,10 @@ impl<State, Message> ElementImpl for Empty<State, Message> {
         constraints.min_size
     }
 
+    fn update_impl(&mut self, _ctx: &mut UiContext, _state: &Self::State) -> bool {
+        false
+    }
+
     fn draw_impl(
         &mut self,
         _ctx: &mut UiContext,
@@ -44,6 +44,10 @@ impl<State, Message> ElementImpl for ExtendedHitbox<State, Message> {
             .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        self.element.update(ctx, state)
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -1,15 +1,19 @@
 mod button;
 mod card;
+mod drag_drop;
 mod empty;
 mod hitbox;
+mod rich_text;
 mod text_field;
 mod texture;
 mod tooltip;
 
 pub use button::*;
 pub use card::*;
+pub use drag_drop::*;
 pub use empty::*;
 pub use hitbox::*;
+pub use rich_text::*;
 pub use text_field::*;
 pub use texture::*;
 pub use tooltip::*;
@@ -0,0 +1,244 @@
+use crate::text::layout::{TextAlign, TextLayout, TextLayoutOptions};
+use crate::text::rich_text::span_of_chars;
+use crate::{
+    ElementId, ElementImpl, ElementSize, ElementWorld, InteractionEvent, SizeConstraints,
+    StateToParams, TextMetrics, TextSpan, UiContext, into_box_impl,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use vn_scene::{BoxPrimitiveData, Color, Scene, TextPrimitiveData, Transform};
+
+#[derive(Clone)]
+pub struct RichTextParams {
+    pub spans: Vec<TextSpan>,
+    pub align: TextAlign,
+    /// Cuts off the text after this many lines, ending it with an ellipsis.
+    pub max_lines: Option<usize>,
+    pub metrics: Rc<dyn TextMetrics>,
+}
+
+/// Read-only text made of [TextSpan]s in different styles, wrapped to the available width.
+pub struct RichText<State: 'static, Message: 'static> {
+    id: ElementId,
+    params: StateToParams<State, RichTextParams>,
+    spans: Option<Vec<TextSpan>>,
+    options: TextLayoutOptions,
+    layout: Option<TextLayout>,
+    /// The span of each character of the laid out text.
+    span_of_chars: Vec<usize>,
+    last_max_width: Option<f32>,
+    _phantom: std::marker::PhantomData<Message>,
+}
+
+impl<State: 'static, Message: 'static> RichText<State, Message> {
+    pub fn new<P: Into<StateToParams<State, RichTextParams>>>(
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        Self {
+            id: world.borrow_mut().next_id(),
+            params: params.into(),
+            spans: None,
+            options: TextLayoutOptions::default(),
+            layout: None,
+            span_of_chars: Vec::new(),
+            last_max_width: None,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+/// Draws a filled rectangle, used for backgrounds and lines through or under text.
+fn add_rect(
+    scene: &mut dyn Scene,
+    position: [f32; 2],
+    size: [f32; 2],
+    color: Color,
+    clip_rect: vn_scene::Rect,
+) {
+    scene.add_box(BoxPrimitiveData {
+        transform: Transform {
+            translation: position,
+            ..Transform::DEFAULT
+        },
+        size,
+        color,
+        border_color: Color::TRANSPARENT,
+        border_thickness: 0.0,
+        border_radius: 0.0,
+        clip_rect,
+    });
+}
+
+impl<State, Message> ElementImpl for RichText<State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+
+        let max_width = constraints.max_size.width.to_option();
+        let options = TextLayoutOptions {
+            align: params.align,
+            max_lines: params.max_lines,
+        };
+        if self.spans.as_ref() != Some(&params.spans)
+            || self.options != options
+            || self.last_max_width != max_width
+        {
+            self.layout = Some(TextLayout::layout_rich(
+                &params.spans,
+                max_width,
+                options,
+                params.metrics.as_ref(),
+            ));
+            self.span_of_chars = span_of_chars(&params.spans);
+            self.options = options;
+            self.last_max_width = max_width;
+            self.spans = Some(params.spans);
+        }
+
+        let layout = self.layout.as_ref().unwrap();
+        ElementSize {
+            width: layout.total_width,
+            height: layout.total_height,
+        }
+        .clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        self.spans.as_ref() != Some(&params.spans)
+            || self.options.align != params.align
+            || self.options.max_lines != params.max_lines
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        _state: &Self::State,
+        origin: (f32, f32),
+        _size: ElementSize,
+        scene: &mut dyn Scene,
+    ) {
+        let (Some(layout), Some(spans)) = (&self.layout, &self.spans) else {
+            return;
+        };
+        let clip_rect = ctx.clip_rect;
+
+        let mut y = origin.1;
+        for line in &layout.lines {
+            // where the characters of each span are on the line, which can be several places
+            // for text of mixed directions
+            let mut span_start = 0;
+            let mut pieces: Vec<(usize, Vec<(f32, f32)>)> = Vec::new();
+            for (idx, span) in spans.iter().enumerate() {
+                let range = span_start..span_start + span.text.chars().count();
+                span_start = range.end;
+                let extents = line.extents(&range);
+                if !extents.is_empty() {
+                    pieces.push((idx, extents));
+                }
+            }
+
+            // backgrounds first, so they don't cover the text of neighbouring spans
+            for (span, extents) in &pieces {
+                if let Some(background) = spans[*span].style.background {
+                    for (x, width) in extents {
+                        add_rect(
+                            scene,
+                            [origin.0 + x, y],
+                            [*width, line.height],
+                            background,
+                            clip_rect,
+                        );
+                    }
+                }
+            }
+
+            // glyphs next to each other in the same span are drawn together
+            let mut segments: Vec<(usize, Vec<vn_scene::GlyphInstanceData>)> = Vec::new();
+            for placed in line.glyphs.iter().chain(&line.ellipsis) {
+                let span = self.span_of_chars[placed.char_index];
+                let glyph = vn_scene::GlyphInstanceData {
+                    texture_id: placed.glyph.texture_id.clone(),
+                    position: [placed.x + placed.glyph.x_bearing, placed.glyph.y_offset],
+                    size: placed.glyph.size,
+                    uv_rect: placed.glyph.uv_rect,
+                };
+                match segments.last_mut() {
+                    Some((last_span, glyphs)) if *last_span == span => glyphs.push(glyph),
+                    _ => segments.push((span, vec![glyph])),
+                }
+            }
+            for (span, mut glyphs) in segments {
+                glyphs.retain(|glyph| glyph.size != [0.0, 0.0]);
+                scene.add_text(TextPrimitiveData {
+                    transform: Transform {
+                        translation: [origin.0 + line.x_offset, y],
+                        ..Transform::DEFAULT
+                    },
+                    tint: spans[span].style.color,
+                    glyphs,
+                    clip_rect,
+                });
+            }
+
+            for (span, extents) in &pieces {
+                let style = &spans[*span].style;
+                let thickness = (style.font_size / 14.0).max(1.0);
+                for (x, width) in extents {
+                    if style.underline {
+                        add_rect(
+                            scene,
+                            [origin.0 + x, y + line.ascent + thickness],
+                            [*width, thickness],
+                            style.color,
+                            clip_rect,
+                        );
+                    }
+                    if style.strikethrough {
+                        // roughly through the middle of lowercase letters
+                        add_rect(
+                            scene,
+                            [origin.0 + x, y + line.ascent - style.font_size * 0.3],
+                            [*width, thickness],
+                            style.color,
+                            clip_rect,
+                        );
+                    }
+                }
+            }
+
+            y += line.height;
+        }
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        _ctx: &mut UiContext,
+        _state: &Self::State,
+        _event: &InteractionEvent,
+    ) -> Vec<Self::Message> {
+        vec![]
+    }
+}
+
+into_box_impl!(RichText);
@@ -1,14 +1,23 @@
-use crate::text::layout::TextLayout;
+use crate::text::input_filter::InputFilter;
+use crate::text::layout::{TextAlign, TextLayout, TextLayoutOptions, VerticalAlign};
 use crate::utils::ToArray;
 use crate::{
-    DynamicDimension, ElementId, ElementImpl, ElementSize, ElementWorld, EventHandler,
-    InteractionState, Interpolatable, SizeConstraints, StateToParams, TextFieldAction, TextMetrics,
-    UiContext, into_box_impl,
+    DynamicDimension, ElementId, ElementImpl, ElementSize, ElementWorld, EventHandler, FontRole,
+    InteractionEvent, InteractionEventKind, InteractionState, Interpolatable, Modifiers,
+    SizeConstraints, StateToParams, TabIndex, TextFieldAction, TextMetrics, Theme, UiContext,
+    WidgetState, into_box_impl,
 };
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 use vn_scene::{BoxPrimitiveData, Color, Rect, Scene, TextPrimitiveData, Transform};
+use vn_utils::string::{
+    InsertAtCharIndex, next_grapheme_boundary, previous_grapheme_boundary,
+    snap_to_grapheme_boundary,
+};
 use web_time::Instant;
+use winit::keyboard::{Key, NamedKey};
 
 #[derive(Clone, PartialEq, Interpolatable)]
 pub struct TextVisuals {
@@ -16,6 +25,11 @@ pub struct TextVisuals {
     pub text: String,
     #[interpolate_snappy = "snap_middle"]
     pub caret_position: Option<usize>,
+    /// The other end of the selection, which spans from here to the caret.
+    #[interpolate_snappy = "snap_middle"]
+    pub selection_anchor: Option<usize>,
+    /// A font name or a [crate::FontQuery] of families with fallbacks, e.g. `"bold Inter, Noto
+    /// Sans JP"`.
     #[interpolate_snappy = "snap_middle"]
     pub font: String,
     pub font_size: f32,
@@ -24,6 +38,114 @@ pub struct TextVisuals {
     pub caret_width: Option<f32>,
     #[interpolate_none_as_default]
     pub caret_blink_duration: Option<f32>,
+    /// Defaults to the text color, but translucent.
+    #[interpolate_snappy = "snap_middle"]
+    pub selection_color: Option<Color>,
+    /// Shows every character as this one, e.g. for passwords. Masked text can't be copied.
+    #[interpolate_snappy = "snap_middle"]
+    pub mask: Option<char>,
+    /// Shown in the translucent text color while the text is empty.
+    #[interpolate_snappy = "snap_middle"]
+    pub placeholder: Option<String>,
+    /// Draws a border in the error color (red by default), e.g. while the text is not valid.
+    #[interpolate_snappy = "snap_middle"]
+    pub error: bool,
+    #[interpolate_snappy = "snap_middle"]
+    pub error_color: Option<Color>,
+    #[interpolate_snappy = "snap_middle"]
+    pub align: TextAlign,
+    /// Where the text is placed if the field is higher than the text, e.g. when stretched.
+    #[interpolate_snappy = "snap_middle"]
+    pub vertical_align: VerticalAlign,
+    /// Cuts off the text after this many lines, ending it with an ellipsis. Meant for labels,
+    /// the caret can't be moved into the cut off text.
+    #[interpolate_snappy = "snap_middle"]
+    pub max_lines: Option<usize>,
+}
+
+impl TextVisuals {
+    /// Read-only `text` in the text field style of `theme` for `state`. Set the caret to make it
+    /// editable.
+    pub fn themed(theme: &Theme, state: WidgetState, font: &FontRole, text: impl ToString) -> Self {
+        let style = theme.text_field.get(state);
+        Self {
+            text: text.to_string(),
+            caret_position: None,
+            selection_anchor: None,
+            font: font.font.clone(),
+            font_size: font.size,
+            color: style.text_color,
+            caret_width: Some(style.caret_width),
+            caret_blink_duration: None,
+            selection_color: Some(style.selection_color),
+            mask: None,
+            placeholder: None,
+            error: false,
+            error_color: Some(style.error_color),
+            align: TextAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            max_lines: None,
+        }
+    }
+
+    /// The selected characters, if any.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let caret = self.caret_position?;
+        let anchor = self.selection_anchor?;
+        (anchor != caret).then(|| anchor.min(caret)..anchor.max(caret))
+    }
+
+    fn shows_placeholder(&self) -> bool {
+        self.text.is_empty() && self.placeholder.is_some()
+    }
+
+    /// The text as it is laid out: masked, or the placeholder if the text is empty.
+    fn display_text(&self) -> Cow<'_, str> {
+        match (&self.placeholder, self.mask) {
+            (Some(placeholder), _) if self.text.is_empty() => Cow::Borrowed(placeholder),
+            (_, Some(mask)) => Cow::Owned(self.text.chars().map(|_| mask).collect()),
+            _ => Cow::Borrowed(&self.text),
+        }
+    }
+}
+
+/// Text being composed with an input method. It is shown underlined at the caret until it is
+/// committed, which inserts the final text like typing does.
+#[derive(Clone, PartialEq)]
+struct Preedit {
+    text: String,
+    /// The position of the input method's cursor within the text, in characters.
+    cursor: Option<usize>,
+}
+
+impl Preedit {
+    fn from_ime(text: &str, cursor: Option<(usize, usize)>) -> Option<Self> {
+        let cursor = cursor
+            .and_then(|(start, _)| text.get(..start))
+            .map(|s| s.chars().count());
+        (!text.is_empty()).then(|| Self {
+            text: text.to_string(),
+            cursor,
+        })
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+}
+
+/// The text as it is laid out, with the text being composed inserted at the caret.
+fn layout_text<'a>(visuals: &'a TextVisuals, preedit: Option<&Preedit>) -> Cow<'a, str> {
+    let Some(preedit) = preedit else {
+        return visuals.display_text();
+    };
+    let mut text = match visuals.mask {
+        Some(mask) => visuals.text.chars().map(|_| mask).collect(),
+        None => visuals.text.clone(),
+    };
+    let caret = visuals.caret_position.unwrap_or(0);
+    text.insert_str_at_char_index(caret, &preedit.text);
+    Cow::Owned(text)
 }
 
 #[derive(Clone)]
@@ -31,6 +153,9 @@ pub struct TextFieldParams<Message> {
     pub visuals: TextVisuals,
     pub metrics: Rc<dyn TextMetrics>,
     pub interaction: InteractionState,
+    /// Editable fields should be reachable via Tab, labels usually not.
+    pub tab_index: TabIndex,
+    pub input_filter: InputFilter,
     pub text_field_action_handler: EventHandler<TextFieldAction, Message>,
 }
 
@@ -40,6 +165,8 @@ impl<Message: Clone> Interpolatable for TextFieldParams<Message> {
             visuals: self.visuals.interpolate(&other.visuals, t),
             metrics: other.metrics.clone(),
             interaction: other.interaction.clone(),
+            tab_index: other.tab_index,
+            input_filter: other.input_filter.clone(),
             text_field_action_handler: other.text_field_action_handler.clone(),
         }
     }
@@ -54,7 +181,12 @@ pub struct TextField<State: 'static, Message: 'static> {
     gained_focus_at: Option<Instant>,
     show_caret: bool,
     line_height: f32,
+    /// Offset of the text within the field due to the vertical alignment, as last drawn.
+    text_offset_y: f32,
     last_max_width: Option<f32>,
+    /// The anchor of the selection while selecting with the mouse.
+    drag_anchor: Option<usize>,
+    preedit: Option<Preedit>,
     _phantom: std::marker::PhantomData<Message>,
 }
 
@@ -72,7 +204,10 @@ impl<State: 'static, Message: 'static> TextField<State, Message> {
             show_caret: false,
             gained_focus_at: None,
             size: ElementSize::ZERO,
+            text_offset_y: 0.0,
             last_max_width: None,
+            drag_anchor: None,
+            preedit: None,
             _phantom: std::marker::PhantomData,
         }
     }
@@ -106,10 +241,14 @@ impl<State: 'static, Message: 'static> TextField<State, Message> {
                 .line_height(&params.visuals.font, params.visuals.font_size);
             let caret_space = params.visuals.caret_width.unwrap_or(2.0);
             let layout = TextLayout::layout(
-                &params.visuals.text,
+                &layout_text(&params.visuals, self.preedit.as_ref()),
                 &params.visuals.font,
                 params.visuals.font_size,
                 max_width.map(|w| w - caret_space).to_option(),
+                TextLayoutOptions {
+                    align: params.visuals.align,
+                    max_lines: params.visuals.max_lines,
+                },
                 params.metrics.as_ref(),
             );
 
@@ -122,6 +261,403 @@ impl<State: 'static, Message: 'static> TextField<State, Message> {
         }
         changed
     }
+
+    /// The caret position closest to a point in local coordinates, also for points outside the
+    /// text, e.g. while dragging a selection.
+    fn caret_at(&self, x: f32, y: f32, chars: &[char]) -> Option<usize> {
+        let layout = self.layout.as_ref()?;
+        let y = y - self.text_offset_y;
+        let mut position =
+            layout.hit_test(x, y.clamp(0.0, (layout.total_height - 0.5).max(0.0)))?;
+        // the layout contains the text being composed, which isn't part of the text yet
+        if let Some(preedit) = &self.preedit {
+            let caret = self
+                .visuals
+                .as_ref()
+                .and_then(|v| v.caret_position)
+                .unwrap_or(0);
+            position = match position {
+                p if p <= caret => p,
+                p if p < caret + preedit.len() => caret,
+                p => p - preedit.len(),
+            };
+        }
+        // the layout may be the one of the placeholder
+        Some(snap_to_grapheme_boundary(chars, position.min(chars.len())))
+    }
+
+    /// Keeps track of the text being composed, which is shown but not part of the state.
+    fn update_preedit(&mut self, event: &InteractionEvent) {
+        let preedit = match &event.kind {
+            InteractionEventKind::Ime(crate::Ime::Preedit(text, cursor)) => {
+                Preedit::from_ime(text, *cursor)
+            }
+            InteractionEventKind::Ime(crate::Ime::Commit(_) | crate::Ime::Disabled)
+            | InteractionEventKind::FocusLost => None,
+            _ => return,
+        };
+        if preedit != self.preedit {
+            self.preedit = preedit;
+            // forces laying out the text again
+            self.visuals = None;
+        }
+    }
+
+    /// The actions caused by an event reaching the text field.
+    fn actions(
+        &mut self,
+        ctx: &UiContext,
+        params: &TextFieldParams<Message>,
+        event: &InteractionEvent,
+    ) -> Vec<TextFieldAction> {
+        let filter = &params.input_filter;
+        let Editing {
+            chars,
+            shown,
+            caret,
+            anchor,
+            target,
+            ..
+        } = Editing::new(&params.visuals);
+
+        match &event.kind {
+            // selecting with the mouse starts on press, the click that follows doesn't move the
+            // caret anymore
+            InteractionEventKind::MouseDown {
+                button: crate::MouseButton::Left,
+                local_x,
+                local_y,
+                ..
+            } => match self.caret_at(*local_x, *local_y, &chars) {
+                Some(position) => {
+                    let start = match event.modifiers.shift {
+                        true => anchor.unwrap_or(caret),
+                        false => position,
+                    };
+                    self.drag_anchor = Some(start);
+                    ctx.event_manager.borrow_mut().capture_pointer(self.id);
+                    select(start, position)
+                }
+                None => vec![],
+            },
+            InteractionEventKind::MouseMove {
+                local_x, local_y, ..
+            } => match (self.drag_anchor, self.caret_at(*local_x, *local_y, &chars)) {
+                (Some(start), Some(position)) if position != caret => select(start, position),
+                _ => vec![],
+            },
+            InteractionEventKind::MouseUp { .. } | InteractionEventKind::FocusLost => {
+                self.drag_anchor = None;
+                vec![]
+            }
+            InteractionEventKind::DoubleClick {
+                local_x, local_y, ..
+            } => match self.caret_at(*local_x, *local_y, &chars) {
+                Some(position) => {
+                    let word = word_at(&shown, position);
+                    select(word.start, word.end)
+                }
+                None => vec![],
+            },
+            InteractionEventKind::TripleClick {
+                local_x, local_y, ..
+            } => match self.caret_at(*local_x, *local_y, &chars) {
+                Some(position) => {
+                    let line = line_at(&shown, position);
+                    select(line.start, line.end)
+                }
+                None => vec![],
+            },
+            InteractionEventKind::Keyboard(key_event) if key_event.state.is_pressed() => {
+                let text = key_event.text.as_deref();
+                self.key_actions(ctx, params, &key_event.logical_key, text, event.modifiers)
+            }
+            InteractionEventKind::Ime(crate::Ime::Commit(text)) => {
+                replace(&chars, target, text, filter)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The actions caused by pressing `key` while the text field is focused. `text` is the text
+    /// the key produces, which is `None` while an input method composes text.
+    fn key_actions(
+        &self,
+        ctx: &UiContext,
+        params: &TextFieldParams<Message>,
+        key: &Key,
+        text: Option<&str>,
+        modifiers: Modifiers,
+    ) -> Vec<TextFieldAction> {
+        let visuals = &params.visuals;
+        let filter = &params.input_filter;
+        let Editing {
+            chars,
+            shown,
+            caret,
+            anchor,
+            selection,
+            target,
+        } = Editing::new(visuals);
+        let extend = modifiers.shift;
+        let by_word = modifiers.control;
+
+        match key {
+            Key::Character(s) if modifiers.control => {
+                let clipboard = ctx.event_manager.borrow().clipboard();
+                let selected = || chars[target.clone()].iter().collect::<String>();
+                match s.to_lowercase().as_str() {
+                    "a" => select(0, chars.len()),
+                    "z" if extend => vec![TextFieldAction::Redo],
+                    "z" => vec![TextFieldAction::Undo],
+                    "y" => vec![TextFieldAction::Redo],
+                    "c" if selection.is_some() && visuals.mask.is_none() => {
+                        clipboard.borrow_mut().set_text(selected());
+                        vec![]
+                    }
+                    "x" if selection.is_some() && visuals.mask.is_none() => {
+                        clipboard.borrow_mut().set_text(selected());
+                        replace(&chars, target, "", filter)
+                    }
+                    "v" => match clipboard.borrow_mut().get_text() {
+                        Some(text) => replace(&chars, target, &text, filter),
+                        None => vec![],
+                    },
+                    _ => vec![],
+                }
+            }
+            Key::Named(NamedKey::Enter) => replace(&chars, target, "\n", filter),
+            Key::Named(NamedKey::Backspace) => match selection {
+                Some(selection) => replace(&chars, selection, "", filter),
+                None if caret > 0 => {
+                    let start = match by_word {
+                        true => previous_word_start(&shown, caret),
+                        false => previous_grapheme_boundary(&chars, caret),
+                    };
+                    replace(&chars, start..caret, "", filter)
+                }
+                None => vec![],
+            },
+            Key::Named(NamedKey::Delete) => match selection {
+                Some(selection) => replace(&chars, selection, "", filter),
+                None if caret < chars.len() => {
+                    let end = match by_word {
+                        true => next_word_end(&shown, caret),
+                        false => next_grapheme_boundary(&chars, caret),
+                    };
+                    replace(&chars, caret..end, "", filter)
+                }
+                None => vec![],
+            },
+            // without shift, an arrow collapses the selection to its side
+            Key::Named(NamedKey::ArrowLeft) => match &selection {
+                Some(selection) if !extend => {
+                    vec![TextFieldAction::CaretMove(selection.start)]
+                }
+                _ => {
+                    let target = match by_word {
+                        true => previous_word_start(&shown, caret),
+                        false => previous_grapheme_boundary(&chars, caret),
+                    };
+                    move_caret(anchor, caret, target, extend)
+                }
+            },
+            Key::Named(NamedKey::ArrowRight) => match &selection {
+                Some(selection) if !extend => {
+                    vec![TextFieldAction::CaretMove(selection.end)]
+                }
+                _ => {
+                    let target = match by_word {
+                        true => next_word_end(&shown, caret),
+                        false => next_grapheme_boundary(&chars, caret),
+                    };
+                    move_caret(anchor, caret, target, extend)
+                }
+            },
+            Key::Named(direction @ (NamedKey::ArrowUp | NamedKey::ArrowDown)) => {
+                match &self.layout {
+                    Some(layout) => {
+                        let delta = match direction {
+                            NamedKey::ArrowUp => -1,
+                            _ => 1,
+                        };
+                        let intended_x = layout.get_caret_x(caret);
+                        let target = layout.get_vertical_move(caret, delta, intended_x);
+                        let target = snap_to_grapheme_boundary(&chars, target);
+                        move_caret(anchor, caret, target, extend)
+                    }
+                    None => vec![],
+                }
+            }
+            // Ctrl+Home / Ctrl+End move to the start / end of the text
+            Key::Named(NamedKey::Home) => {
+                let target = match (modifiers.control, &self.layout) {
+                    (false, Some(layout)) => layout.line_bounds(caret).0,
+                    _ => 0,
+                };
+                move_caret(anchor, caret, target, extend)
+            }
+            Key::Named(NamedKey::End) => {
+                let target = match (modifiers.control, &self.layout) {
+                    (false, Some(layout)) => layout.line_bounds(caret).1,
+                    _ => chars.len(),
+                };
+                move_caret(anchor, caret, target.min(chars.len()), extend)
+            }
+            // text composed with an input method arrives as a commit instead, the key events
+            // while composing carry no text
+            _ => match text {
+                Some(text) if !text.chars().any(char::is_control) => {
+                    replace(&chars, target, text, filter)
+                }
+                _ => vec![],
+            },
+        }
+    }
+}
+
+/// The text of a field with the caret and selection clamped to it.
+struct Editing {
+    chars: Vec<char>,
+    /// The characters as shown, words of masked text are not revealed, it's all one word.
+    shown: Vec<char>,
+    caret: usize,
+    anchor: Option<usize>,
+    selection: Option<Range<usize>>,
+    /// The selection, or the empty range at the caret.
+    target: Range<usize>,
+}
+
+impl Editing {
+    fn new(visuals: &TextVisuals) -> Self {
+        let chars: Vec<char> = visuals.text.chars().collect();
+        let shown = match visuals.mask {
+            Some(mask) => vec![mask; chars.len()],
+            None => chars.clone(),
+        };
+        let caret = visuals.caret_position.unwrap_or(0).min(chars.len());
+        let anchor = visuals.selection_anchor.map(|a| a.min(chars.len()));
+        let selection = visuals
+            .selection()
+            .map(|s| s.start.min(chars.len())..s.end.min(chars.len()));
+        let target = selection.clone().unwrap_or(caret..caret);
+        Self {
+            chars,
+            shown,
+            caret,
+            anchor,
+            selection,
+            target,
+        }
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// The start of the word before `idx`, skipping anything between.
+fn previous_word_start(chars: &[char], idx: usize) -> usize {
+    let mut idx = idx.min(chars.len());
+    while idx > 0 && !is_word_char(chars[idx - 1]) {
+        idx -= 1;
+    }
+    while idx > 0 && is_word_char(chars[idx - 1]) {
+        idx -= 1;
+    }
+    idx
+}
+
+/// The end of the word after `idx`, skipping anything between.
+fn next_word_end(chars: &[char], idx: usize) -> usize {
+    let mut idx = idx.min(chars.len());
+    while idx < chars.len() && !is_word_char(chars[idx]) {
+        idx += 1;
+    }
+    while idx < chars.len() && is_word_char(chars[idx]) {
+        idx += 1;
+    }
+    idx
+}
+
+/// The word at `idx`, or the run of whitespace or punctuation if there is no word.
+fn word_at(chars: &[char], idx: usize) -> Range<usize> {
+    if chars.is_empty() {
+        return 0..0;
+    }
+    let idx = idx.min(chars.len() - 1);
+    let class = |c: char| (is_word_char(c), c.is_whitespace(), c == '\n');
+    let target = class(chars[idx]);
+
+    let mut start = idx;
+    while start > 0 && class(chars[start - 1]) == target {
+        start -= 1;
+    }
+    let mut end = idx + 1;
+    while end < chars.len() && class(chars[end]) == target {
+        end += 1;
+    }
+    start..end
+}
+
+/// The line at `idx`, without the newline ending it.
+fn line_at(chars: &[char], idx: usize) -> Range<usize> {
+    let idx = idx.min(chars.len());
+    let start = chars[..idx]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1);
+    let end = chars[idx..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |i| idx + i);
+    start..end
+}
+
+/// Selects from `anchor` to `caret`, or just moves the caret if the selection would be empty.
+fn select(anchor: usize, caret: usize) -> Vec<TextFieldAction> {
+    if anchor == caret {
+        vec![TextFieldAction::CaretMove(caret)]
+    } else {
+        vec![TextFieldAction::Select { anchor, caret }]
+    }
+}
+
+/// Moves the caret to `target`, extending the selection from its anchor if `extend` is set.
+fn move_caret(
+    anchor: Option<usize>,
+    caret: usize,
+    target: usize,
+    extend: bool,
+) -> Vec<TextFieldAction> {
+    if extend {
+        select(anchor.unwrap_or(caret), target)
+    } else {
+        vec![TextFieldAction::CaretMove(target)]
+    }
+}
+
+/// Replaces the characters in `range` by `insert` and places the caret after the insertion,
+/// unless the filter rejects it.
+fn replace(
+    chars: &[char],
+    range: Range<usize>,
+    insert: &str,
+    filter: &InputFilter,
+) -> Vec<TextFieldAction> {
+    let Some(insert) = filter.filter_edit(chars, range.clone(), insert) else {
+        return vec![];
+    };
+    let new_text = chars[..range.start]
+        .iter()
+        .copied()
+        .chain(insert.chars())
+        .chain(chars[range.end..].iter().copied())
+        .collect();
+    vec![
+        TextFieldAction::TextChange(new_text),
+        TextFieldAction::CaretMove(range.start + insert.chars().count()),
+    ]
 }
 
 impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
@@ -140,35 +676,16 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
     ) -> ElementSize {
         self.update_state(state, constraints.max_size.width, ctx);
 
+        self.size.clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
         let params = self.params.call(crate::StateToParamsArgs {
             state,
             id: self.id,
             ctx,
         });
-
-        let is_focused = params.interaction.is_focused;
-        let caret_blink_duration = self
-            .visuals
-            .as_ref()
-            .and_then(|v| v.caret_blink_duration)
-            .unwrap_or(1.0);
-
-        match (is_focused, self.gained_focus_at) {
-            (false, _) => {
-                self.gained_focus_at = None;
-                self.show_caret = false;
-            }
-            (true, None) => {
-                self.gained_focus_at = Some(Instant::now());
-                self.show_caret = true;
-            }
-            (true, Some(start_at)) => {
-                let elapsed = start_at.elapsed().as_secs_f32();
-                self.show_caret = elapsed % caret_blink_duration < caret_blink_duration / 2.0;
-            }
-        }
-
-        self.size.clamp_to_constraints(constraints)
+        self.visuals.as_ref() != Some(&params.visuals)
     }
 
     fn draw_impl(
@@ -186,6 +703,23 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
         });
         let visuals = &params.visuals;
 
+        // the caret blinks without the layout changing, so this happens while drawing
+        let caret_blink_duration = visuals.caret_blink_duration.unwrap_or(1.0);
+        match (params.interaction.is_focused, self.gained_focus_at) {
+            (false, _) => {
+                self.gained_focus_at = None;
+                self.show_caret = false;
+            }
+            (true, None) => {
+                self.gained_focus_at = Some(Instant::now());
+                self.show_caret = true;
+            }
+            (true, Some(start_at)) => {
+                let elapsed = start_at.elapsed().as_secs_f32();
+                self.show_caret = elapsed % caret_blink_duration < caret_blink_duration / 2.0;
+            }
+        }
+
         let caret_height = self.line_height * 0.8;
         let caret_y_extra_offset = self.line_height / 2.0 - caret_height / 2.0;
         let caret_width = self
@@ -197,6 +731,10 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
 
         let caret_space = caret_width;
 
+        // the field may be higher than its text, e.g. when stretched
+        self.text_offset_y = visuals.vertical_align.offset(self.size.height, size.height);
+        let text_origin = (origin.0, origin.1 + self.text_offset_y);
+
         ctx.with_hitbox_hierarchy(
             self.id,
             scene.current_layer_id(),
@@ -205,32 +743,108 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
                 size: size.to_array(),
             },
             |ctx| {
+                // arrow keys move the caret
+                ctx.register_focusable(self.id, params.tab_index, true);
+
                 let clip_rect = ctx.clip_rect;
+                if visuals.error {
+                    scene.add_box(BoxPrimitiveData {
+                        transform: Transform {
+                            translation: origin.to_array(),
+                            ..Transform::DEFAULT
+                        },
+                        size: size.to_array(),
+                        color: Color::TRANSPARENT,
+                        border_color: visuals.error_color.unwrap_or(Color::RED),
+                        border_thickness: 1.0,
+                        border_radius: 2.0,
+                        clip_rect,
+                    });
+                }
+
+                let text_color = match visuals.shows_placeholder() {
+                    true => visuals.color.with_alpha(0.5),
+                    false => visuals.color,
+                };
                 if let Some(layout) = &self.layout {
+                    // the input method replaces the selection once it commits, until then its
+                    // text is shown at the caret instead
+                    let selection = visuals.selection().filter(|_| self.preedit.is_none());
+                    let caret = visuals.caret_position.unwrap_or(0);
+                    let preedit = self
+                        .preedit
+                        .as_ref()
+                        .map(|preedit| caret..caret + preedit.len());
+                    let selection_color = visuals
+                        .selection_color
+                        .unwrap_or(visuals.color.with_alpha(0.3));
+
                     for (i, line) in layout.lines.iter().enumerate() {
                         let line_y_offset = i as f32 * self.line_height;
 
-                        let mut glyphs = Vec::new();
-                        let mut current_x = 0.0;
-                        for glyph in &line.glyphs {
-                            glyphs.push(vn_scene::GlyphInstanceData {
-                                texture_id: glyph.texture_id.clone(),
-                                position: [current_x + glyph.x_bearing, glyph.y_offset],
-                                size: glyph.size,
-                                uv_rect: glyph.uv_rect,
+                        let selected = selection.as_ref().map(|s| line.extents(s));
+                        for (x, width) in selected.into_iter().flatten() {
+                            scene.add_box(BoxPrimitiveData {
+                                transform: Transform {
+                                    translation: [
+                                        origin.0 + caret_space / 2.0 + x,
+                                        text_origin.1 + line_y_offset,
+                                    ],
+                                    ..Transform::DEFAULT
+                                },
+                                size: [width, self.line_height],
+                                color: selection_color,
+                                border_color: Color::TRANSPARENT,
+                                border_thickness: 0.0,
+                                border_radius: 0.0,
+                                clip_rect,
+                            });
+                        }
+
+                        let composed = preedit.as_ref().map(|p| line.extents(p));
+                        for (x, width) in composed.into_iter().flatten() {
+                            scene.add_box(BoxPrimitiveData {
+                                transform: Transform {
+                                    translation: [
+                                        origin.0 + caret_space / 2.0 + x,
+                                        text_origin.1 + line_y_offset + self.line_height - 2.0,
+                                    ],
+                                    ..Transform::DEFAULT
+                                },
+                                size: [width, 1.0],
+                                color: visuals.color,
+                                border_color: Color::TRANSPARENT,
+                                border_thickness: 0.0,
+                                border_radius: 0.0,
+                                clip_rect,
                             });
-                            current_x += glyph.advance;
                         }
 
+                        let glyphs = line
+                            .glyphs
+                            .iter()
+                            .chain(&line.ellipsis)
+                            .filter(|placed| placed.glyph.size != [0.0, 0.0])
+                            .map(|placed| vn_scene::GlyphInstanceData {
+                                texture_id: placed.glyph.texture_id.clone(),
+                                position: [
+                                    placed.x + placed.glyph.x_bearing,
+                                    placed.glyph.y_offset,
+                                ],
+                                size: placed.glyph.size,
+                                uv_rect: placed.glyph.uv_rect,
+                            })
+                            .collect();
+
                         scene.add_text(TextPrimitiveData {
                             transform: Transform {
                                 translation: [
-                                    origin.0 + caret_space / 2.0,
-                                    origin.1 + line_y_offset,
+                                    origin.0 + caret_space / 2.0 + line.x_offset,
+                                    text_origin.1 + line_y_offset,
                                 ],
                                 ..Transform::DEFAULT
                             },
-                            tint: visuals.color,
+                            tint: text_color,
                             glyphs,
                             clip_rect,
                         });
@@ -238,12 +852,18 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
 
                     if self.show_caret {
                         if let Some(caret_position) = visuals.caret_position {
+                            // within the text being composed, where the input method puts it
+                            let caret_position = caret_position
+                                + self
+                                    .preedit
+                                    .as_ref()
+                                    .map_or(0, |p| p.cursor.unwrap_or(p.len()));
                             scene.with_next_layer(&mut |canvas| {
                                 let (caret_x_offset, caret_y_offset) =
                                     layout.get_caret_pos(caret_position);
 
                                 let caret_x = origin.0 + caret_x_offset + caret_width / 2.0;
-                                let caret_y = origin.1 + caret_y_offset + caret_y_extra_offset;
+                                let caret_y = text_origin.1 + caret_y_offset + caret_y_extra_offset;
 
                                 canvas.add_box(BoxPrimitiveData {
                                     transform: Transform {
@@ -269,7 +889,7 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
         &mut self,
         ctx: &mut UiContext,
         state: &Self::State,
-        event: &crate::InteractionEvent,
+        event: &InteractionEvent,
     ) -> Vec<Self::Message> {
         let params = self.params.call(crate::StateToParamsArgs {
             state,
@@ -277,123 +897,353 @@ impl<State, Message: Clone> ElementImpl for TextField<State, Message> {
             ctx,
         });
 
-        if event.target != Some(self.id) {
+        if !event.reaches(self.id) {
             return Vec::new();
         }
+        self.update_preedit(event);
 
-        let messages =
-            params
-                .text_field_action_handler
-                .handle(self.id, event, || match &event.kind {
-                    crate::InteractionEventKind::Click {
-                        local_x, local_y, ..
-                    } => {
-                        if let Some(layout) = &self.layout {
-                            if let Some(c_pos) = layout.hit_test(*local_x, *local_y) {
-                                return vec![TextFieldAction::CaretMove(c_pos)];
-                            }
-                        }
-                        vec![]
-                    }
-                    crate::InteractionEventKind::Keyboard(key_event) => {
-                        if key_event.state.is_pressed() {
-                            use vn_utils::string::{InsertAtCharIndex, RemoveAtCharIndex};
-                            use winit::keyboard::{Key, NamedKey};
-
-                            let current_text = &params.visuals.text;
-                            let caret = params.visuals.caret_position.unwrap_or(0);
-
-                            match &key_event.logical_key {
-                                Key::Character(s) => {
-                                    let mut new_text = current_text.clone();
-                                    new_text.insert_str_at_char_index(caret, s);
-                                    vec![
-                                        TextFieldAction::TextChange(new_text),
-                                        TextFieldAction::CaretMove(caret + s.chars().count()),
-                                    ]
-                                }
-                                Key::Named(NamedKey::Space) => {
-                                    let mut new_text = current_text.clone();
-                                    new_text.insert_at_char_index(caret, ' ');
-                                    vec![
-                                        TextFieldAction::TextChange(new_text),
-                                        TextFieldAction::CaretMove(caret + 1),
-                                    ]
-                                }
-                                Key::Named(NamedKey::Backspace) => {
-                                    if caret > 0 && caret <= current_text.chars().count() {
-                                        let mut new_text = current_text.clone();
-                                        new_text.remove_at_char_index(caret - 1);
-                                        vec![
-                                            TextFieldAction::TextChange(new_text),
-                                            TextFieldAction::CaretMove(caret - 1),
-                                        ]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                                Key::Named(NamedKey::Delete) => {
-                                    if caret < current_text.chars().count() {
-                                        let mut new_text = current_text.clone();
-                                        new_text.remove_at_char_index(caret);
-                                        vec![TextFieldAction::TextChange(new_text)]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                                Key::Named(NamedKey::ArrowLeft) => {
-                                    if caret > 0 {
-                                        vec![TextFieldAction::CaretMove(caret - 1)]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                                Key::Named(NamedKey::ArrowRight) => {
-                                    if caret < current_text.chars().count() {
-                                        vec![TextFieldAction::CaretMove(caret + 1)]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                                Key::Named(NamedKey::ArrowUp) => {
-                                    if let Some(layout) = &self.layout {
-                                        let intended_x = layout.get_caret_x(caret);
-                                        let new_caret =
-                                            layout.get_vertical_move(caret, -1, intended_x);
-                                        vec![TextFieldAction::CaretMove(new_caret)]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                                Key::Named(NamedKey::ArrowDown) => {
-                                    if let Some(layout) = &self.layout {
-                                        let intended_x = layout.get_caret_x(caret);
-                                        let new_caret =
-                                            layout.get_vertical_move(caret, 1, intended_x);
-                                        vec![TextFieldAction::CaretMove(new_caret)]
-                                    } else {
-                                        vec![]
-                                    }
-                                }
-                                Key::Named(NamedKey::Enter) => {
-                                    let mut new_text = current_text.clone();
-                                    new_text.insert_at_char_index(caret, '\n');
-                                    vec![
-                                        TextFieldAction::TextChange(new_text),
-                                        TextFieldAction::CaretMove(caret + 1),
-                                    ]
-                                }
-                                _ => vec![],
-                            }
-                        } else {
-                            vec![]
-                        }
-                    }
-                    _ => vec![],
-                });
-
-        messages
+        params
+            .text_field_action_handler
+            .handle(self.id, event, || self.actions(ctx, &params, event))
     }
 }
 
 into_box_impl!(TextField);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{FixedMetrics, context};
+    use crate::{MemoryClipboard, MouseButton};
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn visuals(text: &str, caret: usize, anchor: Option<usize>) -> TextVisuals {
+        let theme = Theme::default();
+        TextVisuals {
+            caret_position: Some(caret),
+            selection_anchor: anchor,
+            ..TextVisuals::themed(&theme, WidgetState::Focused, &theme.fonts.body, text)
+        }
+    }
+
+    fn params(visuals: TextVisuals) -> TextFieldParams<()> {
+        TextFieldParams {
+            visuals,
+            metrics: Rc::new(FixedMetrics),
+            interaction: InteractionState::default(),
+            tab_index: TabIndex::default(),
+            input_filter: InputFilter::default(),
+            text_field_action_handler: EventHandler::none(),
+        }
+    }
+
+    /// A field showing `visuals`, laid out with characters 10 wide.
+    fn field(ctx: &UiContext, visuals: &TextVisuals) -> TextField<TextVisuals, ()> {
+        let mut field = TextField::new(
+            |args: crate::StateToParamsArgs<TextVisuals>| params(args.state.clone()),
+            Rc::new(RefCell::new(ElementWorld::new())),
+        );
+        field.update_state(visuals, DynamicDimension::Hint(1000.0), ctx);
+        field
+    }
+
+    /// Applies the actions like an application would.
+    fn apply(mut visuals: TextVisuals, actions: Vec<TextFieldAction>) -> TextVisuals {
+        for action in actions {
+            match action {
+                TextFieldAction::TextChange(text) => visuals.text = text,
+                TextFieldAction::CaretMove(caret) => {
+                    visuals.caret_position = Some(caret);
+                    visuals.selection_anchor = None;
+                }
+                TextFieldAction::Select { anchor, caret } => {
+                    visuals.caret_position = Some(caret);
+                    visuals.selection_anchor = Some(anchor);
+                }
+                TextFieldAction::Undo | TextFieldAction::Redo => {}
+            }
+        }
+        visuals
+    }
+
+    /// Presses `key` in a field showing `visuals` and applies the resulting actions.
+    fn press(ctx: &UiContext, visuals: TextVisuals, key: Key, modifiers: Modifiers) -> TextVisuals {
+        // the text of the key, as without an input method
+        let text = match &key {
+            Key::Character(s) if !modifiers.control => Some(s.to_string()),
+            Key::Named(NamedKey::Space) => Some(" ".to_string()),
+            _ => None,
+        };
+        let params = params(visuals.clone());
+        let actions =
+            field(ctx, &visuals).key_actions(ctx, &params, &key, text.as_deref(), modifiers);
+        apply(visuals, actions)
+    }
+
+    fn named(key: NamedKey) -> Key {
+        Key::Named(key)
+    }
+
+    fn character(c: &str) -> Key {
+        Key::Character(c.into())
+    }
+
+    const NONE: Modifiers = Modifiers {
+        shift: false,
+        control: false,
+        alt: false,
+        meta: false,
+    };
+    const SHIFT: Modifiers = Modifiers {
+        shift: true,
+        ..NONE
+    };
+    const CONTROL: Modifiers = Modifiers {
+        control: true,
+        ..NONE
+    };
+    const CONTROL_SHIFT: Modifiers = Modifiers {
+        shift: true,
+        control: true,
+        ..NONE
+    };
+
+    fn state(visuals: &TextVisuals) -> (&str, Option<usize>, Option<usize>) {
+        (
+            &visuals.text,
+            visuals.caret_position,
+            visuals.selection_anchor,
+        )
+    }
+
+    #[test]
+    fn word_boundaries() {
+        let text = chars("foo  bar_baz, qux");
+        assert_eq!(previous_word_start(&text, 17), 14);
+        assert_eq!(previous_word_start(&text, 14), 5);
+        assert_eq!(previous_word_start(&text, 8), 5);
+        assert_eq!(previous_word_start(&text, 5), 0);
+        assert_eq!(previous_word_start(&text, 0), 0);
+        assert_eq!(next_word_end(&text, 0), 3);
+        assert_eq!(next_word_end(&text, 3), 12);
+        assert_eq!(next_word_end(&text, 12), 17);
+
+        // words, whitespace and punctuation are selected as runs of their own
+        assert_eq!(word_at(&text, 6), 5..12);
+        assert_eq!(word_at(&text, 3), 3..5);
+        assert_eq!(word_at(&text, 12), 12..13);
+        assert_eq!(word_at(&text, 100), 14..17);
+        assert_eq!(word_at(&[], 0), 0..0);
+    }
+
+    #[test]
+    fn lines() {
+        let text = chars("one\ntwo\n\nthree");
+        assert_eq!(line_at(&text, 0), 0..3);
+        assert_eq!(line_at(&text, 3), 0..3);
+        assert_eq!(line_at(&text, 5), 4..7);
+        assert_eq!(line_at(&text, 8), 8..8);
+        assert_eq!(line_at(&text, 14), 9..14);
+        assert_eq!(line_at(&[], 0), 0..0);
+    }
+
+    #[test]
+    fn replacing() {
+        let text = chars("hello world");
+        let any = InputFilter::default();
+        let actions = replace(&text, 6..11, "there", &any);
+        assert!(matches!(&actions[..], [
+            TextFieldAction::TextChange(text),
+            TextFieldAction::CaretMove(11),
+        ] if text == "hello there"));
+
+        let actions = replace(&text, 5..5, ",", &any);
+        assert!(matches!(&actions[..], [
+            TextFieldAction::TextChange(text),
+            TextFieldAction::CaretMove(6),
+        ] if text == "hello, world"));
+
+        // rejected edits cause no actions
+        let digits = InputFilter {
+            kind: crate::InputKind::Integer {
+                min: None,
+                max: None,
+            },
+            ..InputFilter::default()
+        };
+        assert!(replace(&text, 0..0, "x", &digits).is_empty());
+    }
+
+    #[test]
+    fn word_jumps_and_selection() {
+        let ctx = context();
+        let text = "foo bar baz";
+
+        let moved = press(
+            &ctx,
+            visuals(text, 11, None),
+            named(NamedKey::ArrowLeft),
+            CONTROL,
+        );
+        assert_eq!(state(&moved), (text, Some(8), None));
+        let moved = press(&ctx, moved, named(NamedKey::ArrowLeft), CONTROL);
+        assert_eq!(state(&moved), (text, Some(4), None));
+        let moved = press(&ctx, moved, named(NamedKey::ArrowRight), CONTROL);
+        assert_eq!(state(&moved), (text, Some(7), None));
+
+        // shift extends the selection from where it started
+        let selected = press(&ctx, moved, named(NamedKey::ArrowRight), CONTROL_SHIFT);
+        assert_eq!(state(&selected), (text, Some(11), Some(7)));
+        let selected = press(&ctx, selected, named(NamedKey::ArrowLeft), SHIFT);
+        assert_eq!(state(&selected), (text, Some(10), Some(7)));
+        assert_eq!(selected.selection(), Some(7..10));
+
+        // without shift, arrows collapse the selection to its side
+        let collapsed = press(&ctx, selected.clone(), named(NamedKey::ArrowLeft), NONE);
+        assert_eq!(state(&collapsed), (text, Some(7), None));
+        let collapsed = press(&ctx, selected, named(NamedKey::ArrowRight), NONE);
+        assert_eq!(state(&collapsed), (text, Some(10), None));
+
+        let home = press(&ctx, visuals(text, 5, None), named(NamedKey::Home), SHIFT);
+        assert_eq!(state(&home), (text, Some(0), Some(5)));
+        let all = press(&ctx, visuals(text, 5, None), character("a"), CONTROL);
+        assert_eq!(state(&all), (text, Some(11), Some(0)));
+
+        let deleted = press(
+            &ctx,
+            visuals(text, 7, None),
+            named(NamedKey::Backspace),
+            CONTROL,
+        );
+        assert_eq!(state(&deleted), ("foo  baz", Some(4), None));
+        let deleted = press(
+            &ctx,
+            visuals(text, 3, None),
+            named(NamedKey::Delete),
+            CONTROL,
+        );
+        assert_eq!(state(&deleted), ("foo baz", Some(3), None));
+    }
+
+    #[test]
+    fn clipboard_round_trips() {
+        let ctx = context();
+        ctx.event_manager
+            .borrow_mut()
+            .set_clipboard(Rc::new(RefCell::new(MemoryClipboard::new())));
+        let clipboard = ctx.event_manager.borrow().clipboard();
+
+        // copying keeps the text
+        let copied = press(
+            &ctx,
+            visuals("foo bar", 4, Some(7)),
+            character("c"),
+            CONTROL,
+        );
+        assert_eq!(state(&copied), ("foo bar", Some(4), Some(7)));
+        assert_eq!(clipboard.borrow_mut().get_text().as_deref(), Some("bar"));
+
+        // pasting replaces the selection
+        let pasted = press(
+            &ctx,
+            visuals("foo baz", 0, Some(3)),
+            character("v"),
+            CONTROL,
+        );
+        assert_eq!(state(&pasted), ("bar baz", Some(3), None));
+
+        let cut = press(
+            &ctx,
+            visuals("foo bar", 0, Some(4)),
+            character("x"),
+            CONTROL,
+        );
+        assert_eq!(state(&cut), ("bar", Some(0), None));
+        let pasted = press(&ctx, cut, character("v"), CONTROL);
+        assert_eq!(state(&pasted), ("foo bar", Some(4), None));
+
+        // nothing is copied without a selection or from masked text
+        let copied = press(&ctx, visuals("secret", 6, None), character("x"), CONTROL);
+        assert_eq!(state(&copied), ("secret", Some(6), None));
+        let masked = TextVisuals {
+            mask: Some('*'),
+            ..visuals("secret", 6, Some(0))
+        };
+        press(&ctx, masked, character("c"), CONTROL);
+        assert_eq!(clipboard.borrow_mut().get_text().as_deref(), Some("foo "));
+    }
+
+    fn double_click(field: &TextField<TextVisuals, ()>, x: f32, y: f32) -> InteractionEvent {
+        let kind = InteractionEventKind::DoubleClick {
+            button: MouseButton::Left,
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        };
+        InteractionEvent::new(Some(field.id), kind)
+    }
+
+    fn triple_click(field: &TextField<TextVisuals, ()>, x: f32, y: f32) -> InteractionEvent {
+        let kind = InteractionEventKind::TripleClick {
+            button: MouseButton::Left,
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        };
+        InteractionEvent::new(Some(field.id), kind)
+    }
+
+    #[test]
+    fn clicks_select_words_and_lines() {
+        let ctx = context();
+        let text = "one two\nthree four";
+        let visuals = visuals(text, 0, None);
+        let params = params(visuals.clone());
+        let mut field = field(&ctx, &visuals);
+        let first_line = field.line_height / 2.0;
+        let second_line = field.line_height * 1.5;
+
+        let event = double_click(&field, 25.0, second_line);
+        let word = apply(visuals.clone(), field.actions(&ctx, &params, &event));
+        assert_eq!(word.selection(), Some(8..13));
+
+        let event = triple_click(&field, 25.0, second_line);
+        let line = apply(visuals.clone(), field.actions(&ctx, &params, &event));
+        assert_eq!(state(&line), (text, Some(18), Some(8)));
+
+        let event = triple_click(&field, 25.0, first_line);
+        let line = apply(visuals, field.actions(&ctx, &params, &event));
+        assert_eq!(state(&line), (text, Some(7), Some(0)));
+    }
+
+    #[test]
+    fn typing_inserts_the_text_of_keys_and_commits() {
+        let ctx = context();
+        let typed = press(&ctx, visuals("ac", 1, None), character("b"), NONE);
+        assert_eq!(state(&typed), ("abc", Some(2), None));
+        let typed = press(&ctx, typed, named(NamedKey::Space), NONE);
+        assert_eq!(state(&typed), ("ab c", Some(3), None));
+        let typed = press(&ctx, visuals("ab", 0, Some(2)), character("x"), NONE);
+        assert_eq!(state(&typed), ("x", Some(1), None));
+
+        // keys pressed while an input method composes text carry none, only the commit inserts
+        let visuals = visuals("ab", 2, None);
+        let params = params(visuals.clone());
+        let mut field = field(&ctx, &visuals);
+        let key = character("k");
+        assert!(
+            field
+                .key_actions(&ctx, &params, &key, None, NONE)
+                .is_empty()
+        );
+        let commit = InteractionEventKind::Ime(crate::Ime::Commit("\u{AC00}".to_string()));
+        let event = InteractionEvent::new(Some(field.id), commit);
+        let committed = apply(visuals, field.actions(&ctx, &params, &event));
+        assert_eq!(state(&committed), ("ab\u{AC00}", Some(3), None));
+    }
+}
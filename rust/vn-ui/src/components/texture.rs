@@ -1,6 +1,6 @@
 use crate::{
     DynamicDimension, ElementId, ElementImpl, ElementSize, ElementWorld, InteractionEvent,
-    SizeConstraints, StateToParams, UiContext, into_box_impl,
+    LayoutInputs, SizeConstraints, StateToParams, UiContext, into_box_impl,
 };
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -68,6 +68,7 @@ pub struct TextureParams {
 pub struct Texture<State: 'static, Message: 'static> {
     id: ElementId,
     params: StateToParams<State, TextureParams>,
+    layout_inputs: LayoutInputs<(ElementSize, FitStrategy)>,
     _phantom: std::marker::PhantomData<Message>,
 }
 
@@ -79,6 +80,7 @@ impl<State, Message> Texture<State, Message> {
         Self {
             id: world.borrow_mut().next_id(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
             _phantom: std::marker::PhantomData,
         }
     }
@@ -174,6 +176,16 @@ impl<State, Message> ElementImpl for Texture<State, Message> {
         }
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        self.layout_inputs
+            .changed((params.preferred_size, params.fit_strategy))
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -117,6 +117,20 @@ impl<State: 'static, Message: 'static> ElementImpl for ToolTip<State, Message> {
             .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        // showing and hiding the tooltip is timed while laying it out
+        let pending =
+            self.show_tooltip || params.interaction.is_hovered || self.hovered_start_at.is_some();
+        let element_dirty = self.element.update(ctx, state);
+        let tooltip_dirty = self.tooltip.update(ctx, state);
+        pending || element_dirty || tooltip_dirty
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
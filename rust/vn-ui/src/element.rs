@@ -1,15 +1,23 @@
 use crate::{ElementId, ElementSize, InteractionEvent, SizeConstraints, UiContext};
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 use vn_scene::Scene;
 
+/// A layout cache that can be kept across render cycles. Sizes cached in an earlier cycle are
+/// only reused for elements that were marked clean in the current one.
 pub struct SimpleLayoutCache {
-    cache: HashMap<ElementId, (SizeConstraints, ElementSize)>,
+    cache: HashMap<ElementId, (SizeConstraints, ElementSize, u64)>,
+    clean: HashSet<ElementId>,
+    frame: u64,
 }
 
 impl SimpleLayoutCache {
     pub fn new() -> Self {
         Self {
             cache: HashMap::new(),
+            clean: HashSet::new(),
+            frame: 0,
         }
     }
 }
@@ -17,14 +25,21 @@ impl SimpleLayoutCache {
 pub trait LayoutCache {
     fn lookup(&self, element_id: ElementId, constraints: SizeConstraints) -> Option<ElementSize>;
     fn cache(&mut self, element_id: ElementId, constraints: SizeConstraints, size: ElementSize);
+
+    /// Starts a new render cycle, see [Element::update].
+    fn next_frame(&mut self) {}
+
+    /// Marks an element whose layout inputs did not change since the previous render cycle.
+    fn mark_clean(&mut self, _element_id: ElementId) {}
 }
 
 impl LayoutCache for SimpleLayoutCache {
     fn lookup(&self, element_id: ElementId, constraints: SizeConstraints) -> Option<ElementSize> {
         self.cache
             .get(&element_id)
-            .and_then(|(cached_constraints, s)| {
-                if constraints == *cached_constraints {
+            .and_then(|(cached_constraints, s, frame)| {
+                let valid = *frame == self.frame || self.clean.contains(&element_id);
+                if valid && constraints == *cached_constraints {
                     Some(*s)
                 } else {
                     None
@@ -33,7 +48,59 @@ impl LayoutCache for SimpleLayoutCache {
     }
 
     fn cache(&mut self, element_id: ElementId, constraints: SizeConstraints, size: ElementSize) {
-        self.cache.insert(element_id, (constraints, size));
+        self.cache
+            .insert(element_id, (constraints, size, self.frame));
+    }
+
+    fn next_frame(&mut self) {
+        self.frame += 1;
+        self.clean.clear();
+    }
+
+    fn mark_clean(&mut self, element_id: ElementId) {
+        self.clean.insert(element_id);
+    }
+}
+
+/// Allows keeping the cache around between render cycles while handing it to a [UiContext].
+impl<C: LayoutCache> LayoutCache for Rc<RefCell<C>> {
+    fn lookup(&self, element_id: ElementId, constraints: SizeConstraints) -> Option<ElementSize> {
+        self.borrow().lookup(element_id, constraints)
+    }
+
+    fn cache(&mut self, element_id: ElementId, constraints: SizeConstraints, size: ElementSize) {
+        self.borrow_mut().cache(element_id, constraints, size)
+    }
+
+    fn next_frame(&mut self) {
+        self.borrow_mut().next_frame()
+    }
+
+    fn mark_clean(&mut self, element_id: ElementId) {
+        self.borrow_mut().mark_clean(element_id)
+    }
+}
+
+/// The params an element's layout depended on in the previous render cycle, used to implement
+/// [ElementImpl::update_impl].
+pub struct LayoutInputs<T>(Option<T>);
+
+impl<T> Default for LayoutInputs<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<T: PartialEq> LayoutInputs<T> {
+    pub fn new() -> Self {
+        Self(None)
+    }
+
+    /// Stores `inputs` and returns whether they differ from the previously stored ones.
+    pub fn changed(&mut self, inputs: T) -> bool {
+        let changed = self.0.as_ref() != Some(&inputs);
+        self.0 = Some(inputs);
+        changed
     }
 }
 
@@ -58,6 +125,15 @@ pub trait ElementImpl {
         constraints: SizeConstraints,
     ) -> ElementSize;
 
+    /// Compares the params the layout depends on with the ones of the previous render cycle and
+    /// updates all children. Returns true if this element or any of its descendants has to be
+    /// laid out again. The default always returns true, which is never wrong but defeats caching.
+    ///
+    /// !!! DO NOT MANUALLY CALL THIS, CALL [update](Self::update) INSTEAD !!!
+    fn update_impl(&mut self, _ctx: &mut UiContext, _state: &Self::State) -> bool {
+        true
+    }
+
     /// Draws the element at the specified origin with the given size into the scene.
     ///
     /// !!! DO NOT MANUALLY CALL THIS, CALL [draw](Self::draw) INSTEAD !!!
@@ -87,6 +163,18 @@ pub trait Element: ElementImpl {
         self.id_impl()
     }
 
+    /// Call this once per render cycle, after [LayoutCache::next_frame] and before
+    /// [layout](Self::layout). Elements that did not change since the previous cycle are marked
+    /// clean, so their cached sizes are reused. Returns true if this element or any of its
+    /// descendants changed, which makes the parent dirty as well.
+    fn update(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let dirty = self.update_impl(ctx, state);
+        if !dirty {
+            ctx.layout_cache.mark_clean(self.id());
+        }
+        dirty
+    }
+
     /// Call this method to perform the layouting work. It must be called before drawing the element.
     /// And elements assume that the size they get drawn with is the size they report here.
     ///
@@ -157,13 +245,17 @@ pub trait Element: ElementImpl {
         }
     }
 
-    /// Handles an interaction event.
+    /// Handles an interaction event. Events whose propagation was stopped by an element earlier
+    /// on their path are not passed on.
     fn handle_event(
         &mut self,
         ctx: &mut UiContext,
         state: &Self::State,
         event: &InteractionEvent,
     ) -> Vec<Self::Message> {
+        if event.is_propagation_stopped() {
+            return vec![];
+        }
         log::trace!(
             "Start handling event {:?} for element {:?}",
             event,
@@ -184,3 +276,80 @@ impl<State: 'static, Message: 'static, T: ElementImpl<State = State, Message = M
     for T
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DynamicDimension, DynamicSize};
+
+    fn constraints(width: f32) -> SizeConstraints {
+        SizeConstraints {
+            min_size: ElementSize::ZERO,
+            max_size: DynamicSize {
+                width: DynamicDimension::Limit(width),
+                height: DynamicDimension::Hint(0.0),
+            },
+            scene_size: (100.0, 100.0),
+        }
+    }
+
+    const SIZE: ElementSize = ElementSize {
+        width: 10.0,
+        height: 20.0,
+    };
+
+    #[test]
+    fn sizes_are_kept_for_clean_elements() {
+        let mut cache = SimpleLayoutCache::new();
+        let (clean, dirty) = (ElementId(1), ElementId(2));
+        cache.cache(clean, constraints(50.0), SIZE);
+        cache.cache(dirty, constraints(50.0), SIZE);
+
+        // within the frame, everything cached is valid for the same constraints
+        assert_eq!(cache.lookup(dirty, constraints(50.0)), Some(SIZE));
+        assert_eq!(cache.lookup(dirty, constraints(60.0)), None);
+
+        cache.next_frame();
+        cache.mark_clean(clean);
+        assert_eq!(cache.lookup(clean, constraints(50.0)), Some(SIZE));
+        assert_eq!(cache.lookup(clean, constraints(60.0)), None);
+        assert_eq!(cache.lookup(dirty, constraints(50.0)), None);
+
+        // laying out a dirty element again makes it valid for the rest of the frame
+        cache.cache(dirty, constraints(50.0), SIZE);
+        assert_eq!(cache.lookup(dirty, constraints(50.0)), Some(SIZE));
+
+        // being clean only lasts a frame
+        cache.next_frame();
+        assert_eq!(cache.lookup(clean, constraints(50.0)), None);
+        assert_eq!(cache.lookup(dirty, constraints(50.0)), None);
+        assert_eq!(cache.lookup(ElementId(3), constraints(50.0)), None);
+    }
+
+    #[test]
+    fn shared_cache() {
+        let shared = Rc::new(RefCell::new(SimpleLayoutCache::new()));
+        let mut cache: Box<dyn LayoutCache> = Box::new(shared.clone());
+        cache.cache(ElementId(1), constraints(50.0), SIZE);
+        cache.next_frame();
+        cache.mark_clean(ElementId(1));
+        assert_eq!(
+            shared.borrow().lookup(ElementId(1), constraints(50.0)),
+            Some(SIZE)
+        );
+    }
+
+    #[test]
+    fn layout_inputs() {
+        let mut inputs = LayoutInputs::new();
+        // the first inputs always count as changed
+        assert!(inputs.changed((1.0, vec![ElementId(1)])));
+        assert!(!inputs.changed((1.0, vec![ElementId(1)])));
+        assert!(inputs.changed((2.0, vec![ElementId(1)])));
+        assert!(inputs.changed((2.0, vec![ElementId(1), ElementId(2)])));
+        assert!(!inputs.changed((2.0, vec![ElementId(1), ElementId(2)])));
+        // NaN never equals itself, so it's never clean
+        assert!(inputs.changed((f32::NAN, vec![])));
+        assert!(inputs.changed((f32::NAN, vec![])));
+    }
+}
@@ -1,17 +1,42 @@
 use crate::ElementId;
+use std::ops::Range;
 
 pub struct ElementWorld {
     next_id: u32,
+    /// ids handed out before new ones, see [ElementWorld::reuse]
+    reused: Range<u32>,
 }
 
 impl ElementWorld {
     pub fn new() -> Self {
-        Self { next_id: 0 }
+        Self {
+            next_id: 0,
+            reused: 0..0,
+        }
     }
 
     pub fn next_id(&mut self) -> ElementId {
+        if let Some(id) = self.reused.next() {
+            return ElementId(id);
+        }
         let id = ElementId(self.next_id);
         self.next_id += 1;
         id
     }
+
+    /// The ids the next calls to [ElementWorld::next_id] return are `ids`, e.g. to build an
+    /// element again with the ids it had before. Once they are used up, new ids are handed out.
+    pub(crate) fn reuse(&mut self, ids: Range<u32>) {
+        self.reused = ids;
+    }
+
+    /// Stops handing out the ids passed to [ElementWorld::reuse] that are left.
+    pub(crate) fn end_reuse(&mut self) {
+        self.reused = 0..0;
+    }
+
+    /// The id the next call to [ElementWorld::next_id] returns, if no ids are reused.
+    pub(crate) fn peek_id(&self) -> u32 {
+        self.next_id
+    }
 }
@@ -1,22 +1,148 @@
-use crate::LayoutCache;
-use std::cell::RefCell;
+use crate::hit_test::{HitGrid, HitShape, Hitbox};
+use crate::{Clipboard, LayoutCache, MemoryClipboard};
+use std::any::{Any, TypeId};
+use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use web_time::{Duration, Instant};
+use winit::keyboard::{Key, NamedKey};
 
 #[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub struct ElementId(pub u32);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MouseButton {
     Left,
     Right,
     Middle,
 }
 
+/// The stage of propagation an event is delivered in. Targeted events travel from the root
+/// down to the target (capture), are delivered to the target itself and then travel back up
+/// to the root (bubble), following the parents registered via `with_hitbox_hierarchy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventPhase {
+    Capture,
+    /// Delivered exactly once per event, also for events without a target, so elements
+    /// observing all events regardless of target should only look at this phase.
+    Target,
+    Bubble,
+}
+
 #[derive(Debug, Clone)]
 pub struct InteractionEvent {
     pub target: Option<ElementId>,
+    /// The element the event is currently delivered to.
+    pub current_target: Option<ElementId>,
+    pub phase: EventPhase,
     pub kind: InteractionEventKind,
+    /// The modifier keys held down when the event happened.
+    pub modifiers: Modifiers,
+    // position along the propagation path and the position at which propagation was stopped,
+    // shared between all deliveries of the same event
+    step: usize,
+    stopped_at: Rc<Cell<Option<usize>>>,
+}
+
+impl InteractionEvent {
+    pub fn new(target: Option<ElementId>, kind: InteractionEventKind) -> Self {
+        Self {
+            target,
+            current_target: target,
+            phase: EventPhase::Target,
+            kind,
+            modifiers: Modifiers::default(),
+            step: 0,
+            stopped_at: Rc::new(Cell::new(None)),
+        }
+    }
+
+    /// True if `id` should handle this event as a regular listener, i.e. it is the target or
+    /// the event bubbles up from one of its descendants.
+    pub fn reaches(&self, id: ElementId) -> bool {
+        self.current_target == Some(id) && self.phase != EventPhase::Capture
+    }
+
+    /// True if `id` is an ancestor of the target and sees the event before the target does.
+    pub fn is_capturing(&self, id: ElementId) -> bool {
+        self.current_target == Some(id) && self.phase == EventPhase::Capture
+    }
+
+    /// Prevents the event from being delivered to any further element along its path.
+    pub fn stop_propagation(&self) {
+        if self.stopped_at.get().is_none() {
+            self.stopped_at.set(Some(self.step));
+        }
+    }
+
+    pub fn is_propagation_stopped(&self) -> bool {
+        self.stopped_at.get().is_some_and(|step| step < self.step)
+    }
+
+    fn bubbles(&self) -> bool {
+        matches!(
+            self.kind,
+            InteractionEventKind::MouseMove { .. }
+                | InteractionEventKind::MouseDown { .. }
+                | InteractionEventKind::MouseUp { .. }
+                | InteractionEventKind::MouseScroll { .. }
+                | InteractionEventKind::Click { .. }
+                | InteractionEventKind::DoubleClick { .. }
+                | InteractionEventKind::TripleClick { .. }
+                | InteractionEventKind::LongPress { .. }
+                | InteractionEventKind::Keyboard(_)
+                | InteractionEventKind::Ime(_)
+        )
+    }
+
+    fn delivered_to(&self, id: ElementId, phase: EventPhase, step: usize) -> Self {
+        Self {
+            current_target: Some(id),
+            phase,
+            step,
+            ..self.clone()
+        }
+    }
+}
+
+/// The distance of a scroll wheel movement. Positive values scroll towards the start (up / left).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScrollDelta {
+    /// Lines to scroll, as reported by most mouse wheels.
+    Lines { x: f32, y: f32 },
+    /// Exact distances, as reported by touchpads.
+    Pixels { x: f32, y: f32 },
+}
+
+impl ScrollDelta {
+    /// The distance a single line of `Lines` scrolls.
+    pub const LINE_SIZE: f32 = 32.0;
+
+    pub fn to_pixels(self) -> (f32, f32) {
+        match self {
+            ScrollDelta::Lines { x, y } => (x * Self::LINE_SIZE, y * Self::LINE_SIZE),
+            ScrollDelta::Pixels { x, y } => (x, y),
+        }
+    }
+
+    fn swap_axes(self) -> Self {
+        match self {
+            ScrollDelta::Lines { x, y } => ScrollDelta::Lines { x: y, y: x },
+            ScrollDelta::Pixels { x, y } => ScrollDelta::Pixels { x: y, y: x },
+        }
+    }
+}
+
+impl From<winit::event::MouseScrollDelta> for ScrollDelta {
+    fn from(delta: winit::event::MouseScrollDelta) -> Self {
+        match delta {
+            winit::event::MouseScrollDelta::LineDelta(x, y) => ScrollDelta::Lines { x, y },
+            winit::event::MouseScrollDelta::PixelDelta(position) => ScrollDelta::Pixels {
+                x: position.x as f32,
+                y: position.y as f32,
+            },
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -41,8 +167,13 @@ pub enum InteractionEventKind {
         local_x: f32,
         local_y: f32,
     },
+    /// Sent to the element under the cursor and bubbled up to the nearest scrollable ancestor.
     MouseScroll {
+        delta: ScrollDelta,
+        x: f32,
         y: f32,
+        local_x: f32,
+        local_y: f32,
     },
     Click {
         button: MouseButton,
@@ -51,91 +182,757 @@ pub enum InteractionEventKind {
         local_x: f32,
         local_y: f32,
     },
+    /// Sent after the second of two clicks on the same element within the double click interval
+    /// and distance.
+    DoubleClick {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        local_x: f32,
+        local_y: f32,
+    },
+    /// Sent after a click following a double click, with the same conditions.
+    TripleClick {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        local_x: f32,
+        local_y: f32,
+    },
+    /// Sent when the mouse is held down on an element without moving for the long press delay.
+    /// Releasing it afterwards does not cause a click.
+    LongPress {
+        button: MouseButton,
+        x: f32,
+        y: f32,
+        local_x: f32,
+        local_y: f32,
+    },
     MouseEnter,
     MouseLeave,
     FocusGained,
     FocusLost,
     Keyboard(crate::KeyEvent),
+    /// Queued when the window reports changed modifier keys, in order with the keyboard events,
+    /// so every event carries the modifiers held down when it happened. Not delivered to
+    /// elements.
+    ModifiersChanged(Modifiers),
+    /// Input method events for the focused element: the text being composed (preedit) and the
+    /// text to insert once composition is done (commit).
+    Ime(crate::Ime),
+    /// Sent to a draggable element once the cursor moved further than the drag threshold while
+    /// pressed on it. Until the drag ends, the pointer is captured by that element.
+    DragStart {
+        x: f32,
+        y: f32,
+        start_x: f32,
+        start_y: f32,
+    },
+    DragMove {
+        x: f32,
+        y: f32,
+        start_x: f32,
+        start_y: f32,
+    },
+    /// Sent to the dragged element when the button is released or the drag is cancelled.
+    DragEnd {
+        x: f32,
+        y: f32,
+        start_x: f32,
+        start_y: f32,
+        drop_target: Option<ElementId>,
+    },
+    /// Sent to a drop target accepting the dragged payload when the cursor enters it.
+    DragEnter {
+        payload: DragPayload,
+    },
+    DragLeave,
+    Drop {
+        payload: DragPayload,
+        x: f32,
+        y: f32,
+        local_x: f32,
+        local_y: f32,
+    },
+}
+
+/// The data carried by a drag, matched by type against the registered drop targets.
+#[derive(Clone)]
+pub struct DragPayload {
+    value: Rc<dyn Any>,
+    type_name: &'static str,
+}
+
+impl DragPayload {
+    pub fn new<T: Any>(value: T) -> Self {
+        Self {
+            value: Rc::new(value),
+            type_name: std::any::type_name::<T>(),
+        }
+    }
+
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    fn value_type_id(&self) -> TypeId {
+        (*self.value).type_id()
+    }
+}
+
+impl std::fmt::Debug for DragPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DragPayload<{}>", self.type_name)
+    }
+}
+
+/// Position of an element in the keyboard focus order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TabIndex {
+    /// Not reachable via the keyboard, but can still be focused by clicking it.
+    #[default]
+    None,
+    /// Reachable in registration (i.e. draw) order.
+    Auto,
+    /// Reached before all `Auto` elements, in ascending order.
+    Explicit(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Modifier keys currently held down, as reported by the window via
+/// [InteractionEventKind::ModifiersChanged].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub meta: bool,
+}
+
+impl From<winit::keyboard::ModifiersState> for Modifiers {
+    fn from(state: winit::keyboard::ModifiersState) -> Self {
+        Self {
+            shift: state.shift_key(),
+            control: state.control_key(),
+            alt: state.alt_key(),
+            meta: state.super_key(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Focusable {
+    id: ElementId,
+    tab_index: TabIndex,
+    captures_arrow_keys: bool,
+}
+
+#[derive(Clone)]
+struct Draggable {
+    id: ElementId,
+    payload: Option<DragPayload>,
+    // overrides the drag threshold of the event manager
+    threshold: Option<f32>,
+}
+
+#[derive(Clone)]
+struct DropTarget {
+    id: ElementId,
+    accepts: TypeId,
+}
+
+/// How many of each kind of registration the event manager held at some point of drawing, see
+/// [EventManager::registrations_since].
+#[derive(Clone, Copy)]
+pub(crate) struct RegistrationMark {
+    hitboxes: usize,
+    focusables: usize,
+    draggables: usize,
+    drop_targets: usize,
+}
+
+/// The hitboxes, parents and other registrations made while drawing a subtree, to make them
+/// again without drawing it, see [crate::DrawCache].
+#[derive(Clone, Default)]
+pub(crate) struct Registrations {
+    hitboxes: Vec<Hitbox>,
+    parents: Vec<(ElementId, ElementId)>,
+    focusables: Vec<Focusable>,
+    draggables: Vec<Draggable>,
+    drop_targets: Vec<DropTarget>,
+}
+
+impl Registrations {
+    /// The elements that registered a hitbox.
+    pub fn ids(&self) -> impl Iterator<Item = ElementId> + '_ {
+        self.hitboxes.iter().map(|hitbox| hitbox.id)
+    }
+}
+
+struct Press {
+    target: ElementId,
+    button: MouseButton,
+    position: (f32, f32),
+    at: Instant,
+    long_pressed: bool,
+    // consecutive clicks, counted for the last click
+    clicks: u32,
+}
+
+struct DragState {
+    source: ElementId,
+    payload: Option<DragPayload>,
+    threshold: f32,
+    start: (f32, f32),
+    position: (f32, f32),
+    started: bool,
+    drop_target: Option<ElementId>,
 }
 
 pub struct EventManager {
-    insertion_order: u32,
-    hitboxes: HashMap<ElementId, (u32, u32, crate::Rect)>, // id -> (layer, insertion_order, bounds)
+    hitboxes: HitGrid,
     hovered_elements: HashSet<ElementId>,
     focused_element: Option<ElementId>,
-    // We might need a parent mapping to implement bubbling correctly if we don't do it during tree traversal
+    // in registration order
+    focusables: Vec<Focusable>,
+    modifiers: Modifiers,
+    draggables: Vec<Draggable>,
+    drop_targets: Vec<DropTarget>,
+    drag: Option<DragState>,
+    drag_threshold: f32,
+    // receives all pointer events while set, regardless of the hitboxes under the cursor
+    pointer_capture: Option<ElementId>,
+    pointer_position: (f32, f32),
+    press: Option<Press>,
+    last_click: Option<Press>,
+    double_click_interval: Duration,
+    double_click_distance: f32,
+    long_press_delay: Duration,
+    // time of the events currently processed
+    now: Instant,
+    // used for hover and event propagation
     parents: HashMap<ElementId, ElementId>,
+    // the ancestors of every element asked for, built from `parents` once per frame instead of
+    // walking the parents for every event
+    ancestor_paths: RefCell<HashMap<ElementId, Rc<[ElementId]>>>,
     event_queue: Vec<InteractionEvent>,
+    clipboard: Rc<RefCell<dyn Clipboard>>,
 }
 
 impl EventManager {
     pub fn new() -> Self {
         Self {
-            insertion_order: 0,
-            hitboxes: HashMap::new(),
+            hitboxes: HitGrid::new(),
             hovered_elements: HashSet::new(),
             focused_element: None,
+            focusables: Vec::new(),
+            modifiers: Modifiers::default(),
+            draggables: Vec::new(),
+            drop_targets: Vec::new(),
+            drag: None,
+            drag_threshold: 4.0,
+            pointer_capture: None,
+            pointer_position: (0.0, 0.0),
+            press: None,
+            last_click: None,
+            double_click_interval: Duration::from_millis(500),
+            double_click_distance: 4.0,
+            long_press_delay: Duration::from_millis(600),
+            now: Instant::now(),
             parents: HashMap::new(),
+            ancestor_paths: RefCell::new(HashMap::new()),
             event_queue: Vec::new(),
+            clipboard: Rc::new(RefCell::new(MemoryClipboard::new())),
         }
     }
 
     pub fn queue_event(&mut self, kind: InteractionEventKind) {
-        self.event_queue
-            .push(InteractionEvent { target: None, kind });
+        self.event_queue.push(InteractionEvent::new(None, kind));
     }
 
-    pub fn process_events(&mut self) -> Vec<InteractionEvent> {
+    /// Turns the queued input into interaction events. Should be called every frame with the
+    /// same time as `UiContext::now`, since long presses are detected here as well.
+    pub fn process_events(&mut self, now: Instant) -> Vec<InteractionEvent> {
+        self.now = now;
         let queue = std::mem::take(&mut self.event_queue);
         let mut all_events = Vec::new();
 
         for event in queue {
-            match event.kind {
-                InteractionEventKind::MouseMove { x, y, .. } => {
-                    all_events.extend(self.handle_mouse_move(x, y));
-                }
+            let events = match event.kind {
+                InteractionEventKind::MouseMove { x, y, .. } => self.handle_mouse_move(x, y),
                 InteractionEventKind::MouseDown { button, x, y, .. } => {
-                    all_events.extend(self.handle_mouse_down(x, y, button));
+                    self.handle_mouse_down(x, y, button)
                 }
                 InteractionEventKind::MouseUp { button, x, y, .. } => {
-                    all_events.extend(self.handle_mouse_up(x, y, button));
+                    self.handle_mouse_up(x, y, button)
                 }
-                InteractionEventKind::Keyboard(key_event) => {
-                    all_events.extend(self.handle_key(&key_event));
+                InteractionEventKind::Keyboard(key_event) => self.handle_key(&key_event),
+                InteractionEventKind::ModifiersChanged(modifiers) => {
+                    self.modifiers = modifiers;
+                    vec![]
                 }
-                InteractionEventKind::MouseScroll { y } => {
-                    all_events.push(InteractionEvent {
-                        target: self.focused_element,
-                        kind: InteractionEventKind::MouseScroll { y },
-                    });
+                InteractionEventKind::Ime(ime) => self.handle_ime(ime),
+                InteractionEventKind::MouseScroll { delta, x, y, .. } => {
+                    self.handle_mouse_scroll(x, y, delta)
                 }
-                _ => {}
-            }
+                _ => vec![],
+            };
+            all_events.extend(events.into_iter().map(|mut event| {
+                event.modifiers = self.modifiers;
+                event
+            }));
+        }
+        if let Some(mut event) = self.check_long_press() {
+            event.modifiers = self.modifiers;
+            all_events.push(event);
         }
 
         all_events
+            .into_iter()
+            .flat_map(|event| self.propagate(event))
+            .collect()
+    }
+
+    /// Expands an event into one delivery per element along the path from the root to its
+    /// target and back.
+    fn propagate(&self, event: InteractionEvent) -> Vec<InteractionEvent> {
+        let Some(target) = event.target else {
+            return vec![event];
+        };
+        if !event.bubbles() {
+            return vec![event];
+        }
+
+        let ancestors = self.ancestors(target);
+        let mut deliveries = Vec::with_capacity(ancestors.len() * 2 + 1);
+        for &id in ancestors.iter().rev() {
+            deliveries.push(event.delivered_to(id, EventPhase::Capture, deliveries.len()));
+        }
+        deliveries.push(event.delivered_to(target, EventPhase::Target, deliveries.len()));
+        for &id in ancestors.iter() {
+            deliveries.push(event.delivered_to(id, EventPhase::Bubble, deliveries.len()));
+        }
+        deliveries
+    }
+
+    /// The registered parents of `id`, nearest first.
+    pub fn ancestors(&self, id: ElementId) -> Rc<[ElementId]> {
+        if let Some(path) = self.ancestor_paths.borrow().get(&id) {
+            return path.clone();
+        }
+
+        let path: Rc<[ElementId]> = match self.parents.get(&id) {
+            Some(&parent) => std::iter::once(parent)
+                .chain(self.ancestors(parent).iter().copied())
+                .collect(),
+            None => Rc::new([]),
+        };
+        self.ancestor_paths.borrow_mut().insert(id, path.clone());
+        path
     }
 
     pub fn register_hitbox(&mut self, id: ElementId, layer: u32, bounds: crate::Rect) {
-        self.hitboxes
-            .insert(id, (layer, self.insertion_order, bounds));
-        self.insertion_order += 1;
+        self.register_shaped_hitbox(id, layer, bounds, HitShape::Rect, crate::Rect::NO_CLIP);
+    }
+
+    /// Registers a hitbox reacting only within `shape` and `clip`. Hitboxes on higher layers
+    /// take precedence, then those registered later.
+    pub fn register_shaped_hitbox(
+        &mut self,
+        id: ElementId,
+        layer: u32,
+        bounds: crate::Rect,
+        shape: HitShape,
+        clip: crate::Rect,
+    ) {
+        self.hitboxes.insert(Hitbox {
+            id,
+            layer,
+            order: self.hitboxes.len() as u32,
+            bounds,
+            shape,
+            clip,
+        });
+    }
+
+    /// Makes a registered hitbox reachable via keyboard navigation. Elements capturing arrow
+    /// keys (e.g. text fields moving their caret) receive them as keyboard events instead of
+    /// them moving the focus.
+    pub fn register_focusable(
+        &mut self,
+        id: ElementId,
+        tab_index: TabIndex,
+        captures_arrow_keys: bool,
+    ) {
+        self.focusables.push(Focusable {
+            id,
+            tab_index,
+            captures_arrow_keys,
+        });
+    }
+
+    /// Makes `id` the source of a drag when the mouse is pressed on it or one of its
+    /// descendants. Without a payload, the drag can't be dropped anywhere.
+    pub fn register_draggable(&mut self, id: ElementId, payload: Option<DragPayload>) {
+        self.draggables.push(Draggable {
+            id,
+            payload,
+            threshold: None,
+        });
+    }
+
+    /// Like `register_draggable`, but the drag starts once the cursor moved `threshold` instead
+    /// of the drag threshold. With 0, it starts as soon as the mouse is pressed, e.g. for scroll
+    /// bar thumbs that should follow the cursor right away.
+    pub fn register_draggable_with_threshold(
+        &mut self,
+        id: ElementId,
+        payload: Option<DragPayload>,
+        threshold: f32,
+    ) {
+        self.draggables.push(Draggable {
+            id,
+            payload,
+            threshold: Some(threshold),
+        });
+    }
+
+    /// Makes `id` accept drags carrying a payload of type `T`.
+    pub fn register_drop_target<T: Any>(&mut self, id: ElementId) {
+        self.drop_targets.push(DropTarget {
+            id,
+            accepts: TypeId::of::<T>(),
+        });
+    }
+
+    /// Two clicks count as a double click if the second one follows within `interval` and at
+    /// most `distance` away from the first.
+    pub fn set_double_click(&mut self, interval: Duration, distance: f32) {
+        self.double_click_interval = interval;
+        self.double_click_distance = distance;
+    }
+
+    pub fn set_long_press_delay(&mut self, delay: Duration) {
+        self.long_press_delay = delay;
+    }
+
+    /// Replaces the in-memory clipboard, e.g. with the one of the platform.
+    pub fn set_clipboard(&mut self, clipboard: Rc<RefCell<dyn Clipboard>>) {
+        self.clipboard = clipboard;
+    }
+
+    pub fn clipboard(&self) -> Rc<RefCell<dyn Clipboard>> {
+        self.clipboard.clone()
+    }
+
+    /// Sends all pointer events to `id`, regardless of the element under the cursor, until the
+    /// mouse button is released or the capture is released.
+    pub fn capture_pointer(&mut self, id: ElementId) {
+        self.pointer_capture = Some(id);
+    }
+
+    pub fn release_pointer_capture(&mut self) {
+        self.pointer_capture = None;
+    }
+
+    pub fn pointer_capture(&self) -> Option<ElementId> {
+        self.pointer_capture
+    }
+
+    fn check_long_press(&mut self) -> Option<InteractionEvent> {
+        let press = self.press.as_ref()?;
+        let (x, y) = self.pointer_position;
+        if press.long_pressed
+            || self.now.duration_since(press.at) < self.long_press_delay
+            || (x - press.position.0).hypot(y - press.position.1) > self.drag_threshold
+            || self.drag.as_ref().is_some_and(|drag| drag.started)
+        {
+            return None;
+        }
+
+        let (target, button) = (press.target, press.button);
+        let (local_x, local_y) = self.local_position(target, x, y);
+        self.press.as_mut().unwrap().long_pressed = true;
+        Some(InteractionEvent::new(
+            Some(target),
+            InteractionEventKind::LongPress {
+                button,
+                x,
+                y,
+                local_x,
+                local_y,
+            },
+        ))
+    }
+
+    /// The distance the cursor has to move while pressed before a drag starts.
+    pub fn set_drag_threshold(&mut self, threshold: f32) {
+        self.drag_threshold = threshold;
+    }
+
+    pub fn is_dragging(&self, id: ElementId) -> bool {
+        self.drag
+            .as_ref()
+            .is_some_and(|drag| drag.started && drag.source == id)
+    }
+
+    /// The payload of the running drag, if any.
+    pub fn drag_payload(&self) -> Option<&DragPayload> {
+        self.drag
+            .as_ref()
+            .filter(|drag| drag.started)
+            .and_then(|drag| drag.payload.as_ref())
+    }
+
+    /// True while a drag this drop target accepts is hovering it.
+    pub fn is_drop_target_hovered(&self, id: ElementId) -> bool {
+        self.drag
+            .as_ref()
+            .is_some_and(|drag| drag.drop_target == Some(id))
+    }
+
+    /// Ends the running drag without dropping it.
+    pub fn cancel_drag(&mut self) -> Vec<InteractionEvent> {
+        let mut events = Vec::new();
+        if let Some(drag) = self.drag.take()
+            && drag.started
+        {
+            if let Some(target) = drag.drop_target {
+                events.push(InteractionEvent::new(
+                    Some(target),
+                    InteractionEventKind::DragLeave,
+                ));
+            }
+            events.push(InteractionEvent::new(
+                Some(drag.source),
+                InteractionEventKind::DragEnd {
+                    x: drag.position.0,
+                    y: drag.position.1,
+                    start_x: drag.start.0,
+                    start_y: drag.start.1,
+                    drop_target: None,
+                },
+            ));
+            self.pointer_capture = None;
+        }
+        events
+    }
+
+    fn local_position(&self, id: ElementId, x: f32, y: f32) -> (f32, f32) {
+        match self.hitboxes.get(id) {
+            Some(hitbox) => (x - hitbox.bounds.position[0], y - hitbox.bounds.position[1]),
+            None => (x, y),
+        }
+    }
+
+    /// The element itself or its nearest ancestor registered as draggable.
+    fn draggable_at(&self, id: ElementId) -> Option<&Draggable> {
+        std::iter::once(id)
+            .chain(self.ancestors(id).iter().copied())
+            .find_map(|id| self.draggables.iter().find(|d| d.id == id))
+    }
+
+    /// The topmost drop target under the cursor accepting `payload`.
+    fn drop_target_at(&self, x: f32, y: f32, drag: &DragState) -> Option<ElementId> {
+        let accepts = drag.payload.as_ref()?.value_type_id();
+        self.get_hits(x, y).into_iter().find_map(|hit| {
+            std::iter::once(hit)
+                .chain(self.ancestors(hit).iter().copied())
+                .filter(|id| *id != drag.source)
+                .find(|id| {
+                    self.drop_targets
+                        .iter()
+                        .any(|t| t.id == *id && t.accepts == accepts)
+                })
+        })
+    }
+
+    fn update_drag(&mut self, x: f32, y: f32, events: &mut Vec<InteractionEvent>) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let (start_x, start_y) = drag.start;
+        if !drag.started && (x - start_x).hypot(y - start_y) < drag.threshold {
+            return;
+        }
+
+        let source = drag.source;
+        let started = drag.started;
+        let payload = drag.payload.clone();
+        let old_target = drag.drop_target;
+        let drop_target = self.drop_target_at(x, y, drag);
+
+        if !started {
+            events.push(InteractionEvent::new(
+                Some(source),
+                InteractionEventKind::DragStart {
+                    x,
+                    y,
+                    start_x,
+                    start_y,
+                },
+            ));
+            self.pointer_capture = Some(source);
+        }
+        events.push(InteractionEvent::new(
+            Some(source),
+            InteractionEventKind::DragMove {
+                x,
+                y,
+                start_x,
+                start_y,
+            },
+        ));
+
+        if drop_target != old_target {
+            if let Some(old) = old_target {
+                events.push(InteractionEvent::new(
+                    Some(old),
+                    InteractionEventKind::DragLeave,
+                ));
+            }
+            if let Some(new) = drop_target
+                && let Some(payload) = payload
+            {
+                events.push(InteractionEvent::new(
+                    Some(new),
+                    InteractionEventKind::DragEnter { payload },
+                ));
+            }
+        }
+
+        let drag = self.drag.as_mut().unwrap();
+        drag.started = true;
+        drag.position = (x, y);
+        drag.drop_target = drop_target;
+    }
+
+    /// Drops a running drag at the cursor and ends it. Returns false if no drag was running.
+    fn finish_drag(&mut self, x: f32, y: f32, events: &mut Vec<InteractionEvent>) -> bool {
+        let Some(drag) = self.drag.take() else {
+            return false;
+        };
+        if !drag.started {
+            return false;
+        }
+
+        if let Some(target) = drag.drop_target
+            && let Some(payload) = drag.payload
+        {
+            let (local_x, local_y) = self.local_position(target, x, y);
+            events.push(InteractionEvent::new(
+                Some(target),
+                InteractionEventKind::Drop {
+                    payload,
+                    x,
+                    y,
+                    local_x,
+                    local_y,
+                },
+            ));
+        }
+        events.push(InteractionEvent::new(
+            Some(drag.source),
+            InteractionEventKind::DragEnd {
+                x,
+                y,
+                start_x: drag.start.0,
+                start_y: drag.start.1,
+                drop_target: drag.drop_target,
+            },
+        ));
+        self.pointer_capture = None;
+        true
+    }
+
+    pub(crate) fn registration_mark(&self) -> RegistrationMark {
+        RegistrationMark {
+            hitboxes: self.hitboxes.len(),
+            focusables: self.focusables.len(),
+            draggables: self.draggables.len(),
+            drop_targets: self.drop_targets.len(),
+        }
+    }
+
+    /// Everything registered after `mark` was taken in the current frame.
+    pub(crate) fn registrations_since(&self, mark: RegistrationMark) -> Registrations {
+        let hitboxes = self.hitboxes.since(mark.hitboxes).to_vec();
+        let parents = hitboxes
+            .iter()
+            .filter_map(|hitbox| Some((hitbox.id, *self.parents.get(&hitbox.id)?)))
+            .collect();
+        Registrations {
+            hitboxes,
+            parents,
+            focusables: self.focusables[mark.focusables..].to_vec(),
+            draggables: self.draggables[mark.draggables..].to_vec(),
+            drop_targets: self.drop_targets[mark.drop_targets..].to_vec(),
+        }
+    }
+
+    /// Registers everything in `registrations` again, on top of the hitboxes registered so far.
+    pub(crate) fn replay_registrations(&mut self, registrations: &Registrations) {
+        for hitbox in &registrations.hitboxes {
+            self.register_shaped_hitbox(
+                hitbox.id,
+                hitbox.layer,
+                hitbox.bounds,
+                hitbox.shape,
+                hitbox.clip,
+            );
+        }
+        self.parents.extend(registrations.parents.iter().copied());
+        self.ancestor_paths.get_mut().clear();
+        self.focusables
+            .extend(registrations.focusables.iter().cloned());
+        self.draggables
+            .extend(registrations.draggables.iter().cloned());
+        self.drop_targets
+            .extend(registrations.drop_targets.iter().cloned());
+    }
+
+    /// Whether `id` is hovered, focused, pressed, dragged or hovered by a drag, which elements
+    /// may be drawn differently for.
+    pub(crate) fn interaction_of(&self, id: ElementId) -> [bool; 5] {
+        [
+            self.is_hovered(id),
+            self.is_focused(id),
+            self.is_pressed(id),
+            self.is_dragging(id),
+            self.is_drop_target_hovered(id),
+        ]
     }
 
     pub fn clear_hitboxes(&mut self) {
         self.hitboxes.clear();
+        self.focusables.clear();
+        self.draggables.clear();
+        self.drop_targets.clear();
         self.parents.clear();
-        self.insertion_order = 0;
+        self.ancestor_paths.get_mut().clear();
     }
 
     pub fn set_parent(&mut self, child: ElementId, parent: ElementId) {
         self.parents.insert(child, parent);
+        self.ancestor_paths.get_mut().clear();
     }
 
     pub fn is_hovered(&self, id: ElementId) -> bool {
         self.hovered_elements.contains(&id)
     }
 
+    /// True while a mouse button pressed on `id` is held down.
+    pub fn is_pressed(&self, id: ElementId) -> bool {
+        self.press.as_ref().is_some_and(|press| press.target == id)
+    }
+
     pub fn focused_element(&self) -> Option<ElementId> {
         self.focused_element
     }
@@ -148,16 +945,143 @@ impl EventManager {
         self.focused_element = id;
     }
 
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    fn change_focus(&mut self, id: Option<ElementId>, events: &mut Vec<InteractionEvent>) {
+        if self.focused_element == id {
+            return;
+        }
+        if let Some(old_id) = self.focused_element {
+            events.push(InteractionEvent::new(
+                Some(old_id),
+                InteractionEventKind::FocusLost,
+            ));
+        }
+        self.focused_element = id;
+        if let Some(id) = id {
+            events.push(InteractionEvent::new(
+                Some(id),
+                InteractionEventKind::FocusGained,
+            ));
+        }
+    }
+
+    /// Elements reachable via Tab, explicit tab indices first.
+    fn tab_order(&self) -> Vec<ElementId> {
+        let mut explicit: Vec<(u32, ElementId)> = self
+            .focusables
+            .iter()
+            .filter_map(|f| match f.tab_index {
+                TabIndex::Explicit(index) => Some((index, f.id)),
+                _ => None,
+            })
+            .collect();
+        // stable, so equal indices stay in registration order
+        explicit.sort_by_key(|(index, _)| *index);
+
+        explicit
+            .into_iter()
+            .map(|(_, id)| id)
+            .chain(
+                self.focusables
+                    .iter()
+                    .filter(|f| f.tab_index == TabIndex::Auto)
+                    .map(|f| f.id),
+            )
+            .collect()
+    }
+
+    /// Moves the focus to the next element in the tab order, wrapping around at the end.
+    pub fn focus_next(&mut self) -> Vec<InteractionEvent> {
+        self.focus_step(true)
+    }
+
+    /// Moves the focus to the previous element in the tab order, wrapping around at the start.
+    pub fn focus_previous(&mut self) -> Vec<InteractionEvent> {
+        self.focus_step(false)
+    }
+
+    fn focus_step(&mut self, forward: bool) -> Vec<InteractionEvent> {
+        let order = self.tab_order();
+        let mut events = Vec::new();
+        if order.is_empty() {
+            return events;
+        }
+
+        let current = self
+            .focused_element
+            .and_then(|id| order.iter().position(|o| *o == id));
+        let next = match (current, forward) {
+            (Some(idx), true) => (idx + 1) % order.len(),
+            (Some(idx), false) => (idx + order.len() - 1) % order.len(),
+            (None, true) => 0,
+            (None, false) => order.len() - 1,
+        };
+
+        self.change_focus(Some(order[next]), &mut events);
+        events
+    }
+
+    /// Moves the focus to the closest focusable element in the given direction of the focused
+    /// one, preferring elements that are aligned with it.
+    pub fn focus_in_direction(&mut self, direction: FocusDirection) -> Vec<InteractionEvent> {
+        let mut events = Vec::new();
+        let Some(current) = self.focused_element else {
+            return events;
+        };
+        let Some(from) = self.hitboxes.get(current).map(|hitbox| hitbox.bounds) else {
+            return events;
+        };
+
+        let center = |rect: &crate::Rect| {
+            (
+                rect.position[0] + rect.size[0] / 2.0,
+                rect.position[1] + rect.size[1] / 2.0,
+            )
+        };
+        let (from_x, from_y) = center(&from);
+
+        let target = self
+            .focusables
+            .iter()
+            .filter(|f| f.id != current && f.tab_index != TabIndex::None)
+            .filter_map(|f| {
+                let (x, y) = center(&self.hitboxes.get(f.id)?.bounds);
+                let (along, across) = match direction {
+                    FocusDirection::Right => (x - from_x, y - from_y),
+                    FocusDirection::Left => (from_x - x, y - from_y),
+                    FocusDirection::Down => (y - from_y, x - from_x),
+                    FocusDirection::Up => (from_y - y, x - from_x),
+                };
+                (along > 0.0).then_some((along + across.abs() * 2.0, f.id))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, id)| id);
+
+        if let Some(target) = target {
+            self.change_focus(Some(target), &mut events);
+        }
+        events
+    }
+
+    fn arrow_keys_navigate(&self) -> bool {
+        self.focused_element.is_some_and(|id| {
+            self.focusables
+                .iter()
+                .any(|f| f.id == id && !f.captures_arrow_keys)
+        })
+    }
+
     pub fn handle_mouse_move(&mut self, x: f32, y: f32) -> Vec<InteractionEvent> {
+        self.pointer_position = (x, y);
         let top_hit = self.get_top_hit(x, y);
 
         let mut new_hovered = HashSet::new();
-        if let Some(mut current) = top_hit {
-            new_hovered.insert(current);
-            while let Some(parent) = self.parents.get(&current) {
-                new_hovered.insert(*parent);
-                current = *parent;
-            }
+        if let Some(id) = top_hit {
+            new_hovered.insert(id);
+            new_hovered.extend(self.ancestors(id).iter().copied());
         }
 
         let mut events = Vec::new();
@@ -165,71 +1089,57 @@ impl EventManager {
         // Elements that lost hover
         for id in &self.hovered_elements {
             if !new_hovered.contains(id) {
-                events.push(InteractionEvent {
-                    target: Some(*id),
-                    kind: InteractionEventKind::MouseLeave,
-                });
+                events.push(InteractionEvent::new(
+                    Some(*id),
+                    InteractionEventKind::MouseLeave,
+                ));
             }
         }
 
         // Elements that gained hover
         for id in &new_hovered {
             if !self.hovered_elements.contains(id) {
-                events.push(InteractionEvent {
-                    target: Some(*id),
-                    kind: InteractionEventKind::MouseEnter,
-                });
+                events.push(InteractionEvent::new(
+                    Some(*id),
+                    InteractionEventKind::MouseEnter,
+                ));
             }
         }
 
-        // Always push MouseMove to the top hit
-        if let Some(id) = top_hit {
-            let bounds = self.hitboxes.get(&id).unwrap().2;
-            events.push(InteractionEvent {
-                target: Some(id),
-                kind: InteractionEventKind::MouseMove {
-                    local_x: x - bounds.position[0],
-                    local_y: y - bounds.position[1],
-                    x,
-                    y,
-                },
-            });
-        } else {
-            events.push(InteractionEvent {
-                target: None,
-                kind: InteractionEventKind::MouseMove {
-                    local_x: x,
-                    local_y: y,
-                    x,
-                    y,
-                },
-            })
-        }
+        self.update_drag(x, y, &mut events);
+
+        // MouseMove goes to the element capturing the pointer or the top hit
+        let target = self.pointer_capture.or(top_hit);
+        let (local_x, local_y) = match target {
+            Some(id) => self.local_position(id, x, y),
+            None => (x, y),
+        };
+        events.push(InteractionEvent::new(
+            target,
+            InteractionEventKind::MouseMove {
+                local_x,
+                local_y,
+                x,
+                y,
+            },
+        ));
 
         self.hovered_elements = new_hovered;
 
         events
     }
 
-    fn get_top_hit(&self, x: f32, y: f32) -> Option<ElementId> {
-        let mut hits = self
-            .hitboxes
-            .iter()
-            .filter(|(_, (_, _, rect))| rect.contains([x, y]))
-            .map(|(id, (layer, order, _))| (*id, *layer, *order))
-            .collect::<Vec<_>>();
-
-        // Sort by layer (highest first, then newest)
-        hits.sort_by(|(_, layer1, order1), (_, layer2, order2)| {
-            let cmp = layer2.cmp(&layer1);
-            if cmp == std::cmp::Ordering::Equal {
-                order2.cmp(&order1)
-            } else {
-                cmp
-            }
-        });
+    pub(crate) fn get_top_hit(&self, x: f32, y: f32) -> Option<ElementId> {
+        self.get_hits(x, y).first().copied()
+    }
 
-        hits.first().map(|(id, _, _)| *id)
+    /// All elements under the cursor, topmost first.
+    fn get_hits(&self, x: f32, y: f32) -> Vec<ElementId> {
+        self.hitboxes
+            .hits_at([x, y])
+            .into_iter()
+            .map(|hitbox| hitbox.id)
+            .collect()
     }
 
     pub fn handle_mouse_down(
@@ -238,65 +1148,132 @@ impl EventManager {
         y: f32,
         button: MouseButton,
     ) -> Vec<InteractionEvent> {
+        self.pointer_position = (x, y);
         let top_hit = self.get_top_hit(x, y);
         let mut events = Vec::new();
 
-        if let Some(id) = top_hit {
-            let bounds = self.hitboxes.get(&id).unwrap().2;
-            events.push(InteractionEvent {
-                target: Some(id),
-                kind: InteractionEventKind::MouseDown {
-                    button,
-                    local_x: x - bounds.position[0],
-                    local_y: y - bounds.position[1],
-                    x,
-                    y,
-                },
+        self.press = top_hit.map(|id| Press {
+            target: id,
+            button,
+            position: (x, y),
+            at: self.now,
+            long_pressed: false,
+            clicks: 0,
+        });
+        if let Some(id) = top_hit
+            && button == MouseButton::Left
+        {
+            self.drag = self.draggable_at(id).map(|draggable| DragState {
+                source: draggable.id,
+                payload: draggable.payload.clone(),
+                threshold: draggable.threshold.unwrap_or(self.drag_threshold),
+                start: (x, y),
+                position: (x, y),
+                started: false,
+                drop_target: None,
             });
-            if self.focused_element != Some(id) {
-                if let Some(old_id) = self.focused_element {
-                    events.push(InteractionEvent {
-                        target: Some(old_id),
-                        kind: InteractionEventKind::FocusLost,
-                    });
-                }
-                self.focused_element = Some(id);
-                events.push(InteractionEvent {
-                    target: Some(id),
-                    kind: InteractionEventKind::FocusGained,
-                });
-            }
-        } else {
-            if let Some(old_id) = self.focused_element {
-                events.push(InteractionEvent {
-                    target: Some(old_id),
-                    kind: InteractionEventKind::FocusLost,
-                });
-            }
-            self.focused_element = None;
-
-            events.push(InteractionEvent {
-                target: None,
-                kind: InteractionEventKind::MouseDown {
-                    button,
-                    local_x: x,
-                    local_y: y,
-                    x,
-                    y,
-                },
-            })
         }
 
+        let target = self.pointer_capture.or(top_hit);
+        let (local_x, local_y) = match target {
+            Some(id) => self.local_position(id, x, y),
+            None => (x, y),
+        };
+        events.push(InteractionEvent::new(
+            target,
+            InteractionEventKind::MouseDown {
+                button,
+                local_x,
+                local_y,
+                x,
+                y,
+            },
+        ));
+        self.change_focus(top_hit, &mut events);
+        // starts drags without a threshold
+        self.update_drag(x, y, &mut events);
+
         events
     }
 
+    /// Wheel events go to the element under the cursor, independent of the focus. Without a
+    /// horizontal wheel, holding Shift scrolls horizontally.
+    pub fn handle_mouse_scroll(
+        &mut self,
+        x: f32,
+        y: f32,
+        delta: ScrollDelta,
+    ) -> Vec<InteractionEvent> {
+        let delta = if self.modifiers.shift && delta.to_pixels().0 == 0.0 {
+            delta.swap_axes()
+        } else {
+            delta
+        };
+
+        let top_hit = self.get_top_hit(x, y);
+        let (local_x, local_y) = match top_hit {
+            Some(id) => self.local_position(id, x, y),
+            None => (x, y),
+        };
+
+        vec![InteractionEvent::new(
+            top_hit,
+            InteractionEventKind::MouseScroll {
+                delta,
+                x,
+                y,
+                local_x,
+                local_y,
+            },
+        )]
+    }
+
+    /// Tab / Shift+Tab cycle through the tab order and arrow keys move the focus spatially,
+    /// unless the focused element captures them. All other keys are sent to the focused element.
     pub fn handle_key(&mut self, event: &crate::KeyEvent) -> Vec<InteractionEvent> {
-        let mut events = Vec::new();
-        events.push(InteractionEvent {
-            target: self.focused_element,
-            kind: InteractionEventKind::Keyboard(event.clone()),
-        });
-        events
+        if event.state.is_pressed()
+            && let Some(events) = self.handle_navigation_key(&event.logical_key)
+        {
+            return events;
+        }
+
+        vec![InteractionEvent::new(
+            self.focused_element,
+            InteractionEventKind::Keyboard(event.clone()),
+        )]
+    }
+
+    /// Handles a pressed key that cancels a drag or moves the focus, or returns `None` if it
+    /// goes to the focused element.
+    fn handle_navigation_key(&mut self, key: &Key) -> Option<Vec<InteractionEvent>> {
+        if *key == Key::Named(NamedKey::Escape)
+            && self.drag.as_ref().is_some_and(|drag| drag.started)
+        {
+            return Some(self.cancel_drag());
+        }
+
+        let direction = match key {
+            Key::Named(NamedKey::Tab) if self.modifiers.shift => {
+                return Some(self.focus_previous());
+            }
+            Key::Named(NamedKey::Tab) => return Some(self.focus_next()),
+            Key::Named(NamedKey::ArrowUp) => FocusDirection::Up,
+            Key::Named(NamedKey::ArrowDown) => FocusDirection::Down,
+            Key::Named(NamedKey::ArrowLeft) => FocusDirection::Left,
+            Key::Named(NamedKey::ArrowRight) => FocusDirection::Right,
+            _ => return None,
+        };
+
+        self.arrow_keys_navigate()
+            .then(|| self.focus_in_direction(direction))
+    }
+
+    /// Input method events are sent to the focused element, like keys.
+    pub fn handle_ime(&mut self, event: crate::Ime) -> Vec<InteractionEvent> {
+        vec![InteractionEvent::new(
+            self.focused_element,
+            InteractionEventKind::Ime(event),
+        )]
     }
 
     pub fn handle_mouse_up(
@@ -305,45 +1282,94 @@ impl EventManager {
         y: f32,
         button: MouseButton,
     ) -> Vec<InteractionEvent> {
-        let top_hit = self.get_top_hit(x, y);
+        self.pointer_position = (x, y);
         let mut events = Vec::new();
+        let captured = self.pointer_capture;
+        let dragged = matches!(button, MouseButton::Left) && self.finish_drag(x, y, &mut events);
+        if !dragged {
+            self.drag = None;
+        }
 
-        if let Some(id) = top_hit {
-            let bounds = self.hitboxes.get(&id).unwrap().2;
-            events.push(InteractionEvent {
-                target: Some(id),
-                kind: InteractionEventKind::MouseUp {
+        let top_hit = self.get_top_hit(x, y);
+        let target = captured.or(top_hit);
+        let (local_x, local_y) = match target {
+            Some(id) => self.local_position(id, x, y),
+            None => (x, y),
+        };
+        events.push(InteractionEvent::new(
+            target,
+            InteractionEventKind::MouseUp {
+                button,
+                local_x,
+                local_y,
+                x,
+                y,
+            },
+        ));
+
+        self.pointer_capture = None;
+
+        // the end of a drag or a long press is not a click
+        let press = self.press.take();
+        if let Some(id) = top_hit
+            && !dragged
+            && let Some(press) = press
+            && press.target == id
+            && press.button == button
+            && !press.long_pressed
+        {
+            let (local_x, local_y) = self.local_position(id, x, y);
+            events.push(InteractionEvent::new(
+                Some(id),
+                InteractionEventKind::Click {
                     button,
-                    local_x: x - bounds.position[0],
-                    local_y: y - bounds.position[1],
+                    local_x,
+                    local_y,
                     x,
                     y,
                 },
-            });
+            ));
 
-            if self.focused_element == Some(id) {
-                events.push(InteractionEvent {
-                    target: Some(id),
-                    kind: InteractionEventKind::Click {
-                        button,
-                        local_x: x - bounds.position[0],
-                        local_y: y - bounds.position[1],
-                        x,
-                        y,
-                    },
-                });
-            }
-        } else {
-            events.push(InteractionEvent {
-                target: None,
-                kind: InteractionEventKind::MouseUp {
+            let clicks = match self.last_click.take() {
+                Some(last)
+                    if last.target == id
+                        && last.button == button
+                        && self.now.duration_since(last.at) <= self.double_click_interval
+                        && (x - last.position.0).hypot(y - last.position.1)
+                            <= self.double_click_distance =>
+                {
+                    last.clicks + 1
+                }
+                _ => 1,
+            };
+            let kind = match clicks {
+                2 => Some(InteractionEventKind::DoubleClick {
                     button,
-                    local_x: x,
-                    local_y: y,
+                    local_x,
+                    local_y,
                     x,
                     y,
-                },
-            })
+                }),
+                3 => Some(InteractionEventKind::TripleClick {
+                    button,
+                    local_x,
+                    local_y,
+                    x,
+                    y,
+                }),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                events.push(InteractionEvent::new(Some(id), kind));
+            }
+            if clicks < 3 {
+                self.last_click = Some(Press {
+                    at: self.now,
+                    position: (x, y),
+                    clicks,
+                    ..press
+                });
+            }
         }
 
         events
@@ -353,13 +1379,17 @@ impl EventManager {
 pub struct UiContext {
     pub event_manager: Rc<RefCell<EventManager>>,
     pub parent_id: Option<ElementId>,
-    /// Since the layout cache is used to determine if one should reflow an element but is not
-    /// sensitive to parameter changes, we MUST supply a fresh cache for each render cycle
+    /// The layout cache is not sensitive to parameter changes by itself. Either supply a fresh
+    /// cache for each render cycle, or keep it around and call [LayoutCache::next_frame] and
+    /// [Element::update](crate::Element::update) on the root before laying it out
     pub layout_cache: Box<dyn LayoutCache>,
     pub interactive: bool,
     pub clip_rect: crate::Rect,
     /// Now should never change within a render cycle (i.e. between layout and render calls)
     pub now: web_time::Instant,
+    /// Read by params closures to style elements, see [crate::ThemeSwitcher] to change it at
+    /// runtime. Shared, so a theme sampled once per frame can be passed to every context.
+    pub theme: Rc<crate::Theme>,
 }
 
 impl UiContext {
@@ -375,17 +1405,37 @@ impl UiContext {
             interactive: true,
             clip_rect: crate::Rect::NO_CLIP,
             now,
+            theme: Rc::new(crate::Theme::default()),
         }
     }
 
     pub fn with_hitbox_hierarchy<F>(&mut self, id: ElementId, layer: u32, bounds: crate::Rect, f: F)
     where
         F: FnOnce(&mut Self),
+    {
+        self.with_shaped_hitbox_hierarchy(id, layer, bounds, HitShape::Rect, f);
+    }
+
+    /// Like `with_hitbox_hierarchy`, but only `shape` within the current clip rect reacts to
+    /// the pointer.
+    pub fn with_shaped_hitbox_hierarchy<F>(
+        &mut self,
+        id: ElementId,
+        layer: u32,
+        bounds: crate::Rect,
+        shape: HitShape,
+        f: F,
+    ) where
+        F: FnOnce(&mut Self),
     {
         if self.interactive {
-            self.event_manager
-                .borrow_mut()
-                .register_hitbox(id, layer, bounds);
+            self.event_manager.borrow_mut().register_shaped_hitbox(
+                id,
+                layer,
+                bounds,
+                shape,
+                self.clip_rect,
+            );
             if let Some(parent) = self.parent_id {
                 self.event_manager.borrow_mut().set_parent(id, parent);
             }
@@ -399,6 +1449,54 @@ impl UiContext {
         self.parent_id = old_parent;
     }
 
+    /// Registers `id` as a drag source, if the context is interactive.
+    pub fn register_draggable(&mut self, id: ElementId, payload: Option<DragPayload>) {
+        if self.interactive {
+            self.event_manager
+                .borrow_mut()
+                .register_draggable(id, payload);
+        }
+    }
+
+    /// Registers `id` as a drag source with its own drag threshold, if the context is
+    /// interactive.
+    pub fn register_draggable_with_threshold(
+        &mut self,
+        id: ElementId,
+        payload: Option<DragPayload>,
+        threshold: f32,
+    ) {
+        if self.interactive {
+            self.event_manager
+                .borrow_mut()
+                .register_draggable_with_threshold(id, payload, threshold);
+        }
+    }
+
+    /// Registers `id` as a drop target for payloads of type `T`, if the context is interactive.
+    pub fn register_drop_target<T: Any>(&mut self, id: ElementId) {
+        if self.interactive {
+            self.event_manager
+                .borrow_mut()
+                .register_drop_target::<T>(id);
+        }
+    }
+
+    /// Registers `id` for keyboard navigation, if the context is interactive. Must be called
+    /// after the element registered its hitbox.
+    pub fn register_focusable(
+        &mut self,
+        id: ElementId,
+        tab_index: TabIndex,
+        captures_arrow_keys: bool,
+    ) {
+        if self.interactive && tab_index != TabIndex::None {
+            self.event_manager
+                .borrow_mut()
+                .register_focusable(id, tab_index, captures_arrow_keys);
+        }
+    }
+
     pub fn with_interactivity<F>(&mut self, interactive: bool, f: F)
     where
         F: FnOnce(&mut Self),
@@ -432,3 +1530,335 @@ impl UiContext {
         self.clip_rect = old_clip;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn focus_gained(events: &[InteractionEvent]) -> Option<ElementId> {
+        events
+            .iter()
+            .find(|event| matches!(event.kind, InteractionEventKind::FocusGained))
+            .and_then(|event| event.target)
+    }
+
+    fn set_modifiers(event_manager: &mut EventManager, modifiers: Modifiers) {
+        event_manager.queue_event(InteractionEventKind::ModifiersChanged(modifiers));
+        assert!(event_manager.process_events(Instant::now()).is_empty());
+        assert_eq!(event_manager.modifiers(), modifiers);
+    }
+
+    // element 3 inside 2 inside 1, each covering the same area
+    fn nested_hitboxes() -> EventManager {
+        let mut event_manager = EventManager::new();
+        let bounds = crate::Rect {
+            position: [0.0, 0.0],
+            size: [100.0, 100.0],
+        };
+        for id in 1..=3 {
+            event_manager.register_hitbox(ElementId(id), 0, bounds);
+        }
+        event_manager.set_parent(ElementId(3), ElementId(2));
+        event_manager.set_parent(ElementId(2), ElementId(1));
+        event_manager
+    }
+
+    fn mouse_down_deliveries(event_manager: &mut EventManager) -> Vec<InteractionEvent> {
+        event_manager.queue_event(InteractionEventKind::MouseDown {
+            button: MouseButton::Left,
+            x: 50.0,
+            y: 50.0,
+            local_x: 50.0,
+            local_y: 50.0,
+        });
+        event_manager
+            .process_events(Instant::now())
+            .into_iter()
+            .filter(|event| matches!(event.kind, InteractionEventKind::MouseDown { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn capture_and_bubble() {
+        let mut event_manager = nested_hitboxes();
+        assert_eq!(
+            &*event_manager.ancestors(ElementId(3)),
+            &[ElementId(2), ElementId(1)]
+        );
+
+        let deliveries: Vec<(u32, EventPhase)> = mouse_down_deliveries(&mut event_manager)
+            .iter()
+            .map(|event| {
+                assert_eq!(event.target, Some(ElementId(3)));
+                (event.current_target.unwrap().0, event.phase)
+            })
+            .collect();
+        assert_eq!(
+            deliveries,
+            vec![
+                (1, EventPhase::Capture),
+                (2, EventPhase::Capture),
+                (3, EventPhase::Target),
+                (2, EventPhase::Bubble),
+                (1, EventPhase::Bubble),
+            ]
+        );
+
+        let event = &mouse_down_deliveries(&mut event_manager)[3];
+        assert!(event.reaches(ElementId(2)));
+        assert!(!event.is_capturing(ElementId(2)));
+
+        // the paths follow the parents of the next frame
+        event_manager.clear_hitboxes();
+        event_manager.set_parent(ElementId(3), ElementId(1));
+        assert_eq!(&*event_manager.ancestors(ElementId(3)), &[ElementId(1)]);
+    }
+
+    #[test]
+    fn stop_propagation() {
+        let mut event_manager = nested_hitboxes();
+        let deliveries = mouse_down_deliveries(&mut event_manager);
+
+        // stopped while capturing in 2, so the target and the bubble phase don't see it
+        let stopped: Vec<bool> = deliveries
+            .iter()
+            .map(|event| {
+                let stopped = event.is_propagation_stopped();
+                if event.is_capturing(ElementId(2)) {
+                    event.stop_propagation();
+                }
+                stopped
+            })
+            .collect();
+        assert_eq!(stopped, vec![false, false, true, true, true]);
+
+        // stopping again later doesn't move the point it was stopped at
+        deliveries[4].stop_propagation();
+        assert!(!deliveries[1].is_propagation_stopped());
+
+        // every event propagates on its own
+        let next = mouse_down_deliveries(&mut event_manager);
+        assert!(next.iter().all(|event| !event.is_propagation_stopped()));
+    }
+
+    #[test]
+    fn tab_order() {
+        let mut event_manager = EventManager::new();
+        let tab = Key::Named(NamedKey::Tab);
+        event_manager.register_focusable(ElementId(1), TabIndex::Auto, false);
+        event_manager.register_focusable(ElementId(2), TabIndex::Explicit(1), false);
+        event_manager.register_focusable(ElementId(3), TabIndex::None, false);
+        event_manager.register_focusable(ElementId(4), TabIndex::Auto, false);
+        event_manager.register_focusable(ElementId(5), TabIndex::Explicit(0), false);
+
+        // explicit indices first, then the others in registration order, wrapping around
+        let mut order = Vec::new();
+        for _ in 0..5 {
+            let events = event_manager.handle_navigation_key(&tab).unwrap();
+            order.push(focus_gained(&events).unwrap().0);
+        }
+        assert_eq!(order, vec![5, 2, 1, 4, 5]);
+
+        set_modifiers(
+            &mut event_manager,
+            Modifiers {
+                shift: true,
+                ..Modifiers::default()
+            },
+        );
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            let events = event_manager.handle_navigation_key(&tab).unwrap();
+            order.push(focus_gained(&events).unwrap().0);
+        }
+        assert_eq!(order, vec![4, 1, 2]);
+
+        set_modifiers(&mut event_manager, Modifiers::default());
+        let events = event_manager.handle_navigation_key(&tab).unwrap();
+        assert_eq!(focus_gained(&events), Some(ElementId(1)));
+        assert!(events.iter().any(
+            |event| matches!(event.kind, InteractionEventKind::FocusLost)
+                && event.target == Some(ElementId(2))
+        ));
+    }
+
+    #[test]
+    fn shift_tab_without_focus_starts_at_the_end() {
+        let mut event_manager = EventManager::new();
+        event_manager.register_focusable(ElementId(1), TabIndex::Auto, false);
+        event_manager.register_focusable(ElementId(2), TabIndex::Auto, false);
+        set_modifiers(
+            &mut event_manager,
+            Modifiers {
+                shift: true,
+                ..Modifiers::default()
+            },
+        );
+
+        let events = event_manager
+            .handle_navigation_key(&Key::Named(NamedKey::Tab))
+            .unwrap();
+        assert_eq!(focus_gained(&events), Some(ElementId(2)));
+        // other keys go to the focused element
+        assert!(
+            event_manager
+                .handle_navigation_key(&Key::Named(NamedKey::Enter))
+                .is_none()
+        );
+    }
+
+    // a single 100x100 element, to send pointer input to at given times
+    fn single_hitbox() -> EventManager {
+        let mut event_manager = EventManager::new();
+        event_manager.register_hitbox(
+            ElementId(1),
+            0,
+            crate::Rect {
+                position: [0.0, 0.0],
+                size: [100.0, 100.0],
+            },
+        );
+        event_manager
+    }
+
+    fn pointer_input(
+        event_manager: &mut EventManager,
+        input: &[InteractionEventKind],
+        now: Instant,
+    ) -> Vec<InteractionEventKind> {
+        for kind in input {
+            event_manager.queue_event(kind.clone());
+        }
+        event_manager
+            .process_events(now)
+            .into_iter()
+            .map(|event| event.kind)
+            .collect()
+    }
+
+    fn mouse_down(x: f32, y: f32) -> InteractionEventKind {
+        InteractionEventKind::MouseDown {
+            button: MouseButton::Left,
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        }
+    }
+
+    fn mouse_up(x: f32, y: f32) -> InteractionEventKind {
+        InteractionEventKind::MouseUp {
+            button: MouseButton::Left,
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        }
+    }
+
+    fn mouse_move(x: f32, y: f32) -> InteractionEventKind {
+        InteractionEventKind::MouseMove {
+            x,
+            y,
+            local_x: x,
+            local_y: y,
+        }
+    }
+
+    fn count(kinds: &[InteractionEventKind], f: fn(&InteractionEventKind) -> bool) -> usize {
+        kinds.iter().filter(|kind| f(kind)).count()
+    }
+
+    #[test]
+    fn double_click_window() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let is_double =
+            |kind: &InteractionEventKind| matches!(kind, InteractionEventKind::DoubleClick { .. });
+        let is_triple =
+            |kind: &InteractionEventKind| matches!(kind, InteractionEventKind::TripleClick { .. });
+        let click = [mouse_down(10.0, 10.0), mouse_up(10.0, 10.0)];
+
+        // within the 500ms interval
+        let mut event_manager = single_hitbox();
+        pointer_input(&mut event_manager, &click, ms(0));
+        let second = pointer_input(&mut event_manager, &click, ms(500));
+        assert_eq!(count(&second, is_double), 1);
+        let third = pointer_input(&mut event_manager, &click, ms(900));
+        assert_eq!(count(&third, is_triple), 1);
+        // a fourth click starts over
+        let fourth = pointer_input(&mut event_manager, &click, ms(1000));
+        assert_eq!(count(&fourth, is_double) + count(&fourth, is_triple), 0);
+
+        // too late
+        let mut event_manager = single_hitbox();
+        pointer_input(&mut event_manager, &click, ms(0));
+        let late = pointer_input(&mut event_manager, &click, ms(501));
+        assert_eq!(count(&late, is_double), 0);
+        // but the late click starts a new double click
+        let second = pointer_input(&mut event_manager, &click, ms(700));
+        assert_eq!(count(&second, is_double), 1);
+
+        // too far away
+        let mut event_manager = single_hitbox();
+        pointer_input(&mut event_manager, &click, ms(0));
+        let far = pointer_input(
+            &mut event_manager,
+            &[mouse_down(15.0, 10.0), mouse_up(15.0, 10.0)],
+            ms(100),
+        );
+        assert_eq!(count(&far, is_double), 0);
+    }
+
+    #[test]
+    fn long_press_threshold() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let is_long_press =
+            |kind: &InteractionEventKind| matches!(kind, InteractionEventKind::LongPress { .. });
+        let is_click =
+            |kind: &InteractionEventKind| matches!(kind, InteractionEventKind::Click { .. });
+
+        let mut event_manager = single_hitbox();
+        pointer_input(&mut event_manager, &[mouse_down(10.0, 10.0)], ms(0));
+        let early = pointer_input(&mut event_manager, &[], ms(599));
+        assert_eq!(count(&early, is_long_press), 0);
+        let due = pointer_input(&mut event_manager, &[], ms(600));
+        assert_eq!(count(&due, is_long_press), 1);
+        // only once per press
+        let later = pointer_input(&mut event_manager, &[], ms(2000));
+        assert_eq!(count(&later, is_long_press), 0);
+        // and releasing it is not a click
+        let up = pointer_input(&mut event_manager, &[mouse_up(10.0, 10.0)], ms(2100));
+        assert_eq!(count(&up, is_click), 0);
+
+        // moving within the drag threshold still counts as holding still
+        let mut event_manager = single_hitbox();
+        pointer_input(&mut event_manager, &[mouse_down(10.0, 10.0)], ms(0));
+        pointer_input(&mut event_manager, &[mouse_move(12.0, 12.0)], ms(100));
+        let due = pointer_input(&mut event_manager, &[], ms(600));
+        assert_eq!(count(&due, is_long_press), 1);
+    }
+
+    #[test]
+    fn drag_cancels_long_press() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+        let is_long_press =
+            |kind: &InteractionEventKind| matches!(kind, InteractionEventKind::LongPress { .. });
+
+        let mut event_manager = single_hitbox();
+        event_manager.register_draggable(ElementId(1), None);
+        pointer_input(&mut event_manager, &[mouse_down(10.0, 10.0)], ms(0));
+        let moved = pointer_input(&mut event_manager, &[mouse_move(30.0, 10.0)], ms(100));
+        assert!(
+            moved
+                .iter()
+                .any(|kind| matches!(kind, InteractionEventKind::DragStart { .. }))
+        );
+        // returning to the press position doesn't revive it
+        pointer_input(&mut event_manager, &[mouse_move(10.0, 10.0)], ms(200));
+        let due = pointer_input(&mut event_manager, &[], ms(700));
+        assert_eq!(count(&due, is_long_press), 0);
+    }
+}
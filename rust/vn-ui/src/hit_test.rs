@@ -0,0 +1,245 @@
+use crate::{ElementId, Rect};
+use std::collections::HashMap;
+
+/// The part of a hitbox's bounds that reacts to the pointer.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HitShape {
+    #[default]
+    Rect,
+    /// Excludes the rounded off corners, matching a box drawn with the same `border_radius`.
+    RoundedRect { corner_radius: f32 },
+}
+
+impl HitShape {
+    fn contains(&self, bounds: &Rect, point: [f32; 2]) -> bool {
+        if !bounds.contains(point) {
+            return false;
+        }
+
+        match self {
+            HitShape::Rect => true,
+            HitShape::RoundedRect { corner_radius } => {
+                let half_width = bounds.size[0] / 2.0;
+                let half_height = bounds.size[1] / 2.0;
+                let radius = corner_radius.min(half_width).min(half_height).max(0.0);
+
+                // distance from the inner rectangle the corner circles are centered on
+                let dx = (point[0] - bounds.position[0] - half_width).abs() - (half_width - radius);
+                let dy =
+                    (point[1] - bounds.position[1] - half_height).abs() - (half_height - radius);
+                dx <= 0.0 || dy <= 0.0 || dx * dx + dy * dy <= radius * radius
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Hitbox {
+    pub id: ElementId,
+    pub layer: u32,
+    pub order: u32,
+    pub bounds: Rect,
+    pub shape: HitShape,
+    /// Parts outside the clip rect it was drawn with can't be hit.
+    pub clip: Rect,
+}
+
+impl Hitbox {
+    fn contains(&self, point: [f32; 2]) -> bool {
+        self.clip.contains(point) && self.shape.contains(&self.bounds, point)
+    }
+}
+
+/// Hitboxes bucketed into a uniform grid, so hit tests only look at the hitboxes near the
+/// cursor instead of all of them.
+pub(crate) struct HitGrid {
+    hitboxes: Vec<Hitbox>,
+    // latest registration of each id, earlier ones are skipped when querying
+    by_id: HashMap<ElementId, usize>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // hitboxes spanning too many cells to be worth bucketing
+    large: Vec<usize>,
+}
+
+impl HitGrid {
+    const CELL_SIZE: f32 = 64.0;
+    const MAX_CELLS: i64 = 256;
+
+    pub fn new() -> Self {
+        Self {
+            hitboxes: Vec::new(),
+            by_id: HashMap::new(),
+            cells: HashMap::new(),
+            large: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hitboxes.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.hitboxes.clear();
+        self.by_id.clear();
+        self.cells.clear();
+        self.large.clear();
+    }
+
+    pub fn insert(&mut self, hitbox: Hitbox) {
+        let index = self.hitboxes.len();
+        self.hitboxes.push(hitbox);
+        self.by_id.insert(hitbox.id, index);
+
+        let visible = hitbox.bounds.intersect(&hitbox.clip);
+        if visible.size[0] <= 0.0 || visible.size[1] <= 0.0 {
+            return;
+        }
+
+        let (min_x, min_y) = Self::cell_of(visible.position);
+        let (max_x, max_y) = Self::cell_of([
+            visible.position[0] + visible.size[0],
+            visible.position[1] + visible.size[1],
+        ]);
+        let cell_count = (max_x - min_x + 1) as i64 * (max_y - min_y + 1) as i64;
+        if cell_count > Self::MAX_CELLS {
+            self.large.push(index);
+            return;
+        }
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    /// The hitboxes inserted after the first `index` ones, in insertion order.
+    pub fn since(&self, index: usize) -> &[Hitbox] {
+        &self.hitboxes[index.min(self.hitboxes.len())..]
+    }
+
+    pub fn get(&self, id: ElementId) -> Option<&Hitbox> {
+        self.by_id.get(&id).map(|&index| &self.hitboxes[index])
+    }
+
+    /// All hitboxes containing `point`, topmost (highest layer, then newest) first.
+    pub fn hits_at(&self, point: [f32; 2]) -> Vec<&Hitbox> {
+        let cell = self.cells.get(&Self::cell_of(point));
+        let mut hits = cell
+            .into_iter()
+            .flatten()
+            .chain(&self.large)
+            .filter(|&&index| self.by_id.get(&self.hitboxes[index].id) == Some(&index))
+            .map(|&index| &self.hitboxes[index])
+            .filter(|hitbox| hitbox.contains(point))
+            .collect::<Vec<_>>();
+
+        hits.sort_by_key(|hit| std::cmp::Reverse((hit.layer, hit.order)));
+        hits
+    }
+
+    fn cell_of(point: [f32; 2]) -> (i32, i32) {
+        (
+            (point[0] / Self::CELL_SIZE).floor() as i32,
+            (point[1] / Self::CELL_SIZE).floor() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect {
+            position: [x, y],
+            size: [width, height],
+        }
+    }
+
+    fn hitbox(id: u32, layer: u32, order: u32, bounds: Rect) -> Hitbox {
+        Hitbox {
+            id: ElementId(id),
+            layer,
+            order,
+            bounds,
+            shape: HitShape::Rect,
+            clip: Rect::NO_CLIP,
+        }
+    }
+
+    fn ids(grid: &HitGrid, point: [f32; 2]) -> Vec<u32> {
+        grid.hits_at(point).iter().map(|hit| hit.id.0).collect()
+    }
+
+    #[test]
+    fn grid_buckets() {
+        let mut grid = HitGrid::new();
+        // spans the cells around the origin, including negative ones
+        grid.insert(hitbox(1, 0, 0, rect(-10.0, -10.0, 100.0, 100.0)));
+        // too large to bucket
+        grid.insert(hitbox(2, 0, 1, rect(0.0, 0.0, 10_000.0, 10_000.0)));
+        // clipped away entirely
+        grid.insert(Hitbox {
+            clip: rect(500.0, 500.0, 10.0, 10.0),
+            ..hitbox(3, 0, 2, rect(0.0, 0.0, 10.0, 10.0))
+        });
+        grid.insert(hitbox(4, 1, 3, rect(60.0, 60.0, 10.0, 10.0)));
+
+        assert_eq!(grid.len(), 4);
+        assert_eq!(ids(&grid, [-5.0, -5.0]), vec![1]);
+        assert_eq!(ids(&grid, [5.0, 5.0]), vec![2, 1]);
+        assert_eq!(ids(&grid, [65.0, 65.0]), vec![4, 2, 1]);
+        assert_eq!(ids(&grid, [200.0, 5.0]), vec![2]);
+        assert!(ids(&grid, [-200.0, 5.0]).is_empty());
+
+        // the latest registration of an id replaces earlier ones
+        grid.insert(hitbox(1, 0, 4, rect(200.0, 0.0, 10.0, 10.0)));
+        assert_eq!(ids(&grid, [5.0, 5.0]), vec![2]);
+        assert_eq!(ids(&grid, [205.0, 5.0]), vec![1, 2]);
+        assert_eq!(grid.get(ElementId(1)).unwrap().order, 4);
+
+        grid.clear();
+        assert_eq!(grid.len(), 0);
+        assert!(ids(&grid, [5.0, 5.0]).is_empty());
+    }
+
+    #[test]
+    fn topmost_first() {
+        let mut grid = HitGrid::new();
+        let bounds = rect(0.0, 0.0, 10.0, 10.0);
+        grid.insert(hitbox(1, 1, 0, bounds));
+        grid.insert(hitbox(2, 0, 1, bounds));
+        grid.insert(hitbox(3, 1, 2, bounds));
+        grid.insert(hitbox(4, 0, 3, bounds));
+        assert_eq!(ids(&grid, [5.0, 5.0]), vec![3, 1, 4, 2]);
+    }
+
+    #[test]
+    fn rounded_rect_corners() {
+        let bounds = rect(0.0, 0.0, 100.0, 50.0);
+        let shape = HitShape::RoundedRect {
+            corner_radius: 10.0,
+        };
+        // the very corners are cut off
+        for corner in [[1.0, 1.0], [99.0, 1.0], [1.0, 49.0], [99.0, 49.0]] {
+            assert!(!shape.contains(&bounds, corner), "{corner:?}");
+            assert!(HitShape::Rect.contains(&bounds, corner), "{corner:?}");
+        }
+        // the edges between the corners are not
+        assert!(shape.contains(&bounds, [50.0, 0.5]));
+        assert!(shape.contains(&bounds, [0.5, 25.0]));
+        // inside the corner circle, near its edge
+        assert!(shape.contains(&bounds, [3.0, 3.0]));
+        assert!(!shape.contains(&bounds, [2.0, 2.0]));
+        assert!(!shape.contains(&bounds, [101.0, 25.0]));
+
+        // radii larger than the box make a pill
+        let pill = HitShape::RoundedRect {
+            corner_radius: 100.0,
+        };
+        assert!(pill.contains(&bounds, [25.0, 25.0]));
+        assert!(!pill.contains(&bounds, [5.0, 5.0]));
+        assert!(pill.contains(&bounds, [50.0, 1.0]));
+    }
+}
@@ -83,6 +83,10 @@ impl<State, Message> ElementImpl for Anchor<State, Message> {
         .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        self.child.update(ctx, state)
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -0,0 +1,450 @@
+use crate::event_manager::Registrations;
+use crate::{
+    Element, ElementId, ElementImpl, ElementSize, ElementWorld, InteractionEvent, SizeConstraints,
+    StateToParams, StateToParamsArgs, UiContext, into_box_impl,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use vn_scene::{BoxPrimitiveData, ImagePrimitiveData, Layer, Scene, TextPrimitiveData};
+
+pub struct DrawCacheParams {
+    /// Changes whenever the drawing of the child changes without changing its layout, e.g. a
+    /// hash of its colors and texts. Subtrees that animate have to include the animated values.
+    pub key: u64,
+}
+
+/// Draws its child once and replays the recorded primitives and hitboxes in later frames, as
+/// long as the child's layout is clean (see [Element::update]), the key of its params, its
+/// position, size, clip rect and layer stay the same and none of its hitboxes changed their
+/// hovered, focused, pressed or drag state. Without calling update, the child is always drawn.
+pub struct DrawCache<State: 'static, Message> {
+    id: ElementId,
+    child: Box<dyn Element<State = State, Message = Message>>,
+    params: StateToParams<State, DrawCacheParams>,
+    // whether the child has to be laid out again, as reported by its last update
+    child_dirty: Option<bool>,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    inputs: DrawInputs,
+    ops: Vec<SceneOp>,
+    registrations: Registrations,
+    interaction: Vec<[bool; 5]>,
+}
+
+#[derive(PartialEq)]
+struct DrawInputs {
+    key: u64,
+    origin: (f32, f32),
+    size: ElementSize,
+    clip: [[f32; 2]; 2],
+    parent: Option<ElementId>,
+    interactive: bool,
+    layer: u32,
+    layer_count: usize,
+}
+
+enum SceneOp {
+    Box(BoxPrimitiveData),
+    Image(ImagePrimitiveData),
+    Text(TextPrimitiveData),
+    NextLayer(Vec<SceneOp>),
+    TopLayer(Vec<SceneOp>),
+    Extend(Vec<Layer>),
+}
+
+/// Passes everything on to `scene` and records it.
+struct SceneRecorder<'a> {
+    scene: &'a mut dyn Scene,
+    ops: Vec<SceneOp>,
+}
+
+impl Scene for SceneRecorder<'_> {
+    fn add_box(&mut self, b: BoxPrimitiveData) {
+        self.ops.push(SceneOp::Box(b.clone()));
+        self.scene.add_box(b);
+    }
+
+    fn add_image(&mut self, i: ImagePrimitiveData) {
+        self.ops.push(SceneOp::Image(i.clone()));
+        self.scene.add_image(i);
+    }
+
+    fn add_text(&mut self, t: TextPrimitiveData) {
+        self.ops.push(SceneOp::Text(t.clone()));
+        self.scene.add_text(t);
+    }
+
+    fn with_next_layer(&mut self, f: &mut dyn FnMut(&mut dyn Scene)) {
+        let mut ops = Vec::new();
+        self.scene.with_next_layer(&mut |scene| {
+            let mut recorder = SceneRecorder {
+                scene,
+                ops: Vec::new(),
+            };
+            f(&mut recorder);
+            ops = recorder.ops;
+        });
+        self.ops.push(SceneOp::NextLayer(ops));
+    }
+
+    fn with_top_layer(&mut self, f: &mut dyn FnMut(&mut dyn Scene)) {
+        let mut ops = Vec::new();
+        self.scene.with_top_layer(&mut |scene| {
+            let mut recorder = SceneRecorder {
+                scene,
+                ops: Vec::new(),
+            };
+            f(&mut recorder);
+            ops = recorder.ops;
+        });
+        self.ops.push(SceneOp::TopLayer(ops));
+    }
+
+    fn current_layer_id(&self) -> u32 {
+        self.scene.current_layer_id()
+    }
+
+    fn layers(&self) -> &[Layer] {
+        self.scene.layers()
+    }
+
+    fn extend(&mut self, other: &mut dyn Scene) {
+        self.ops.push(SceneOp::Extend(other.layers().to_vec()));
+        self.scene.extend(other);
+    }
+}
+
+fn replay(ops: &[SceneOp], scene: &mut dyn Scene) {
+    for op in ops {
+        match op {
+            SceneOp::Box(b) => scene.add_box(b.clone()),
+            SceneOp::Image(i) => scene.add_image(i.clone()),
+            SceneOp::Text(t) => scene.add_text(t.clone()),
+            SceneOp::NextLayer(ops) => scene.with_next_layer(&mut |scene| replay(ops, scene)),
+            SceneOp::TopLayer(ops) => scene.with_top_layer(&mut |scene| replay(ops, scene)),
+            // the same as extending the scene with a scene of these layers
+            SceneOp::Extend(layers) => {
+                for layer in layers {
+                    scene.with_top_layer(&mut |scene| {
+                        for b in &layer.boxes {
+                            scene.add_box(b.clone());
+                        }
+                        for i in &layer.images {
+                            scene.add_image(i.clone());
+                        }
+                        for t in &layer.texts {
+                            scene.add_text(t.clone());
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<State: 'static, Message> DrawCache<State, Message> {
+    pub fn new<P: Into<StateToParams<State, DrawCacheParams>>>(
+        child: impl Into<Box<dyn Element<State = State, Message = Message>>>,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        Self {
+            id: world.borrow_mut().next_id(),
+            child: child.into(),
+            params: params.into(),
+            child_dirty: None,
+            recording: None,
+        }
+    }
+}
+
+impl<State, Message> ElementImpl for DrawCache<State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        self.child.layout(ctx, state, constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let dirty = self.child.update(ctx, state);
+        self.child_dirty = Some(dirty);
+        dirty
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        origin: (f32, f32),
+        size: ElementSize,
+        scene: &mut dyn Scene,
+    ) {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let inputs = DrawInputs {
+            key: params.key,
+            origin,
+            size,
+            clip: [ctx.clip_rect.position, ctx.clip_rect.size],
+            parent: ctx.parent_id,
+            interactive: ctx.interactive,
+            layer: scene.current_layer_id(),
+            layer_count: scene.layers().len(),
+        };
+
+        let child_clean = self.child_dirty.take() == Some(false);
+        if child_clean && let Some(recording) = &self.recording {
+            let mut event_manager = ctx.event_manager.borrow_mut();
+            let unchanged = recording.inputs == inputs
+                && recording
+                    .registrations
+                    .ids()
+                    .map(|id| event_manager.interaction_of(id))
+                    .eq(recording.interaction.iter().copied());
+            if unchanged {
+                replay(&recording.ops, scene);
+                event_manager.replay_registrations(&recording.registrations);
+                return;
+            }
+        }
+
+        let mark = ctx.event_manager.borrow().registration_mark();
+        let mut recorder = SceneRecorder {
+            scene,
+            ops: Vec::new(),
+        };
+        self.child.draw(ctx, state, origin, size, &mut recorder);
+
+        let event_manager = ctx.event_manager.borrow();
+        let registrations = event_manager.registrations_since(mark);
+        let interaction = registrations
+            .ids()
+            .map(|id| event_manager.interaction_of(id))
+            .collect();
+        self.recording = Some(Recording {
+            inputs,
+            ops: recorder.ops,
+            registrations,
+            interaction,
+        });
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        event: &InteractionEvent,
+    ) -> Vec<Self::Message> {
+        self.child.handle_event(ctx, state, event)
+    }
+}
+
+pub trait DrawCacheExt<State, Message> {
+    fn draw_cache<P: Into<StateToParams<State, DrawCacheParams>>>(
+        self,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> DrawCache<State, Message>;
+}
+
+impl<State, Message, E: Into<Box<dyn Element<State = State, Message = Message>>> + 'static>
+    DrawCacheExt<State, Message> for E
+{
+    fn draw_cache<P: Into<StateToParams<State, DrawCacheParams>>>(
+        self,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> DrawCache<State, Message> {
+        DrawCache::new(self, params, world)
+    }
+}
+
+into_box_impl!(DrawCache);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, context};
+    use crate::{DynamicDimension, DynamicSize, TabIndex};
+    use std::cell::Cell;
+    use vn_scene::{Color, Rect, Transform};
+
+    // a focusable box that counts how often it is drawn
+    struct Counter {
+        id: ElementId,
+        draws: Rc<Cell<usize>>,
+    }
+
+    impl ElementImpl for Counter {
+        type State = ();
+        type Message = ();
+
+        fn id_impl(&self) -> ElementId {
+            self.id
+        }
+
+        fn layout_impl(
+            &mut self,
+            _ctx: &mut UiContext,
+            _state: &(),
+            _constraints: SizeConstraints,
+        ) -> ElementSize {
+            ElementSize {
+                width: 10.0,
+                height: 10.0,
+            }
+        }
+
+        fn update_impl(&mut self, _ctx: &mut UiContext, _state: &()) -> bool {
+            false
+        }
+
+        fn draw_impl(
+            &mut self,
+            ctx: &mut UiContext,
+            _state: &(),
+            origin: (f32, f32),
+            size: ElementSize,
+            scene: &mut dyn Scene,
+        ) {
+            self.draws.set(self.draws.get() + 1);
+            let bounds = Rect {
+                position: [origin.0, origin.1],
+                size: [size.width, size.height],
+            };
+            ctx.with_hitbox_hierarchy(self.id, scene.current_layer_id(), bounds, |ctx| {
+                ctx.register_focusable(self.id, TabIndex::Auto, false);
+            });
+            scene.with_next_layer(&mut |scene| {
+                scene.add_box(BoxPrimitiveData {
+                    transform: Transform {
+                        translation: bounds.position,
+                        ..Transform::DEFAULT
+                    },
+                    size: bounds.size,
+                    color: Color::WHITE,
+                    border_color: Color::TRANSPARENT,
+                    border_thickness: 0.0,
+                    border_radius: 0.0,
+                    clip_rect: Rect::NO_CLIP,
+                })
+            });
+        }
+
+        fn handle_event_impl(
+            &mut self,
+            _ctx: &mut UiContext,
+            _state: &(),
+            _event: &InteractionEvent,
+        ) -> Vec<()> {
+            vec![]
+        }
+    }
+
+    // lays out and draws a frame the way a persistent layout cache is used
+    fn frame(ctx: &mut UiContext, cache: &mut DrawCache<(), ()>, origin: (f32, f32)) -> TestScene {
+        ctx.layout_cache.next_frame();
+        ctx.event_manager.borrow_mut().clear_hitboxes();
+        cache.update(ctx, &());
+        let size = cache.layout(
+            ctx,
+            &(),
+            SizeConstraints {
+                min_size: ElementSize::ZERO,
+                max_size: DynamicSize {
+                    width: DynamicDimension::Hint(0.0),
+                    height: DynamicDimension::Hint(0.0),
+                },
+                scene_size: (100.0, 100.0),
+            },
+        );
+        let mut scene = TestScene::new();
+        cache.draw(ctx, &(), origin, size, &mut scene);
+        scene
+    }
+
+    fn boxes(scene: &TestScene) -> Vec<usize> {
+        scene
+            .layers()
+            .iter()
+            .map(|layer| layer.boxes.len())
+            .collect()
+    }
+
+    #[test]
+    fn replays_unchanged_subtrees() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let draws = Rc::new(Cell::new(0));
+        let key = Rc::new(Cell::new(0));
+        let id = world.borrow_mut().next_id();
+        let counter: Box<dyn Element<State = (), Message = ()>> = Box::new(Counter {
+            id,
+            draws: draws.clone(),
+        });
+        let mut cache = DrawCache::new(
+            counter,
+            {
+                let key = key.clone();
+                move |_: StateToParamsArgs<()>| DrawCacheParams { key: key.get() }
+            },
+            world.clone(),
+        );
+        let mut ctx = context();
+
+        assert_eq!(boxes(&frame(&mut ctx, &mut cache, (0.0, 0.0))), vec![0, 1]);
+        assert_eq!(draws.get(), 1);
+
+        // replayed, including the hitbox and the focusable
+        assert_eq!(boxes(&frame(&mut ctx, &mut cache, (0.0, 0.0))), vec![0, 1]);
+        assert_eq!(draws.get(), 1);
+        let mut event_manager = ctx.event_manager.borrow_mut();
+        assert_eq!(event_manager.get_top_hit(5.0, 5.0), Some(id));
+        event_manager.focus_next();
+        assert!(event_manager.is_focused(id));
+        drop(event_manager);
+
+        // the focus changed
+        frame(&mut ctx, &mut cache, (0.0, 0.0));
+        assert_eq!(draws.get(), 2);
+        frame(&mut ctx, &mut cache, (0.0, 0.0));
+        assert_eq!(draws.get(), 2);
+
+        key.set(1);
+        frame(&mut ctx, &mut cache, (0.0, 0.0));
+        assert_eq!(draws.get(), 3);
+
+        frame(&mut ctx, &mut cache, (20.0, 0.0));
+        assert_eq!(draws.get(), 4);
+        assert_eq!(ctx.event_manager.borrow().get_top_hit(25.0, 5.0), Some(id));
+
+        // without updating, the layout might have changed
+        ctx.layout_cache.next_frame();
+        let mut scene = TestScene::new();
+        cache.draw(
+            &mut ctx,
+            &(),
+            (20.0, 0.0),
+            ElementSize {
+                width: 10.0,
+                height: 10.0,
+            },
+            &mut scene,
+        );
+        assert_eq!(draws.get(), 5);
+    }
+}
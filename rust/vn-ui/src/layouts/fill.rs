@@ -62,6 +62,10 @@ impl<State, Message> ElementImpl for Fill<State, Message> {
         desired_size
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        self.element.update(ctx, state)
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
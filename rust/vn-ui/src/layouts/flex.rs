@@ -1,28 +1,147 @@
 use crate::{
-    DynamicDimension, Element, ElementId, ElementImpl, ElementSize, ElementWorld, SizeConstraints,
-    StateToParams, StateToParamsArgs, UiContext, into_box_impl,
+    DynamicDimension, DynamicSize, Element, ElementId, ElementImpl, ElementSize, ElementWorld,
+    LayoutInputs, SizeConstraints, StateToParams, StateToParamsArgs, UiContext, into_box_impl,
 };
 use std::cell::RefCell;
+use std::ops::Range;
 use std::rc::Rc;
 use vn_scene::{Rect, Scene};
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum FlexDirection {
     Row,
     Column,
 }
 
+impl FlexDirection {
+    fn main(self, size: ElementSize) -> f32 {
+        match self {
+            FlexDirection::Row => size.width,
+            FlexDirection::Column => size.height,
+        }
+    }
+
+    fn cross(self, size: ElementSize) -> f32 {
+        match self {
+            FlexDirection::Row => size.height,
+            FlexDirection::Column => size.width,
+        }
+    }
+
+    fn size(self, main: f32, cross: f32) -> ElementSize {
+        match self {
+            FlexDirection::Row => ElementSize {
+                width: main,
+                height: cross,
+            },
+            FlexDirection::Column => ElementSize {
+                width: cross,
+                height: main,
+            },
+        }
+    }
+
+    fn split(self, size: DynamicSize) -> (DynamicDimension, DynamicDimension) {
+        match self {
+            FlexDirection::Row => (size.width, size.height),
+            FlexDirection::Column => (size.height, size.width),
+        }
+    }
+
+    fn join(self, main: DynamicDimension, cross: DynamicDimension) -> DynamicSize {
+        match self {
+            FlexDirection::Row => DynamicSize {
+                width: main,
+                height: cross,
+            },
+            FlexDirection::Column => DynamicSize {
+                width: cross,
+                height: main,
+            },
+        }
+    }
+
+    fn point(self, main: f32, cross: f32) -> (f32, f32) {
+        match self {
+            FlexDirection::Row => (main, cross),
+            FlexDirection::Column => (cross, main),
+        }
+    }
+}
+
+/// How the space left along the main axis is distributed. It only applies if the flex is
+/// limited along its main axis and has no weighted children.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexJustify {
+    Start,
+    Center,
+    End,
+    /// The first and last child touch the edges, the rest of the space goes between children.
+    SpaceBetween,
+    /// Every child gets the same space on both of its sides.
+    SpaceAround,
+}
+
+impl FlexJustify {
+    /// Where the first child starts and the space between two children.
+    fn distribute(self, free: f32, count: usize, gap: f32) -> (f32, f32) {
+        match self {
+            FlexJustify::Start => (0.0, gap),
+            FlexJustify::Center => (free / 2.0, gap),
+            FlexJustify::End => (free, gap),
+            FlexJustify::SpaceBetween if count > 1 => (0.0, gap + free / (count - 1) as f32),
+            FlexJustify::SpaceBetween => (0.0, gap),
+            FlexJustify::SpaceAround => {
+                (free / (2 * count) as f32, gap + free / count.max(1) as f32)
+            }
+        }
+    }
+}
+
+/// How children are placed along the cross axis within their line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlexAlign {
+    Start,
+    Center,
+    End,
+    /// Children are forced to the size of the line.
+    Stretch,
+}
+
 #[derive(Clone)]
 pub struct FlexParams<State: 'static, Message: 'static> {
     pub direction: FlexDirection,
-    /// if true, all elements will be forced to the same size along the orthogonal axis.
-    pub force_orthogonal_same_size: bool,
+    /// Space between children and between lines.
+    pub gap: f32,
+    pub justify_content: FlexJustify,
+    pub align_items: FlexAlign,
+    /// If true and the flex is limited along its main axis, children that don't fit anymore
+    /// start a new line.
+    pub wrap: bool,
     pub children: Vec<Rc<RefCell<FlexChild<State, Message>>>>,
 }
 
+impl<State: 'static, Message: 'static> FlexParams<State, Message> {
+    /// No children, no gap, justified to the start and stretched across the cross axis, like a
+    /// CSS flexbox. Set the rest with struct update syntax.
+    pub fn new(direction: FlexDirection) -> Self {
+        Self {
+            direction,
+            gap: 0.0,
+            justify_content: FlexJustify::Start,
+            align_items: FlexAlign::Stretch,
+            wrap: false,
+            children: Vec::new(),
+        }
+    }
+}
+
 pub struct FlexChild<State: 'static, Message: 'static> {
     pub element: Box<dyn Element<State = State, Message = Message>>,
     pub weight: Option<f32>,
+    /// How much this child gives up, relative to the other children and its size, if the
+    /// children of its line overflow the flex. Defaults to 0, i.e. the child never shrinks.
+    pub shrink: f32,
 }
 
 impl<State: 'static, Message: 'static> FlexChild<State, Message> {
@@ -30,6 +149,7 @@ impl<State: 'static, Message: 'static> FlexChild<State, Message> {
         Self {
             element: element.into(),
             weight: None,
+            shrink: 0.0,
         }
     }
 
@@ -40,9 +160,15 @@ impl<State: 'static, Message: 'static> FlexChild<State, Message> {
         Self {
             element: element.into(),
             weight: Some(weight),
+            shrink: 0.0,
         }
     }
 
+    pub fn shrink(mut self, shrink: f32) -> Self {
+        self.shrink = shrink;
+        self
+    }
+
     pub fn into_rc_refcell(self) -> Rc<RefCell<FlexChild<State, Message>>> {
         Rc::new(RefCell::new(self))
     }
@@ -56,10 +182,20 @@ impl<State, Message> Into<FlexChild<State, Message>>
     }
 }
 
+// the children are compared by identity
+type FlexLayoutInputs = (
+    FlexDirection,
+    f32,
+    (FlexJustify, FlexAlign, bool),
+    Vec<(ElementId, Option<f32>, f32)>,
+);
+
 pub struct Flex<State: 'static, Message: 'static> {
     id: ElementId,
-    layout: Vec<ElementSize>,
+    /// offset from the flex's origin and size of each child
+    layout: Vec<((f32, f32), ElementSize)>,
     params: StateToParams<State, FlexParams<State, Message>>,
+    layout_inputs: LayoutInputs<FlexLayoutInputs>,
 }
 
 impl<State: 'static, Message: 'static> Flex<State, Message> {
@@ -71,11 +207,11 @@ impl<State: 'static, Message: 'static> Flex<State, Message> {
             id: world.borrow_mut().next_id(),
             layout: Vec::new(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
         }
     }
 }
 
-// todo: allow for weight / spacing between children?
 impl<State, Message> ElementImpl for Flex<State, Message> {
     type State = State;
     type Message = Message;
@@ -90,145 +226,223 @@ impl<State, Message> ElementImpl for Flex<State, Message> {
         state: &Self::State,
         constraints: SizeConstraints,
     ) -> ElementSize {
-        // what do we do with containers that grow? like anchor?
-        // do we extend constraints to denote that they should not grow along some axis?
-        let mut total_unweighted_in_direction: f32 = 0.0;
-        let mut max_orthogonal: f32 = 0.0;
         let params = self.params.call(StateToParamsArgs {
             state,
             id: self.id,
             ctx,
         });
-
-        if self.layout.len() < params.children.len() {
-            self.layout.extend(vec![
-                ElementSize::ZERO;
-                params.children.len() - self.layout.len()
-            ]);
-        }
-
-        let mut child_constraints = constraints;
-        child_constraints.min_size.width = 0.0;
-        child_constraints.min_size.height = 0.0;
-        child_constraints.max_size.width =
-            DynamicDimension::Hint(constraints.max_size.width.value());
-        child_constraints.max_size.height =
-            DynamicDimension::Hint(constraints.max_size.height.value());
-
-        let mut total_weight = None;
-
-        for (idx, child) in params.children.iter().enumerate() {
-            let mut child = child.borrow_mut();
-            let child_size = child.element.layout_impl(ctx, state, child_constraints);
-
-            if let Some(weight) = child.weight {
-                match total_weight {
-                    None => total_weight = Some(weight),
-                    Some(total) => total_weight = Some(total + weight),
-                }
-            }
-
-            match params.direction {
-                FlexDirection::Row => {
-                    max_orthogonal = max_orthogonal.max(child_size.height);
+        let direction = params.direction;
+        let gap = params.gap;
+        let (max_main, max_cross) = direction.split(constraints.max_size);
+        let child_constraints = |min_main: f32, main, min_cross: f32, cross| SizeConstraints {
+            min_size: direction.size(min_main, min_cross),
+            max_size: direction.join(main, cross),
+            scene_size: constraints.scene_size,
+        };
+
+        let mut children = params
+            .children
+            .iter()
+            .map(|child| child.borrow_mut())
+            .collect::<Vec<_>>();
+
+        // first measure every child without limiting it
+        let unlimited = child_constraints(
+            0.0,
+            DynamicDimension::Hint(max_main.value()),
+            0.0,
+            DynamicDimension::Hint(max_cross.value()),
+        );
+        let mut measured = children
+            .iter_mut()
+            .map(|child| child.element.layout(ctx, state, unlimited))
+            .collect::<Vec<_>>();
+
+        // weighted children take up no space until the rest is distributed
+        let mut mains = children
+            .iter()
+            .zip(&measured)
+            .map(|(child, size)| match child.weight {
+                Some(_) => 0.0,
+                None => direction.main(*size),
+            })
+            .collect::<Vec<_>>();
+
+        let lines = match (params.wrap, max_main) {
+            (true, DynamicDimension::Limit(limit)) => {
+                let mut lines = Vec::new();
+                let mut start = 0;
+                let mut used = 0.0;
+                for (idx, main) in mains.iter().enumerate() {
+                    if idx > start && used + gap + main > limit {
+                        lines.push(start..idx);
+                        start = idx;
+                        used = *main;
+                    } else if idx > start {
+                        used += gap + main;
+                    } else {
+                        used = *main;
+                    }
                 }
-                FlexDirection::Column => {
-                    max_orthogonal = max_orthogonal.max(child_size.width);
+                if start < mains.len() {
+                    lines.push(start..mains.len());
                 }
+                lines
             }
-
-            self.layout[idx] = child_size;
-        }
-
-        match params.direction {
-            FlexDirection::Row => {
-                if params.force_orthogonal_same_size {
-                    child_constraints.min_size.height = max_orthogonal;
-                }
-                child_constraints.max_size.height = DynamicDimension::Limit(max_orthogonal);
+            _ => {
+                let all = 0..children.len();
+                vec![all]
             }
-            FlexDirection::Column => {
-                if params.force_orthogonal_same_size {
-                    child_constraints.min_size.width = max_orthogonal;
+        };
+
+        // children whose main size was decided by the flex instead of themselves
+        let mut exact = vec![false; children.len()];
+        let mut line_layouts: Vec<(Range<usize>, f32, f32)> = Vec::with_capacity(lines.len());
+
+        for line in lines {
+            let gaps = gap * line.len().saturating_sub(1) as f32;
+            let used = |mains: &[f32]| mains[line.clone()].iter().sum::<f32>() + gaps;
+            let mut free = max_main.value() - used(&mains);
+
+            if free < 0.0 && matches!(max_main, DynamicDimension::Limit(_)) {
+                let basis: f32 = line
+                    .clone()
+                    .map(|idx| children[idx].shrink * mains[idx])
+                    .sum();
+                if basis > 0.0 {
+                    let overflow = -free;
+                    for idx in line.clone() {
+                        if children[idx].shrink > 0.0 {
+                            mains[idx] = (mains[idx]
+                                - overflow * children[idx].shrink * mains[idx] / basis)
+                                .max(0.0);
+                            exact[idx] = true;
+                        }
+                    }
+                    free = max_main.value() - used(&mains);
                 }
-                child_constraints.max_size.width = DynamicDimension::Limit(max_orthogonal);
             }
-        }
 
-        for (idx, child) in params.children.iter().enumerate() {
-            let mut child = child.borrow_mut();
-            if let Some(_) = child.weight {
-                continue;
+            let total_weight: f32 = line.clone().filter_map(|idx| children[idx].weight).sum();
+            if total_weight > 0.0 {
+                let space = free.max(0.0);
+                for idx in line.clone() {
+                    if let Some(weight) = children[idx].weight {
+                        mains[idx] = space * weight / total_weight;
+                        exact[idx] = true;
+                    }
+                }
+                free = 0.0;
             }
 
-            let child_size = child.element.layout_impl(ctx, state, child_constraints);
-
-            match params.direction {
-                FlexDirection::Row => {
-                    total_unweighted_in_direction += child_size.width;
-                }
-                FlexDirection::Column => {
-                    total_unweighted_in_direction += child_size.height;
+            let mut line_cross: f32 = 0.0;
+            for idx in line.clone() {
+                if exact[idx] {
+                    measured[idx] = children[idx].element.layout(
+                        ctx,
+                        state,
+                        child_constraints(
+                            mains[idx],
+                            DynamicDimension::Limit(mains[idx]),
+                            0.0,
+                            DynamicDimension::Hint(max_cross.value()),
+                        ),
+                    );
                 }
+                line_cross = line_cross.max(direction.cross(measured[idx]));
             }
 
-            self.layout[idx] = child_size;
+            let free = match max_main {
+                DynamicDimension::Limit(_) => free.max(0.0),
+                DynamicDimension::Hint(_) => 0.0,
+            };
+            line_layouts.push((line, line_cross, free));
         }
 
-        let remaining_available_space = match params.direction {
-            FlexDirection::Row => constraints.max_size.width,
-            FlexDirection::Column => constraints.max_size.height,
+        if let [(_, line_cross, _)] = line_layouts.as_mut_slice() {
+            *line_cross = line_cross.max(direction.cross(constraints.min_size));
         }
-        .map(|v| (v - total_unweighted_in_direction).max(0.0))
-        .value();
 
-        let mut total_in_direction = total_unweighted_in_direction;
+        self.layout.clear();
+        self.layout
+            .resize(children.len(), ((0.0, 0.0), ElementSize::ZERO));
+
+        let mut total_main: f32 = 0.0;
+        let mut line_offset = 0.0;
+        for (line, line_cross, free) in &line_layouts {
+            let (mut position, spacing) = params.justify_content.distribute(*free, line.len(), gap);
+
+            for idx in line.clone() {
+                let stretch = params.align_items == FlexAlign::Stretch;
+                let constraints = child_constraints(
+                    if exact[idx] { mains[idx] } else { 0.0 },
+                    if exact[idx] {
+                        DynamicDimension::Limit(mains[idx])
+                    } else {
+                        DynamicDimension::Hint(max_main.value())
+                    },
+                    if stretch { *line_cross } else { 0.0 },
+                    DynamicDimension::Limit(*line_cross),
+                );
+                let size = children[idx]
+                    .element
+                    .layout(ctx, state, constraints)
+                    .clamp_to_constraints(constraints);
+
+                let cross_offset = match params.align_items {
+                    FlexAlign::Start | FlexAlign::Stretch => 0.0,
+                    FlexAlign::Center => (line_cross - direction.cross(size)) / 2.0,
+                    FlexAlign::End => line_cross - direction.cross(size),
+                };
 
-        if let Some(total_weight) = total_weight {
-            total_in_direction += remaining_available_space;
+                self.layout[idx] = (direction.point(position, line_offset + cross_offset), size);
+                position += direction.main(size) + spacing;
+            }
 
-            let unit_per_weight = if total_weight > 0.0 {
-                (remaining_available_space / total_weight).max(0.0)
-            } else {
-                0.0
+            let content = line
+                .clone()
+                .map(|idx| direction.main(self.layout[idx].1))
+                .sum::<f32>()
+                + gap * line.len().saturating_sub(1) as f32;
+            let line_main = match params.justify_content {
+                FlexJustify::Start => content,
+                _ => content + free,
             };
+            total_main = total_main.max(line_main);
+            line_offset += line_cross + gap;
+        }
 
-            for (idx, child) in params.children.iter().enumerate() {
-                let mut child = child.borrow_mut();
-                if child.weight.is_none() {
-                    continue;
-                }
-
-                match params.direction {
-                    FlexDirection::Row => {
-                        let space = child.weight.unwrap() * unit_per_weight;
-                        child_constraints.min_size.width = space;
-                        child_constraints.max_size.width = DynamicDimension::Limit(space);
-                    }
-                    FlexDirection::Column => {
-                        let space = child.weight.unwrap() * unit_per_weight;
-                        child_constraints.min_size.height = space;
-                        child_constraints.max_size.height = DynamicDimension::Limit(space);
-                    }
-                }
+        let total_cross = (line_offset - gap).max(0.0);
 
-                self.layout[idx] = child.element.layout_impl(ctx, state, child_constraints);
-            }
-        }
+        direction
+            .size(total_main, total_cross)
+            .clamp_to_constraints(constraints)
+    }
 
-        let size = match params.direction {
-            FlexDirection::Row => ElementSize {
-                width: total_in_direction,
-                height: max_orthogonal,
-            },
-            FlexDirection::Column => ElementSize {
-                width: max_orthogonal,
-                height: total_in_direction,
-            },
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let children = params
+            .children
+            .iter()
+            .map(|child| {
+                let child_ref = child.borrow();
+                (child_ref.element.id(), child_ref.weight, child_ref.shrink)
+            })
+            .collect();
+        let mut dirty = self.layout_inputs.changed((
+            params.direction,
+            params.gap,
+            (params.justify_content, params.align_items, params.wrap),
+            children,
+        ));
+        for child in &params.children {
+            dirty |= child.borrow_mut().element.update(ctx, state);
         }
-        .clamp_to_constraints(constraints);
-
-        size
+        dirty
     }
 
     fn draw_impl(
@@ -251,38 +465,14 @@ impl<State, Message> ElementImpl for Flex<State, Message> {
                 size: [size.width, size.height],
             },
             |ctx| {
-                let mut offset = match params.direction {
-                    FlexDirection::Row => origin.0,
-                    FlexDirection::Column => origin.1,
-                };
-                for (idx, child) in params.children.iter().enumerate() {
-                    let mut child = child.borrow_mut();
-                    let mut child_size = self.layout[idx];
-
-                    match params.direction {
-                        FlexDirection::Row => {
-                            // making sure we are not drawing out of bounds for some reason
-                            child_size.width =
-                                child_size.width.min(size.width - (offset - origin.0));
-                            child_size.height = child_size.height.min(size.height);
-
-                            child
-                                .element
-                                .draw(ctx, state, (offset, origin.1), child_size, canvas);
-                            offset += self.layout[idx].width;
-                        }
-                        FlexDirection::Column => {
-                            // making sure we are not drawing out of bounds for some reason
-                            child_size.width = child_size.width.min(size.width);
-                            child_size.height =
-                                child_size.height.min(size.height - (offset - origin.1));
-
-                            child
-                                .element
-                                .draw(ctx, state, (origin.0, offset), child_size, canvas);
-                            offset += self.layout[idx].height;
-                        }
-                    }
+                for (child, (offset, child_size)) in params.children.iter().zip(&self.layout) {
+                    child.borrow_mut().element.draw(
+                        ctx,
+                        state,
+                        (origin.0 + offset.0, origin.1 + offset.1),
+                        *child_size,
+                        canvas,
+                    );
                 }
             },
         )
@@ -309,3 +499,381 @@ impl<State, Message> ElementImpl for Flex<State, Message> {
 }
 
 into_box_impl!(Flex);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventManager, PreferSize, PreferSizeParams, SimpleLayoutCache};
+    use DynamicDimension::{Hint, Limit};
+
+    type Child = Rc<RefCell<FlexChild<(), ()>>>;
+
+    fn sized(width: f32, height: f32, world: &Rc<RefCell<ElementWorld>>) -> FlexChild<(), ()> {
+        FlexChild::new(PreferSize::new(
+            crate::Empty::new(world.clone()),
+            move |_: StateToParamsArgs<()>| PreferSizeParams {
+                width: Some(width),
+                height: Some(height),
+            },
+            world.clone(),
+        ))
+    }
+
+    fn flex(
+        direction: FlexDirection,
+        gap: f32,
+        justify_content: FlexJustify,
+        align_items: FlexAlign,
+        wrap: bool,
+        children: Vec<FlexChild<(), ()>>,
+        world: &Rc<RefCell<ElementWorld>>,
+    ) -> Flex<(), ()> {
+        let children: Vec<Child> = children
+            .into_iter()
+            .map(FlexChild::into_rc_refcell)
+            .collect();
+        Flex::new(
+            move |_: StateToParamsArgs<()>| FlexParams {
+                direction,
+                gap,
+                justify_content,
+                align_items,
+                wrap,
+                children: children.clone(),
+            },
+            world.clone(),
+        )
+    }
+
+    fn layout(
+        flex: &mut Flex<(), ()>,
+        width: DynamicDimension,
+        height: DynamicDimension,
+    ) -> ElementSize {
+        let mut ctx = UiContext::new(
+            Rc::new(RefCell::new(EventManager::new())),
+            Box::new(SimpleLayoutCache::new()),
+            web_time::Instant::now(),
+        );
+        flex.layout(
+            &mut ctx,
+            &(),
+            SizeConstraints {
+                min_size: ElementSize::ZERO,
+                max_size: DynamicSize { width, height },
+                scene_size: (1000.0, 1000.0),
+            },
+        )
+    }
+
+    fn size(width: f32, height: f32) -> ElementSize {
+        ElementSize { width, height }
+    }
+
+    fn children(
+        count: usize,
+        width: f32,
+        height: f32,
+        world: &Rc<RefCell<ElementWorld>>,
+    ) -> Vec<FlexChild<(), ()>> {
+        (0..count).map(|_| sized(width, height, world)).collect()
+    }
+
+    fn offsets(flex: &Flex<(), ()>) -> Vec<(f32, f32)> {
+        flex.layout.iter().map(|(offset, _)| *offset).collect()
+    }
+
+    #[test]
+    fn gap_between_children() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            5.0,
+            FlexJustify::Start,
+            FlexAlign::Start,
+            false,
+            children(3, 10.0, 10.0, &world),
+            &world,
+        );
+        assert_eq!(layout(&mut flex, Hint(0.0), Hint(0.0)), size(40.0, 10.0));
+        assert_eq!(offsets(&flex), vec![(0.0, 0.0), (15.0, 0.0), (30.0, 0.0)]);
+    }
+
+    #[test]
+    fn justify_content() {
+        let cases = [
+            (FlexJustify::Start, vec![0.0, 20.0], 40.0),
+            (FlexJustify::Center, vec![30.0, 50.0], 100.0),
+            (FlexJustify::End, vec![60.0, 80.0], 100.0),
+            (FlexJustify::SpaceBetween, vec![0.0, 80.0], 100.0),
+            (FlexJustify::SpaceAround, vec![15.0, 65.0], 100.0),
+        ];
+
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        for (justify, expected, width) in cases {
+            let mut flex = flex(
+                FlexDirection::Row,
+                0.0,
+                justify,
+                FlexAlign::Start,
+                false,
+                children(2, 20.0, 10.0, &world),
+                &world,
+            );
+            assert_eq!(
+                layout(&mut flex, Limit(100.0), Limit(100.0)),
+                size(width, 10.0),
+                "{justify:?}"
+            );
+            let xs: Vec<f32> = offsets(&flex).iter().map(|(x, _)| *x).collect();
+            assert_eq!(xs, expected, "{justify:?}");
+        }
+    }
+
+    #[test]
+    fn justify_content_is_ignored_without_limit() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            0.0,
+            FlexJustify::End,
+            FlexAlign::Start,
+            false,
+            children(2, 20.0, 10.0, &world),
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Hint(100.0), Hint(100.0)),
+            size(40.0, 10.0)
+        );
+        assert_eq!(offsets(&flex), vec![(0.0, 0.0), (20.0, 0.0)]);
+    }
+
+    #[test]
+    fn align_items() {
+        let cases = [
+            (FlexAlign::Start, 0.0, 10.0),
+            (FlexAlign::Center, 5.0, 10.0),
+            (FlexAlign::End, 10.0, 10.0),
+            (FlexAlign::Stretch, 0.0, 20.0),
+        ];
+
+        for (align, expected_y, expected_height) in cases {
+            let world = Rc::new(RefCell::new(ElementWorld::new()));
+            let mut flex = flex(
+                FlexDirection::Row,
+                0.0,
+                FlexJustify::Start,
+                align,
+                false,
+                vec![sized(10.0, 10.0, &world), sized(10.0, 20.0, &world)],
+                &world,
+            );
+            assert_eq!(layout(&mut flex, Hint(0.0), Hint(0.0)), size(20.0, 20.0));
+            assert_eq!(flex.layout[0].0, (0.0, expected_y), "{align:?}");
+            assert_eq!(flex.layout[0].1.height, expected_height, "{align:?}");
+            assert_eq!(flex.layout[1].0, (10.0, 0.0), "{align:?}");
+        }
+    }
+
+    #[test]
+    fn column_swaps_axes() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Column,
+            4.0,
+            FlexJustify::End,
+            FlexAlign::Center,
+            false,
+            vec![sized(10.0, 10.0, &world), sized(30.0, 10.0, &world)],
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Limit(50.0), Limit(50.0)),
+            size(30.0, 50.0)
+        );
+        assert_eq!(offsets(&flex), vec![(10.0, 26.0), (0.0, 40.0)]);
+    }
+
+    #[test]
+    fn wrap_into_lines() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            5.0,
+            FlexJustify::Start,
+            FlexAlign::Start,
+            true,
+            children(5, 30.0, 10.0, &world),
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Limit(100.0), Hint(0.0)),
+            size(100.0, 25.0)
+        );
+        assert_eq!(
+            offsets(&flex),
+            vec![
+                (0.0, 0.0),
+                (35.0, 0.0),
+                (70.0, 0.0),
+                (0.0, 15.0),
+                (35.0, 15.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_with_justify_and_align() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            0.0,
+            FlexJustify::Center,
+            FlexAlign::End,
+            true,
+            vec![
+                sized(60.0, 10.0, &world),
+                sized(30.0, 20.0, &world),
+                sized(50.0, 10.0, &world),
+            ],
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Limit(100.0), Hint(0.0)),
+            size(100.0, 30.0)
+        );
+        assert_eq!(offsets(&flex), vec![(5.0, 10.0), (65.0, 0.0), (25.0, 20.0)]);
+    }
+
+    #[test]
+    fn wrap_without_limit_keeps_one_line() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            0.0,
+            FlexJustify::Start,
+            FlexAlign::Start,
+            true,
+            children(5, 30.0, 10.0, &world),
+            &world,
+        );
+        assert_eq!(layout(&mut flex, Hint(100.0), Hint(0.0)), size(150.0, 10.0));
+    }
+
+    #[test]
+    fn shrink_overflowing_children() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            0.0,
+            FlexJustify::Start,
+            FlexAlign::Start,
+            false,
+            vec![
+                sized(80.0, 10.0, &world).shrink(1.0),
+                sized(80.0, 10.0, &world).shrink(1.0),
+            ],
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Limit(100.0), Hint(0.0)),
+            size(100.0, 10.0)
+        );
+        assert_eq!(flex.layout[0], ((0.0, 0.0), size(50.0, 10.0)));
+        assert_eq!(flex.layout[1], ((50.0, 0.0), size(50.0, 10.0)));
+    }
+
+    #[test]
+    fn shrink_by_factor() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            0.0,
+            FlexJustify::Start,
+            FlexAlign::Start,
+            false,
+            vec![
+                sized(80.0, 10.0, &world),
+                sized(60.0, 10.0, &world).shrink(1.0),
+            ],
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Limit(100.0), Hint(0.0)),
+            size(100.0, 10.0)
+        );
+        assert_eq!(flex.layout[0].1, size(80.0, 10.0));
+        assert_eq!(flex.layout[1], ((80.0, 0.0), size(20.0, 10.0)));
+    }
+
+    #[test]
+    fn weights_fill_the_space_left_by_gaps() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut flex = flex(
+            FlexDirection::Row,
+            10.0,
+            FlexJustify::Center,
+            FlexAlign::Stretch,
+            false,
+            vec![
+                sized(20.0, 10.0, &world),
+                FlexChild::weighted(crate::Empty::new(world.clone()), 1.0),
+                FlexChild::weighted(crate::Empty::new(world.clone()), 3.0),
+            ],
+            &world,
+        );
+        assert_eq!(
+            layout(&mut flex, Limit(100.0), Limit(50.0)),
+            size(100.0, 10.0)
+        );
+        assert_eq!(
+            flex.layout,
+            vec![
+                ((0.0, 0.0), size(20.0, 10.0)),
+                ((30.0, 0.0), size(15.0, 10.0)),
+                ((55.0, 0.0), size(45.0, 10.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn replacing_a_child_makes_it_dirty() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let children: Rc<RefCell<Vec<Child>>> = Rc::new(RefCell::new(
+            children(2, 10.0, 10.0, &world)
+                .into_iter()
+                .map(FlexChild::into_rc_refcell)
+                .collect(),
+        ));
+        let mut flex = Flex::new(
+            {
+                let children = children.clone();
+                move |_: StateToParamsArgs<()>| FlexParams {
+                    direction: FlexDirection::Row,
+                    gap: 0.0,
+                    justify_content: FlexJustify::Start,
+                    align_items: FlexAlign::Start,
+                    wrap: false,
+                    children: children.borrow().clone(),
+                }
+            },
+            world.clone(),
+        );
+        let mut ctx = UiContext::new(
+            Rc::new(RefCell::new(EventManager::new())),
+            Box::new(SimpleLayoutCache::new()),
+            web_time::Instant::now(),
+        );
+        assert!(flex.update(&mut ctx, &()));
+        assert!(!flex.update(&mut ctx, &()));
+
+        // a new child of the same size, which may even reuse the allocation of the old one
+        children.borrow_mut().pop();
+        children
+            .borrow_mut()
+            .push(sized(10.0, 10.0, &world).into_rc_refcell());
+        assert!(flex.update(&mut ctx, &()));
+        assert!(!flex.update(&mut ctx, &()));
+    }
+}
@@ -0,0 +1,691 @@
+use crate::{
+    DynamicDimension, DynamicSize, Element, ElementId, ElementImpl, ElementSize, ElementWorld,
+    LayoutInputs, SizeConstraints, StateToParams, StateToParamsArgs, UiContext, into_box_impl,
+};
+use std::cell::RefCell;
+use std::rc::Rc;
+use vn_scene::{Rect, Scene};
+
+/// The size of a row or column of a [Grid].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridTrack {
+    /// A fixed size in pixels.
+    Fixed(f32),
+    /// A share of the space left over by the other tracks, relative to the other fractional
+    /// tracks. Behaves like `Auto` if the grid is not limited along that axis.
+    Fraction(f32),
+    /// Just large enough for the largest child in it.
+    Auto,
+}
+
+/// How a child is placed within its cell along one axis.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GridAlignment {
+    /// The child is forced to the size of the cell.
+    #[default]
+    Stretch,
+    Start,
+    Center,
+    End,
+}
+
+impl GridAlignment {
+    fn offset(self, cell: f32, child: f32) -> f32 {
+        match self {
+            GridAlignment::Stretch | GridAlignment::Start => 0.0,
+            GridAlignment::Center => (cell - child) / 2.0,
+            GridAlignment::End => cell - child,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GridParams<State: 'static, Message: 'static> {
+    /// Children placed beyond the defined tracks get additional `Auto` tracks.
+    pub rows: Vec<GridTrack>,
+    pub columns: Vec<GridTrack>,
+    pub row_gap: f32,
+    pub column_gap: f32,
+    pub children: Vec<Rc<RefCell<GridChild<State, Message>>>>,
+}
+
+pub struct GridChild<State: 'static, Message: 'static> {
+    pub element: Box<dyn Element<State = State, Message = Message>>,
+    pub row: usize,
+    pub column: usize,
+    pub row_span: usize,
+    pub column_span: usize,
+    pub align_x: GridAlignment,
+    pub align_y: GridAlignment,
+}
+
+impl<State: 'static, Message: 'static> GridChild<State, Message> {
+    pub fn new(
+        element: impl Into<Box<dyn Element<State = State, Message = Message>>>,
+        row: usize,
+        column: usize,
+    ) -> Self {
+        Self {
+            element: element.into(),
+            row,
+            column,
+            row_span: 1,
+            column_span: 1,
+            align_x: GridAlignment::Stretch,
+            align_y: GridAlignment::Stretch,
+        }
+    }
+
+    pub fn span(mut self, rows: usize, columns: usize) -> Self {
+        self.row_span = rows.max(1);
+        self.column_span = columns.max(1);
+        self
+    }
+
+    pub fn align(mut self, x: GridAlignment, y: GridAlignment) -> Self {
+        self.align_x = x;
+        self.align_y = y;
+        self
+    }
+
+    pub fn into_rc_refcell(self) -> Rc<RefCell<GridChild<State, Message>>> {
+        Rc::new(RefCell::new(self))
+    }
+}
+
+/// A child's extent along one axis: first track, number of tracks and its measured size.
+struct TrackSpan {
+    start: usize,
+    span: usize,
+    size: f32,
+}
+
+/// Computes the size of every track along one axis.
+fn size_tracks(
+    tracks: &[GridTrack],
+    gap: f32,
+    available: DynamicDimension,
+    spans: &[TrackSpan],
+) -> Vec<f32> {
+    let limit = available.to_option();
+    let sized_by_content = |track: &GridTrack| match track {
+        GridTrack::Fixed(_) => false,
+        GridTrack::Fraction(_) => limit.is_none(),
+        GridTrack::Auto => true,
+    };
+
+    let mut sizes = tracks
+        .iter()
+        .map(|track| match track {
+            GridTrack::Fixed(size) => *size,
+            _ => 0.0,
+        })
+        .collect::<Vec<_>>();
+
+    for span in spans.iter().filter(|span| span.span == 1) {
+        if sized_by_content(&tracks[span.start]) {
+            sizes[span.start] = sizes[span.start].max(span.size);
+        }
+    }
+
+    // children spanning multiple tracks grow the content sized tracks they span evenly
+    for span in spans.iter().filter(|span| span.span > 1) {
+        let range = span.start..span.start + span.span;
+        let current = sizes[range.clone()].iter().sum::<f32>() + gap * (span.span - 1) as f32;
+        let growable = range
+            .clone()
+            .filter(|&idx| sized_by_content(&tracks[idx]))
+            .collect::<Vec<_>>();
+        if span.size > current && !growable.is_empty() {
+            let grow_by = (span.size - current) / growable.len() as f32;
+            for idx in growable {
+                sizes[idx] += grow_by;
+            }
+        }
+    }
+
+    if let Some(limit) = limit {
+        let total_fraction: f32 = tracks
+            .iter()
+            .filter_map(|track| match track {
+                GridTrack::Fraction(fraction) => Some(*fraction),
+                _ => None,
+            })
+            .sum();
+
+        if total_fraction > 0.0 {
+            let used = sizes.iter().sum::<f32>() + gap * tracks.len().saturating_sub(1) as f32;
+            let remaining = (limit - used).max(0.0);
+            for (idx, track) in tracks.iter().enumerate() {
+                if let GridTrack::Fraction(fraction) = track {
+                    sizes[idx] = remaining * fraction / total_fraction;
+                }
+            }
+        }
+    }
+
+    sizes
+}
+
+/// Start of each track, followed by the end of the last one.
+fn track_offsets(sizes: &[f32], gap: f32) -> Vec<f32> {
+    let mut offsets = Vec::with_capacity(sizes.len() + 1);
+    let mut offset = 0.0;
+    for size in sizes {
+        offsets.push(offset);
+        offset += size + gap;
+    }
+    offsets.push((offset - gap).max(0.0));
+    offsets
+}
+
+/// The extent of `span` tracks starting at `start`, including the gaps between them.
+fn span_extent(offsets: &[f32], gap: f32, start: usize, span: usize) -> (f32, f32) {
+    let end = offsets[start + span];
+    let end = if start + span == offsets.len() - 1 {
+        end
+    } else {
+        end - gap
+    };
+    (offsets[start], (end - offsets[start]).max(0.0))
+}
+
+/// The tracks, extended by `Auto` tracks for children placed beyond them.
+fn all_tracks(tracks: &[GridTrack], needed: usize) -> Vec<GridTrack> {
+    let mut tracks = tracks.to_vec();
+    if tracks.len() < needed {
+        tracks.resize(needed, GridTrack::Auto);
+    }
+    tracks
+}
+
+// the children are compared by identity and placement
+type GridLayoutInputs = (
+    Vec<GridTrack>,
+    Vec<GridTrack>,
+    [f32; 2],
+    Vec<(ElementId, [usize; 4], [GridAlignment; 2])>,
+);
+
+/// Arranges its children in rows and columns. Children may span multiple cells.
+pub struct Grid<State: 'static, Message: 'static> {
+    id: ElementId,
+    /// offset from the grid's origin and size of each child
+    layout: Vec<((f32, f32), ElementSize)>,
+    params: StateToParams<State, GridParams<State, Message>>,
+    layout_inputs: LayoutInputs<GridLayoutInputs>,
+}
+
+impl<State: 'static, Message: 'static> Grid<State, Message> {
+    pub fn new<P: Into<StateToParams<State, GridParams<State, Message>>>>(
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        Self {
+            id: world.borrow_mut().next_id(),
+            layout: Vec::new(),
+            params: params.into(),
+            layout_inputs: LayoutInputs::new(),
+        }
+    }
+}
+
+impl<State, Message> ElementImpl for Grid<State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+
+        let placements = params
+            .children
+            .iter()
+            .map(|child| {
+                let child = child.borrow();
+                (
+                    child.row,
+                    child.row_span.max(1),
+                    child.column,
+                    child.column_span.max(1),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let rows = all_tracks(
+            &params.rows,
+            placements.iter().map(|p| p.0 + p.1).max().unwrap_or(0),
+        );
+        let columns = all_tracks(
+            &params.columns,
+            placements.iter().map(|p| p.2 + p.3).max().unwrap_or(0),
+        );
+
+        let unlimited = |dimension: DynamicDimension| DynamicDimension::Hint(dimension.value());
+
+        // columns first, children spanning only fixed columns already know their width
+        let column_spans = params
+            .children
+            .iter()
+            .zip(&placements)
+            .map(|(child, &(_, _, column, column_span))| {
+                let spanned = &columns[column..column + column_span];
+                let width = if spanned.iter().all(|t| matches!(t, GridTrack::Fixed(_))) {
+                    let fixed: f32 = spanned
+                        .iter()
+                        .map(|track| match track {
+                            GridTrack::Fixed(size) => *size,
+                            _ => 0.0,
+                        })
+                        .sum();
+                    DynamicDimension::Limit(fixed + params.column_gap * (column_span - 1) as f32)
+                } else {
+                    unlimited(constraints.max_size.width)
+                };
+
+                let size = child.borrow_mut().element.layout(
+                    ctx,
+                    state,
+                    SizeConstraints {
+                        min_size: ElementSize::ZERO,
+                        max_size: DynamicSize {
+                            width,
+                            height: unlimited(constraints.max_size.height),
+                        },
+                        scene_size: constraints.scene_size,
+                    },
+                );
+                TrackSpan {
+                    start: column,
+                    span: column_span,
+                    size: size.width,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let column_sizes = size_tracks(
+            &columns,
+            params.column_gap,
+            constraints.max_size.width,
+            &column_spans,
+        );
+        let column_offsets = track_offsets(&column_sizes, params.column_gap);
+
+        // then rows, with the children limited to the width of their cells
+        let row_spans = params
+            .children
+            .iter()
+            .zip(&placements)
+            .map(|(child, &(row, row_span, column, column_span))| {
+                let (_, width) =
+                    span_extent(&column_offsets, params.column_gap, column, column_span);
+                let size = child.borrow_mut().element.layout(
+                    ctx,
+                    state,
+                    SizeConstraints {
+                        min_size: ElementSize::ZERO,
+                        max_size: DynamicSize {
+                            width: DynamicDimension::Limit(width),
+                            height: unlimited(constraints.max_size.height),
+                        },
+                        scene_size: constraints.scene_size,
+                    },
+                );
+                TrackSpan {
+                    start: row,
+                    span: row_span,
+                    size: size.height,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let row_sizes = size_tracks(
+            &rows,
+            params.row_gap,
+            constraints.max_size.height,
+            &row_spans,
+        );
+        let row_offsets = track_offsets(&row_sizes, params.row_gap);
+
+        self.layout.clear();
+        for (child, &(row, row_span, column, column_span)) in
+            params.children.iter().zip(&placements)
+        {
+            let mut child = child.borrow_mut();
+            let (x, width) = span_extent(&column_offsets, params.column_gap, column, column_span);
+            let (y, height) = span_extent(&row_offsets, params.row_gap, row, row_span);
+
+            let min_size = ElementSize {
+                width: match child.align_x {
+                    GridAlignment::Stretch => width,
+                    _ => 0.0,
+                },
+                height: match child.align_y {
+                    GridAlignment::Stretch => height,
+                    _ => 0.0,
+                },
+            };
+            let child_constraints = SizeConstraints {
+                min_size,
+                max_size: DynamicSize {
+                    width: DynamicDimension::Limit(width),
+                    height: DynamicDimension::Limit(height),
+                },
+                scene_size: constraints.scene_size,
+            };
+
+            let child_size = child
+                .element
+                .layout(ctx, state, child_constraints)
+                .clamp_to_constraints(child_constraints);
+
+            self.layout.push((
+                (
+                    x + child.align_x.offset(width, child_size.width),
+                    y + child.align_y.offset(height, child_size.height),
+                ),
+                child_size,
+            ));
+        }
+
+        ElementSize {
+            width: column_offsets[column_sizes.len()],
+            height: row_offsets[row_sizes.len()],
+        }
+        .clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let children = params
+            .children
+            .iter()
+            .map(|child| {
+                let placement = child.borrow();
+                (
+                    placement.element.id(),
+                    [
+                        placement.row,
+                        placement.column,
+                        placement.row_span,
+                        placement.column_span,
+                    ],
+                    [placement.align_x, placement.align_y],
+                )
+            })
+            .collect();
+        let mut dirty = self.layout_inputs.changed((
+            params.rows.clone(),
+            params.columns.clone(),
+            [params.row_gap, params.column_gap],
+            children,
+        ));
+        for child in &params.children {
+            dirty |= child.borrow_mut().element.update(ctx, state);
+        }
+        dirty
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        origin: (f32, f32),
+        size: ElementSize,
+        canvas: &mut dyn Scene,
+    ) {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+
+        ctx.with_clipping(
+            Rect {
+                position: [origin.0, origin.1],
+                size: [size.width, size.height],
+            },
+            |ctx| {
+                for (child, (offset, child_size)) in params.children.iter().zip(&self.layout) {
+                    child.borrow_mut().element.draw(
+                        ctx,
+                        state,
+                        (origin.0 + offset.0, origin.1 + offset.1),
+                        *child_size,
+                        canvas,
+                    );
+                }
+            },
+        )
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        event: &crate::InteractionEvent,
+    ) -> Vec<Self::Message> {
+        let params = self.params.call(StateToParamsArgs {
+            ctx,
+            state,
+            id: self.id,
+        });
+
+        let mut messages = Vec::new();
+        for child in &mut params.children.iter() {
+            messages.extend(child.borrow_mut().element.handle_event(ctx, state, event));
+        }
+        messages
+    }
+}
+
+into_box_impl!(Grid);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventManager, PreferSize, PreferSizeParams, SimpleLayoutCache};
+    use DynamicDimension::{Hint, Limit};
+
+    fn span(start: usize, span: usize, size: f32) -> TrackSpan {
+        TrackSpan { start, span, size }
+    }
+
+    fn sized(width: f32, height: f32, world: &Rc<RefCell<ElementWorld>>) -> PreferSize<(), ()> {
+        PreferSize::new(
+            crate::Empty::new(world.clone()),
+            move |_: StateToParamsArgs<()>| PreferSizeParams {
+                width: Some(width),
+                height: Some(height),
+            },
+            world.clone(),
+        )
+    }
+
+    fn grid(
+        rows: Vec<GridTrack>,
+        columns: Vec<GridTrack>,
+        gap: f32,
+        children: Vec<GridChild<(), ()>>,
+        world: &Rc<RefCell<ElementWorld>>,
+    ) -> Grid<(), ()> {
+        let children: Vec<Rc<RefCell<GridChild<(), ()>>>> = children
+            .into_iter()
+            .map(GridChild::into_rc_refcell)
+            .collect();
+        Grid::new(
+            move |_: StateToParamsArgs<()>| GridParams {
+                rows: rows.clone(),
+                columns: columns.clone(),
+                row_gap: gap,
+                column_gap: gap,
+                children: children.clone(),
+            },
+            world.clone(),
+        )
+    }
+
+    fn layout(
+        grid: &mut Grid<(), ()>,
+        width: DynamicDimension,
+        height: DynamicDimension,
+    ) -> ElementSize {
+        let mut ctx = UiContext::new(
+            Rc::new(RefCell::new(EventManager::new())),
+            Box::new(SimpleLayoutCache::new()),
+            web_time::Instant::now(),
+        );
+        grid.layout(
+            &mut ctx,
+            &(),
+            SizeConstraints {
+                min_size: ElementSize::ZERO,
+                max_size: DynamicSize { width, height },
+                scene_size: (1000.0, 1000.0),
+            },
+        )
+    }
+
+    fn size(width: f32, height: f32) -> ElementSize {
+        ElementSize { width, height }
+    }
+
+    #[test]
+    fn fixed_fraction_and_auto_tracks() {
+        let tracks = [
+            GridTrack::Fixed(20.0),
+            GridTrack::Fraction(1.0),
+            GridTrack::Auto,
+            GridTrack::Fraction(3.0),
+        ];
+        let spans = [span(0, 1, 50.0), span(1, 1, 10.0), span(2, 1, 15.0)];
+
+        // fixed tracks ignore their children, fractions share what's left of the limit
+        assert_eq!(
+            size_tracks(&tracks, 5.0, Limit(130.0), &spans),
+            vec![20.0, 20.0, 15.0, 60.0]
+        );
+        // nothing left to share
+        assert_eq!(
+            size_tracks(&tracks, 5.0, Limit(10.0), &spans),
+            vec![20.0, 0.0, 15.0, 0.0]
+        );
+        // without a limit, fractions are sized by content like auto tracks
+        assert_eq!(
+            size_tracks(&tracks, 5.0, Hint(130.0), &spans),
+            vec![20.0, 10.0, 15.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn spans_grow_content_sized_tracks() {
+        let tracks = [GridTrack::Fixed(10.0), GridTrack::Auto, GridTrack::Auto];
+        // 10 + 5 + 0 + 5 + 0 = 20 of the 50 needed are there, the auto tracks split the rest
+        let spans = [span(0, 3, 50.0)];
+        assert_eq!(
+            size_tracks(&tracks, 5.0, Hint(0.0), &spans),
+            vec![10.0, 15.0, 15.0]
+        );
+
+        // single track children are sized first, spans only add what's still missing
+        let spans = [span(0, 3, 50.0), span(1, 1, 25.0)];
+        assert_eq!(
+            size_tracks(&tracks, 5.0, Hint(0.0), &spans),
+            vec![10.0, 27.5, 2.5]
+        );
+
+        // spans that fit or only cover fixed tracks change nothing
+        let spans = [span(0, 2, 5.0), span(0, 1, 100.0)];
+        assert_eq!(
+            size_tracks(&tracks, 5.0, Hint(0.0), &spans),
+            vec![10.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn offsets_and_extents() {
+        let offsets = track_offsets(&[10.0, 20.0, 30.0], 5.0);
+        assert_eq!(offsets, vec![0.0, 15.0, 40.0, 70.0]);
+        assert_eq!(track_offsets(&[], 5.0), vec![0.0]);
+
+        assert_eq!(span_extent(&offsets, 5.0, 0, 1), (0.0, 10.0));
+        assert_eq!(span_extent(&offsets, 5.0, 1, 1), (15.0, 20.0));
+        // the last track has no gap after it
+        assert_eq!(span_extent(&offsets, 5.0, 2, 1), (40.0, 30.0));
+        // gaps between spanned tracks count
+        assert_eq!(span_extent(&offsets, 5.0, 0, 2), (0.0, 35.0));
+        assert_eq!(span_extent(&offsets, 5.0, 0, 3), (0.0, 70.0));
+    }
+
+    #[test]
+    fn layout_with_spans() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut grid = grid(
+            vec![GridTrack::Fixed(10.0)],
+            vec![GridTrack::Fixed(20.0), GridTrack::Fraction(1.0)],
+            5.0,
+            vec![
+                GridChild::new(sized(10.0, 10.0, &world), 0, 0),
+                GridChild::new(sized(10.0, 10.0, &world), 0, 1),
+                // an extra auto row
+                GridChild::new(sized(10.0, 30.0, &world), 1, 0).span(1, 2),
+            ],
+            &world,
+        );
+        assert_eq!(
+            layout(&mut grid, Limit(100.0), Hint(0.0)),
+            size(100.0, 45.0)
+        );
+        assert_eq!(
+            grid.layout,
+            vec![
+                ((0.0, 0.0), size(20.0, 10.0)),
+                ((25.0, 0.0), size(75.0, 10.0)),
+                ((0.0, 15.0), size(100.0, 30.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn alignment_in_cells() {
+        let cases = [
+            (GridAlignment::Stretch, (0.0, 0.0), size(40.0, 30.0)),
+            (GridAlignment::Start, (0.0, 0.0), size(10.0, 10.0)),
+            (GridAlignment::Center, (15.0, 10.0), size(10.0, 10.0)),
+            (GridAlignment::End, (30.0, 20.0), size(10.0, 10.0)),
+        ];
+
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        for (alignment, offset, child_size) in cases {
+            let mut grid = grid(
+                vec![GridTrack::Fixed(30.0)],
+                vec![GridTrack::Fixed(40.0)],
+                0.0,
+                vec![GridChild::new(sized(10.0, 10.0, &world), 0, 0).align(alignment, alignment)],
+                &world,
+            );
+            assert_eq!(
+                layout(&mut grid, Hint(0.0), Hint(0.0)),
+                size(40.0, 30.0),
+                "{alignment:?}"
+            );
+            assert_eq!(grid.layout, vec![(offset, child_size)], "{alignment:?}");
+        }
+    }
+}
@@ -52,6 +52,10 @@ impl<State, Message> ElementImpl for Interactive<State, Message> {
         self.child.layout(ctx, state, constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        self.child.update(ctx, state)
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
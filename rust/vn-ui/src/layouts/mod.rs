@@ -1,17 +1,23 @@
 mod anchor;
+mod draw_cache;
 mod fill;
 mod flex;
+mod grid;
 mod interactive;
 mod padding;
 mod prefer_size;
 mod scroll_area;
 mod stack;
+mod virtual_list;
 
 pub use anchor::*;
+pub use draw_cache::*;
 pub use fill::*;
 pub use flex::*;
+pub use grid::*;
 pub use interactive::*;
 pub use padding::*;
 pub use prefer_size::*;
 pub use scroll_area::*;
 pub use stack::*;
+pub use virtual_list::*;
@@ -1,6 +1,6 @@
 use crate::{
-    Element, ElementId, ElementImpl, ElementSize, ElementWorld, SizeConstraints, StateToParams,
-    UiContext, into_box_impl,
+    Element, ElementId, ElementImpl, ElementSize, ElementWorld, LayoutInputs, SizeConstraints,
+    StateToParams, UiContext, into_box_impl,
 };
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -84,6 +84,7 @@ pub struct Padding<State: 'static, Message: 'static> {
     id: ElementId,
     child: Box<dyn Element<State = State, Message = Message>>,
     params: StateToParams<State, PaddingParams>,
+    layout_inputs: LayoutInputs<[f32; 4]>,
 }
 
 impl<State, Message> Padding<State, Message> {
@@ -96,6 +97,7 @@ impl<State, Message> Padding<State, Message> {
             id: world.borrow_mut().next_id(),
             child: child.into(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
         }
     }
 }
@@ -147,6 +149,21 @@ impl<State, Message> ElementImpl for Padding<State, Message> {
         .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self.layout_inputs.changed([
+            params.pad_left,
+            params.pad_right,
+            params.pad_top,
+            params.pad_bottom,
+        ]);
+        self.child.update(ctx, state) || changed
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
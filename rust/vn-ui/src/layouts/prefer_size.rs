@@ -1,6 +1,7 @@
 use crate::{
     DynamicDimension, DynamicSize, Element, ElementId, ElementImpl, ElementSize, ElementWorld,
-    InteractionEvent, SizeConstraints, StateToParams, StateToParamsArgs, UiContext, into_box_impl,
+    InteractionEvent, LayoutInputs, SizeConstraints, StateToParams, StateToParamsArgs, UiContext,
+    into_box_impl,
 };
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -15,6 +16,7 @@ pub struct PreferSize<State: 'static, Message> {
     id: ElementId,
     child: Box<dyn Element<State = State, Message = Message>>,
     params: StateToParams<State, PreferSizeParams>,
+    layout_inputs: LayoutInputs<(Option<f32>, Option<f32>)>,
 }
 
 impl<State: 'static, Message> PreferSize<State, Message> {
@@ -27,6 +29,7 @@ impl<State: 'static, Message> PreferSize<State, Message> {
             id: world.borrow_mut().next_id(),
             child: child.into(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
         }
     }
 }
@@ -79,6 +82,16 @@ impl<State, Message> ElementImpl for PreferSize<State, Message> {
         size
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self.layout_inputs.changed((params.width, params.height));
+        self.child.update(ctx, state) || changed
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -1,9 +1,10 @@
 use crate::utils::ToArray;
 use crate::{
     DynamicDimension, Element, ElementId, ElementImpl, ElementSize, ElementWorld, EventHandler,
-    ScrollAreaAction, SizeConstraints, StateToParams, UiContext, into_box_impl,
+    LayoutInputs, ScrollAreaAction, SizeConstraints, StateToParams, Theme, UiContext,
+    into_box_impl,
 };
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use vn_scene::{BoxPrimitiveData, Color, Rect, Scene, Transform};
 
@@ -15,6 +16,17 @@ pub struct ScrollBarParams {
     pub color: Color,
 }
 
+impl ScrollBarParams {
+    pub fn themed(theme: &Theme, position: Option<f32>) -> Self {
+        Self {
+            position,
+            width: theme.scroll_bar.width,
+            margin: theme.scroll_bar.margin,
+            color: theme.scroll_bar.color,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ScrollAreaParams<Message> {
     pub scroll_x: ScrollBarParams,
@@ -22,21 +34,17 @@ pub struct ScrollAreaParams<Message> {
     pub scroll_action_handler: EventHandler<ScrollAreaAction, Message>,
 }
 
-struct DragState {
-    id: ElementId,
-    initial_scroll: f32,
-    initial_mouse: f32,
-}
-
 pub struct ScrollArea<State: 'static, Message: 'static> {
     id: ElementId,
     scroll_v_id: ElementId,
     scroll_h_id: ElementId,
     child: Box<dyn Element<State = State, Message = Message>>,
     params: StateToParams<State, ScrollAreaParams<Message>>,
+    layout_inputs: LayoutInputs<[f32; 4]>,
     child_size: ElementSize,
     viewport_size: ElementSize,
-    drag_state: RefCell<Option<DragState>>,
+    // scroll position when dragging a scroll bar started
+    drag_start_scroll: Cell<f32>,
 }
 
 impl<State, Message: Clone> ScrollArea<State, Message> {
@@ -52,9 +60,10 @@ impl<State, Message: Clone> ScrollArea<State, Message> {
             scroll_h_id: world.next_id(),
             child: child.into(),
             params: params.into(),
+            layout_inputs: LayoutInputs::new(),
             child_size: ElementSize::ZERO,
             viewport_size: ElementSize::ZERO,
-            drag_state: RefCell::new(None),
+            drag_start_scroll: Cell::new(0.0),
         }
     }
 }
@@ -101,6 +110,21 @@ impl<State, Message: Clone> ElementImpl for ScrollArea<State, Message> {
         size
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(crate::StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self.layout_inputs.changed([
+            params.scroll_x.width,
+            params.scroll_x.margin,
+            params.scroll_y.width,
+            params.scroll_y.margin,
+        ]);
+        self.child.update(ctx, state) || changed
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
@@ -200,7 +224,9 @@ impl<State, Message: Clone> ElementImpl for ScrollArea<State, Message> {
                             self.scroll_v_id,
                             scene.current_layer_id(),
                             scrollbar_rect,
-                            |_| {},
+                            |ctx| {
+                                ctx.register_draggable_with_threshold(self.scroll_v_id, None, 0.0)
+                            },
                         );
 
                         scene.add_box(BoxPrimitiveData {
@@ -252,7 +278,9 @@ impl<State, Message: Clone> ElementImpl for ScrollArea<State, Message> {
                             self.scroll_h_id,
                             scene.current_layer_id(),
                             scrollbar_rect,
-                            |_| {},
+                            |ctx| {
+                                ctx.register_draggable_with_threshold(self.scroll_h_id, None, 0.0)
+                            },
                         );
 
                         scene.add_box(BoxPrimitiveData {
@@ -302,68 +330,76 @@ impl<State, Message: Clone> ElementImpl for ScrollArea<State, Message> {
             params
                 .scroll_action_handler
                 .handle(self.id, event, || match &event.kind {
-                    crate::InteractionEventKind::MouseMove { x, y, .. } => {
-                        if let Some(drag) = self.drag_state.borrow().as_ref() {
-                            if drag.id == self.scroll_v_id {
-                                let delta_mouse = y - drag.initial_mouse;
-                                let scroll_ratio =
-                                    self.child_size.height / self.viewport_size.height;
-                                let new_scroll = drag.initial_scroll + delta_mouse * scroll_ratio;
-                                return vec![ScrollAreaAction::ScrollY(new_scroll.clamp(
-                                    0.0,
-                                    (self.child_size.height - self.viewport_size.height).max(0.0),
-                                ))];
-                            } else if drag.id == self.scroll_h_id {
-                                let delta_mouse = x - drag.initial_mouse;
-                                let scroll_ratio = self.child_size.width / self.viewport_size.width;
-                                let new_scroll = drag.initial_scroll + delta_mouse * scroll_ratio;
-                                return vec![ScrollAreaAction::ScrollX(new_scroll.clamp(
-                                    0.0,
-                                    (self.child_size.width - self.viewport_size.width).max(0.0),
-                                ))];
-                            }
+                    crate::InteractionEventKind::DragStart { .. } => {
+                        if event.reaches(self.scroll_v_id) {
+                            self.drag_start_scroll
+                                .set(params.scroll_y.position.unwrap_or(0.0));
+                        } else if event.reaches(self.scroll_h_id) {
+                            self.drag_start_scroll
+                                .set(params.scroll_x.position.unwrap_or(0.0));
                         }
                         vec![]
                     }
-                    crate::InteractionEventKind::MouseScroll { y } => {
-                        if ctx.event_manager.borrow().is_hovered(self.id) {
-                            let current = params.scroll_y.position.unwrap_or(0.0);
-                            let next = (current - y).clamp(
+                    crate::InteractionEventKind::DragMove {
+                        x,
+                        y,
+                        start_x,
+                        start_y,
+                    } => {
+                        if event.reaches(self.scroll_v_id) {
+                            let scroll_ratio = self.child_size.height / self.viewport_size.height;
+                            let new_scroll =
+                                self.drag_start_scroll.get() + (y - start_y) * scroll_ratio;
+                            return vec![ScrollAreaAction::ScrollY(new_scroll.clamp(
                                 0.0,
                                 (self.child_size.height - self.viewport_size.height).max(0.0),
+                            ))];
+                        } else if event.reaches(self.scroll_h_id) {
+                            let scroll_ratio = self.child_size.width / self.viewport_size.width;
+                            let new_scroll =
+                                self.drag_start_scroll.get() + (x - start_x) * scroll_ratio;
+                            return vec![ScrollAreaAction::ScrollX(new_scroll.clamp(
+                                0.0,
+                                (self.child_size.width - self.viewport_size.width).max(0.0),
+                            ))];
+                        }
+                        vec![]
+                    }
+                    // wheel events hit the element under the cursor and bubble up to the
+                    // nearest scroll area; at its limits they bubble on to outer scroll areas
+                    crate::InteractionEventKind::MouseScroll { delta, .. }
+                        if event.reaches(self.id) =>
+                    {
+                        let (delta_x, delta_y) = delta.to_pixels();
+                        let mut actions = Vec::new();
+
+                        if let Some(current) = params.scroll_x.position {
+                            let next = (current - delta_x).clamp(
+                                0.0,
+                                (self.child_size.width - self.viewport_size.width).max(0.0),
                             );
-
                             if current != next {
-                                return vec![ScrollAreaAction::ScrollY(next)];
+                                actions.push(ScrollAreaAction::ScrollX(next));
                             }
                         }
-                        vec![]
+                        if let Some(current) = params.scroll_y.position {
+                            let next = (current - delta_y).clamp(
+                                0.0,
+                                (self.child_size.height - self.viewport_size.height).max(0.0),
+                            );
+                            if current != next {
+                                actions.push(ScrollAreaAction::ScrollY(next));
+                            }
+                        }
+
+                        if !actions.is_empty() {
+                            event.stop_propagation();
+                        }
+                        actions
                     }
                     _ => vec![],
                 });
 
-        match &event.kind {
-            crate::InteractionEventKind::MouseDown { x, y, .. } => {
-                if event.target == Some(self.scroll_v_id) {
-                    *self.drag_state.borrow_mut() = Some(DragState {
-                        id: self.scroll_v_id,
-                        initial_scroll: params.scroll_y.position.unwrap_or(0.0),
-                        initial_mouse: *y,
-                    });
-                } else if event.target == Some(self.scroll_h_id) {
-                    *self.drag_state.borrow_mut() = Some(DragState {
-                        id: self.scroll_h_id,
-                        initial_scroll: params.scroll_x.position.unwrap_or(0.0),
-                        initial_mouse: *x,
-                    });
-                }
-            }
-            crate::InteractionEventKind::MouseUp { .. } => {
-                *self.drag_state.borrow_mut() = None;
-            }
-            _ => {}
-        }
-
         messages.extend(self.child.handle_event(ctx, state, event));
         messages
     }
@@ -394,3 +430,114 @@ where
 }
 
 into_box_impl!(ScrollArea);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, context};
+    use crate::{
+        DynamicSize, Empty, InteractionEventKind, MouseButton, PreferSize, PreferSizeParams,
+        StateToParamsArgs,
+    };
+    use web_time::Instant;
+
+    // a 100x400 child in a 100x100 area, scrolled to the top
+    fn scroll_area(world: &Rc<RefCell<ElementWorld>>) -> ScrollArea<(), ScrollAreaAction> {
+        let bar = ScrollBarParams {
+            position: Some(0.0),
+            width: 16.0,
+            margin: 8.0,
+            color: Color::WHITE,
+        };
+        ScrollArea::new(
+            PreferSize::new(
+                Empty::new(world.clone()),
+                |_: StateToParamsArgs<()>| PreferSizeParams {
+                    width: Some(100.0),
+                    height: Some(400.0),
+                },
+                world.clone(),
+            ),
+            move |_: StateToParamsArgs<()>| ScrollAreaParams {
+                scroll_x: bar,
+                scroll_y: bar,
+                scroll_action_handler: EventHandler::new(|_, action| vec![action]),
+            },
+            world.clone(),
+        )
+    }
+
+    fn mouse(
+        ctx: &mut UiContext,
+        area: &mut ScrollArea<(), ScrollAreaAction>,
+        kind: InteractionEventKind,
+    ) -> (Vec<InteractionEventKind>, Vec<ScrollAreaAction>) {
+        ctx.event_manager.borrow_mut().queue_event(kind);
+        let events = ctx
+            .event_manager
+            .borrow_mut()
+            .process_events(Instant::now());
+        let kinds = events.iter().map(|event| event.kind.clone()).collect();
+        let actions = events
+            .iter()
+            .flat_map(|event| area.handle_event(ctx, &(), event))
+            .collect();
+        (kinds, actions)
+    }
+
+    #[test]
+    fn thumb_follows_the_cursor_right_away() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let mut area = scroll_area(&world);
+        let mut ctx = context();
+        let size = area.layout(
+            &mut ctx,
+            &(),
+            SizeConstraints {
+                min_size: ElementSize::ZERO,
+                max_size: DynamicSize {
+                    width: DynamicDimension::Limit(100.0),
+                    height: DynamicDimension::Limit(100.0),
+                },
+                scene_size: (1000.0, 1000.0),
+            },
+        );
+        area.draw(&mut ctx, &(), (0.0, 0.0), size, &mut TestScene::new());
+
+        // the vertical thumb is 16 wide, 8 right of the 76 wide viewport
+        let (kinds, actions) = mouse(
+            &mut ctx,
+            &mut area,
+            InteractionEventKind::MouseDown {
+                button: MouseButton::Left,
+                x: 90.0,
+                y: 5.0,
+                local_x: 90.0,
+                local_y: 5.0,
+            },
+        );
+        assert!(
+            kinds
+                .iter()
+                .any(|kind| matches!(kind, InteractionEventKind::DragStart { .. }))
+        );
+        assert!(matches!(actions[..], [ScrollAreaAction::ScrollY(y)] if y == 0.0));
+
+        // well below the drag threshold
+        let (_, actions) = mouse(
+            &mut ctx,
+            &mut area,
+            InteractionEventKind::MouseMove {
+                x: 90.0,
+                y: 7.0,
+                local_x: 90.0,
+                local_y: 7.0,
+            },
+        );
+        let expected = 2.0 * 400.0 / 76.0;
+        assert!(
+            matches!(actions[..], [ScrollAreaAction::ScrollY(y)] if (y - expected).abs() < 1e-4),
+            "{actions:?}"
+        );
+    }
+}
@@ -58,6 +58,14 @@ impl<State, Message> ElementImpl for Stack<State, Message> {
         .clamp_to_constraints(constraints)
     }
 
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let mut dirty = false;
+        for child in &mut self.children {
+            dirty |= child.update(ctx, state);
+        }
+        dirty
+    }
+
     fn draw_impl(
         &mut self,
         ctx: &mut UiContext,
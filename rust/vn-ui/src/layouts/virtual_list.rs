@@ -0,0 +1,668 @@
+use crate::utils::ToArray;
+use crate::{
+    DynamicDimension, DynamicSize, Element, ElementId, ElementImpl, ElementSize, ElementWorld,
+    LayoutInputs, SizeConstraints, StateToParams, StateToParamsArgs, UiContext, into_box_impl,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+use vn_scene::{Rect, Scene};
+
+/// Builds the element of the item at an index.
+pub trait ItemBuilderFn<State, Message>:
+    Fn(usize, Rc<RefCell<ElementWorld>>) -> Box<dyn Element<State = State, Message = Message>> + 'static
+{
+}
+
+impl<State, Message, F> ItemBuilderFn<State, Message> for F where
+    F: Fn(usize, Rc<RefCell<ElementWorld>>) -> Box<dyn Element<State = State, Message = Message>>
+        + 'static
+{
+}
+
+/// Rows of items kept around the visible ones, so scrolling back and forth a little doesn't
+/// build them again.
+const OVERSCAN: usize = 2;
+
+/// The items of a virtualized element. Items are built the first time they become visible and
+/// kept while they are within [OVERSCAN] rows of the visible ones. Items that are built again,
+/// after scrolling back or a new revision, get the `ElementId`s they had before, so each index
+/// keeps its ids (and with them focus and hover) as long as it exists. This expects the builder
+/// to create the same number of elements for an index every time.
+struct VirtualItems<State: 'static, Message: 'static> {
+    world: Rc<RefCell<ElementWorld>>,
+    builder: Box<dyn ItemBuilderFn<State, Message>>,
+    items: HashMap<usize, Box<dyn Element<State = State, Message = Message>>>,
+    /// the ids the builder used for an index
+    ids: HashMap<usize, Range<u32>>,
+    /// indices drawn in the last frame, in order
+    visible: Vec<usize>,
+    scene_size: (f32, f32),
+    revision: u64,
+}
+
+impl<State: 'static, Message: 'static> VirtualItems<State, Message> {
+    fn new(builder: impl ItemBuilderFn<State, Message>, world: Rc<RefCell<ElementWorld>>) -> Self {
+        Self {
+            world,
+            builder: Box::new(builder),
+            items: HashMap::new(),
+            ids: HashMap::new(),
+            visible: Vec::new(),
+            scene_size: (0.0, 0.0),
+            revision: 0,
+        }
+    }
+
+    /// Updates the built items and returns whether any of them changed. Items of an outdated
+    /// `revision` are dropped, to be built again.
+    fn update(&mut self, ctx: &mut UiContext, state: &State, revision: u64) -> bool {
+        if revision != self.revision {
+            self.revision = revision;
+            self.items.clear();
+            self.visible.clear();
+            return true;
+        }
+        let mut dirty = false;
+        for item in self.items.values_mut() {
+            dirty |= item.update(ctx, state);
+        }
+        dirty
+    }
+
+    /// Called while laying out the element holding the items. Drops the items and ids of indices
+    /// that don't exist anymore.
+    fn layout(&mut self, count: usize, constraints: SizeConstraints) {
+        self.scene_size = constraints.scene_size;
+        self.items.retain(|idx, _| *idx < count);
+        self.ids.retain(|idx, _| *idx < count);
+        self.visible.retain(|idx| *idx < count);
+    }
+
+    /// Lays out and draws the items in `indices`, placing each at the origin `position` returns.
+    fn draw(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &State,
+        indices: impl Iterator<Item = usize>,
+        item_size: ElementSize,
+        position: impl Fn(usize) -> (f32, f32),
+        canvas: &mut dyn Scene,
+    ) {
+        let constraints = SizeConstraints {
+            min_size: item_size,
+            max_size: DynamicSize {
+                width: DynamicDimension::Limit(item_size.width),
+                height: DynamicDimension::Limit(item_size.height),
+            },
+            scene_size: self.scene_size,
+        };
+
+        self.visible.clear();
+        for idx in indices {
+            if !self.items.contains_key(&idx) {
+                let item = self.build(idx);
+                self.items.insert(idx, item);
+            }
+            let item = self.items.get_mut(&idx).unwrap();
+
+            let size = item
+                .layout(ctx, state, constraints)
+                .clamp_to_constraints(constraints);
+            item.draw(ctx, state, position(idx), size, canvas);
+            self.visible.push(idx);
+        }
+    }
+
+    /// Builds the item at `idx` with the ids it had before, if it was built before.
+    fn build(&mut self, idx: usize) -> Box<dyn Element<State = State, Message = Message>> {
+        match self.ids.get(&idx) {
+            Some(ids) => {
+                self.world.borrow_mut().reuse(ids.clone());
+                let item = (self.builder)(idx, self.world.clone());
+                self.world.borrow_mut().end_reuse();
+                item
+            }
+            None => {
+                let start = self.world.borrow().peek_id();
+                let item = (self.builder)(idx, self.world.clone());
+                self.ids.insert(idx, start..self.world.borrow().peek_id());
+                item
+            }
+        }
+    }
+
+    /// Drops the items outside of `keep`, called after drawing.
+    fn evict(&mut self, keep: std::ops::Range<usize>) {
+        self.items.retain(|idx, _| keep.contains(idx));
+    }
+
+    fn handle_event(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &State,
+        event: &crate::InteractionEvent,
+    ) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for idx in &self.visible {
+            if let Some(item) = self.items.get_mut(idx) {
+                messages.extend(item.handle_event(ctx, state, event));
+            }
+        }
+        messages
+    }
+}
+
+/// The part of `bounds` that is not clipped away, relative to the origin of `bounds`.
+fn visible_part(ctx: &UiContext, bounds: Rect) -> Rect {
+    let visible = bounds.intersect(&ctx.clip_rect);
+    Rect {
+        position: [
+            visible.position[0] - bounds.position[0],
+            visible.position[1] - bounds.position[1],
+        ],
+        size: visible.size,
+    }
+}
+
+/// `tracks` and up to [OVERSCAN] tracks before and after them, of `count` tracks.
+fn overscan(tracks: &std::ops::Range<usize>, count: usize) -> std::ops::Range<usize> {
+    tracks.start.saturating_sub(OVERSCAN)..(tracks.end + OVERSCAN).min(count)
+}
+
+/// The tracks of size `stride` overlapping `start..end`.
+fn visible_tracks(start: f32, end: f32, stride: f32, count: usize) -> std::ops::Range<usize> {
+    if stride <= 0.0 || end <= start {
+        return 0..0;
+    }
+    let first = (start / stride).floor().max(0.0) as usize;
+    let last = (end / stride).ceil().max(0.0) as usize;
+    first.min(count)..last.min(count)
+}
+
+pub struct VirtualListParams {
+    pub count: usize,
+    /// Every item is laid out with this height, and the width of the list if it is limited
+    /// horizontally, this width otherwise.
+    pub item_size: ElementSize,
+    pub gap: f32,
+    /// Changing it drops all items to build them again, e.g. after the items were reordered and
+    /// the elements built for an index show the wrong data.
+    pub revision: u64,
+}
+
+/// A vertical list of `count` items of the same size. Only the items within the visible part of
+/// the list (e.g. within the viewport of a `ScrollArea`) are built, laid out and drawn.
+pub struct VirtualList<State: 'static, Message: 'static> {
+    id: ElementId,
+    params: StateToParams<State, VirtualListParams>,
+    items: VirtualItems<State, Message>,
+    width: f32,
+    layout_inputs: LayoutInputs<(usize, ElementSize, f32)>,
+}
+
+impl<State: 'static, Message: 'static> VirtualList<State, Message> {
+    /// `builder` creates the element of the item at an index when it becomes visible for the
+    /// first time.
+    pub fn new<P: Into<StateToParams<State, VirtualListParams>>>(
+        builder: impl ItemBuilderFn<State, Message>,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        let id = world.borrow_mut().next_id();
+        Self {
+            id,
+            params: params.into(),
+            items: VirtualItems::new(builder, world),
+            width: 0.0,
+            layout_inputs: LayoutInputs::new(),
+        }
+    }
+}
+
+impl<State, Message> ElementImpl for VirtualList<State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        self.items.layout(params.count, constraints);
+
+        // the items themselves are laid out while drawing, once it is known which are visible
+        self.width = constraints.max_size.width.unwrap_or(params.item_size.width);
+        let height = params.count as f32 * (params.item_size.height + params.gap);
+
+        ElementSize {
+            width: self.width,
+            height: (height - params.gap).max(0.0),
+        }
+        .clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self
+            .layout_inputs
+            .changed((params.count, params.item_size, params.gap));
+        self.items.update(ctx, state, params.revision) || changed
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        origin: (f32, f32),
+        size: ElementSize,
+        canvas: &mut dyn Scene,
+    ) {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+
+        let bounds = Rect {
+            position: origin.to_array(),
+            size: size.to_array(),
+        };
+        let visible = visible_part(ctx, bounds);
+        let stride = params.item_size.height + params.gap;
+        let rows = visible_tracks(
+            visible.position[1],
+            visible.position[1] + visible.size[1],
+            stride,
+            params.count,
+        );
+
+        let item_size = ElementSize {
+            width: self.width,
+            height: params.item_size.height,
+        };
+        let keep = overscan(&rows, params.count);
+        ctx.with_clipping(bounds, |ctx| {
+            self.items.draw(
+                ctx,
+                state,
+                rows,
+                item_size,
+                |idx| (origin.0, origin.1 + idx as f32 * stride),
+                canvas,
+            );
+        });
+        self.items.evict(keep);
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        event: &crate::InteractionEvent,
+    ) -> Vec<Self::Message> {
+        self.items.handle_event(ctx, state, event)
+    }
+}
+
+into_box_impl!(VirtualList);
+
+pub struct VirtualGridParams {
+    pub count: usize,
+    pub item_size: ElementSize,
+    /// Items per row. If `None`, as many as fit into the width of the grid, which is only known
+    /// if the grid is limited horizontally (a `ScrollArea` does not limit its child).
+    pub columns: Option<usize>,
+    pub gap: f32,
+    /// See [VirtualListParams::revision].
+    pub revision: u64,
+}
+
+/// Like a [VirtualList], but with items arranged in rows of the same number of columns.
+pub struct VirtualGrid<State: 'static, Message: 'static> {
+    id: ElementId,
+    params: StateToParams<State, VirtualGridParams>,
+    items: VirtualItems<State, Message>,
+    columns: usize,
+    layout_inputs: LayoutInputs<(usize, ElementSize, Option<usize>, f32)>,
+}
+
+impl<State: 'static, Message: 'static> VirtualGrid<State, Message> {
+    /// `builder` creates the element of the item at an index when it becomes visible for the
+    /// first time.
+    pub fn new<P: Into<StateToParams<State, VirtualGridParams>>>(
+        builder: impl ItemBuilderFn<State, Message>,
+        params: P,
+        world: Rc<RefCell<ElementWorld>>,
+    ) -> Self {
+        let id = world.borrow_mut().next_id();
+        Self {
+            id,
+            params: params.into(),
+            items: VirtualItems::new(builder, world),
+            columns: 1,
+            layout_inputs: LayoutInputs::new(),
+        }
+    }
+}
+
+impl<State, Message> ElementImpl for VirtualGrid<State, Message> {
+    type State = State;
+    type Message = Message;
+
+    fn id_impl(&self) -> ElementId {
+        self.id
+    }
+
+    fn layout_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        constraints: SizeConstraints,
+    ) -> ElementSize {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        self.items.layout(params.count, constraints);
+
+        let stride = params.item_size.grow_by(ElementSize {
+            width: params.gap,
+            height: params.gap,
+        });
+        self.columns = params
+            .columns
+            .unwrap_or_else(|| {
+                ((constraints.max_size.width.value() + params.gap) / stride.width).floor() as usize
+            })
+            .max(1);
+        let rows = params.count.div_ceil(self.columns);
+
+        ElementSize {
+            width: (self.columns as f32 * stride.width - params.gap).max(0.0),
+            height: (rows as f32 * stride.height - params.gap).max(0.0),
+        }
+        .clamp_to_constraints(constraints)
+    }
+
+    fn update_impl(&mut self, ctx: &mut UiContext, state: &Self::State) -> bool {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+        let changed = self.layout_inputs.changed((
+            params.count,
+            params.item_size,
+            params.columns,
+            params.gap,
+        ));
+        self.items.update(ctx, state, params.revision) || changed
+    }
+
+    fn draw_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        origin: (f32, f32),
+        size: ElementSize,
+        canvas: &mut dyn Scene,
+    ) {
+        let params = self.params.call(StateToParamsArgs {
+            state,
+            id: self.id,
+            ctx,
+        });
+
+        let bounds = Rect {
+            position: origin.to_array(),
+            size: size.to_array(),
+        };
+        let visible = visible_part(ctx, bounds);
+        let stride = params.item_size.grow_by(ElementSize {
+            width: params.gap,
+            height: params.gap,
+        });
+        let columns = self.columns;
+        let rows = visible_tracks(
+            visible.position[1],
+            visible.position[1] + visible.size[1],
+            stride.height,
+            params.count.div_ceil(columns),
+        );
+        let visible_columns = visible_tracks(
+            visible.position[0],
+            visible.position[0] + visible.size[0],
+            stride.width,
+            columns,
+        );
+        let count = params.count;
+        let keep_rows = overscan(&rows, count.div_ceil(columns));
+        let keep = keep_rows.start * columns..(keep_rows.end * columns).min(count);
+        let indices = rows.flat_map(move |row| {
+            visible_columns
+                .clone()
+                .map(move |column| row * columns + column)
+                .filter(move |idx| *idx < count)
+        });
+
+        ctx.with_clipping(bounds, |ctx| {
+            self.items.draw(
+                ctx,
+                state,
+                indices,
+                params.item_size,
+                |idx| {
+                    (
+                        origin.0 + (idx % columns) as f32 * stride.width,
+                        origin.1 + (idx / columns) as f32 * stride.height,
+                    )
+                },
+                canvas,
+            );
+        });
+        self.items.evict(keep);
+    }
+
+    fn handle_event_impl(
+        &mut self,
+        ctx: &mut UiContext,
+        state: &Self::State,
+        event: &crate::InteractionEvent,
+    ) -> Vec<Self::Message> {
+        self.items.handle_event(ctx, state, event)
+    }
+}
+
+into_box_impl!(VirtualGrid);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestScene, context};
+    use crate::{PreferSize, PreferSizeParams};
+    use std::cell::Cell;
+
+    #[test]
+    fn visible_tracks_of_a_viewport() {
+        // rows 10 high with a gap of 5, i.e. a stride of 15
+        assert_eq!(visible_tracks(0.0, 30.0, 15.0, 10), 0..2);
+        // partially visible first and last rows count
+        assert_eq!(visible_tracks(10.0, 35.0, 15.0, 10), 0..3);
+        assert_eq!(visible_tracks(15.0, 30.0, 15.0, 10), 1..2);
+        // a viewport within the gap after a row still shows that row's track
+        assert_eq!(visible_tracks(11.0, 14.0, 15.0, 10), 0..1);
+        // scrolled past the end
+        assert_eq!(visible_tracks(140.0, 200.0, 15.0, 10), 9..10);
+        assert_eq!(visible_tracks(300.0, 400.0, 15.0, 10), 10..10);
+        // empty lists, viewports and strides
+        assert_eq!(visible_tracks(0.0, 30.0, 15.0, 0), 0..0);
+        assert_eq!(visible_tracks(30.0, 30.0, 15.0, 10), 0..0);
+        assert_eq!(visible_tracks(0.0, 30.0, 0.0, 10), 0..0);
+
+        assert_eq!(overscan(&(0..2), 10), 0..4);
+        assert_eq!(overscan(&(5..7), 10), 3..9);
+        assert_eq!(overscan(&(9..10), 10), 7..10);
+    }
+
+    // a list of 100 items 10 high, which reads the item width from the state
+    fn list(
+        builds: Rc<Cell<usize>>,
+        revision: Rc<Cell<u64>>,
+        world: &Rc<RefCell<ElementWorld>>,
+    ) -> VirtualList<f32, ()> {
+        VirtualList::new(
+            move |_, world: Rc<RefCell<ElementWorld>>| {
+                builds.set(builds.get() + 1);
+                let item: Box<dyn Element<State = f32, Message = ()>> = Box::new(PreferSize::new(
+                    crate::Empty::new(world.clone()),
+                    |args: StateToParamsArgs<f32>| PreferSizeParams {
+                        width: Some(*args.state),
+                        height: None,
+                    },
+                    world,
+                ));
+                item
+            },
+            move |_: StateToParamsArgs<f32>| VirtualListParams {
+                count: 100,
+                item_size: ElementSize {
+                    width: 50.0,
+                    height: 10.0,
+                },
+                gap: 0.0,
+                revision: revision.get(),
+            },
+            world.clone(),
+        )
+    }
+
+    // draws the list scrolled down by `scroll` into a 100x30 viewport
+    fn draw(ctx: &mut UiContext, list: &mut VirtualList<f32, ()>, state: f32, scroll: f32) {
+        let constraints = SizeConstraints {
+            min_size: ElementSize::ZERO,
+            max_size: DynamicSize {
+                width: DynamicDimension::Limit(100.0),
+                height: DynamicDimension::Hint(0.0),
+            },
+            scene_size: (100.0, 100.0),
+        };
+        let size = list.layout(ctx, &state, constraints);
+        let viewport = Rect {
+            position: [0.0, 0.0],
+            size: [100.0, 30.0],
+        };
+        ctx.with_clipping(viewport, |ctx| {
+            list.draw(ctx, &state, (0.0, -scroll), size, &mut TestScene::new());
+        });
+    }
+
+    fn built(list: &VirtualList<f32, ()>) -> Vec<usize> {
+        let mut indices: Vec<usize> = list.items.items.keys().copied().collect();
+        indices.sort();
+        indices
+    }
+
+    #[test]
+    fn items_outside_the_overscan_are_dropped() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let builds = Rc::new(Cell::new(0));
+        let mut list = list(builds.clone(), Rc::new(Cell::new(0)), &world);
+        let mut ctx = context();
+
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        assert_eq!(list.items.visible, vec![0, 1, 2]);
+        assert_eq!(builds.get(), 3);
+
+        // within the overscan, nothing is built again
+        draw(&mut ctx, &mut list, 10.0, 10.0);
+        assert_eq!(list.items.visible, vec![1, 2, 3]);
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        assert_eq!(builds.get(), 4);
+        assert_eq!(built(&list), vec![0, 1, 2, 3]);
+
+        draw(&mut ctx, &mut list, 10.0, 500.0);
+        assert_eq!(list.items.visible, vec![50, 51, 52]);
+        assert_eq!(built(&list), vec![50, 51, 52]);
+        assert_eq!(builds.get(), 7);
+    }
+
+    #[test]
+    fn items_built_again_keep_their_ids() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let builds = Rc::new(Cell::new(0));
+        let revision = Rc::new(Cell::new(0));
+        let mut list = list(builds.clone(), revision.clone(), &world);
+        let mut ctx = context();
+        let ids = |list: &VirtualList<f32, ()>| -> Vec<ElementId> {
+            list.items
+                .visible
+                .iter()
+                .map(|idx| list.items.items[idx].id())
+                .collect()
+        };
+
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        let first = ids(&list);
+        let next_id = world.borrow().peek_id();
+
+        draw(&mut ctx, &mut list, 10.0, 500.0);
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        assert_eq!(builds.get(), 9);
+        assert_eq!(ids(&list), first);
+
+        // so do items built for a new revision
+        revision.set(1);
+        list.update(&mut ctx, &10.0);
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        assert_eq!(builds.get(), 12);
+        assert_eq!(ids(&list), first);
+
+        // scrolling back and forth only allocates ids for items never built before
+        draw(&mut ctx, &mut list, 10.0, 500.0);
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        assert_eq!(world.borrow().peek_id(), next_id + 6);
+    }
+
+    #[test]
+    fn item_changes_make_the_list_dirty() {
+        let world = Rc::new(RefCell::new(ElementWorld::new()));
+        let builds = Rc::new(Cell::new(0));
+        let revision = Rc::new(Cell::new(0));
+        let mut list = list(builds.clone(), revision.clone(), &world);
+        let mut ctx = context();
+
+        assert!(list.update(&mut ctx, &10.0));
+        draw(&mut ctx, &mut list, 10.0, 0.0);
+        // the items built while drawing are new
+        assert!(list.update(&mut ctx, &10.0));
+        assert!(!list.update(&mut ctx, &10.0));
+
+        // the items read the width from the state
+        assert!(list.update(&mut ctx, &20.0));
+        assert!(!list.update(&mut ctx, &20.0));
+
+        // a new revision builds the items again
+        revision.set(1);
+        assert!(list.update(&mut ctx, &20.0));
+        assert!(built(&list).is_empty());
+        draw(&mut ctx, &mut list, 20.0, 0.0);
+        assert_eq!(builds.get(), 6);
+    }
+}
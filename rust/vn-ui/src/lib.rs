@@ -25,31 +25,105 @@ mod components;
 mod element;
 mod element_world;
 mod event_manager;
+mod hit_test;
 mod interaction;
 mod layouts;
 mod sizes;
+#[cfg(test)]
+mod test_utils;
 pub mod text;
+mod theme;
 mod utils;
 
 pub use components::*;
 pub use element::*;
 pub use element_world::*;
 pub use event_manager::*;
+pub use hit_test::HitShape;
 pub use interaction::*;
 pub use layouts::*;
 pub use sizes::*;
 use std::fmt::Debug;
 use std::rc::Rc;
+pub use text::edit_history::{EditHistory, TextSnapshot};
+pub use text::input_filter::{CharSet, InputFilter, InputKind};
+pub use text::layout::{TextAlign, TextLayoutOptions, VerticalAlign};
+pub use text::rich_text::{TextSpan, TextStyle};
+pub use theme::*;
 pub use vn_ui_animation::*;
 pub use vn_ui_animation_macros::*;
 
-pub use vn_scene::{Color, KeyCode, KeyEvent, Rect, Scene};
+pub use vn_scene::{Color, FontQuery, FontStyle, FontWeight, Ime, KeyCode, KeyEvent, Rect, Scene};
+
+/// A glyph of shaped text, see [TextMetrics::shape].
+#[derive(Clone, Debug)]
+pub struct ShapedGlyph {
+    /// The glyph, with its advance and offsets as positioned by the shaper.
+    pub glyph: vn_scene::GlyphData,
+    /// The index of the first character of the cluster the glyph belongs to. Characters up to
+    /// the next cluster, e.g. the letters of a ligature or a letter and its combining marks,
+    /// share the glyphs of their cluster.
+    pub cluster: usize,
+}
 
 /// This keeps the UI agnostic to any specific graphics and resource management
+///
+/// Fonts are passed as strings, which name a single font or are a [FontQuery] of families with
+/// fallbacks, weight and style.
 pub trait TextMetrics {
     fn size_of_text(&self, text: &str, font: &str, font_size: f32) -> (f32, f32);
     fn line_height(&self, font: &str, font_size: f32) -> f32;
     fn get_glyphs(&self, text: &str, font: &str, font_size: f32) -> Vec<vn_scene::GlyphData>;
+
+    /// Shapes `text` of a single direction, applying kerning, ligatures and mark positioning,
+    /// and returns its glyphs from left to right, i.e. reversed for right to left text. Without
+    /// a shaper, every character gets its glyph of [Self::get_glyphs].
+    fn shape(&self, text: &str, font: &str, font_size: f32, rtl: bool) -> Vec<ShapedGlyph> {
+        let mut glyphs: Vec<ShapedGlyph> = self
+            .get_glyphs(text, font, font_size)
+            .into_iter()
+            .enumerate()
+            .map(|(cluster, glyph)| ShapedGlyph { glyph, cluster })
+            .collect();
+        if rtl {
+            glyphs.reverse();
+        }
+        glyphs
+    }
+
+    /// The distance from the top of a line to the baseline, used to align text of different
+    /// sizes. Defaults to 80% of the line height, which is close for most fonts.
+    fn ascent(&self, font: &str, font_size: f32) -> f32 {
+        self.line_height(font, font_size) * 0.8
+    }
+}
+
+/// Access to the clipboard of the platform, used for copy, cut and paste in text fields.
+pub trait Clipboard {
+    fn get_text(&mut self) -> Option<String>;
+    fn set_text(&mut self, text: String);
+}
+
+/// A clipboard that only lives as long as the application, for platforms without one and tests.
+#[derive(Default)]
+pub struct MemoryClipboard {
+    text: Option<String>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.text = Some(text);
+    }
 }
 
 pub struct StateToParamsArgs<'a, State: 'static> {
@@ -61,7 +135,17 @@ pub struct StateToParamsArgs<'a, State: 'static> {
 #[derive(Clone, Debug)]
 pub enum TextFieldAction {
     TextChange(String),
+    /// Moves the caret and clears the selection.
     CaretMove(usize),
+    /// Selects the characters between `anchor` and `caret`, with the caret at `caret`.
+    Select {
+        anchor: usize,
+        caret: usize,
+    },
+    /// Ctrl+Z, see [EditHistory].
+    Undo,
+    /// Ctrl+Shift+Z or Ctrl+Y.
+    Redo,
 }
 
 #[derive(Clone, Debug)]
@@ -0,0 +1,133 @@
+use crate::{EventManager, ShapedGlyph, SimpleLayoutCache, TextMetrics, UiContext};
+use std::cell::RefCell;
+use std::rc::Rc;
+use vn_scene::{
+    BoxPrimitiveData, GlyphData, ImagePrimitiveData, Layer, Rect, Scene, TextPrimitiveData,
+    TextureId,
+};
+
+/// A context with a fresh event manager and layout cache.
+pub fn context() -> UiContext {
+    UiContext::new(
+        Rc::new(RefCell::new(EventManager::new())),
+        Box::new(SimpleLayoutCache::new()),
+        web_time::Instant::now(),
+    )
+}
+
+/// Collects primitives into layers like the wgpu scene, without rendering them.
+pub struct TestScene {
+    layers: Vec<Layer>,
+    active_layers: Vec<usize>,
+}
+
+impl TestScene {
+    pub fn new() -> Self {
+        Self {
+            layers: vec![Layer::new()],
+            active_layers: vec![0],
+        }
+    }
+
+    fn active_layer(&mut self) -> &mut Layer {
+        let index = *self.active_layers.last().unwrap();
+        &mut self.layers[index]
+    }
+
+    fn with_layer(&mut self, index: usize, f: &mut dyn FnMut(&mut dyn Scene)) {
+        if index == self.layers.len() {
+            self.layers.push(Layer::new());
+        }
+        self.active_layers.push(index);
+        f(self);
+        self.active_layers.pop();
+    }
+}
+
+impl Scene for TestScene {
+    fn add_box(&mut self, b: BoxPrimitiveData) {
+        self.active_layer().add_box(b);
+    }
+
+    fn add_image(&mut self, i: ImagePrimitiveData) {
+        self.active_layer().add_image(i);
+    }
+
+    fn add_text(&mut self, t: TextPrimitiveData) {
+        self.active_layer().add_text(t);
+    }
+
+    fn with_next_layer(&mut self, f: &mut dyn FnMut(&mut dyn Scene)) {
+        let index = self.current_layer_id() as usize + 1;
+        self.with_layer(index, f);
+    }
+
+    fn with_top_layer(&mut self, f: &mut dyn FnMut(&mut dyn Scene)) {
+        let index = self.layers.len();
+        self.with_layer(index, f);
+    }
+
+    fn current_layer_id(&self) -> u32 {
+        *self.active_layers.last().unwrap() as u32
+    }
+
+    fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    fn extend(&mut self, other: &mut dyn Scene) {
+        self.layers.extend(other.layers().iter().cloned());
+    }
+}
+
+fn glyph(advance: f32) -> GlyphData {
+    GlyphData {
+        texture_id: TextureId(Rc::new(0)),
+        advance,
+        x_bearing: 0.0,
+        y_offset: 0.0,
+        size: [0.0, 0.0],
+        uv_rect: Rect {
+            position: [0.0, 0.0],
+            size: [0.0, 0.0],
+        },
+    }
+}
+
+/// Every character is 10 wide, lines are as high as the font size. "fi" is shaped into a
+/// single glyph, like a ligature.
+pub struct FixedMetrics;
+
+impl TextMetrics for FixedMetrics {
+    fn size_of_text(&self, text: &str, _font: &str, font_size: f32) -> (f32, f32) {
+        (text.chars().count() as f32 * 10.0, font_size)
+    }
+
+    fn line_height(&self, _font: &str, font_size: f32) -> f32 {
+        font_size
+    }
+
+    fn get_glyphs(&self, text: &str, _font: &str, _font_size: f32) -> Vec<GlyphData> {
+        text.chars().map(|_| glyph(10.0)).collect()
+    }
+
+    fn shape(&self, text: &str, _font: &str, _font_size: f32, rtl: bool) -> Vec<ShapedGlyph> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut glyphs = Vec::new();
+        let mut idx = 0;
+        while idx < chars.len() {
+            glyphs.push(ShapedGlyph {
+                glyph: glyph(10.0),
+                cluster: idx,
+            });
+            idx += match chars[idx..].starts_with(&['f', 'i']) {
+                true => 2,
+                false => 1,
+            };
+        }
+        if rtl {
+            glyphs.reverse();
+        }
+        glyphs
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::VecDeque;
+
+/// The state of a text field an edit can be undone to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextSnapshot {
+    pub text: String,
+    pub caret: Option<usize>,
+    pub selection_anchor: Option<usize>,
+}
+
+/// A change of a single character, which may be merged with the previous one of the same kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Typing {
+    /// Inserted a character ending at `end`. After a space, the next word is a new step.
+    Insert { end: usize, space: bool },
+    /// Removed the character before `start` (Backspace).
+    RemoveBefore { start: usize },
+    /// Removed the character at `at` (Delete).
+    RemoveAt { at: usize },
+}
+
+impl Typing {
+    fn between(before: &TextSnapshot, text: &str) -> Option<Self> {
+        let old: Vec<char> = before.text.chars().collect();
+        let new: Vec<char> = text.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        match (old.len() - prefix - suffix, new.len() - prefix - suffix) {
+            (0, 1) => Some(Typing::Insert {
+                end: prefix + 1,
+                space: new[prefix].is_whitespace(),
+            }),
+            (1, 0) if before.caret == Some(prefix + 1) => {
+                Some(Typing::RemoveBefore { start: prefix })
+            }
+            (1, 0) if before.caret == Some(prefix) => Some(Typing::RemoveAt { at: prefix }),
+            _ => None,
+        }
+    }
+
+    /// True if `next` happened right where this one left off.
+    fn continues_with(self, next: Self) -> bool {
+        match (self, next) {
+            (Typing::Insert { end, space: false }, Typing::Insert { end: next_end, .. }) => {
+                next_end == end + 1
+            }
+            (Typing::RemoveBefore { start }, Typing::RemoveBefore { start: next_start }) => {
+                next_start + 1 == start
+            }
+            (Typing::RemoveAt { at }, Typing::RemoveAt { at: next_at }) => next_at == at,
+            _ => false,
+        }
+    }
+
+    /// Where the caret is after this change.
+    fn caret(self) -> usize {
+        match self {
+            Typing::Insert { end, .. } => end,
+            Typing::RemoveBefore { start } => start,
+            Typing::RemoveAt { at } => at,
+        }
+    }
+}
+
+/// Undo and redo stacks for the text of a text field. It lives in the state next to the text, so
+/// the state owner records each `TextFieldAction::TextChange` it applies, reports caret moves and
+/// selections, and restores snapshots on `TextFieldAction::Undo` / `TextFieldAction::Redo`.
+///
+/// Consecutive typing or deleting of single characters is merged into one step, until the caret
+/// jumps elsewhere, a word is finished with a space or another kind of edit happens.
+#[derive(Clone, Debug)]
+pub struct EditHistory {
+    undo: VecDeque<TextSnapshot>,
+    redo: Vec<TextSnapshot>,
+    // the typing the top of the undo stack can be extended with
+    typing: Option<Typing>,
+    limit: usize,
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EditHistory {
+    pub fn new() -> Self {
+        Self::with_limit(100)
+    }
+
+    /// Keeps at most `limit` steps, dropping the oldest.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            typing: None,
+            limit,
+        }
+    }
+
+    /// Records a change of the text from `before` to `text`. Call it before applying the change.
+    pub fn record(&mut self, before: TextSnapshot, text: &str) {
+        if before.text == text {
+            return;
+        }
+
+        let typing = Typing::between(&before, text);
+        let merge = matches!(
+            (self.typing, typing),
+            (Some(last), Some(next)) if last.continues_with(next)
+        );
+        if !merge {
+            self.undo.push_back(before);
+            if self.undo.len() > self.limit {
+                self.undo.pop_front();
+            }
+        }
+        self.typing = typing;
+        self.redo.clear();
+    }
+
+    /// Ends the current step, so the next change isn't merged into it.
+    pub fn break_coalescing(&mut self) {
+        self.typing = None;
+    }
+
+    /// Ends the current step if the caret moved away from where the last change left it. Call it
+    /// for every caret move, including the ones following each change.
+    pub fn caret_moved(&mut self, caret: usize) {
+        if self.typing.map(Typing::caret) != Some(caret) {
+            self.break_coalescing();
+        }
+    }
+
+    /// The snapshot to restore to undo the last step, given the current one.
+    pub fn undo(&mut self, current: TextSnapshot) -> Option<TextSnapshot> {
+        let snapshot = self.undo.pop_back()?;
+        self.redo.push(current);
+        self.typing = None;
+        Some(snapshot)
+    }
+
+    /// The snapshot to restore to redo the last undone step, given the current one.
+    pub fn redo(&mut self, current: TextSnapshot) -> Option<TextSnapshot> {
+        let snapshot = self.redo.pop()?;
+        self.undo.push_back(current);
+        self.typing = None;
+        Some(snapshot)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.typing = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &str, caret: usize) -> TextSnapshot {
+        TextSnapshot {
+            text: text.to_string(),
+            caret: Some(caret),
+            selection_anchor: None,
+        }
+    }
+
+    /// Types `text` at the end of `current`, like a text field would.
+    fn type_text(history: &mut EditHistory, current: &mut TextSnapshot, text: &str) {
+        for c in text.chars() {
+            let mut new_text = current.text.clone();
+            new_text.push(c);
+            history.record(current.clone(), &new_text);
+            *current = snapshot(&new_text, new_text.chars().count());
+            history.caret_moved(new_text.chars().count());
+        }
+    }
+
+    #[test]
+    fn typing_is_undone_word_by_word() {
+        let mut history = EditHistory::new();
+        let mut current = snapshot("", 0);
+        type_text(&mut history, &mut current, "hello world");
+
+        let undone = history.undo(current.clone()).unwrap();
+        assert_eq!(undone, snapshot("hello ", 6));
+        let undone = history.undo(undone).unwrap();
+        assert_eq!(undone, snapshot("", 0));
+        assert!(!history.can_undo());
+
+        let redone = history.redo(undone).unwrap();
+        assert_eq!(redone, snapshot("hello ", 6));
+        assert_eq!(history.redo(redone).unwrap(), current);
+    }
+
+    #[test]
+    fn backspaces_are_merged() {
+        let mut history = EditHistory::new();
+        history.record(snapshot("abc", 3), "ab");
+        history.record(snapshot("ab", 2), "a");
+
+        assert_eq!(history.undo(snapshot("a", 1)).unwrap(), snapshot("abc", 3));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn other_edits_are_separate_steps() {
+        let mut history = EditHistory::new();
+        history.record(snapshot("a", 1), "ab");
+        // pasted
+        history.record(snapshot("ab", 2), "abcd");
+        history.record(snapshot("abcd", 4), "abcde");
+
+        let undone = history.undo(snapshot("abcde", 5)).unwrap();
+        assert_eq!(undone, snapshot("abcd", 4));
+        assert_eq!(history.undo(undone).unwrap(), snapshot("ab", 2));
+    }
+
+    #[test]
+    fn recording_clears_redo() {
+        let mut history = EditHistory::new();
+        history.record(snapshot("", 0), "a");
+        history.undo(snapshot("a", 1)).unwrap();
+        assert!(history.can_redo());
+
+        history.record(snapshot("", 0), "b");
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn moving_the_caret_ends_the_step() {
+        let mut history = EditHistory::new();
+        let mut current = snapshot("", 0);
+        type_text(&mut history, &mut current, "ab");
+        history.caret_moved(0);
+        history.record(snapshot("ab", 0), "cab");
+        history.caret_moved(1);
+        history.record(snapshot("cab", 1), "cdab");
+        history.caret_moved(2);
+
+        let undone = history.undo(snapshot("cdab", 2)).unwrap();
+        assert_eq!(undone, snapshot("ab", 0));
+        assert_eq!(history.undo(undone).unwrap(), snapshot("", 0));
+    }
+
+    #[test]
+    fn oldest_steps_are_dropped() {
+        let mut history = EditHistory::with_limit(2);
+        history.record(snapshot("", 0), "a");
+        history.break_coalescing();
+        history.record(snapshot("a", 1), "ab");
+        history.break_coalescing();
+        history.record(snapshot("ab", 2), "abc");
+
+        let undone = history.undo(snapshot("abc", 3)).unwrap();
+        assert_eq!(undone, snapshot("ab", 2));
+        assert_eq!(history.undo(undone).unwrap(), snapshot("a", 1));
+        assert!(!history.can_undo());
+    }
+}
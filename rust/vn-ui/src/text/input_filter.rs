@@ -0,0 +1,244 @@
+use std::ops::Range;
+
+/// A set of characters written like a regex bracket expression without the brackets, e.g.
+/// `a-zA-Z0-9_`. A leading `^` negates the set, a `-` at the start or end is taken literally.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharSet {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharSet {
+    pub fn new(pattern: &str) -> Self {
+        let mut chars: Vec<char> = pattern.chars().collect();
+        let negated = chars.first() == Some(&'^');
+        if negated {
+            chars.remove(0);
+        }
+
+        let mut ranges = Vec::new();
+        let mut idx = 0;
+        while idx < chars.len() {
+            if idx + 2 < chars.len() && chars[idx + 1] == '-' {
+                ranges.push((chars[idx], chars[idx + 2]));
+                idx += 3;
+            } else {
+                ranges.push((chars[idx], chars[idx]));
+                idx += 1;
+            }
+        }
+
+        Self { ranges, negated }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        let in_ranges = self
+            .ranges
+            .iter()
+            .any(|(start, end)| (*start..=*end).contains(&c));
+        in_ranges != self.negated
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum InputKind {
+    #[default]
+    Text,
+    /// Whole numbers. The bounds are checked while typing as far as appending digits can't fix
+    /// them, i.e. the maximum of positive and the minimum of negative numbers.
+    Integer { min: Option<i64>, max: Option<i64> },
+    /// Decimal numbers with a `.` as separator, bounded like integers.
+    Float { min: Option<f64>, max: Option<f64> },
+}
+
+impl InputKind {
+    fn allows(&self, c: char) -> bool {
+        match self {
+            InputKind::Text => true,
+            InputKind::Integer { .. } => c.is_ascii_digit() || c == '-',
+            InputKind::Float { .. } => c.is_ascii_digit() || c == '-' || c == '.',
+        }
+    }
+
+    /// True if `text` is or can become a number of this kind by typing more.
+    fn accepts_partial(&self, text: &str) -> bool {
+        let (min, max, decimal) = match self {
+            InputKind::Text => return true,
+            InputKind::Integer { min, max } => {
+                (min.map(|v| v as f64), max.map(|v| v as f64), false)
+            }
+            InputKind::Float { min, max } => (*min, *max, true),
+        };
+
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        if digits.len() != text.len() && min.is_some_and(|min| min >= 0.0) {
+            return false;
+        }
+        let mut separators = 0;
+        for c in digits.chars() {
+            match c {
+                '0'..='9' => {}
+                '.' if decimal => separators += 1,
+                _ => return false,
+            }
+        }
+        if separators > 1 {
+            return false;
+        }
+
+        match text.parse::<f64>() {
+            Ok(value) if value >= 0.0 => max.is_none_or(|max| value <= max),
+            Ok(value) => min.is_none_or(|min| value >= min),
+            // "", "-", "." or "-."
+            Err(_) => true,
+        }
+    }
+
+    fn validate(&self, text: &str) -> bool {
+        match self {
+            InputKind::Text => true,
+            InputKind::Integer { min, max } => text.parse::<i64>().is_ok_and(|value| {
+                min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+            }),
+            InputKind::Float { min, max } => text.parse::<f64>().is_ok_and(|value| {
+                min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+            }),
+        }
+    }
+}
+
+/// Restricts what can be typed or pasted into a `TextField`. Edits that would break the filter
+/// are dropped before they are emitted as `TextFieldAction::TextChange`, disallowed characters
+/// are removed from pasted text and pasted text is cut to the maximum length.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputFilter {
+    pub kind: InputKind,
+    pub allowed: Option<CharSet>,
+    pub max_length: Option<usize>,
+}
+
+impl InputFilter {
+    pub fn integer(min: Option<i64>, max: Option<i64>) -> Self {
+        Self {
+            kind: InputKind::Integer { min, max },
+            ..Self::default()
+        }
+    }
+
+    pub fn float(min: Option<f64>, max: Option<f64>) -> Self {
+        Self {
+            kind: InputKind::Float { min, max },
+            ..Self::default()
+        }
+    }
+
+    /// Only characters of the [CharSet] described by `pattern`.
+    pub fn chars(pattern: &str) -> Self {
+        Self {
+            allowed: Some(CharSet::new(pattern)),
+            ..Self::default()
+        }
+    }
+
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// What is left of `insert` to replace `range` of `chars` with, or `None` if the edit is
+    /// rejected. Removing text is always allowed.
+    pub fn filter_edit(&self, chars: &[char], range: Range<usize>, insert: &str) -> Option<String> {
+        if insert.is_empty() {
+            return Some(String::new());
+        }
+
+        let mut filtered: String = insert
+            .chars()
+            .filter(|c| self.kind.allows(*c))
+            .filter(|c| self.allowed.as_ref().is_none_or(|set| set.contains(*c)))
+            .collect();
+        if let Some(max_length) = self.max_length {
+            let room = max_length.saturating_sub(chars.len() - range.len());
+            filtered = filtered.chars().take(room).collect();
+        }
+        if filtered.is_empty() {
+            return None;
+        }
+
+        let text: String = chars[..range.start]
+            .iter()
+            .copied()
+            .chain(filtered.chars())
+            .chain(chars[range.end..].iter().copied())
+            .collect();
+        self.kind.accepts_partial(&text).then_some(filtered)
+    }
+
+    /// True if `text` is a complete input, e.g. a number within the bounds. Use it to show the
+    /// validation state of a field.
+    pub fn validate(&self, text: &str) -> bool {
+        self.max_length
+            .is_none_or(|max_length| text.chars().count() <= max_length)
+            && self
+                .allowed
+                .as_ref()
+                .is_none_or(|set| text.chars().all(|c| set.contains(c)))
+            && self.kind.validate(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(filter: &InputFilter, text: &str, range: Range<usize>, insert: &str) -> Option<String> {
+        let chars: Vec<char> = text.chars().collect();
+        filter.filter_edit(&chars, range, insert)
+    }
+
+    #[test]
+    fn char_set_ranges_and_negation() {
+        let set = CharSet::new("a-c_-");
+        assert!(set.contains('b') && set.contains('_') && set.contains('-'));
+        assert!(!set.contains('d'));
+
+        let set = CharSet::new("^0-9");
+        assert!(set.contains('x'));
+        assert!(!set.contains('5'));
+    }
+
+    #[test]
+    fn integer_bounds_while_typing() {
+        let filter = InputFilter::integer(Some(-5), Some(20));
+        assert_eq!(edit(&filter, "1", 1..1, "5"), Some("5".to_string()));
+        assert_eq!(edit(&filter, "2", 1..1, "5"), None);
+        assert_eq!(edit(&filter, "", 0..0, "-"), Some("-".to_string()));
+        assert_eq!(edit(&filter, "-", 1..1, "6"), None);
+        assert_eq!(edit(&filter, "1", 1..1, "a"), None);
+
+        assert!(!filter.validate("-"));
+        assert!(filter.validate("-5"));
+        assert!(!InputFilter::integer(Some(10), None).validate("5"));
+    }
+
+    #[test]
+    fn paste_is_filtered_and_cut_to_length() {
+        let filter = InputFilter::chars("a-z").max_length(5);
+        assert_eq!(
+            edit(&filter, "ab", 2..2, "c1d2e3f"),
+            Some("cde".to_string())
+        );
+        assert_eq!(edit(&filter, "abcde", 5..5, "f"), None);
+        // replacing a selection makes room
+        assert_eq!(edit(&filter, "abcde", 0..2, "xyz"), Some("xy".to_string()));
+    }
+
+    #[test]
+    fn float_allows_one_separator() {
+        let filter = InputFilter::float(None, None);
+        assert_eq!(edit(&filter, "1.5", 3..3, "."), None);
+        assert_eq!(edit(&filter, "1", 1..1, "."), Some(".".to_string()));
+        assert!(filter.validate("1.5"));
+        assert!(!filter.validate("-."));
+    }
+}
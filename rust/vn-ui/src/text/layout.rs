@@ -1,20 +1,92 @@
-use crate::TextMetrics;
+use crate::text::rich_text::TextSpan;
+use crate::{ShapedGlyph, TextMetrics};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use unicode_bidi::BidiInfo;
 use vn_scene::GlyphData;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Widens the spaces of wrapped lines to fill the width. The last line and lines ending in
+    /// a newline are left aligned.
+    Justify,
+}
+
+/// Where text smaller than its element is placed vertically.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Center,
+    Bottom,
+}
+
+impl VerticalAlign {
+    /// The offset of text of height `content` within the height `available`.
+    pub fn offset(self, content: f32, available: f32) -> f32 {
+        let free = (available - content).max(0.0);
+        match self {
+            VerticalAlign::Top => 0.0,
+            VerticalAlign::Center => free / 2.0,
+            VerticalAlign::Bottom => free,
+        }
+    }
+}
+
+/// How lines are arranged, besides wrapping at the maximum width.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextLayoutOptions {
+    /// Lines are aligned within the maximum width, or within the widest line if there is none.
+    pub align: TextAlign,
+    /// Lines after this many are cut off and the last remaining one ends in an ellipsis.
+    pub max_lines: Option<usize>,
+}
+
 #[derive(Clone)]
 pub enum LineTermination {
     Newline,
     WordWrap,
 }
 
+/// A glyph placed on a line.
+#[derive(Clone)]
+pub struct PlacedGlyph {
+    pub glyph: GlyphData,
+    /// The pen position relative to the start of the line, not including
+    /// [LaidOutLine::x_offset]. The glyph is drawn at `x + glyph.x_bearing`.
+    pub x: f32,
+    /// The first character of the cluster the glyph was shaped from.
+    pub char_index: usize,
+}
+
 #[derive(Clone)]
 pub struct LaidOutLine {
-    /// Glyphs that make up this line. The last glyph is the one that ends the line.
-    /// unless [Self::terminator] is [LineTermination:Newline], in which case the newline
-    /// character is not contained here as there is no rendered glyph for it.
-    pub glyphs: Vec<GlyphData>,
+    /// The width each character of this line takes up, in logical order. Characters shaped into
+    /// a single glyph, like ligatures, share its advance, so the caret can be put between them.
+    /// Unless [Self::terminator] is [LineTermination::Newline], the last one is the one that
+    /// ends the line. Otherwise, the newline character is not contained here.
+    pub advances: Vec<f32>,
+    /// Where each character of [Self::advances] starts after reordering the line for
+    /// bidirectional text, relative to the start of the line.
+    pub positions: Vec<f32>,
+    /// Whether each character of [Self::advances] runs right to left, which puts the caret
+    /// before it on its right edge.
+    pub rtl: Vec<bool>,
+    /// The glyphs to draw, in visual order.
+    pub glyphs: Vec<PlacedGlyph>,
+    /// Drawn after the glyphs if the text was cut off after this line.
+    pub ellipsis: Vec<PlacedGlyph>,
+    /// Where the line starts, depending on the alignment.
+    pub x_offset: f32,
+    /// Width of the glyphs and the ellipsis, not including the offset.
     pub width: f32,
     pub height: f32,
+    /// Distance from the top of the line to its baseline, which all glyphs of the line share.
+    pub ascent: f32,
     /// First character index of this line
     pub char_start: usize,
     /// Last character index of this line (exclusive). If [Self::terminator] is [LineTermination::Newline],
@@ -23,6 +95,89 @@ pub struct LaidOutLine {
     pub terminator: LineTermination,
 }
 
+impl LaidOutLine {
+    fn new(char_start: usize, height: f32) -> Self {
+        Self {
+            advances: vec![],
+            positions: vec![],
+            rtl: vec![],
+            glyphs: vec![],
+            ellipsis: vec![],
+            x_offset: 0.0,
+            width: 0.0,
+            height,
+            ascent: 0.0,
+            char_start,
+            char_end: 0,
+            terminator: LineTermination::WordWrap,
+        }
+    }
+
+    /// The x of the caret before the character at `offset` within the line, or after the last
+    /// character if there is none. Before a right to left character is on its right.
+    pub fn caret_x(&self, offset: usize) -> f32 {
+        let x = if offset < self.advances.len() {
+            match self.rtl[offset] {
+                true => self.positions[offset] + self.advances[offset],
+                false => self.positions[offset],
+            }
+        } else if let Some(last) = self.advances.len().checked_sub(1) {
+            match self.rtl[last] {
+                true => self.positions[last],
+                false => self.positions[last] + self.advances[last],
+            }
+        } else {
+            0.0
+        };
+        self.x_offset + x
+    }
+
+    /// The caret position within the line closest to `x`.
+    pub fn offset_at(&self, x: f32) -> usize {
+        let x = x - self.x_offset;
+        let distance = |idx: &usize| {
+            let start = self.positions[*idx];
+            (start - x).max(x - start - self.advances[*idx]).max(0.0)
+        };
+        let Some(idx) = (0..self.advances.len()).min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        else {
+            return 0;
+        };
+
+        // the leading half of a character puts the caret before it
+        let left_half = x < self.positions[idx] + self.advances[idx] / 2.0;
+        match left_half != self.rtl[idx] {
+            true => idx,
+            false => idx + 1,
+        }
+    }
+
+    /// The horizontal extents of the characters in `range` on this line as `(x, width)`, from
+    /// left to right. Text of mixed directions can split a range into several pieces.
+    pub fn extents(&self, range: &Range<usize>) -> Vec<(f32, f32)> {
+        let start = range.start.max(self.char_start) - self.char_start;
+        let end = range.end.min(self.char_start + self.advances.len());
+        let mut cells: Vec<(f32, f32)> = (start..end.saturating_sub(self.char_start))
+            .map(|idx| (self.positions[idx], self.advances[idx]))
+            .collect();
+        cells.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut extents: Vec<(f32, f32)> = Vec::new();
+        for (x, width) in cells {
+            match extents.last_mut() {
+                Some((last_x, last_width)) if (*last_x + *last_width - x).abs() < 0.01 => {
+                    *last_width += width
+                }
+                _ => extents.push((x, width)),
+            }
+        }
+        for (x, _) in &mut extents {
+            *x += self.x_offset;
+        }
+        extents
+    }
+}
+
 #[derive(Clone)]
 pub struct TextLayout {
     pub lines: Vec<LaidOutLine>,
@@ -36,142 +191,272 @@ pub struct TextLayout {
 // this only matters for very long text. But since we recompute the layout on every change
 // it isn't really worth it, unless we also address that.
 
+/// A part of a text in a single font and size.
+struct TextRun<'a> {
+    text: &'a str,
+    font: &'a str,
+    font_size: f32,
+}
+
+/// The order to show characters with the given embedding levels in, reversing every run of
+/// right to left text (rule L2 of the Unicode bidirectional algorithm).
+fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let Some(lowest_odd) = levels.iter().copied().filter(|level| level % 2 == 1).min() else {
+        return order;
+    };
+
+    for level in (lowest_odd..=highest).rev() {
+        let mut start = 0;
+        while start < order.len() {
+            if levels[order[start]] < level {
+                start += 1;
+                continue;
+            }
+            let end = (start..order.len())
+                .find(|idx| levels[order[*idx]] < level)
+                .unwrap_or(order.len());
+            order[start..end].reverse();
+            start = end;
+        }
+    }
+    order
+}
+
+/// The result of shaping a text, per character.
+struct ShapedText {
+    advances: Vec<f32>,
+    /// The first character of the cluster of each character.
+    clusters: Vec<usize>,
+    /// The glyphs of each cluster at its first character, relative to the left of the cluster.
+    cluster_glyphs: Vec<Vec<PlacedGlyph>>,
+}
+
+impl ShapedText {
+    fn new(char_count: usize) -> Self {
+        Self {
+            advances: vec![0.0; char_count],
+            clusters: (0..char_count).collect(),
+            cluster_glyphs: vec![Vec::new(); char_count],
+        }
+    }
+
+    /// Adds the glyphs of the characters `start..end`, shaped in one piece.
+    fn add(&mut self, glyphs: Vec<ShapedGlyph>, start: usize, end: usize) {
+        // the left edge and the advance of each cluster, by its first character
+        let mut extents: BTreeMap<usize, (f32, f32)> = BTreeMap::new();
+        let mut pen = 0.0;
+        for shaped in glyphs {
+            let cluster = start + shaped.cluster.min(end - start - 1);
+            let (left, advance) = extents.entry(cluster).or_insert((pen, 0.0));
+            self.cluster_glyphs[cluster].push(PlacedGlyph {
+                x: pen - *left,
+                char_index: cluster,
+                glyph: shaped.glyph.clone(),
+            });
+            *advance += shaped.glyph.advance;
+            pen += shaped.glyph.advance;
+        }
+
+        // characters without glyphs of their own are part of the cluster before them
+        let starts: Vec<usize> = extents.keys().copied().collect();
+        for (idx, cluster) in starts.iter().enumerate() {
+            let cluster_end = starts.get(idx + 1).copied().unwrap_or(end);
+            let advance = extents[cluster].1 / (cluster_end - cluster) as f32;
+            for char_idx in *cluster..cluster_end {
+                self.advances[char_idx] = advance;
+                self.clusters[char_idx] = *cluster;
+            }
+        }
+    }
+}
+
 impl TextLayout {
     pub fn layout(
         text: &str,
         font: &str,
         font_size: f32,
         max_width: Option<f32>,
+        options: TextLayoutOptions,
         text_metrics: &dyn TextMetrics,
     ) -> Self {
-        let mut lines = vec![];
+        let run = TextRun {
+            text,
+            font,
+            font_size,
+        };
+        Self::layout_runs(&[run], max_width, options, text_metrics)
+    }
 
-        let line_height = text_metrics.line_height(font, font_size);
-        let glyphs = text_metrics.get_glyphs(text, font, font_size);
+    /// Lays out the text of all `spans` as one, e.g. for a `RichText`. Lines are as high as the
+    /// spans on them need and the glyphs of every span are moved onto the baseline of the line.
+    pub fn layout_rich(
+        spans: &[TextSpan],
+        max_width: Option<f32>,
+        options: TextLayoutOptions,
+        text_metrics: &dyn TextMetrics,
+    ) -> Self {
+        let runs: Vec<TextRun> = spans
+            .iter()
+            .map(|span| TextRun {
+                text: &span.text,
+                font: &span.style.font,
+                font_size: span.style.font_size,
+            })
+            .collect();
+        Self::layout_runs(&runs, max_width, options, text_metrics)
+    }
 
-        assert_eq!(
-            glyphs.len(),
-            text.chars().count(),
-            "Glyph count mismatch for text: {:?}",
-            text
-        );
+    fn layout_runs(
+        runs: &[TextRun],
+        max_width: Option<f32>,
+        options: TextLayoutOptions,
+        text_metrics: &dyn TextMetrics,
+    ) -> Self {
+        let mut lines = vec![];
+
+        let text: String = runs.iter().map(|run| run.text).collect();
+        let text = text.as_str();
+        let chars: Vec<char> = text.chars().collect();
+        // line height and ascent of each run
+        let run_metrics: Vec<(f32, f32)> = runs
+            .iter()
+            .map(|run| {
+                (
+                    text_metrics.line_height(run.font, run.font_size),
+                    text_metrics.ascent(run.font, run.font_size),
+                )
+            })
+            .collect();
+        let line_height = run_metrics.first().map_or(0.0, |(height, _)| *height);
+
+        let char_runs: Vec<usize> = runs
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, run)| std::iter::repeat_n(idx, run.text.chars().count()))
+            .collect();
+
+        // the embedding level and the level of the paragraph of each character
+        let bidi = BidiInfo::new(text, None);
+        let (levels, paragraph_levels): (Vec<u8>, Vec<u8>) = text
+            .char_indices()
+            .map(|(byte, _)| {
+                let paragraph = bidi.paragraphs.iter().find(|p| p.range.contains(&byte));
+                (
+                    bidi.levels[byte].number(),
+                    paragraph.map_or(0, |p| p.level.number()),
+                )
+            })
+            .unzip();
+
+        // text is shaped in pieces of a single run and direction, before it is broken into lines
+        let char_bytes: Vec<usize> = text
+            .char_indices()
+            .map(|(byte, _)| byte)
+            .chain([text.len()])
+            .collect();
+        let mut shaped = ShapedText::new(chars.len());
+        let mut start = 0;
+        while start < chars.len() {
+            let end = (start..chars.len())
+                .find(|idx| char_runs[*idx] != char_runs[start] || levels[*idx] != levels[start])
+                .unwrap_or(chars.len());
+            let run = &runs[char_runs[start]];
+            let glyphs = text_metrics.shape(
+                &text[char_bytes[start]..char_bytes[end]],
+                run.font,
+                run.font_size,
+                levels[start] % 2 == 1,
+            );
+            shaped.add(glyphs, start, end);
+            start = end;
+        }
 
         let mut last_space: Option<usize> = None;
-        let mut current_line_glyphs = LaidOutLine {
-            glyphs: vec![],
-            width: 0.0,
-            height: line_height,
-            char_start: 0,
-            char_end: 0,
-            terminator: LineTermination::WordWrap,
-        };
+        let mut current_line = LaidOutLine::new(0, line_height);
         let mut current_word = Vec::new();
         let mut current_word_width = 0.0;
 
-        for (idx, (glyph, c)) in glyphs.into_iter().zip(text.chars()).enumerate() {
+        for (idx, (advance, c)) in shaped.advances.iter().copied().zip(&chars).enumerate() {
             match c {
                 '\n' => {
-                    current_line_glyphs.char_end = idx + 1;
-                    current_line_glyphs.width += current_word_width;
-                    current_line_glyphs.terminator = LineTermination::Newline;
-                    current_line_glyphs.glyphs.append(&mut current_word);
-
-                    lines.push(current_line_glyphs);
-
-                    current_line_glyphs = LaidOutLine {
-                        glyphs: vec![],
-                        width: 0.0,
-                        height: line_height,
-                        char_start: idx + 1,
-                        char_end: 0,
-                        terminator: LineTermination::WordWrap,
-                    };
+                    current_line.char_end = idx + 1;
+                    current_line.width += current_word_width;
+                    current_line.terminator = LineTermination::Newline;
+                    current_line.advances.append(&mut current_word);
+
+                    lines.push(current_line);
+
+                    current_line = LaidOutLine::new(idx + 1, line_height);
 
                     current_word_width = 0.0;
                     last_space = None;
                 }
                 _ => {
                     if let Some(max_width) = max_width
-                        && max_width
-                            < current_line_glyphs.width + current_word_width + glyph.advance
+                        && max_width < current_line.width + current_word_width + advance
                     {
                         // space to next line
-                        if c == ' ' {
+                        if *c == ' ' {
                             last_space = Some(idx);
-                            current_word_width = 0.0;
 
-                            current_line_glyphs.glyphs.append(&mut current_word);
-                            current_line_glyphs.width += current_word_width;
-                            current_line_glyphs.char_end = idx;
-                            current_line_glyphs.terminator = LineTermination::WordWrap;
-                            lines.push(current_line_glyphs);
-
-                            current_line_glyphs = LaidOutLine {
-                                width: glyph.advance,
-                                glyphs: vec![glyph],
-                                height: line_height,
-                                char_start: idx,
-                                char_end: 0,
-                                terminator: LineTermination::WordWrap,
+                            current_line.advances.append(&mut current_word);
+                            current_line.width += current_word_width;
+                            current_word_width = 0.0;
+                            current_line.char_end = idx;
+                            current_line.terminator = LineTermination::WordWrap;
+                            lines.push(current_line);
+
+                            current_line = LaidOutLine {
+                                width: advance,
+                                advances: vec![advance],
+                                ..LaidOutLine::new(idx, line_height)
                             };
                         }
                         // move entire word to next line (if it fits)
                         else if let Some(last_space) = last_space.take()
-                            && current_word_width + glyph.advance <= max_width
+                            && current_word_width + advance <= max_width
                         {
-                            current_word_width += glyph.advance;
+                            current_word_width += advance;
 
-                            current_word.push(glyph);
+                            current_word.push(advance);
 
-                            current_line_glyphs.terminator = LineTermination::WordWrap;
-                            current_line_glyphs.char_end = last_space + 1;
+                            current_line.terminator = LineTermination::WordWrap;
+                            current_line.char_end = last_space + 1;
 
-                            lines.push(current_line_glyphs);
+                            lines.push(current_line);
 
-                            current_line_glyphs = LaidOutLine {
-                                glyphs: vec![],
-                                width: 0.0,
-                                height: line_height,
-                                char_start: last_space + 1,
-                                char_end: 0,
-                                terminator: LineTermination::WordWrap,
-                            };
+                            current_line = LaidOutLine::new(last_space + 1, line_height);
                         }
                         // break a word
                         else {
-                            current_line_glyphs.terminator = LineTermination::WordWrap;
-                            current_line_glyphs.char_end = idx;
-                            current_line_glyphs.width += current_word_width;
-                            current_word_width = glyph.advance;
-
-                            current_line_glyphs.glyphs.append(&mut current_word);
-                            lines.push(current_line_glyphs);
-
-                            current_line_glyphs = LaidOutLine {
-                                glyphs: vec![],
-                                width: 0.0,
-                                height: line_height,
-                                char_start: idx,
-                                char_end: 0,
-                                terminator: LineTermination::WordWrap,
-                            };
-                            current_word.push(glyph);
+                            current_line.terminator = LineTermination::WordWrap;
+                            current_line.char_end = idx;
+                            current_line.width += current_word_width;
+                            current_word_width = advance;
+
+                            current_line.advances.append(&mut current_word);
+                            lines.push(current_line);
+
+                            current_line = LaidOutLine::new(idx, line_height);
+                            current_word.push(advance);
                         }
                     } else {
                         // add a word to current line
-                        if c == ' ' {
+                        if *c == ' ' {
                             last_space = Some(idx);
-                            current_line_glyphs.width += current_word_width + glyph.advance;
-                            current_line_glyphs.char_end = idx + 1; // we don't really need to do this
-                            current_line_glyphs.glyphs.append(&mut current_word);
-                            current_line_glyphs.glyphs.push(glyph);
+                            current_line.width += current_word_width + advance;
+                            current_line.char_end = idx + 1; // we don't really need to do this
+                            current_line.advances.append(&mut current_word);
+                            current_line.advances.push(advance);
 
                             current_word_width = 0.0;
                         }
                         // extend current word
                         else {
-                            current_word_width += glyph.advance;
-                            current_word.push(glyph);
+                            current_word_width += advance;
+                            current_word.push(advance);
                         }
                     }
                 }
@@ -181,21 +466,163 @@ impl TextLayout {
         // last word is still missing from the line, but we know it fits, otherwise word wrap
         // would have happened before.
         if !current_word.is_empty() {
-            current_line_glyphs.glyphs.append(&mut current_word);
-            current_line_glyphs.width += current_word_width;
+            current_line.advances.append(&mut current_word);
+            current_line.width += current_word_width;
         }
 
         // last line is still missing from the layout
-        current_line_glyphs.char_end = text.chars().count() + 1;
-        current_line_glyphs.terminator = LineTermination::WordWrap;
-        lines.push(current_line_glyphs);
+        current_line.char_end = chars.len() + 1;
+        current_line.terminator = LineTermination::WordWrap;
+        lines.push(current_line);
+
+        // lines are as high as the runs on them need, with a common baseline
+        for line in &mut lines {
+            let end = line.char_end.min(char_runs.len());
+            let mut line_runs = char_runs[line.char_start.min(end)..end].to_vec();
+            // an empty line is as high as the text before it
+            if line_runs.is_empty() && !runs.is_empty() {
+                let before = char_runs[..line.char_start.min(char_runs.len())].last();
+                line_runs.push(before.copied().unwrap_or(0));
+            }
+
+            let (ascent, descent) = line_runs.iter().fold((0.0f32, 0.0f32), |acc, run| {
+                let (height, ascent) = run_metrics[*run];
+                (acc.0.max(ascent), acc.1.max(height - ascent))
+            });
+            line.ascent = ascent;
+            line.height = ascent + descent;
+        }
+
+        if let Some(max_lines) = options.max_lines
+            && lines.len() > max_lines.max(1)
+            && !runs.is_empty()
+        {
+            lines.truncate(max_lines.max(1));
+            let line = lines.last_mut().unwrap();
+
+            // in the style of the last character on the line
+            let last_char = (line.char_start + line.advances.len()).saturating_sub(1);
+            let run = char_runs.get(last_char).copied().unwrap_or(0);
+            let ellipsis = text_metrics.get_glyphs("...", runs[run].font, runs[run].font_size);
+            let ellipsis_width: f32 = ellipsis.iter().map(|g| g.advance).sum();
+
+            // make room for the ellipsis, which shouldn't follow a space
+            while let Some(last) = line.advances.last()
+                && (chars[line.char_start + line.advances.len() - 1] == ' '
+                    || max_width.is_some_and(|max| line.width + ellipsis_width > max))
+            {
+                line.width -= last;
+                line.advances.pop();
+            }
+            line.char_end = line.char_start + line.advances.len() + 1;
+            line.terminator = LineTermination::WordWrap;
+
+            let mut x = line.width;
+            for mut glyph in ellipsis {
+                glyph.y_offset += line.ascent - run_metrics[run].1;
+                let advance = glyph.advance;
+                line.ellipsis.push(PlacedGlyph {
+                    glyph,
+                    x,
+                    char_index: last_char,
+                });
+                x += advance;
+            }
+            line.width += ellipsis_width;
+        }
 
-        let total_width = lines
+        let natural_width = lines
             .iter()
             .map(|l| l.width)
             .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap_or(0.0)
-            .ceil();
+            .unwrap_or(0.0);
+        let align_width = match (options.align, max_width) {
+            (TextAlign::Left, _) | (_, None) => natural_width,
+            (_, Some(max_width)) => max_width.max(natural_width),
+        };
+        let line_count = lines.len();
+        for (line_idx, line) in lines.iter_mut().enumerate() {
+            let char_start = line.char_start;
+            let is_space = |idx: &usize| chars.get(char_start + idx) == Some(&' ');
+            // trailing spaces are not visible and don't count for the alignment
+            let trailing_spaces: f32 = (0..line.advances.len())
+                .rev()
+                .take_while(is_space)
+                .map(|idx| line.advances[idx])
+                .sum();
+            let free = (align_width - line.width + trailing_spaces).max(0.0);
+
+            match options.align {
+                TextAlign::Left => {}
+                TextAlign::Center => line.x_offset = free / 2.0,
+                TextAlign::Right => line.x_offset = free,
+                TextAlign::Justify => {
+                    let wrapped = matches!(line.terminator, LineTermination::WordWrap)
+                        && line_idx + 1 < line_count
+                        && line.ellipsis.is_empty();
+                    let first = (0..line.advances.len()).find(|idx| !is_space(idx));
+                    let last = (0..line.advances.len()).rev().find(|idx| !is_space(idx));
+                    if let (true, Some(first), Some(last)) = (wrapped, first, last) {
+                        let gaps: Vec<usize> = (first..last).filter(is_space).collect();
+                        if !gaps.is_empty() {
+                            let extra = free / gaps.len() as f32;
+                            for idx in &gaps {
+                                line.advances[*idx] += extra;
+                            }
+                            line.width += extra * gaps.len() as f32;
+                        }
+                    }
+                }
+            }
+        }
+
+        // lines are reordered for right to left text and their glyphs are placed
+        for line in &mut lines {
+            let start = line.char_start;
+            let count = line.advances.len();
+            let mut line_levels = levels[start..start + count].to_vec();
+            // trailing whitespace goes in the direction of the paragraph (rule L1)
+            for idx in (0..count).rev() {
+                if !chars[start + idx].is_whitespace() {
+                    break;
+                }
+                line_levels[idx] = paragraph_levels[start + idx];
+            }
+            let order = visual_order(&line_levels);
+
+            line.positions = vec![0.0; count];
+            let mut x = 0.0;
+            for idx in &order {
+                line.positions[*idx] = x;
+                x += line.advances[*idx];
+            }
+            line.rtl = line_levels.iter().map(|level| level % 2 == 1).collect();
+
+            // a cluster broken by a line wrap is drawn on the line of its first character
+            for idx in &order {
+                let char_index = start + idx;
+                if shaped.clusters[char_index] != char_index {
+                    continue;
+                }
+                let left = (*idx..count)
+                    .take_while(|other| shaped.clusters[start + other] == char_index)
+                    .map(|other| line.positions[other])
+                    .fold(f32::INFINITY, f32::min);
+                let y_shift = line.ascent - run_metrics[char_runs[char_index]].1;
+                for placed in &shaped.cluster_glyphs[char_index] {
+                    let mut glyph = placed.glyph.clone();
+                    glyph.y_offset += y_shift;
+                    line.glyphs.push(PlacedGlyph {
+                        glyph,
+                        x: left + placed.x,
+                        char_index,
+                    });
+                }
+            }
+        }
+
+        // aligned lines fill the width, not counting their trailing spaces
+        let total_width = align_width.ceil();
         let total_height = lines.iter().map(|l| l.height).sum::<f32>().ceil();
 
         Self {
@@ -223,26 +650,8 @@ impl TextLayout {
             }
         }
 
-        let line = match line {
-            Some(line) => line,
-            None => return None,
-        };
-
-        // find the glyph that the click is closer to
-        let mut centered_glyphs = vec![0.0; line.glyphs.len()];
-        {
-            let mut glyph_x_offset = 0.0;
-            for (i, glyph) in line.glyphs.iter().enumerate() {
-                centered_glyphs[i] = glyph_x_offset + glyph.advance / 2.0;
-                glyph_x_offset += glyph.advance;
-            }
-        }
-
-        let hit = centered_glyphs.binary_search_by(|g_x| g_x.partial_cmp(&x).unwrap());
-
-        match hit {
-            Ok(idx) | Err(idx) => Some(line.char_start + idx),
-        }
+        let line = line?;
+        Some(line.char_start + line.offset_at(x))
     }
 
     pub fn get_caret_pos(&self, char_index: usize) -> (f32, f32) {
@@ -251,7 +660,7 @@ impl TextLayout {
         // Prefer the line where char_index is at the start (for wrapped lines)
         for line in &self.lines {
             if char_index == line.char_start {
-                return (0.0, current_y);
+                return (line.caret_x(0), current_y);
             }
             current_y += line.height;
         }
@@ -260,21 +669,34 @@ impl TextLayout {
         let mut last_line = None;
         for line in &self.lines {
             if char_index >= line.char_start && char_index < line.char_end {
-                let offset = char_index - line.char_start;
-                let x = line.glyphs.iter().take(offset).map(|g| g.advance).sum();
-                return (x, current_y);
+                return (line.caret_x(char_index - line.char_start), current_y);
             }
             last_line = Some((line, current_y));
             current_y += line.height;
         }
 
         if let Some((line, y)) = last_line {
-            (line.width, y)
+            (line.caret_x(line.advances.len()), y)
         } else {
             (0.0, 0.0)
         }
     }
 
+    /// The first and the last caret position on the line containing `char_index`.
+    pub fn line_bounds(&self, char_index: usize) -> (usize, usize) {
+        let line = self
+            .lines
+            .iter()
+            .find(|line| char_index >= line.char_start && char_index < line.char_end)
+            .or(self.lines.last());
+
+        match line {
+            // like in vertical moves, the end is before the newline / wrap
+            Some(line) => (line.char_start, line.char_end - 1),
+            None => (0, 0),
+        }
+    }
+
     pub fn get_caret_x(&self, char_index: usize) -> f32 {
         self.get_caret_pos(char_index).0
     }
@@ -300,28 +722,168 @@ impl TextLayout {
             return current_pos;
         }
 
+        // we always want to be at the end of the text, which is before the newline / wrap
         let target_line = &self.lines[target_line_idx];
-        let mut glyph_x = 0.0;
+        let last = (target_line.char_end - target_line.char_start).saturating_sub(1);
+        target_line.char_start + target_line.offset_at(intended_x).min(last)
+    }
+}
 
-        for (i, glyph) in target_line.glyphs.iter().enumerate() {
-            let next_glyph_x = glyph_x + glyph.advance;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FixedMetrics;
+    use crate::text::rich_text::TextStyle;
+    use vn_scene::Color;
 
-            if intended_x >= glyph_x && intended_x < next_glyph_x {
-                return if intended_x - glyph_x < next_glyph_x - intended_x {
-                    target_line.char_start + i
-                } else {
-                    target_line.char_start + i + 1
-                };
-            }
-            glyph_x = next_glyph_x;
-        }
+    fn span(text: &str, font_size: f32) -> TextSpan {
+        TextSpan::new(text, TextStyle::new("font", font_size, Color::WHITE))
+    }
 
-        // x out of glyph bounds
-        if intended_x < 0.0 {
-            target_line.char_start
-        } else {
-            // we always want to be at the end of the text, which is before the newline / wrap
-            target_line.char_end - 1
-        }
+    #[test]
+    fn spans_share_the_baseline_of_the_line() {
+        let layout = TextLayout::layout_rich(
+            &[span("ab", 10.0), span("cd", 20.0)],
+            None,
+            TextLayoutOptions::default(),
+            &FixedMetrics,
+        );
+
+        assert_eq!(layout.lines.len(), 1);
+        let line = &layout.lines[0];
+        assert_eq!(line.height, 20.0);
+        assert_eq!(line.ascent, 16.0);
+        // the small glyphs move down by the difference of the ascents
+        assert_eq!(line.glyphs[0].glyph.y_offset, 8.0);
+        assert_eq!(line.glyphs[2].glyph.y_offset, 0.0);
+    }
+
+    #[test]
+    fn lines_are_as_high_as_their_spans() {
+        let layout = TextLayout::layout_rich(
+            &[span("big ", 20.0), span("small\nsmall", 10.0)],
+            Some(50.0),
+            TextLayoutOptions::default(),
+            &FixedMetrics,
+        );
+
+        let heights: Vec<f32> = layout.lines.iter().map(|l| l.height).collect();
+        assert_eq!(heights, vec![20.0, 10.0, 10.0]);
+        assert_eq!(layout.total_height, 40.0);
+        assert_eq!(layout.hit_test(0.0, 25.0), Some(4));
+    }
+
+    fn layout(text: &str, max_width: f32, options: TextLayoutOptions) -> TextLayout {
+        TextLayout::layout(text, "font", 10.0, Some(max_width), options, &FixedMetrics)
+    }
+
+    #[test]
+    fn alignment_offsets_lines_without_trailing_spaces() {
+        let align = |align| TextLayoutOptions {
+            align,
+            ..TextLayoutOptions::default()
+        };
+
+        let centered = layout("ab cdef", 60.0, align(TextAlign::Center));
+        let offsets: Vec<f32> = centered.lines.iter().map(|l| l.x_offset).collect();
+        assert_eq!(offsets, vec![20.0, 10.0]);
+        assert_eq!(centered.total_width, 60.0);
+        assert_eq!(centered.get_caret_pos(3), (10.0, 10.0));
+        assert_eq!(centered.hit_test(12.0, 15.0), Some(3));
+
+        let right = layout("ab cdef", 60.0, align(TextAlign::Right));
+        assert_eq!(right.lines[0].x_offset, 40.0);
+    }
+
+    #[test]
+    fn alignment_of_lines_wrapped_at_a_space() {
+        let centered = layout(
+            "abcdef gh",
+            60.0,
+            TextLayoutOptions {
+                align: TextAlign::Center,
+                ..TextLayoutOptions::default()
+            },
+        );
+        let lines: Vec<(f32, f32)> = centered
+            .lines
+            .iter()
+            .map(|l| (l.width, l.x_offset))
+            .collect();
+        // the word fills the first line, the space starts the second
+        assert_eq!(lines, vec![(60.0, 0.0), (30.0, 15.0)]);
+        assert_eq!(centered.get_caret_pos(3), (30.0, 0.0));
+        assert_eq!(centered.get_caret_pos(6), (15.0, 10.0));
+    }
+
+    #[test]
+    fn justify_widens_spaces_of_wrapped_lines() {
+        let options = TextLayoutOptions {
+            align: TextAlign::Justify,
+            ..TextLayoutOptions::default()
+        };
+        let justified = layout("a b c dddd e", 70.0, options);
+
+        // "a b c " fills 70 with its two inner spaces, the last line stays as it is
+        assert_eq!(justified.lines[0].advances[1], 20.0);
+        assert_eq!(justified.get_caret_pos(4), (60.0, 0.0));
+        let last = justified.lines.last().unwrap();
+        assert!(last.advances.iter().all(|advance| *advance == 10.0));
+    }
+
+    #[test]
+    fn max_lines_ends_with_an_ellipsis() {
+        let options = TextLayoutOptions {
+            max_lines: Some(1),
+            ..TextLayoutOptions::default()
+        };
+        let truncated = layout("abcd efgh", 60.0, options);
+
+        assert_eq!(truncated.lines.len(), 1);
+        let line = &truncated.lines[0];
+        // "abc" and "..." fit into 60
+        assert_eq!(line.advances.len(), 3);
+        assert_eq!(line.ellipsis.len(), 3);
+        assert_eq!(line.ellipsis[0].x, 30.0);
+        assert_eq!(line.width, 60.0);
+    }
+
+    #[test]
+    fn long_words_break_anywhere() {
+        let broken = layout("ab cdefghij", 40.0, TextLayoutOptions::default());
+        let starts: Vec<usize> = broken.lines.iter().map(|l| l.char_start).collect();
+        assert_eq!(starts, vec![0, 3, 7]);
+    }
+
+    #[test]
+    fn ligatures_share_their_advance() {
+        let shaped = layout("fine", 100.0, TextLayoutOptions::default());
+
+        let line = &shaped.lines[0];
+        assert_eq!(line.advances, vec![5.0, 5.0, 10.0, 10.0]);
+        assert_eq!(line.glyphs.len(), 3);
+        assert_eq!(shaped.get_caret_pos(1), (5.0, 0.0));
+        assert_eq!(shaped.hit_test(6.0, 5.0), Some(1));
+    }
+
+    #[test]
+    fn right_to_left_text_is_reordered() {
+        let mixed = layout(
+            "ab \u{5d0}\u{5d1}\u{5d2}",
+            100.0,
+            TextLayoutOptions::default(),
+        );
+
+        let line = &mixed.lines[0];
+        assert_eq!(line.positions, vec![0.0, 10.0, 20.0, 50.0, 40.0, 30.0]);
+        let glyph_chars: Vec<usize> = line.glyphs.iter().map(|g| g.char_index).collect();
+        assert_eq!(glyph_chars, vec![0, 1, 2, 5, 4, 3]);
+
+        // the caret before the first hebrew letter is on its right
+        assert_eq!(mixed.get_caret_pos(3), (60.0, 0.0));
+        assert_eq!(mixed.get_caret_pos(6), (30.0, 0.0));
+        assert_eq!(mixed.hit_test(58.0, 5.0), Some(3));
+        assert_eq!(mixed.hit_test(52.0, 5.0), Some(4));
+        assert_eq!(line.extents(&(2..4)), vec![(20.0, 10.0), (50.0, 10.0)]);
     }
 }
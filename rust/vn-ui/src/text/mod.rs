@@ -1 +1,4 @@
+pub mod edit_history;
+pub mod input_filter;
 pub mod layout;
+pub mod rich_text;
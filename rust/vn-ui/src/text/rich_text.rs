@@ -0,0 +1,75 @@
+use vn_scene::Color;
+use vn_ui_animation_macros::Interpolatable;
+
+/// How a [TextSpan] is drawn.
+#[derive(Clone, Debug, PartialEq, Interpolatable)]
+pub struct TextStyle {
+    /// A font name or a [crate::FontQuery], which [TextStyle::new] also takes directly.
+    #[interpolate_snappy = "snap_middle"]
+    pub font: String,
+    pub font_size: f32,
+    pub color: Color,
+    #[interpolate_snappy = "snap_middle"]
+    pub underline: bool,
+    #[interpolate_snappy = "snap_middle"]
+    pub strikethrough: bool,
+    /// Fills the line height behind the text of the span.
+    #[interpolate_snappy = "snap_middle"]
+    pub background: Option<Color>,
+}
+
+impl TextStyle {
+    pub fn new(font: impl ToString, font_size: f32, color: Color) -> Self {
+        Self {
+            font: font.to_string(),
+            font_size,
+            color,
+            underline: false,
+            strikethrough: false,
+            background: None,
+        }
+    }
+
+    pub fn underline(mut self) -> Self {
+        self.underline = true;
+        self
+    }
+
+    pub fn strikethrough(mut self) -> Self {
+        self.strikethrough = true;
+        self
+    }
+
+    pub fn background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+}
+
+/// A piece of rich text in a single style. Text of several spans is laid out as one, wrapping
+/// across span boundaries, with each line as high as its largest span and all spans of a line
+/// sharing its baseline.
+#[derive(Clone, Debug, PartialEq, Interpolatable)]
+pub struct TextSpan {
+    #[interpolate_snappy = "snap_middle"]
+    pub text: String,
+    pub style: TextStyle,
+}
+
+impl TextSpan {
+    pub fn new(text: impl ToString, style: TextStyle) -> Self {
+        Self {
+            text: text.to_string(),
+            style,
+        }
+    }
+}
+
+/// The index of the span containing each character of the concatenated text of `spans`.
+pub(crate) fn span_of_chars(spans: &[TextSpan]) -> Vec<usize> {
+    spans
+        .iter()
+        .enumerate()
+        .flat_map(|(idx, span)| std::iter::repeat_n(idx, span.text.chars().count()))
+        .collect()
+}
@@ -0,0 +1,425 @@
+use crate::{
+    AnimationController, CardParams, Easing, ElementId, FontQuery, InteractionState, UiContext,
+};
+use vn_scene::Color;
+use vn_ui_animation_macros::Interpolatable;
+use web_time::{Duration, Instant};
+
+const fn rgb(r: f32, g: f32, b: f32) -> Color {
+    Color { r, g, b, a: 1.0 }
+}
+
+/// What an element is doing, to pick its style from [StateStyles].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WidgetState {
+    #[default]
+    Normal,
+    Hovered,
+    Focused,
+    Pressed,
+    Disabled,
+}
+
+impl WidgetState {
+    /// The state that shows when several apply: disabled before pressed before hovered before
+    /// focused.
+    pub fn new(interaction: InteractionState, is_pressed: bool, is_disabled: bool) -> Self {
+        if is_disabled {
+            WidgetState::Disabled
+        } else if is_pressed {
+            WidgetState::Pressed
+        } else if interaction.is_hovered {
+            WidgetState::Hovered
+        } else if interaction.is_focused {
+            WidgetState::Focused
+        } else {
+            WidgetState::Normal
+        }
+    }
+
+    /// The state of element `id` according to the event manager.
+    pub fn of(ctx: &UiContext, id: ElementId, is_disabled: bool) -> Self {
+        let event_manager = ctx.event_manager.borrow();
+        let interaction = InteractionState {
+            is_hovered: event_manager.is_hovered(id),
+            is_focused: event_manager.is_focused(id),
+        };
+        Self::new(interaction, event_manager.is_pressed(id), is_disabled)
+    }
+}
+
+/// A style for each [WidgetState] of a component.
+#[derive(Clone, Debug, PartialEq, Interpolatable)]
+pub struct StateStyles<S: vn_ui_animation::Interpolatable> {
+    pub normal: S,
+    pub hovered: S,
+    pub focused: S,
+    pub pressed: S,
+    pub disabled: S,
+}
+
+impl<S: vn_ui_animation::Interpolatable> StateStyles<S> {
+    /// The same style in every state.
+    pub fn uniform(style: S) -> Self {
+        Self {
+            normal: style.clone(),
+            hovered: style.clone(),
+            focused: style.clone(),
+            pressed: style.clone(),
+            disabled: style,
+        }
+    }
+
+    pub fn get(&self, state: WidgetState) -> &S {
+        match state {
+            WidgetState::Normal => &self.normal,
+            WidgetState::Hovered => &self.hovered,
+            WidgetState::Focused => &self.focused,
+            WidgetState::Pressed => &self.pressed,
+            WidgetState::Disabled => &self.disabled,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Interpolatable)]
+pub struct ColorTokens {
+    /// Behind everything, e.g. the window.
+    pub background: Color,
+    /// Panels on top of the background.
+    pub surface: Color,
+    pub text: Color,
+    /// Secondary text like labels of inputs.
+    pub text_muted: Color,
+    /// Marks selected and active elements.
+    pub accent: Color,
+    pub border: Color,
+    pub error: Color,
+    /// Behind selected text.
+    pub selection: Color,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Interpolatable)]
+pub struct SizeTokens {
+    pub small: f32,
+    pub medium: f32,
+    pub large: f32,
+}
+
+/// The font and size text of a certain kind is drawn with.
+#[derive(Clone, Debug, PartialEq, Interpolatable)]
+pub struct FontRole {
+    /// A font name or a [FontQuery].
+    #[interpolate_snappy = "snap_middle"]
+    pub font: String,
+    pub size: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Interpolatable)]
+pub struct FontRoles {
+    pub body: FontRole,
+    /// Labels of inputs and other small text.
+    pub label: FontRole,
+    pub title: FontRole,
+}
+
+impl FontRoles {
+    /// All roles in `font`, with smaller labels and larger, bold titles.
+    pub fn new(font: impl ToString, size: f32) -> Self {
+        let font = font.to_string();
+        Self {
+            body: FontRole {
+                font: font.clone(),
+                size,
+            },
+            label: FontRole {
+                font: font.clone(),
+                size: size * 0.85,
+            },
+            title: FontRole {
+                font: FontQuery::parse(&font).bold().to_string(),
+                size: size * 1.25,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Interpolatable)]
+pub struct ButtonStyle {
+    pub background: Color,
+    pub border_color: Color,
+    pub border_width: f32,
+    pub corner_radius: f32,
+    /// The color of the button's content.
+    pub text_color: Color,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Interpolatable)]
+pub struct TextFieldStyle {
+    pub text_color: Color,
+    pub caret_width: f32,
+    pub selection_color: Color,
+    pub error_color: Color,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Interpolatable)]
+pub struct ScrollBarStyle {
+    pub width: f32,
+    pub margin: f32,
+    pub color: Color,
+}
+
+/// Named tokens and the default styles of components derived from them. Params closures read
+/// the theme of [UiContext::theme], so switching it restyles the whole UI.
+#[derive(Clone, Debug, PartialEq, Interpolatable)]
+pub struct Theme {
+    pub colors: ColorTokens,
+    /// Gaps and paddings.
+    pub spacing: SizeTokens,
+    /// Corner radii.
+    pub radii: SizeTokens,
+    pub fonts: FontRoles,
+    pub button: StateStyles<ButtonStyle>,
+    /// Panels and other containers.
+    pub panel: CardParams,
+    /// The frame around text fields, which takes the state of its field.
+    pub input: StateStyles<CardParams>,
+    pub text_field: StateStyles<TextFieldStyle>,
+    pub scroll_bar: ScrollBarStyle,
+}
+
+impl Theme {
+    /// Light text on a black background.
+    pub fn dark() -> Self {
+        Self::from_tokens(ColorTokens {
+            background: Color::BLACK,
+            surface: Color::BLACK,
+            text: Color::WHITE,
+            text_muted: Color::WHITE.with_alpha(0.5),
+            accent: Color::GREEN,
+            border: Color::WHITE,
+            error: Color::RED,
+            selection: Color::WHITE.with_alpha(0.3),
+        })
+    }
+
+    /// Dark text on a white background.
+    pub fn light() -> Self {
+        let text = rgb(0.1, 0.1, 0.12);
+        Self::from_tokens(ColorTokens {
+            background: rgb(0.95, 0.95, 0.96),
+            surface: Color::WHITE,
+            text,
+            text_muted: text.with_alpha(0.6),
+            accent: rgb(0.0, 0.55, 0.25),
+            border: text,
+            error: rgb(0.8, 0.1, 0.1),
+            selection: rgb(0.0, 0.55, 0.25).with_alpha(0.3),
+        })
+    }
+
+    /// Derives the component styles from `colors` and the default sizes and fonts. The font is
+    /// `"sans-serif"`, which is either registered as a font family or replaced with
+    /// [Self::with_fonts].
+    pub fn from_tokens(colors: ColorTokens) -> Self {
+        let spacing = SizeTokens {
+            small: 5.0,
+            medium: 10.0,
+            large: 25.0,
+        };
+        let radii = SizeTokens {
+            small: 2.0,
+            medium: 5.0,
+            large: 10.0,
+        };
+
+        let button = ButtonStyle {
+            background: colors.text.with_alpha(0.1),
+            border_color: colors.border.with_alpha(0.5),
+            border_width: 2.0,
+            corner_radius: radii.medium,
+            text_color: colors.text,
+        };
+        let highlighted = ButtonStyle {
+            background: colors.text.with_alpha(0.15),
+            border_color: colors.border,
+            ..button
+        };
+
+        let input = CardParams {
+            background_color: colors.text.with_alpha(0.1),
+            border_size: 2.0,
+            border_color: colors.border.with_alpha(0.5),
+            corner_radius: radii.medium,
+        };
+        let highlighted_input = CardParams {
+            background_color: colors.text.with_alpha(0.15),
+            border_color: colors.border,
+            ..input
+        };
+
+        let text_field = TextFieldStyle {
+            text_color: colors.text,
+            caret_width: 2.0,
+            selection_color: colors.selection,
+            error_color: colors.error,
+        };
+
+        Self {
+            colors,
+            spacing,
+            radii,
+            fonts: FontRoles::new("sans-serif", 16.0),
+            button: StateStyles {
+                normal: button,
+                hovered: highlighted,
+                focused: highlighted,
+                pressed: ButtonStyle {
+                    background: colors.accent.with_alpha(0.25),
+                    border_color: colors.accent,
+                    ..highlighted
+                },
+                disabled: ButtonStyle {
+                    text_color: colors.text.with_alpha(0.5),
+                    ..button
+                },
+            },
+            panel: CardParams {
+                background_color: colors.surface,
+                border_size: 2.0,
+                border_color: colors.border,
+                corner_radius: radii.medium,
+            },
+            input: StateStyles {
+                normal: input,
+                hovered: highlighted_input,
+                focused: highlighted_input,
+                pressed: highlighted_input,
+                disabled: CardParams {
+                    background_color: colors.text.with_alpha(0.05),
+                    border_color: colors.border.with_alpha(0.25),
+                    ..input
+                },
+            },
+            text_field: StateStyles {
+                disabled: TextFieldStyle {
+                    text_color: colors.text_muted,
+                    ..text_field
+                },
+                ..StateStyles::uniform(text_field)
+            },
+            scroll_bar: ScrollBarStyle {
+                width: 16.0,
+                margin: 8.0,
+                color: colors.border,
+            },
+        }
+    }
+
+    pub fn with_fonts(mut self, fonts: FontRoles) -> Self {
+        self.fonts = fonts;
+        self
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Switches between themes at runtime, animating every token from the current theme to the new
+/// one.
+pub struct ThemeSwitcher {
+    controller: AnimationController<Theme>,
+    duration: Duration,
+}
+
+impl ThemeSwitcher {
+    pub fn new(theme: Theme, duration: Duration) -> Self {
+        Self {
+            controller: AnimationController::new(theme),
+            duration,
+        }
+    }
+
+    /// The theme at `now`, which is between two themes while switching.
+    pub fn theme(&self, now: Instant) -> Theme {
+        self.controller.value(now)
+    }
+
+    /// Starts animating to `theme`, from wherever a running switch currently is.
+    pub fn switch_to(&self, theme: Theme, now: Instant) {
+        let current = self.theme(now);
+        self.controller.update_state(|state| {
+            state.start_value = current;
+            state.target_value = theme;
+            state.start_time = now;
+            state.duration = self.duration;
+            state.easing = Easing::EaseInOutQuad;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vn_ui_animation::Interpolatable;
+
+    #[test]
+    fn state_precedence() {
+        let hovered_and_focused = InteractionState {
+            is_hovered: true,
+            is_focused: true,
+        };
+        assert_eq!(
+            WidgetState::new(hovered_and_focused, false, false),
+            WidgetState::Hovered
+        );
+        assert_eq!(
+            WidgetState::new(hovered_and_focused, true, false),
+            WidgetState::Pressed
+        );
+        assert_eq!(
+            WidgetState::new(hovered_and_focused, true, true),
+            WidgetState::Disabled
+        );
+
+        let theme = Theme::dark();
+        assert_eq!(
+            theme.button.get(WidgetState::Focused),
+            &theme.button.focused
+        );
+    }
+
+    #[test]
+    fn disabled_styles_are_muted() {
+        for theme in [Theme::dark(), Theme::light()] {
+            let (normal, disabled) = (&theme.input.normal, &theme.input.disabled);
+            assert!(disabled.border_color.a < normal.border_color.a);
+            assert!(disabled.background_color.a < normal.background_color.a);
+            let (normal, disabled) = (&theme.button.normal, &theme.button.disabled);
+            assert!(disabled.text_color.a < normal.text_color.a);
+        }
+    }
+
+    #[test]
+    fn switching_themes_blends_colors() {
+        let dark = Theme::dark().with_fonts(FontRoles::new("a", 10.0));
+        let light = Theme::light().with_fonts(FontRoles::new("b", 20.0));
+
+        let half = dark.interpolate(&light, 0.5);
+        assert_eq!(half.fonts.body.size, 15.0);
+        assert_eq!(half.fonts.body.font, "b");
+        let background = half.colors.background;
+        assert!(background.r > 0.0 && background.r < light.colors.background.r);
+
+        let start = Instant::now();
+        let switcher = ThemeSwitcher::new(dark.clone(), Duration::from_millis(200));
+        assert_eq!(switcher.theme(start), dark);
+        switcher.switch_to(light.clone(), start);
+        assert_eq!(switcher.theme(start), dark);
+        let end = switcher.theme(start + Duration::from_millis(200));
+        assert_eq!(end.fonts, light.fonts);
+        assert!((end.colors.background.r - light.colors.background.r).abs() < 1e-6);
+    }
+}
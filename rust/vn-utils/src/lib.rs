@@ -80,6 +80,8 @@ pub mod result {
 }
 
 pub mod string {
+    use unicode_segmentation::UnicodeSegmentation;
+
     pub trait CharIndex {
         fn byte_pos_for_char_index(&self, index: usize) -> Option<usize>;
     }
@@ -122,4 +124,91 @@ pub mod string {
             self.remove(index);
         }
     }
+
+    /// The indices of the characters starting a grapheme cluster, followed by the length of
+    /// `chars`.
+    fn grapheme_boundaries(chars: &[char]) -> Vec<usize> {
+        let text: String = chars.iter().collect();
+        let mut boundaries = Vec::with_capacity(chars.len() + 1);
+        let (mut idx, mut byte_idx) = (0, 0);
+        for (start, _) in text.grapheme_indices(true) {
+            idx += text[byte_idx..start].chars().count();
+            byte_idx = start;
+            boundaries.push(idx);
+        }
+        boundaries.push(chars.len());
+        boundaries
+    }
+
+    /// True if a caret may be placed before `chars[idx]`, i.e. `idx` does not split an extended
+    /// grapheme cluster (a user-perceived character like `e` + combining accent, an emoji
+    /// sequence joined with zero width joiners or a flag made of two regional indicators).
+    pub fn is_grapheme_boundary(chars: &[char], idx: usize) -> bool {
+        if idx == 0 || idx >= chars.len() {
+            return true;
+        }
+        grapheme_boundaries(chars).binary_search(&idx).is_ok()
+    }
+
+    /// The closest grapheme cluster boundary before `idx`, or 0.
+    pub fn previous_grapheme_boundary(chars: &[char], idx: usize) -> usize {
+        let idx = idx.min(chars.len());
+        grapheme_boundaries(chars)
+            .into_iter()
+            .rfind(|boundary| *boundary < idx)
+            .unwrap_or(0)
+    }
+
+    /// The closest grapheme cluster boundary after `idx`, or the length of `chars`.
+    pub fn next_grapheme_boundary(chars: &[char], idx: usize) -> usize {
+        grapheme_boundaries(chars)
+            .into_iter()
+            .find(|boundary| *boundary > idx)
+            .unwrap_or(chars.len())
+    }
+
+    /// Moves `idx` back to the start of the grapheme cluster it points into.
+    pub fn snap_to_grapheme_boundary(chars: &[char], idx: usize) -> usize {
+        let idx = idx.min(chars.len());
+        grapheme_boundaries(chars)
+            .into_iter()
+            .rfind(|boundary| *boundary <= idx)
+            .unwrap_or(0)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn boundaries(text: &str) -> Vec<usize> {
+            let chars: Vec<char> = text.chars().collect();
+            (0..=chars.len())
+                .filter(|idx| is_grapheme_boundary(&chars, *idx))
+                .collect()
+        }
+
+        #[test]
+        fn combining_marks_and_emoji_sequences_stay_together() {
+            // e + combining acute accent
+            assert_eq!(boundaries("e\u{301}x"), vec![0, 2, 3]);
+            // woman + skin tone + zwj + laptop
+            assert_eq!(
+                boundaries("\u{1F469}\u{1F3FD}\u{200D}\u{1F4BB}"),
+                vec![0, 4]
+            );
+            assert_eq!(boundaries("a\r\nb"), vec![0, 1, 3, 4]);
+            // a Hangul syllable of leading consonant, vowel and trailing consonant
+            assert_eq!(boundaries("\u{1100}\u{1161}\u{11A8}a"), vec![0, 3, 4]);
+        }
+
+        #[test]
+        fn regional_indicators_pair_up() {
+            // three indicators: a flag and a lone one
+            let chars: Vec<char> = "\u{1F1E9}\u{1F1EA}\u{1F1EB}".chars().collect();
+            assert_eq!(next_grapheme_boundary(&chars, 0), 2);
+            assert_eq!(next_grapheme_boundary(&chars, 2), 3);
+            assert_eq!(previous_grapheme_boundary(&chars, 2), 0);
+            assert_eq!(snap_to_grapheme_boundary(&chars, 1), 0);
+        }
+    }
 }
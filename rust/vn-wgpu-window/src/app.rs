@@ -7,7 +7,7 @@ use std::rc::Rc;
 use std::sync::Arc;
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
-use winit::event::{MouseScrollDelta, WindowEvent};
+use winit::event::WindowEvent;
 use winit::event_loop::ActiveEventLoop;
 use winit::window::{Window, WindowId};
 
@@ -88,6 +88,7 @@ where
         }
 
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        window.set_ime_allowed(true);
 
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -161,6 +162,8 @@ where
                 }
             }
             WindowEvent::KeyboardInput { event, .. } => state.handle_key(event_loop, &event),
+            WindowEvent::Ime(event) => state.handle_ime(&event),
+            WindowEvent::ModifiersChanged(modifiers) => state.handle_modifiers(&modifiers),
             WindowEvent::CursorMoved { position, .. } => {
                 state.handle_mouse_position(position.x as f32, position.y as f32);
             }
@@ -171,13 +174,7 @@ where
             } => {
                 state.handle_mouse_button(button, button_state);
             }
-            WindowEvent::MouseWheel { delta, .. } => {
-                let (x, y) = match delta {
-                    MouseScrollDelta::LineDelta(x, y) => (x * 32.0, y * 32.0),
-                    MouseScrollDelta::PixelDelta(pos) => (pos.x as f32, pos.y as f32),
-                };
-                state.handle_mouse_wheel(x, y);
-            }
+            WindowEvent::MouseWheel { delta, .. } => state.handle_mouse_wheel(delta),
             _ => {}
         }
     }
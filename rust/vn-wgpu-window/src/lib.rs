@@ -10,7 +10,7 @@ pub mod resource_manager;
 pub mod scene;
 pub mod scene_renderer;
 pub mod text;
-pub use text::Glyph;
+pub use text::{Glyph, ShapedGlyph};
 mod texture;
 
 pub use app::App;
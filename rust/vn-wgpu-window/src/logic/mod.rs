@@ -1,5 +1,5 @@
 use crate::renderer::Renderer;
-use winit::event::KeyEvent;
+use winit::event::{Ime, KeyEvent, Modifiers, MouseScrollDelta};
 use winit::event_loop::ActiveEventLoop;
 
 pub trait StateLogic<R: Renderer>: Sized + 'static {
@@ -8,6 +8,16 @@ pub trait StateLogic<R: Renderer>: Sized + 'static {
     #[allow(unused_variables)]
     fn handle_key(&mut self, event_loop: &ActiveEventLoop, event: &KeyEvent) {}
 
+    /// Input method events, i.e. text composed with an IME, dead keys or an emoji picker. The
+    /// window allows IME input, so text typed this way is not reported via [Self::handle_key].
+    #[allow(unused_variables)]
+    fn handle_ime(&mut self, event: &Ime) {}
+
+    /// The modifier keys changed. Key events don't report them, so this is the only way to
+    /// know which modifiers are held down.
+    #[allow(unused_variables)]
+    fn handle_modifiers(&mut self, modifiers: &Modifiers) {}
+
     #[allow(unused_variables)]
     fn handle_mouse_position(&mut self, x: f32, y: f32) {}
 
@@ -20,7 +30,7 @@ pub trait StateLogic<R: Renderer>: Sized + 'static {
     }
 
     #[allow(unused_variables)]
-    fn handle_mouse_wheel(&mut self, delta_x: f32, delta_y: f32) {}
+    fn handle_mouse_wheel(&mut self, delta: MouseScrollDelta) {}
 
     #[allow(unused_variables)]
     fn resized(&mut self, width: u32, height: u32) {}
@@ -4,7 +4,7 @@ use crate::logic::StateLogic;
 use crate::resource_manager::ResourceManager;
 use crate::scene_renderer::SceneRenderer;
 use std::rc::Rc;
-use winit::event::KeyEvent;
+use winit::event::{Ime, KeyEvent, Modifiers};
 use winit::event_loop::ActiveEventLoop;
 use winit::window::Window;
 
@@ -68,6 +68,14 @@ impl<T: StateLogic<R>, R: Renderer> RenderingContext<T, R> {
         self.logic.handle_key(event_loop, event);
     }
 
+    pub fn handle_ime(&mut self, event: &Ime) {
+        self.logic.handle_ime(event);
+    }
+
+    pub fn handle_modifiers(&mut self, modifiers: &Modifiers) {
+        self.logic.handle_modifiers(modifiers);
+    }
+
     pub fn update(&mut self) {
         self.logic.update();
     }
@@ -84,8 +92,8 @@ impl<T: StateLogic<R>, R: Renderer> RenderingContext<T, R> {
         self.logic.handle_mouse_button(button, state);
     }
 
-    pub fn handle_mouse_wheel(&mut self, delta_x: f32, delta_y: f32) {
-        self.logic.handle_mouse_wheel(delta_x, delta_y);
+    pub fn handle_mouse_wheel(&mut self, delta: winit::event::MouseScrollDelta) {
+        self.logic.handle_mouse_wheel(delta);
     }
 
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
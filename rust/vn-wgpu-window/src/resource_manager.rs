@@ -6,20 +6,25 @@ use std::collections::HashMap;
 use std::fmt;
 use std::rc::Rc;
 use ttf_parser::GlyphId;
+use vn_scene::{FontQuery, FontStyle, FontWeight};
 use vn_utils::result::MonoResult;
+use vn_utils::string::is_grapheme_boundary;
 
 /// Manages textures, fonts, and cached text rendering.
 pub struct ResourceManager {
     wgpu: Rc<WgpuContext>,
     textures: RefCell<HashMap<TextureId, Rc<Texture>>>,
     fonts: RefCell<HashMap<String, Rc<Font>>>,
+    families: RefCell<HashMap<String, Vec<FamilyMember>>>,
+    // the resolved font chain of each font string, cleared when fonts are added
+    chains: RefCell<HashMap<String, Vec<Rc<Font>>>>,
     fallback_font: Rc<Font>,
     text_renderer: RefCell<TextRenderer>,
     glyph_size_increment: Cell<f32>,
     pub texture_atlas: RefCell<TextureAtlasCatalog>,
 }
 
-use crate::text::Glyph;
+use crate::text::{Glyph, ShapedGlyph};
 
 impl fmt::Debug for ResourceManager {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -42,6 +47,63 @@ pub enum Sampling {
     Linear,
 }
 
+/// A font of a family, with the weight and style it was made for.
+struct FamilyMember {
+    weight: FontWeight,
+    style: FontStyle,
+    font: Rc<Font>,
+}
+
+impl FamilyMember {
+    /// How far this member is from what `query` asks for. Any weight is closer than another
+    /// style, and of two weights as far apart, the one further in the direction of the query
+    /// from regular wins.
+    fn distance(&self, query: &FontQuery) -> (bool, u16, bool) {
+        let lighter = self.weight < query.weight;
+        (
+            self.style != query.style,
+            self.weight.0.abs_diff(query.weight.0),
+            lighter == (query.weight >= FontWeight::REGULAR),
+        )
+    }
+}
+
+/// The fonts with their parsed faces, leaving out those that fail to parse.
+fn parse_faces(fonts: &[Rc<Font>]) -> Vec<(&Font, ttf_parser::Face<'_>)> {
+    fonts
+        .iter()
+        .filter_map(|font| match font.face() {
+            Ok(face) => Some((font.as_ref(), face)),
+            Err(e) => {
+                log::error!("Failed to parse font: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The index of the font of `faces` to draw each character with, which is the first one with a
+/// glyph for it. Characters that continue a grapheme, like combining marks, keep the font of
+/// the character before them. Without any font, there are no indices.
+fn font_per_char(faces: &[(&Font, ttf_parser::Face)], chars: &[char]) -> Vec<usize> {
+    if faces.is_empty() {
+        return Vec::new();
+    }
+
+    let mut fonts: Vec<usize> = Vec::with_capacity(chars.len());
+    for (idx, c) in chars.iter().enumerate() {
+        let font = match fonts.last() {
+            Some(previous) if !is_grapheme_boundary(chars, idx) => *previous,
+            _ => faces
+                .iter()
+                .position(|(_, face)| face.glyph_index(*c).is_some())
+                .unwrap_or(0),
+        };
+        fonts.push(font);
+    }
+    fonts
+}
+
 impl ResourceManager {
     pub fn new(wgpu: Rc<WgpuContext>, fallback_font: &[u8]) -> Self {
         let fallback_font = Rc::new(Font::new(fallback_font.to_vec()));
@@ -53,6 +115,8 @@ impl ResourceManager {
             wgpu,
             textures,
             fonts: RefCell::new(HashMap::new()),
+            families: RefCell::new(HashMap::new()),
+            chains: RefCell::new(HashMap::new()),
             fallback_font,
             glyph_size_increment: Cell::new(4.0),
             texture_atlas: RefCell::new(texture_atlas),
@@ -127,21 +191,85 @@ impl ResourceManager {
         let font = Rc::new(Font::new(bytes.to_vec()));
         let mut fonts = self.fonts.borrow_mut();
         fonts.insert(name.to_string(), font.clone());
+        self.chains.borrow_mut().clear();
         Ok(font)
     }
 
-    pub fn get_font(&self, name: &str) -> Result<Rc<Font>, Rc<Font>> {
-        let font = self.fonts.borrow().get(name).cloned();
-        font.ok_or_else(|| self.fallback_font.clone())
+    /// Adds a font to a family, reading its weight and style from the font. Text can then name
+    /// the family and the variant to pick, e.g. `"bold italic Inter"`, see [FontQuery].
+    pub fn add_font_to_family(
+        &self,
+        family: &str,
+        bytes: &[u8],
+    ) -> Result<Rc<Font>, anyhow::Error> {
+        let font = Rc::new(Font::new(bytes.to_vec()));
+        let face = font
+            .face()
+            .map_err(|e| anyhow::anyhow!("Font parse error: {}", e))?;
+        let member = FamilyMember {
+            weight: FontWeight(face.weight().to_number()),
+            style: match face.is_italic() || face.is_oblique() {
+                true => FontStyle::Italic,
+                false => FontStyle::Normal,
+            },
+            font: font.clone(),
+        };
+
+        self.families
+            .borrow_mut()
+            .entry(family.to_string())
+            .or_default()
+            .push(member);
+        self.chains.borrow_mut().clear();
+        Ok(font)
     }
 
-    pub fn line_height(&self, font_name: &str, font_size: f32) -> f32 {
-        let font = self.get_font(font_name);
+    /// The fonts to search for the glyphs of text in `font`, a [FontQuery] of families or
+    /// font names, ending with the fallback font. Of each family, the member closest to the
+    /// weight and style of the query is used.
+    pub fn font_chain(&self, font: &str) -> Vec<Rc<Font>> {
+        if let Some(chain) = self.chains.borrow().get(font) {
+            return chain.clone();
+        }
 
-        if font.is_err() {
-            log::warn!("Font {} not found, falling back to default", font_name);
+        let query = FontQuery::parse(font);
+        let families = self.families.borrow();
+        let fonts = self.fonts.borrow();
+
+        let mut chain: Vec<Rc<Font>> = query
+            .families
+            .iter()
+            .filter_map(|name| {
+                let font = match families.get(name) {
+                    Some(members) => members
+                        .iter()
+                        .min_by_key(|member| member.distance(&query))
+                        .map(|member| member.font.clone()),
+                    None => fonts.get(name).cloned(),
+                };
+                if font.is_none() {
+                    log::warn!("Font {} not found, falling back to default", name);
+                }
+                font
+            })
+            .collect();
+        chain.push(self.fallback_font.clone());
+        self.chains
+            .borrow_mut()
+            .insert(font.to_string(), chain.clone());
+        chain
+    }
+
+    /// The first font of the query `name`, or the fallback font if none of it is loaded.
+    pub fn get_font(&self, name: &str) -> Result<Rc<Font>, Rc<Font>> {
+        let chain = self.font_chain(name);
+        match chain.len() > 1 {
+            true => Ok(chain[0].clone()),
+            false => Err(chain[0].clone()),
         }
+    }
 
+    pub fn line_height(&self, font_name: &str, font_size: f32) -> f32 {
         let font = self.get_font(font_name).value();
 
         let face = match font.face() {
@@ -155,6 +283,19 @@ impl ResourceManager {
         face.line_height(font_size)
     }
 
+    /// The distance from the top of a line to the baseline, matching where glyphs are rendered.
+    pub fn ascent(&self, font_name: &str, font_size: f32) -> f32 {
+        let font = self.get_font(font_name).value();
+
+        match font.face() {
+            Ok(face) => face.ascender() as f32 * face.scale(font_size),
+            Err(e) => {
+                log::error!("Failed to parse font: {}", e);
+                font_size * 0.8
+            }
+        }
+    }
+
     pub fn get_glyphs(
         &self,
         graphics_context: &crate::graphics::GraphicsContext,
@@ -162,23 +303,162 @@ impl ResourceManager {
         font_name: &str,
         font_size: f32,
     ) -> Vec<Glyph> {
-        let font = self.get_font(font_name);
+        let chain = self.font_chain(font_name);
+        let faces = parse_faces(&chain);
+        let chars: Vec<char> = text.chars().collect();
+        let fonts = font_per_char(&faces, &chars);
+
+        chars
+            .iter()
+            .zip(fonts)
+            .map(|(c, font_idx)| {
+                let (font, face) = &faces[font_idx];
+                let glyph_id = face.glyph_index(*c).unwrap_or(GlyphId(0));
+                self.glyph(graphics_context, font, glyph_id, font_size)
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to render glyph {}: {}", c, e);
+                        let advance = face.glyph_hor_advance(glyph_id).unwrap_or(0);
+                        self.empty_glyph(advance as f32 * face.scale(font_size))
+                    })
+            })
+            .collect()
+    }
 
-        if font.is_err() {
-            log::warn!("Font {} not found, falling back to default", font_name);
+    /// Shapes `text` of a single direction, applying kerning, ligatures and mark positioning.
+    /// Characters missing from the first font of `font_name` are shaped with the next one that
+    /// has them. The glyphs are returned from left to right, each with the index of the first
+    /// character of its cluster.
+    pub fn shape(
+        &self,
+        graphics_context: &crate::graphics::GraphicsContext,
+        text: &str,
+        font_name: &str,
+        font_size: f32,
+        rtl: bool,
+    ) -> Vec<ShapedGlyph> {
+        let chain = self.font_chain(font_name);
+        let faces = parse_faces(&chain);
+        let chars: Vec<char> = text.chars().collect();
+        let fonts = font_per_char(&faces, &chars);
+        if fonts.is_empty() {
+            return Vec::new();
+        }
+        let char_bytes: Vec<usize> = text
+            .char_indices()
+            .map(|(byte, _)| byte)
+            .chain([text.len()])
+            .collect();
+
+        // characters of the same font are shaped together
+        let mut runs = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let end = (start..chars.len())
+                .find(|idx| fonts[*idx] != fonts[start])
+                .unwrap_or(chars.len());
+            runs.push(start..end);
+            start = end;
+        }
+        // the glyphs go from left to right, which is the last run first for right to left text
+        if rtl {
+            runs.reverse();
         }
 
-        let font = self.get_font(font_name).value();
+        runs.into_iter()
+            .flat_map(|run| {
+                let (font, face) = &faces[fonts[run.start]];
+                let text = &text[char_bytes[run.start]..char_bytes[run.end]];
+                let mut glyphs = self.shape_run(graphics_context, font, face, text, font_size, rtl);
+                for glyph in &mut glyphs {
+                    glyph.cluster += run.start;
+                }
+                glyphs
+            })
+            .collect()
+    }
 
-        let face = match font.face() {
-            Ok(f) => f,
-            Err(e) => {
-                log::error!("Failed to parse font: {}", e);
-                return Vec::new();
-            }
-        };
+    fn shape_run(
+        &self,
+        graphics_context: &crate::graphics::GraphicsContext,
+        font: &Font,
+        face: &ttf_parser::Face,
+        text: &str,
+        font_size: f32,
+        rtl: bool,
+    ) -> Vec<ShapedGlyph> {
+        let face = rustybuzz::Face::from_face(face.clone());
+        let scale = face.scale(font_size);
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(match rtl {
+            true => rustybuzz::Direction::RightToLeft,
+            false => rustybuzz::Direction::LeftToRight,
+        });
+        buffer.guess_segment_properties();
+        let shaped = rustybuzz::shape(&face, &[], buffer);
+
+        // clusters are byte offsets into the text
+        let char_indices: HashMap<usize, usize> = text
+            .char_indices()
+            .enumerate()
+            .map(|(char_idx, (byte, _))| (byte, char_idx))
+            .collect();
+
+        shaped
+            .glyph_infos()
+            .iter()
+            .zip(shaped.glyph_positions())
+            .map(|(info, position)| {
+                let glyph_id = GlyphId(info.glyph_id as u16);
+                // glyphs that can't be rendered still take up their space
+                let mut glyph = self
+                    .glyph(graphics_context, font, glyph_id, font_size)
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to render glyph {}: {}", info.glyph_id, e);
+                        self.empty_glyph(0.0)
+                    });
+                glyph.advance = position.x_advance as f32 * scale;
+                glyph.x_bearing += position.x_offset as f32 * scale;
+                // font units point up
+                glyph.y_offset -= position.y_offset as f32 * scale;
+
+                ShapedGlyph {
+                    glyph,
+                    cluster: char_indices
+                        .get(&(info.cluster as usize))
+                        .copied()
+                        .unwrap_or(0),
+                }
+            })
+            .collect()
+    }
+
+    /// A glyph drawing nothing, in place of one that failed to render.
+    fn empty_glyph(&self, advance: f32) -> Glyph {
+        let texture_atlas = self.texture_atlas.borrow();
+        Glyph {
+            // any texture the renderer knows, nothing of it is shown
+            texture: texture_atlas.atlases.last().unwrap().texture.id.clone(),
+            size: (0.0, 0.0),
+            uv_rect: vn_scene::Rect {
+                position: [0.0, 0.0],
+                size: [0.0, 0.0],
+            },
+            advance,
+            x_bearing: 0.0,
+            y_offset: 0.0,
+        }
+    }
 
-        let mut glyphs = Vec::new();
+    /// The glyph of the font at the size, rendered into the texture atlas if it isn't yet.
+    fn glyph(
+        &self,
+        graphics_context: &crate::graphics::GraphicsContext,
+        font: &Font,
+        glyph_id: GlyphId,
+        font_size: f32,
+    ) -> anyhow::Result<Glyph> {
         let font_ptr = Rc::as_ptr(&font.data) as usize;
         let font_id = format!("{:x}", font_ptr);
 
@@ -186,53 +466,37 @@ impl ResourceManager {
         let quantized_size = (font_size / increment).ceil() * increment;
         let scale_factor = font_size / quantized_size;
 
-        for c in text.chars() {
-            let glyph_id = face.glyph_index(c).unwrap_or(GlyphId(0));
-
-            let key = TextureAtlasKey {
-                font_name: font_id.clone(),
-                glyph_id: glyph_id.0 as u32,
-                glyph_size: (quantized_size * 100.0) as u32,
-            };
-
-            if let Some(glyph) = self.texture_atlas.borrow().get_glyph(&key) {
-                let mut glyph = glyph;
-                glyph.size.0 *= scale_factor;
-                glyph.size.1 *= scale_factor;
-                glyph.advance *= scale_factor;
-                glyph.x_bearing *= scale_factor;
-                glyph.y_offset *= scale_factor;
-
-                glyphs.push(glyph);
-                continue;
-            }
+        let key = TextureAtlasKey {
+            font_name: font_id,
+            glyph_id: glyph_id.0 as u32,
+            glyph_size: (quantized_size * 100.0) as u32,
+        };
 
-            let atlas_borrow = &mut *self.texture_atlas.borrow_mut();
-
-            match self.text_renderer.borrow_mut().render_glyph(
-                graphics_context,
-                self,
-                atlas_borrow,
-                &font,
-                glyph_id,
-                quantized_size,
-            ) {
-                Ok(mut glyph) => {
-                    atlas_borrow.insert_glyph(key.clone(), glyph.clone());
-
-                    glyph.size.0 *= scale_factor;
-                    glyph.size.1 *= scale_factor;
-                    glyph.advance *= scale_factor;
-                    glyph.x_bearing *= scale_factor;
-                    glyph.y_offset *= scale_factor;
-
-                    glyphs.push(glyph);
-                }
-                Err(e) => log::error!("Failed to render glyph {}: {}", c, e),
+        let cached = self.texture_atlas.borrow().get_glyph(&key);
+        let mut glyph = match cached {
+            Some(glyph) => glyph,
+            None => {
+                let atlas_borrow = &mut *self.texture_atlas.borrow_mut();
+                let glyph = self.text_renderer.borrow_mut().render_glyph(
+                    graphics_context,
+                    self,
+                    atlas_borrow,
+                    font,
+                    glyph_id,
+                    quantized_size,
+                )?;
+                atlas_borrow.insert_glyph(key, glyph.clone());
+                glyph
             }
-        }
+        };
+
+        glyph.size.0 *= scale_factor;
+        glyph.size.1 *= scale_factor;
+        glyph.advance *= scale_factor;
+        glyph.x_bearing *= scale_factor;
+        glyph.y_offset *= scale_factor;
 
-        glyphs
+        Ok(glyph)
     }
 
     pub fn update(&self) {
@@ -16,6 +16,14 @@ pub struct Glyph {
     pub y_offset: f32,
 }
 
+/// A glyph of shaped text, see [crate::resource_manager::ResourceManager::shape].
+#[derive(Clone, Debug)]
+pub struct ShapedGlyph {
+    pub glyph: Glyph,
+    /// The index of the first character of the cluster the glyph belongs to.
+    pub cluster: usize,
+}
+
 pub trait FontFaceTrueScale {
     fn scale(&self, font_size: f32) -> f32;
     fn line_height(&self, font_size: f32) -> f32;